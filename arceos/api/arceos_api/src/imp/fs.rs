@@ -4,6 +4,7 @@ use axfs::fops::{Directory, File};
 
 pub use axfs::fops::DirEntry as AxDirEntry;
 pub use axfs::fops::FileAttr as AxFileAttr;
+pub use axfs::fops::FileMeta as AxFileMeta;
 pub use axfs::fops::FilePerm as AxFilePerm;
 pub use axfs::fops::FileType as AxFileType;
 pub use axfs::fops::OpenOptions as AxOpenOptions;
@@ -58,6 +59,10 @@ pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr> {
     file.0.get_attr()
 }
 
+pub fn ax_file_meta(file: &AxFileHandle) -> AxResult<AxFileMeta> {
+    file.0.meta()
+}
+
 pub fn ax_read_dir(dir: &mut AxDirHandle, dirents: &mut [AxDirEntry]) -> AxResult<usize> {
     dir.0.read_dir(dirents)
 }
@@ -78,6 +83,55 @@ pub fn ax_rename(old: &str, new: &str) -> AxResult {
     axfs::api::rename(old, new)
 }
 
+pub fn ax_symlink(original: &str, link: &str) -> AxResult {
+    axfs::api::symlink(original, link)
+}
+
+pub fn ax_hard_link(original: &str, link: &str) -> AxResult {
+    axfs::api::hard_link(original, link)
+}
+
+pub fn ax_read_link(path: &str) -> AxResult<String> {
+    axfs::api::read_link(path)
+}
+
+pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr> {
+    let metadata = axfs::api::symlink_metadata(path)?;
+    Ok(AxFileAttr::new(
+        metadata.permissions(),
+        metadata.file_type(),
+        metadata.size(),
+        metadata.blocks(),
+    ))
+}
+
+pub fn ax_path_meta(path: &str, follow: bool) -> AxResult<(AxFileAttr, AxFileMeta)> {
+    let metadata = if follow {
+        axfs::api::metadata(path)?
+    } else {
+        axfs::api::symlink_metadata(path)?
+    };
+    let attr = AxFileAttr::new(
+        metadata.permissions(),
+        metadata.file_type(),
+        metadata.size(),
+        metadata.blocks(),
+    );
+    let meta = AxFileMeta {
+        nlink: metadata.nlink(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        atime: metadata.accessed(),
+        mtime: metadata.modified(),
+        ctime: metadata.changed(),
+    };
+    Ok((attr, meta))
+}
+
+pub fn ax_set_permissions(path: &str, perm: AxFilePerm) -> AxResult {
+    axfs::api::set_permissions(path, perm)
+}
+
 pub fn ax_current_dir() -> AxResult<String> {
     axfs::api::current_dir()
 }
@@ -182,6 +182,7 @@ pub mod fs {
         pub type AxDirHandle;
         pub type AxOpenOptions;
         pub type AxFileAttr;
+        pub type AxFileMeta;
         pub type AxFileType;
         pub type AxFilePerm;
         pub type AxDirEntry;
@@ -230,6 +231,9 @@ pub mod fs {
         pub fn ax_seek_file(file: &mut AxFileHandle, pos: AxSeekFrom) -> AxResult<u64>;
         /// Returns attributes of the file.
         pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr>;
+        /// Returns the ownership, timestamps and number of hard links of the
+        /// file.
+        pub fn ax_file_meta(file: &AxFileHandle) -> AxResult<AxFileMeta>;
 
         /// Reads directory entries starts from the current position into the
         /// given buffer, returns the number of entries read.
@@ -249,6 +253,20 @@ pub mod fs {
         ///
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
+        /// Creates a new symbolic link `link` that points to `original`.
+        pub fn ax_symlink(original: &str, link: &str) -> AxResult;
+        /// Creates a new hard link `link` to the file `original`.
+        pub fn ax_hard_link(original: &str, link: &str) -> AxResult;
+        /// Reads the target of a symbolic link.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Returns attributes of the file at `path` without following symbolic
+        /// links.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
+        /// Returns attributes, ownership and timestamps of the file at `path`.
+        /// Symbolic links are followed if `follow` is `true`.
+        pub fn ax_path_meta(path: &str, follow: bool) -> AxResult<(AxFileAttr, AxFileMeta)>;
+        /// Changes the permissions of the file at `path`.
+        pub fn ax_set_permissions(path: &str, perm: AxFilePerm) -> AxResult;
 
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
@@ -37,6 +37,7 @@ typedef struct {{
 
         let allow_types = [
             "stat",
+            "statfs",
             "size_t",
             "ssize_t",
             "off_t",
@@ -52,6 +53,8 @@ typedef struct {{
             "iovec",
             "clockid_t",
             "rlimit",
+            "flock",
+            "inotify_event",
             "aibuf",
         ];
         let allow_vars = [
@@ -62,12 +65,22 @@ typedef struct {{
             "IPPROTO_.*",
             "FD_.*",
             "F_.*",
+            "FALLOC_.*",
+            "XATTR_.*",
+            "LOCK_.*",
+            "IN_CLOEXEC",
+            "IN_NONBLOCK",
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "UTIME_.*",
+            "AT_.*",
+            "MS_.*",
+            "MNT_.*",
+            "PACKET_.*",
         ];
 
         #[derive(Debug)]
@@ -0,0 +1,511 @@
+//! Advisory file locks of `flock` and the record locks of `fcntl`.
+//!
+//! Each file has a lock table shared by all its opened files, which only
+//! exists while any lock is held or waited for. `flock` locks are owned by the
+//! open file description and released when it is closed. Record locks are
+//! owned by the task, which is the process in ArceOS (see [`sys_getpid`]), and
+//! released when it closes any file descriptor of the file or exits.
+//!
+//! [`sys_getpid`]: crate::sys_getpid
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::fops::FileId;
+use spin::Mutex;
+
+use super::fs::File;
+use crate::ctypes;
+
+/// The type of a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockKind {
+    /// A shared (read) lock.
+    Shared,
+    /// An exclusive (write) lock.
+    Exclusive,
+}
+
+impl LockKind {
+    fn conflicts(self, other: Self) -> bool {
+        self == Self::Exclusive || other == Self::Exclusive
+    }
+}
+
+/// A record lock on the bytes in `start..end` of a file.
+#[derive(Debug, Clone, Copy)]
+struct RecordLock {
+    /// The ID of the task.
+    owner: u64,
+    start: u64,
+    end: u64,
+    kind: LockKind,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+#[derive(Default)]
+struct LockState {
+    /// The `flock` locks and the addresses of their open file descriptions.
+    flocks: Vec<(usize, LockKind)>,
+    records: Vec<RecordLock>,
+}
+
+impl LockState {
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty()
+    }
+
+    fn try_flock(&mut self, owner: usize, kind: LockKind) -> bool {
+        if self
+            .flocks
+            .iter()
+            .any(|&(o, k)| o != owner && k.conflicts(kind))
+        {
+            return false;
+        }
+        self.flocks.push((owner, kind));
+        true
+    }
+
+    fn unflock(&mut self, owner: usize) -> bool {
+        let len = self.flocks.len();
+        self.flocks.retain(|&(o, _)| o != owner);
+        self.flocks.len() != len
+    }
+
+    fn conflict(&self, lock: &RecordLock) -> Option<&RecordLock> {
+        self.records.iter().find(|r| {
+            r.owner != lock.owner && r.overlaps(lock.start, lock.end) && r.kind.conflicts(lock.kind)
+        })
+    }
+
+    /// Takes the record lock unless it conflicts with others, which replaces
+    /// the locks of the owner in its range, and is merged with the adjacent
+    /// or overlapping ones of the same kind.
+    fn try_lock_record(&mut self, mut lock: RecordLock) -> bool {
+        if self.conflict(&lock).is_some() {
+            return false;
+        }
+        while let Some(r) = self.records.iter().find(|r| {
+            r.owner == lock.owner
+                && r.kind == lock.kind
+                && r.start <= lock.end
+                && lock.start <= r.end
+                && (r.start < lock.start || lock.end < r.end)
+        }) {
+            lock.start = lock.start.min(r.start);
+            lock.end = lock.end.max(r.end);
+        }
+        self.unlock_records(lock.owner, lock.start, lock.end);
+        self.records.push(lock);
+        true
+    }
+
+    /// Removes the bytes in `start..end` from the record locks of `owner`,
+    /// splitting the locks partially in the range.
+    fn unlock_records(&mut self, owner: u64, start: u64, end: u64) -> bool {
+        let mut changed = false;
+        let mut records = Vec::with_capacity(self.records.len() + 1);
+        for r in self.records.drain(..) {
+            if r.owner != owner || !r.overlaps(start, end) {
+                records.push(r);
+                continue;
+            }
+            changed = true;
+            if r.start < start {
+                records.push(RecordLock { end: start, ..r });
+            }
+            if end < r.end {
+                records.push(RecordLock { start: end, ..r });
+            }
+        }
+        self.records = records;
+        changed
+    }
+}
+
+/// The locks of a file.
+struct LockTable {
+    state: Mutex<LockState>,
+    /// The tasks waiting for the locks to be released.
+    #[cfg(feature = "multitask")]
+    wait_queue: axtask::WaitQueue,
+}
+
+impl LockTable {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(LockState::default()),
+            #[cfg(feature = "multitask")]
+            wait_queue: axtask::WaitQueue::new(),
+        }
+    }
+
+    /// Changes the locks with `f`, and wakes up the waiting tasks if any lock
+    /// is released.
+    fn release(&self, f: impl FnOnce(&mut LockState) -> bool) {
+        if f(&mut self.state.lock()) {
+            #[cfg(feature = "multitask")]
+            self.wait_queue.notify_all(false);
+        }
+    }
+}
+
+static LOCK_TABLES: Mutex<BTreeMap<FileId, Arc<LockTable>>> = Mutex::new(BTreeMap::new());
+
+/// Returns the lock table of the file `id`, creates it if not exists.
+fn get_table(id: &FileId) -> Arc<LockTable> {
+    LOCK_TABLES
+        .lock()
+        .entry(id.clone())
+        .or_insert_with(|| Arc::new(LockTable::new()))
+        .clone()
+}
+
+/// Drops `table` got by [`get_table`], and removes it if it has no locks and
+/// is not used by others.
+fn put_table(id: &FileId, table: Arc<LockTable>) {
+    let mut tables = LOCK_TABLES.lock();
+    // one reference is in `tables`
+    if Arc::strong_count(&table) == 2 && table.state.lock().is_empty() {
+        tables.remove(id);
+    }
+}
+
+/// Acquires a lock of the file `id` with `try_lock`, which returns whether it
+/// is acquired. Waits for it to succeed if `wait` is `true`, otherwise fails
+/// with `EAGAIN`.
+fn acquire(id: &FileId, wait: bool, try_lock: impl Fn(&mut LockState) -> bool) -> LinuxResult {
+    let table = get_table(id);
+    let res = if try_lock(&mut table.state.lock()) {
+        Ok(())
+    } else if !wait {
+        Err(LinuxError::EAGAIN)
+    } else {
+        #[cfg(feature = "multitask")]
+        {
+            table
+                .wait_queue
+                .wait_until(|| try_lock(&mut table.state.lock()));
+            Ok(())
+        }
+        #[cfg(not(feature = "multitask"))]
+        {
+            // no other task can release it
+            Err(LinuxError::EDEADLK)
+        }
+    };
+    put_table(id, table);
+    res
+}
+
+/// Changes the locks of the file `id` with `f`, which returns whether any lock
+/// is released.
+fn release(id: &FileId, f: impl FnOnce(&mut LockState) -> bool) {
+    let table = LOCK_TABLES.lock().get(id).cloned();
+    if let Some(table) = table {
+        table.release(f);
+        put_table(id, table);
+    }
+}
+
+/// Returns the owner of the record locks taken by the current task, which is
+/// its ID as returned by [`sys_getpid`](crate::sys_getpid).
+fn current_owner() -> u64 {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::current().id().as_u64()
+    }
+    #[cfg(not(feature = "multitask"))]
+    {
+        2 // `main` task ID
+    }
+}
+
+/// Releases the `flock` lock held by the open file description at `owner`.
+pub(super) fn release_flock(id: &FileId, owner: usize) {
+    release(id, |state| state.unflock(owner));
+}
+
+/// Releases the record locks of the current task on the file `id`, when it
+/// closes a file descriptor of it.
+pub(super) fn release_records(id: &FileId) {
+    let owner = current_owner();
+    release(id, |state| state.unlock_records(owner, 0, u64::MAX));
+}
+
+/// Releases all record locks of the current task, when it exits.
+pub(crate) fn release_task_records() {
+    let owner = current_owner();
+    let tables: Vec<_> = LOCK_TABLES
+        .lock()
+        .iter()
+        .map(|(id, table)| (id.clone(), table.clone()))
+        .collect();
+    for (id, table) in tables {
+        table.release(|state| state.unlock_records(owner, 0, u64::MAX));
+        put_table(&id, table);
+    }
+}
+
+/// Converts the range in `fl` to the absolute range of bytes `start..end`.
+fn record_range(file: &File, fl: &ctypes::flock) -> LinuxResult<(u64, u64)> {
+    let (offset, size) = file.offset_and_size()?;
+    let base = match fl.l_whence {
+        0 => 0,
+        1 => offset as i64,
+        2 => size as i64,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let start = base.checked_add(fl.l_start).ok_or(LinuxError::EOVERFLOW)?;
+    let (start, end) = match fl.l_len {
+        // to the end of the file, however it grows
+        0 => (start, i64::MAX),
+        len if len > 0 => (start, start.checked_add(len).ok_or(LinuxError::EOVERFLOW)?),
+        len => (start.checked_add(len).ok_or(LinuxError::EINVAL)?, start),
+    };
+    if start < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let end = if end == i64::MAX {
+        u64::MAX
+    } else {
+        end as u64
+    };
+    Ok((start as u64, end))
+}
+
+/// Handles the `F_GETLK`, `F_SETLK` and `F_SETLKW` commands of `fcntl` on
+/// the file `fd`, with the `struct flock` at `fl`.
+pub(super) fn fcntl_lock(fd: c_int, cmd: u32, fl: *mut ctypes::flock) -> LinuxResult<c_int> {
+    if fl.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let file = File::from_fd(fd)?;
+    let fl = unsafe { &mut *fl };
+    let (start, end) = record_range(&file, fl)?;
+    let owner = current_owner();
+    let kind = match fl.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockKind::Shared),
+        ctypes::F_WRLCK => Some(LockKind::Exclusive),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    match (cmd, kind) {
+        (ctypes::F_GETLK, Some(kind)) => {
+            let lock = RecordLock {
+                owner,
+                start,
+                end,
+                kind,
+            };
+            let table = get_table(file.id());
+            let conflict = table.state.lock().conflict(&lock).copied();
+            put_table(file.id(), table);
+            match conflict {
+                Some(r) => {
+                    fl.l_type = match r.kind {
+                        LockKind::Shared => ctypes::F_RDLCK,
+                        LockKind::Exclusive => ctypes::F_WRLCK,
+                    } as _;
+                    fl.l_whence = 0; // SEEK_SET
+                    fl.l_start = r.start as _;
+                    fl.l_len = if r.end == u64::MAX {
+                        0
+                    } else {
+                        (r.end - r.start) as _
+                    };
+                    fl.l_pid = r.owner as _;
+                }
+                None => fl.l_type = ctypes::F_UNLCK as _,
+            }
+        }
+        (ctypes::F_GETLK, None) => return Err(LinuxError::EINVAL),
+        (_, Some(kind)) => {
+            let lock = RecordLock {
+                owner,
+                start,
+                end,
+                kind,
+            };
+            let wait = cmd == ctypes::F_SETLKW;
+            acquire(file.id(), wait, |state| state.try_lock_record(lock))?;
+        }
+        (_, None) => release(file.id(), |state| state.unlock_records(owner, start, end)),
+    }
+    Ok(0)
+}
+
+/// Apply or remove an advisory lock on the file `fd`.
+///
+/// `operation` is one of `LOCK_SH`, `LOCK_EX` and `LOCK_UN`, optionally with
+/// `LOCK_NB` to fail with `EWOULDBLOCK` instead of waiting for a conflicting
+/// lock to be released. The lock is shared by the duplicated file descriptors,
+/// and converting it is not atomic.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let file = File::from_fd(fd)?;
+        let owner = Arc::as_ptr(&file) as usize;
+        let op = operation as u32;
+        let kind = match op & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => LockKind::Shared,
+            ctypes::LOCK_EX => LockKind::Exclusive,
+            ctypes::LOCK_UN => {
+                release_flock(file.id(), owner);
+                return Ok(0);
+            }
+            _ => return Err(LinuxError::EINVAL),
+        };
+        // like Linux, the old lock is released before waiting for the new one
+        release_flock(file.id(), owner);
+        let wait = op & ctypes::LOCK_NB == 0;
+        acquire(file.id(), wait, |state| state.try_flock(owner, kind))?;
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(owner: u64, start: u64, end: u64, kind: LockKind) -> RecordLock {
+        RecordLock {
+            owner,
+            start,
+            end,
+            kind,
+        }
+    }
+
+    /// The sorted ranges and kinds of the record locks of `owner`.
+    fn ranges(state: &LockState, owner: u64) -> Vec<(u64, u64, LockKind)> {
+        let mut ranges: Vec<_> = state
+            .records
+            .iter()
+            .filter(|r| r.owner == owner)
+            .map(|r| (r.start, r.end, r.kind))
+            .collect();
+        ranges.sort_by_key(|r| r.0);
+        ranges
+    }
+
+    #[test]
+    fn test_unlock_split() {
+        use LockKind::*;
+        let mut state = LockState::default();
+        assert!(state.try_lock_record(record(1, 0, 100, Exclusive)));
+        assert!(state.try_lock_record(record(2, 200, 300, Shared)));
+
+        // unlocking the middle splits the lock
+        assert!(state.unlock_records(1, 40, 60));
+        assert_eq!(
+            ranges(&state, 1),
+            [(0, 40, Exclusive), (60, 100, Exclusive)]
+        );
+        // unlocking both ends of the pieces
+        assert!(state.unlock_records(1, 30, 70));
+        assert_eq!(
+            ranges(&state, 1),
+            [(0, 30, Exclusive), (70, 100, Exclusive)]
+        );
+        // nothing of the owner in the range
+        assert!(!state.unlock_records(1, 30, 70));
+        assert!(!state.unlock_records(1, 200, 300));
+        assert_eq!(ranges(&state, 2), [(200, 300, Shared)]);
+        // to the end of the file
+        assert!(state.unlock_records(1, 10, u64::MAX));
+        assert_eq!(ranges(&state, 1), [(0, 10, Exclusive)]);
+        assert!(state.unlock_records(1, 0, u64::MAX));
+        assert!(ranges(&state, 1).is_empty());
+        assert!(!state.is_empty());
+        assert!(state.unlock_records(2, 0, u64::MAX));
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_lock_merge() {
+        use LockKind::*;
+        let mut state = LockState::default();
+        // adjacent and overlapping locks of the same kind are merged
+        assert!(state.try_lock_record(record(1, 0, 10, Shared)));
+        assert!(state.try_lock_record(record(1, 10, 20, Shared)));
+        assert!(state.try_lock_record(record(1, 30, 40, Shared)));
+        assert_eq!(ranges(&state, 1), [(0, 20, Shared), (30, 40, Shared)]);
+        assert!(state.try_lock_record(record(1, 15, 35, Shared)));
+        assert_eq!(ranges(&state, 1), [(0, 40, Shared)]);
+
+        // a lock of another kind replaces the range
+        assert!(state.try_lock_record(record(1, 10, 20, Exclusive)));
+        assert_eq!(
+            ranges(&state, 1),
+            [(0, 10, Shared), (10, 20, Exclusive), (20, 40, Shared)]
+        );
+        assert!(state.try_lock_record(record(1, 0, 40, Shared)));
+        assert_eq!(ranges(&state, 1), [(0, 40, Shared)]);
+
+        // the locks of other owners are not merged
+        assert!(state.try_lock_record(record(2, 40, 50, Shared)));
+        assert_eq!(ranges(&state, 1), [(0, 40, Shared)]);
+        assert_eq!(ranges(&state, 2), [(40, 50, Shared)]);
+    }
+
+    #[test]
+    fn test_record_conflict() {
+        use LockKind::*;
+        let mut state = LockState::default();
+        assert!(state.try_lock_record(record(1, 0, 100, Shared)));
+        assert!(state.try_lock_record(record(2, 50, 150, Shared)));
+        assert!(!state.try_lock_record(record(3, 120, 130, Exclusive)));
+        assert!(state.try_lock_record(record(3, 150, u64::MAX, Exclusive)));
+        assert!(!state.try_lock_record(record(1, 200, 201, Shared)));
+
+        // the owner can upgrade its own lock without others in the range
+        assert!(state.try_lock_record(record(1, 0, 50, Exclusive)));
+        assert!(!state.try_lock_record(record(1, 0, 60, Exclusive)));
+        assert_eq!(ranges(&state, 1), [(0, 50, Exclusive), (50, 100, Shared)]);
+
+        // F_GETLK reports a conflicting lock of another owner
+        let conflict = state.conflict(&record(2, 10, 20, Shared)).copied();
+        let conflict = conflict.map(|r| (r.owner, r.start, r.end, r.kind));
+        assert_eq!(conflict, Some((1, 0, 50, Exclusive)));
+        let conflict = state.conflict(&record(1, 1000, 1001, Shared)).copied();
+        let conflict = conflict.map(|r| (r.owner, r.start, r.end, r.kind));
+        assert_eq!(conflict, Some((3, 150, u64::MAX, Exclusive)));
+        assert!(state.conflict(&record(1, 60, 70, Shared)).is_none());
+        assert!(state.conflict(&record(2, 60, 70, Shared)).is_none());
+        assert!(state.conflict(&record(2, 100, 150, Exclusive)).is_none());
+    }
+
+    #[test]
+    fn test_flock() {
+        use LockKind::*;
+        let mut state = LockState::default();
+        assert!(state.try_flock(1, Shared));
+        assert!(state.try_flock(2, Shared));
+        assert!(!state.try_flock(3, Exclusive));
+
+        // upgrading is not atomic, the old lock is released first
+        assert!(state.unflock(1));
+        assert!(!state.try_flock(1, Exclusive));
+        assert!(state.unflock(2));
+        assert!(!state.unflock(2));
+        assert!(state.try_flock(1, Exclusive));
+        assert!(!state.try_flock(2, Shared));
+
+        // downgrading lets others share it
+        assert!(state.unflock(1));
+        assert!(state.try_flock(1, Shared));
+        assert!(state.try_flock(2, Shared));
+        assert!(state.unflock(1));
+        assert!(state.unflock(2));
+        assert!(state.is_empty());
+    }
+}
@@ -1,8 +1,9 @@
-use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::ffi::{c_char, c_int, c_long, c_ulong, c_void};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axfs::fops::{AllocMode, FileAttr, FileId, FileMeta, FilePerm, OpenOptions, XattrMode};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -11,20 +12,34 @@ use crate::{ctypes, utils::char_ptr_to_str};
 
 pub struct File {
     inner: Mutex<axfs::fops::File>,
+    id: FileId,
 }
 
 impl File {
     fn new(inner: axfs::fops::File) -> Self {
         Self {
+            id: inner.file_id().clone(),
             inner: Mutex::new(inner),
         }
     }
 
+    /// Returns the identity of the opened file, which keys its locks.
+    pub(super) fn id(&self) -> &FileId {
+        &self.id
+    }
+
+    /// Returns the current position and the size of the file.
+    pub(super) fn offset_and_size(&self) -> LinuxResult<(u64, u64)> {
+        let mut file = self.inner.lock();
+        let size = file.get_attr()?.size();
+        Ok((file.seek(SeekFrom::Current(0))?, size))
+    }
+
     fn add_to_fd_table(self) -> LinuxResult<c_int> {
         super::fd_ops::add_file_like(Arc::new(self))
     }
 
-    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+    pub(super) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         let f = super::fd_ops::get_file_like(fd)?;
         f.into_any()
             .downcast::<Self>()
@@ -42,21 +57,8 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        let file = self.inner.lock();
+        Ok(stat_of(&file.get_attr()?, &file.meta()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -75,10 +77,61 @@ impl FileLike for File {
     }
 }
 
+impl Drop for File {
+    fn drop(&mut self) {
+        // `flock` locks are owned by the open file description
+        super::file_lock::release_flock(&self.id, self as *const Self as usize);
+    }
+}
+
+/// Convert file attributes and metadata to a `stat`.
+fn stat_of(attr: &FileAttr, meta: &FileMeta) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: 1,
+        st_nlink: meta.nlink as _,
+        st_mode,
+        st_uid: meta.uid,
+        st_gid: meta.gid,
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        st_atime: meta.atime.into(),
+        st_mtime: meta.mtime.into(),
+        st_ctime: meta.ctime.into(),
+        ..Default::default()
+    }
+}
+
+/// Convert permission bits in a `mode_t` to [`FilePerm`].
+fn mode_to_perm(mode: ctypes::mode_t) -> FilePerm {
+    FilePerm::from_bits_truncate((mode & 0o777) as u16)
+}
+
+/// Convert a `uid_t` or `gid_t` to an ID to change to, where `-1` means to
+/// keep the current one.
+fn id_to_change(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+/// Convert a timestamp passed to [`sys_utimensat`] to the time to set, where
+/// `UTIME_OMIT` means to keep the current one.
+fn time_to_change(ts: &ctypes::timespec) -> LinuxResult<Option<Duration>> {
+    match ts.tv_nsec {
+        n if n == ctypes::UTIME_OMIT as c_long => Ok(None),
+        n if n == ctypes::UTIME_NOW as c_long => Ok(Some(axhal::time::wall_time())),
+        0..=999_999_999 if ts.tv_sec >= 0 => Ok(Some((*ts).into())),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
-fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
+fn flags_to_options(flags: c_int, mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
+    options.mode(mode & 0o777);
     match flags & 0b11 {
         ctypes::O_RDONLY => options.read(true),
         ctypes::O_WRONLY => options.write(true),
@@ -122,17 +175,85 @@ pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) ->
 pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off_t {
     debug!("sys_lseek <= {} {} {}", fd, offset, whence);
     syscall_body!(sys_lseek, {
+        let file = File::from_fd(fd)?;
+        let mut file = file.inner.lock();
         let pos = match whence {
             0 => SeekFrom::Start(offset as _),
             1 => SeekFrom::Current(offset as _),
             2 => SeekFrom::End(offset as _),
+            3 | 4 => {
+                // SEEK_DATA and SEEK_HOLE
+                let offset = u64::try_from(offset).map_err(|_| LinuxError::ENXIO)?;
+                let pos = match whence {
+                    3 => file.seek_data(offset)?,
+                    _ => file.seek_hole(offset)?,
+                };
+                return pos.ok_or(LinuxError::ENXIO);
+            }
             _ => return Err(LinuxError::EINVAL),
         };
-        let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
+        let off = file.seek(pos)?;
         Ok(off)
     })
 }
 
+/// Allocate or deallocate the space of the range at `offset` of `len` bytes
+/// of the file indicated by `fd`, as selected by the `FALLOC_FL_*` `mode`.
+///
+/// Return 0 if success.
+pub fn sys_fallocate(fd: c_int, mode: c_int, offset: ctypes::off_t, len: ctypes::off_t) -> c_int {
+    debug!("sys_fallocate <= {} {:#x} {} {}", fd, mode, offset, len);
+    syscall_body!(sys_fallocate, {
+        if offset < 0 || len <= 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (keep_size, punch_hole) = (
+            ctypes::FALLOC_FL_KEEP_SIZE as c_int,
+            ctypes::FALLOC_FL_PUNCH_HOLE as c_int,
+        );
+        let mode = match mode {
+            0 => AllocMode::Allocate,
+            m if m == keep_size => AllocMode::KeepSize,
+            m if m == keep_size | punch_hole => AllocMode::PunchHole,
+            _ => return Err(LinuxError::EOPNOTSUPP),
+        };
+        let file = File::from_fd(fd)?;
+        file.inner.lock().allocate(offset as _, len as _, mode)?;
+        Ok(0)
+    })
+}
+
+/// Synchronize the data and metadata of the file indicated by `fd` to the
+/// device.
+///
+/// Return 0 if success.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        File::from_fd(fd)?.inner.lock().flush()?;
+        Ok(0)
+    })
+}
+
+/// Synchronize the data of the file indicated by `fd` to the device.
+///
+/// Metadata is always synchronized too, so it is the same as [`sys_fsync`].
+pub fn sys_fdatasync(fd: c_int) -> c_int {
+    debug!("sys_fdatasync <= {}", fd);
+    syscall_body!(sys_fdatasync, {
+        File::from_fd(fd)?.inner.lock().flush()?;
+        Ok(0)
+    })
+}
+
+/// Write all cached filesystem data back to the devices.
+pub fn sys_sync() {
+    debug!("sys_sync");
+    if let Err(e) = axfs::api::sync() {
+        warn!("sys_sync: {:?}", e);
+    }
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -143,11 +264,8 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let mut options = OpenOptions::new();
-        options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file).stat()?;
-        unsafe { *buf = st };
+        let metadata = axfs::api::metadata(path?)?;
+        unsafe { *buf = stat_of_metadata(&metadata) };
         Ok(0)
     })
 }
@@ -177,11 +295,268 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let metadata = axfs::api::symlink_metadata(path?)?;
+        unsafe { *buf = stat_of_metadata(&metadata) };
+        Ok(0)
+    })
+}
+
+/// Convert the metadata returned by [`axfs::api`] to a `stat`.
+fn stat_of_metadata(metadata: &axfs::api::Metadata) -> ctypes::stat {
+    let attr = FileAttr::new(
+        metadata.permissions(),
+        metadata.file_type(),
+        metadata.size(),
+        metadata.blocks(),
+    );
+    let meta = FileMeta {
+        nlink: metadata.nlink(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        atime: metadata.accessed(),
+        mtime: metadata.modified(),
+        ctime: metadata.changed(),
+    };
+    stat_of(&attr, &meta)
+}
+
+/// Change the permissions of the file `path` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_chmod, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_chmod <= {:?} {:#o}", path, mode);
+        axfs::api::set_permissions(path, mode_to_perm(mode))?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file indicated by `fd` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        File::from_fd(fd)?
+            .inner
+            .lock()
+            .set_perm(mode_to_perm(mode))?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file `path`, which are kept if they
+/// are `-1`. If `follow` is `false`, a symbolic link itself is changed.
+fn chown_at(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    follow: bool,
+) -> LinuxResult {
+    let path = char_ptr_to_str(path)?;
+    debug!(
+        "chown <= {:?} {} {} {}",
+        path, owner as i32, group as i32, follow
+    );
+    let (uid, gid) = (id_to_change(owner), id_to_change(group));
+    if follow {
+        axfs::api::chown(path, uid, gid)?;
+    } else {
+        axfs::api::lchown(path, uid, gid)?;
+    }
+    Ok(())
+}
+
+/// Change the owner and the group of the file `path`, which are kept if they
+/// are `-1`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chown(path: *const c_char, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    syscall_body!(sys_chown, {
+        chown_at(path, owner, group, true)?;
+        Ok(0)
+    })
+}
+
+/// Same as [`sys_chown`], but a symbolic link itself is changed.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_lchown(path: *const c_char, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    syscall_body!(sys_lchown, {
+        chown_at(path, owner, group, false)?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file indicated by `fd`, which are
+/// kept if they are `-1`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    debug!("sys_fchown <= {} {} {}", fd, owner as i32, group as i32);
+    syscall_body!(sys_fchown, {
+        let (uid, gid) = (id_to_change(owner), id_to_change(group));
+        File::from_fd(fd)?.inner.lock().set_owner(uid, gid)?;
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of the file `path`, or of the
+/// file indicated by `dirfd` if `path` is null.
+///
+/// `times` holds the access and modification times. Both are set to now if it
+/// is null. `UTIME_NOW` and `UTIME_OMIT` are supported. Relative paths must be
+/// relative to the current directory (`AT_FDCWD`), and `AT_SYMLINK_NOFOLLOW`
+/// is not supported.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    debug!(
+        "sys_utimensat <= {} {:#x} {:#x} {:#x}",
+        dirfd, path as usize, times as usize, flags
+    );
+    syscall_body!(sys_utimensat, {
+        if flags != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (atime, mtime) = if times.is_null() {
+            let now = axhal::time::wall_time();
+            (Some(now), Some(now))
+        } else {
+            let times = unsafe { core::slice::from_raw_parts(times, 2) };
+            (time_to_change(&times[0])?, time_to_change(&times[1])?)
+        };
+        if path.is_null() {
+            File::from_fd(dirfd)?.inner.lock().set_times(atime, mtime)?;
+        } else {
+            let path = char_ptr_to_str(path)?;
+            if !path.starts_with('/') && dirfd != ctypes::AT_FDCWD {
+                return Err(LinuxError::EINVAL);
+            }
+            axfs::api::set_times(path, atime, mtime)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Get the real user ID of the calling task.
+pub fn sys_getuid() -> ctypes::uid_t {
+    axfs::api::current_uid()
+}
+
+/// Get the effective user ID, which is always the real user ID.
+pub fn sys_geteuid() -> ctypes::uid_t {
+    axfs::api::current_uid()
+}
+
+/// Get the real group ID of the calling task.
+pub fn sys_getgid() -> ctypes::gid_t {
+    axfs::api::current_gid()
+}
+
+/// Get the effective group ID, which is always the real group ID.
+pub fn sys_getegid() -> ctypes::gid_t {
+    axfs::api::current_gid()
+}
+
+/// Set the user ID that files are accessed with.
+///
+/// The real, effective and saved IDs are always the same, so only root can
+/// switch to another user, and it cannot switch back.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_setuid(uid: ctypes::uid_t) -> c_int {
+    debug!("sys_setuid <= {}", uid);
+    syscall_body!(sys_setuid, {
+        if uid == u32::MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        let cur_uid = axfs::api::current_uid();
+        if cur_uid != 0 && uid != cur_uid {
+            return Err(LinuxError::EPERM);
+        }
+        axfs::api::set_current_uid(uid);
         Ok(0)
     })
 }
 
+/// Set the group ID that files are accessed with.
+///
+/// Only root can switch to another group.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_setgid(gid: ctypes::gid_t) -> c_int {
+    debug!("sys_setgid <= {}", gid);
+    syscall_body!(sys_setgid, {
+        if gid == u32::MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        if axfs::api::current_uid() != 0 && gid != axfs::api::current_gid() {
+            return Err(LinuxError::EPERM);
+        }
+        axfs::api::set_current_gid(gid);
+        Ok(0)
+    })
+}
+
+/// Create a symbolic link `linkpath` that points to `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!(
+            "sys_symlink <= target: {:?}, linkpath: {:?}",
+            target, linkpath
+        );
+        axfs::api::symlink(target, linkpath)?;
+        Ok(0)
+    })
+}
+
+/// Create a hard link `new` to the file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall_body!(sys_link, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!("sys_link <= old: {:?}, new: {:?}", old_path, new_path);
+        axfs::api::hard_link(old_path, new_path)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`, which is not
+/// null-terminated and truncated to `bufsiz` bytes.
+///
+/// Return the number of bytes placed in `buf`.
+pub unsafe fn sys_readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!("sys_readlink <= {:?} {:#x} {}", path, buf as usize, bufsiz);
+    syscall_body!(sys_readlink, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let target = axfs::api::read_link(path?)?;
+        let len = target.len().min(bufsiz);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
 /// Get the path of the current directory.
 pub fn sys_getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
     debug!("sys_getcwd <= {:#x} {}", buf as usize, size);
@@ -215,3 +590,406 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         Ok(0)
     })
 }
+
+/// Mount the filesystem of type `fstype` on `source` at `target`.
+///
+/// `MS_RDONLY`, `MS_NOEXEC` and `MS_SYNCHRONOUS` are supported, and
+/// `MS_REMOUNT` changes them for an existing mount. Writes to a read-only
+/// mount fail with `EROFS`. `data` is a string of the options of the
+/// filesystem if not null, e.g. `size=16m` of a `tmpfs`, see
+/// [`axfs::api::mount_with_options`].
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let target = char_ptr_to_str(target)?;
+        debug!(
+            "sys_mount <= source: {:#x}, target: {:?}, fstype: {:#x}, flags: {:#x}, data: {:#x}",
+            source as usize, target, fstype as usize, flags, data as usize
+        );
+        let flags = flags as u32;
+        if flags & (ctypes::MS_BIND | ctypes::MS_MOVE) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mount_flags = axfs::api::MountFlags::from_bits_truncate(flags);
+        let ignored = flags & !(mount_flags.bits() | ctypes::MS_REMOUNT);
+        if ignored != 0 {
+            warn!("sys_mount: flags {:#x} are not supported, ignored", ignored);
+        }
+        if flags & ctypes::MS_REMOUNT != 0 {
+            axfs::api::remount(target, mount_flags)?;
+        } else {
+            let source = char_ptr_to_str(source)?;
+            let fstype = char_ptr_to_str(fstype)?;
+            let options = if data.is_null() {
+                ""
+            } else {
+                char_ptr_to_str(data as *const c_char)?
+            };
+            axfs::api::mount_with_options(source, target, fstype, mount_flags, options)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Convert the usage of a filesystem to a `statfs`.
+fn statfs_of(stat: &axfs::api::FsStat) -> ctypes::statfs {
+    // the magic numbers of the filesystems in Linux
+    let f_type = match stat.fstype {
+        "ramfs" => 0x858458f6,
+        "vfat" => 0x4d44,
+        "ext2" | "ext3" | "ext4" => 0xef53,
+        "proc" => 0x9fa0,
+        "sysfs" => 0x62656572,
+        "devfs" => 0x1373,
+        "overlay" => 0x794c7630,
+        "9p" => 0x01021997,
+        _ => 0,
+    };
+    ctypes::statfs {
+        f_type,
+        f_bsize: stat.block_size as _,
+        f_blocks: stat.blocks as _,
+        f_bfree: stat.blocks_free as _,
+        f_bavail: stat.blocks_free as _,
+        f_files: stat.files as _,
+        f_ffree: stat.files_free as _,
+        f_namelen: 255,
+        f_frsize: stat.block_size as _,
+        f_flags: stat.flags.bits() as _,
+        ..Default::default()
+    }
+}
+
+/// Get the usage of the filesystem that `path` lives in and write it into
+/// `buf`.
+///
+/// The sizes are 0 if they are unknown or unlimited. Return 0 if success.
+pub unsafe fn sys_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_statfs <= {:?} {:#x}", path, buf as usize);
+    syscall_body!(sys_statfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stat = axfs::api::statfs(path?)?;
+        unsafe { *buf = statfs_of(&stat) };
+        Ok(0)
+    })
+}
+
+/// Get the usage of the filesystem that the file `fd` lives in and write it
+/// into `buf`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    debug!("sys_fstatfs <= {} {:#x}", fd, buf as usize);
+    syscall_body!(sys_fstatfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stat = File::from_fd(fd)?.inner.lock().statfs()?;
+        unsafe { *buf = statfs_of(&stat) };
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted at `target`.
+///
+/// Lazy (`MNT_DETACH`) and expiring (`MNT_EXPIRE`) unmounts are not supported.
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_umount2, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount2 <= target: {:?}, flags: {:#x}", target, flags);
+        if flags as u32 & (ctypes::MNT_DETACH | ctypes::MNT_EXPIRE) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        axfs::api::umount(target)?;
+        Ok(0)
+    })
+}
+
+/// The file whose extended attributes are accessed: a path, which is not
+/// followed if the flag is `false` and it is a symbolic link, or an opened
+/// file.
+#[derive(Debug, Clone, Copy)]
+enum XattrFile<'a> {
+    Path(&'a str, bool),
+    Fd(c_int),
+}
+
+impl XattrFile<'_> {
+    fn get(self, name: &str) -> LinuxResult<Option<Vec<u8>>> {
+        Ok(match self {
+            Self::Path(path, true) => axfs::api::get_xattr(path, name)?,
+            Self::Path(path, false) => axfs::api::lget_xattr(path, name)?,
+            Self::Fd(fd) => File::from_fd(fd)?.inner.lock().get_xattr(name)?,
+        })
+    }
+
+    fn list(self) -> LinuxResult<Vec<String>> {
+        Ok(match self {
+            Self::Path(path, true) => axfs::api::list_xattrs(path)?,
+            Self::Path(path, false) => axfs::api::llist_xattrs(path)?,
+            Self::Fd(fd) => File::from_fd(fd)?.inner.lock().list_xattrs()?,
+        })
+    }
+
+    fn set(self, name: &str, value: &[u8], mode: XattrMode) -> LinuxResult<bool> {
+        Ok(match self {
+            Self::Path(path, true) => axfs::api::set_xattr(path, name, value, mode)?,
+            Self::Path(path, false) => axfs::api::lset_xattr(path, name, value, mode)?,
+            Self::Fd(fd) => File::from_fd(fd)?
+                .inner
+                .lock()
+                .set_xattr(name, value, mode)?,
+        })
+    }
+
+    fn remove(self, name: &str) -> LinuxResult<bool> {
+        Ok(match self {
+            Self::Path(path, true) => axfs::api::remove_xattr(path, name)?,
+            Self::Path(path, false) => axfs::api::lremove_xattr(path, name)?,
+            Self::Fd(fd) => File::from_fd(fd)?.inner.lock().remove_xattr(name)?,
+        })
+    }
+}
+
+/// Copy `data` into `buf` of `size` bytes, or only return its length if
+/// `size` is 0.
+fn copy_xattr_data(data: &[u8], buf: *mut c_void, size: usize) -> LinuxResult<ctypes::ssize_t> {
+    if size == 0 {
+        return Ok(data.len() as _);
+    }
+    if data.len() > size {
+        return Err(LinuxError::ERANGE);
+    }
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, data.len()) };
+    dst.copy_from_slice(data);
+    Ok(data.len() as _)
+}
+
+/// Get the value of the extended attribute `name` of `file` into `value` of
+/// `size` bytes, or only its size if `size` is 0.
+fn get_xattr(
+    file: XattrFile,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> LinuxResult<ctypes::ssize_t> {
+    let name = char_ptr_to_str(name)?;
+    debug!(
+        "getxattr <= {:?} {:?} {:#x} {}",
+        file, name, value as usize, size
+    );
+    let data = file.get(name)?.ok_or(LinuxError::ENODATA)?;
+    copy_xattr_data(&data, value, size)
+}
+
+/// Get the null-terminated names of the extended attributes of `file` into
+/// `list` of `size` bytes, or only their total size if `size` is 0.
+fn list_xattrs(file: XattrFile, list: *mut c_char, size: usize) -> LinuxResult<ctypes::ssize_t> {
+    debug!("listxattr <= {:?} {:#x} {}", file, list as usize, size);
+    let mut names = Vec::new();
+    for name in file.list()? {
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+    }
+    copy_xattr_data(&names, list as *mut c_void, size)
+}
+
+/// Set the extended attribute `name` of `file` to `value` of `size` bytes,
+/// as selected by the `XATTR_CREATE` or `XATTR_REPLACE` `flags`.
+fn set_xattr(
+    file: XattrFile,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> LinuxResult<c_int> {
+    let name = char_ptr_to_str(name)?;
+    debug!(
+        "setxattr <= {:?} {:?} {:#x} {} {:#x}",
+        file, name, value as usize, size, flags
+    );
+    let mode = match flags as u32 {
+        0 => XattrMode::Set,
+        ctypes::XATTR_CREATE => XattrMode::Create,
+        ctypes::XATTR_REPLACE => XattrMode::Replace,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let value = if size == 0 {
+        &[][..]
+    } else if value.is_null() {
+        return Err(LinuxError::EFAULT);
+    } else {
+        unsafe { core::slice::from_raw_parts(value as *const u8, size) }
+    };
+    if !file.set(name, value, mode)? {
+        return Err(LinuxError::ENODATA);
+    }
+    Ok(0)
+}
+
+/// Remove the extended attribute `name` of `file`.
+fn remove_xattr(file: XattrFile, name: *const c_char) -> LinuxResult<c_int> {
+    let name = char_ptr_to_str(name)?;
+    debug!("removexattr <= {:?} {:?}", file, name);
+    if !file.remove(name)? {
+        return Err(LinuxError::ENODATA);
+    }
+    Ok(0)
+}
+
+/// Get the value of the extended attribute `name` of the file `path` into
+/// `value` of `size` bytes. If `size` is 0, only the size of the value is
+/// returned.
+///
+/// Only files in the RAM filesystem have extended attributes, others fail
+/// with `ENOTSUP`. Return the size of the value, or -1 with `ENODATA` if
+/// there is no such attribute and `ERANGE` if `size` is too small.
+pub unsafe fn sys_getxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_getxattr, {
+        let file = XattrFile::Path(char_ptr_to_str(path)?, true);
+        get_xattr(file, name, value, size)
+    })
+}
+
+/// Same as [`sys_getxattr`], but a symbolic link itself is accessed.
+pub unsafe fn sys_lgetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_lgetxattr, {
+        let file = XattrFile::Path(char_ptr_to_str(path)?, false);
+        get_xattr(file, name, value, size)
+    })
+}
+
+/// Same as [`sys_getxattr`], but for the file indicated by `fd`.
+pub unsafe fn sys_fgetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_fgetxattr, {
+        get_xattr(XattrFile::Fd(fd), name, value, size)
+    })
+}
+
+/// Get the names of the extended attributes of the file `path` into `list` of
+/// `size` bytes, each followed by a null byte. If `size` is 0, only the size
+/// of the list is returned.
+///
+/// Return the size of the list, or -1 with `ERANGE` if `size` is too small.
+pub unsafe fn sys_listxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_listxattr, {
+        list_xattrs(XattrFile::Path(char_ptr_to_str(path)?, true), list, size)
+    })
+}
+
+/// Same as [`sys_listxattr`], but a symbolic link itself is accessed.
+pub unsafe fn sys_llistxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_llistxattr, {
+        list_xattrs(XattrFile::Path(char_ptr_to_str(path)?, false), list, size)
+    })
+}
+
+/// Same as [`sys_listxattr`], but for the file indicated by `fd`.
+pub unsafe fn sys_flistxattr(fd: c_int, list: *mut c_char, size: usize) -> ctypes::ssize_t {
+    syscall_body!(sys_flistxattr, list_xattrs(XattrFile::Fd(fd), list, size))
+}
+
+/// Set the extended attribute `name` of the file `path` to `value` of `size`
+/// bytes.
+///
+/// With `XATTR_CREATE` in `flags` it fails with `EEXIST` if the attribute
+/// exists, and with `XATTR_REPLACE` with `ENODATA` if it does not. Return 0
+/// if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_setxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_setxattr, {
+        let file = XattrFile::Path(char_ptr_to_str(path)?, true);
+        set_xattr(file, name, value, size, flags)
+    })
+}
+
+/// Same as [`sys_setxattr`], but a symbolic link itself is changed.
+pub unsafe fn sys_lsetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_lsetxattr, {
+        let file = XattrFile::Path(char_ptr_to_str(path)?, false);
+        set_xattr(file, name, value, size, flags)
+    })
+}
+
+/// Same as [`sys_setxattr`], but for the file indicated by `fd`.
+pub unsafe fn sys_fsetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_fsetxattr, {
+        set_xattr(XattrFile::Fd(fd), name, value, size, flags)
+    })
+}
+
+/// Remove the extended attribute `name` of the file `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1 with `ENODATA` if
+/// there is no such attribute.
+pub fn sys_removexattr(path: *const c_char, name: *const c_char) -> c_int {
+    syscall_body!(sys_removexattr, {
+        remove_xattr(XattrFile::Path(char_ptr_to_str(path)?, true), name)
+    })
+}
+
+/// Same as [`sys_removexattr`], but a symbolic link itself is changed.
+pub fn sys_lremovexattr(path: *const c_char, name: *const c_char) -> c_int {
+    syscall_body!(sys_lremovexattr, {
+        remove_xattr(XattrFile::Path(char_ptr_to_str(path)?, false), name)
+    })
+}
+
+/// Same as [`sys_removexattr`], but for the file indicated by `fd`.
+pub fn sys_fremovexattr(fd: c_int, name: *const c_char) -> c_int {
+    syscall_body!(sys_fremovexattr, remove_xattr(XattrFile::Fd(fd), name))
+}
@@ -0,0 +1,157 @@
+use alloc::sync::Arc;
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::notify::{WatchEvent, WatchMask, Watcher};
+use axio::PollState;
+
+use super::fd_ops::{add_file_like, get_file_like, FileLike};
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// The size of a `struct inotify_event` with the name of `event`, which is
+/// padded with NULs to align the next event.
+fn event_len(event: &WatchEvent) -> usize {
+    let header = size_of::<ctypes::inotify_event>();
+    match &event.name {
+        Some(name) => header + (name.len() + 1).next_multiple_of(header),
+        None => header,
+    }
+}
+
+pub struct Inotify {
+    watcher: Watcher,
+    nonblocking: AtomicBool,
+}
+
+impl Inotify {
+    fn new(nonblocking: bool) -> Self {
+        Self {
+            watcher: Watcher::new(),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+}
+
+impl FileLike for Inotify {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let events = loop {
+            let mut len = 0;
+            let events = self.watcher.take_events(|event| {
+                len += event_len(event);
+                len <= buf.len()
+            });
+            if !events.is_empty() {
+                break events;
+            } else if self.watcher.has_events() {
+                return Err(LinuxError::EINVAL); // too small for the next event
+            } else if self.nonblocking.load(Ordering::Relaxed) {
+                return Err(LinuxError::EAGAIN);
+            }
+            crate::sys_sched_yield(); // TODO: use synconize primitive
+        };
+
+        let mut offset = 0;
+        for event in events {
+            let len = event_len(&event);
+            let header_len = size_of::<ctypes::inotify_event>();
+            let buf = &mut buf[offset..offset + len];
+            buf.fill(0);
+            // the fields `wd`, `mask`, `cookie` and `len` of the header
+            let fields = [
+                event.wd as u32,
+                event.mask.bits(),
+                event.cookie,
+                (len - header_len) as u32,
+            ];
+            for (field, value) in buf.chunks_exact_mut(4).zip(fields) {
+                field.copy_from_slice(&value.to_ne_bytes());
+            }
+            if let Some(name) = &event.name {
+                buf[header_len..header_len + name.len()].copy_from_slice(name.as_bytes());
+            }
+            offset += len;
+        }
+        Ok(offset)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o600u32; // rw-------
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.watcher.has_events(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Create an inotify instance, whose events are read from the returned file
+/// descriptor as `struct inotify_event`s.
+///
+/// `flags` can contain `IN_NONBLOCK` and `IN_CLOEXEC`.
+pub fn sys_inotify_init1(flags: c_int) -> c_int {
+    debug!("sys_inotify_init1 <= {:#x}", flags);
+    syscall_body!(sys_inotify_init1, {
+        let flags = flags as u32;
+        if flags & !(ctypes::IN_NONBLOCK | ctypes::IN_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let nonblocking = flags & ctypes::IN_NONBLOCK != 0;
+        add_file_like(Arc::new(Inotify::new(nonblocking)))
+    })
+}
+
+/// Watch the file or directory at `path` for the events in `mask`, with the
+/// inotify instance `fd`.
+///
+/// Return the watch descriptor, which is the same if `path` is already
+/// watched, whose mask is replaced. Flags other than the events are ignored.
+pub fn sys_inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_inotify_add_watch <= {} {:?} {:#x}", fd, path, mask);
+    syscall_body!(sys_inotify_add_watch, {
+        let inotify = Inotify::from_fd(fd)?;
+        let wd = inotify
+            .watcher
+            .add_watch(path?, WatchMask::from_bits_truncate(mask))?;
+        Ok(wd)
+    })
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    debug!("sys_inotify_rm_watch <= {} {}", fd, wd);
+    syscall_body!(sys_inotify_rm_watch, {
+        Inotify::from_fd(fd)?.watcher.remove_watch(wd)?;
+        Ok(0)
+    })
+}
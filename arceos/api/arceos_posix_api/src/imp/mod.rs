@@ -9,7 +9,11 @@ pub mod time;
 #[cfg(feature = "fd")]
 pub mod fd_ops;
 #[cfg(feature = "fs")]
+pub mod file_lock;
+#[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(feature = "fs")]
+pub mod inotify;
 #[cfg(any(feature = "select", feature = "epoll"))]
 pub mod io_mpx;
 #[cfg(feature = "net")]
@@ -1,20 +1,25 @@
 use alloc::{sync::Arc, vec, vec::Vec};
 use core::ffi::{c_char, c_int, c_void};
 use core::mem::size_of;
-use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
-use axnet::{TcpSocket, UdpSocket};
+use axnet::{LinkAddr, PacketSocket, PacketType, RawSocket, TcpSocket, UdpSocket};
 use axsync::Mutex;
 
 use super::fd_ops::FileLike;
 use crate::ctypes;
 use crate::utils::char_ptr_to_str;
 
+/// The hardware type of `sockaddr_ll`, as all interfaces are Ethernet.
+const ARPHRD_ETHER: u16 = 1;
+
 pub enum Socket {
     Udp(Mutex<UdpSocket>),
     Tcp(Mutex<TcpSocket>),
+    Raw(Mutex<RawSocket>),
+    Packet(Mutex<PacketSocket>),
 }
 
 impl Socket {
@@ -33,6 +38,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().send(buf)?),
+            Socket::Packet(packetsocket) => Ok(packetsocket.lock().send(buf)?),
         }
     }
 
@@ -40,6 +47,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().recv_from(buf).map(|e| e.0)?),
+            Socket::Packet(packetsocket) => Ok(packetsocket.lock().recv_from(buf).map(|e| e.0)?),
         }
     }
 
@@ -47,6 +56,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().poll()?),
+            Socket::Packet(packetsocket) => Ok(packetsocket.lock().poll()?),
         }
     }
 
@@ -54,6 +65,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+            Socket::Raw(rawsocket) => Ok(SocketAddr::new(rawsocket.lock().local_addr()?, 0)),
+            Socket::Packet(_) => Err(LinuxError::EAFNOSUPPORT),
         }
     }
 
@@ -61,6 +74,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+            Socket::Raw(rawsocket) => Ok(SocketAddr::new(rawsocket.lock().peer_addr()?, 0)),
+            Socket::Packet(_) => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
@@ -68,6 +83,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().bind(addr.ip())?),
+            Socket::Packet(_) => Err(LinuxError::EAFNOSUPPORT),
         }
     }
 
@@ -75,6 +92,8 @@ impl Socket {
         match self {
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().connect(addr)?),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().connect(addr.ip())?),
+            Socket::Packet(_) => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
@@ -83,6 +102,8 @@ impl Socket {
             // diff: must bind before sendto
             Socket::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
             Socket::Tcp(_) => Err(LinuxError::EISCONN),
+            Socket::Raw(rawsocket) => Ok(rawsocket.lock().send_to(buf, addr.ip())?),
+            Socket::Packet(_) => Err(LinuxError::EAFNOSUPPORT),
         }
     }
 
@@ -94,20 +115,28 @@ impl Socket {
                 .recv_from(buf)
                 .map(|res| (res.0, Some(res.1)))?),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            Socket::Raw(rawsocket) => Ok(rawsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, Some(SocketAddr::new(res.1, 0))))?),
+            Socket::Packet(packetsocket) => Ok(packetsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, None))?),
         }
     }
 
     fn listen(&self) -> LinuxResult {
         match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
     fn accept(&self) -> LinuxResult<TcpSocket> {
         match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
             Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
@@ -126,6 +155,13 @@ impl Socket {
                 tcpsocket.shutdown()?;
                 Ok(())
             }
+
+            Socket::Raw(rawsocket) => {
+                rawsocket.lock().peer_addr()?;
+                Ok(())
+            }
+
+            Socket::Packet(_) => Err(LinuxError::EOPNOTSUPP),
         }
     }
 }
@@ -165,6 +201,8 @@ impl FileLike for Socket {
         match self {
             Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
             Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+            Socket::Raw(rawsocket) => rawsocket.lock().set_nonblocking(nonblock),
+            Socket::Packet(packetsocket) => packetsocket.lock().set_nonblocking(nonblock),
         }
         Ok(())
     }
@@ -194,17 +232,88 @@ impl From<ctypes::sockaddr_in> for SocketAddrV4 {
     }
 }
 
-fn into_sockaddr(addr: SocketAddr) -> (ctypes::sockaddr, ctypes::socklen_t) {
+impl From<SocketAddrV6> for ctypes::sockaddr_in6 {
+    fn from(addr: SocketAddrV6) -> ctypes::sockaddr_in6 {
+        let mut sin6_addr = ctypes::in6_addr::default();
+        sin6_addr.__in6_union.__s6_addr = addr.ip().octets();
+        ctypes::sockaddr_in6 {
+            sin6_family: ctypes::AF_INET6 as u16,
+            sin6_port: addr.port().to_be(),
+            sin6_flowinfo: addr.flowinfo().to_be(),
+            sin6_addr,
+            sin6_scope_id: addr.scope_id(),
+        }
+    }
+}
+
+impl From<ctypes::sockaddr_in6> for SocketAddrV6 {
+    fn from(addr: ctypes::sockaddr_in6) -> SocketAddrV6 {
+        SocketAddrV6::new(
+            Ipv6Addr::from(unsafe { addr.sin6_addr.__in6_union.__s6_addr }),
+            u16::from_be(addr.sin6_port),
+            u32::from_be(addr.sin6_flowinfo),
+            addr.sin6_scope_id,
+        )
+    }
+}
+
+impl From<LinkAddr> for ctypes::sockaddr_ll {
+    fn from(addr: LinkAddr) -> ctypes::sockaddr_ll {
+        let mut sll_addr = [0; 8];
+        sll_addr[..6].copy_from_slice(&addr.hwaddr);
+        let sll_pkttype = match addr.pkttype {
+            PacketType::Host => ctypes::PACKET_HOST,
+            PacketType::Broadcast => ctypes::PACKET_BROADCAST,
+            PacketType::Multicast => ctypes::PACKET_MULTICAST,
+            PacketType::OtherHost => ctypes::PACKET_OTHERHOST,
+            PacketType::Outgoing => ctypes::PACKET_OUTGOING,
+        };
+        ctypes::sockaddr_ll {
+            sll_family: ctypes::AF_PACKET as u16,
+            sll_protocol: addr.protocol.to_be(),
+            sll_ifindex: addr.ifindex as c_int,
+            sll_hatype: ARPHRD_ETHER,
+            sll_pkttype: sll_pkttype as u8,
+            sll_halen: 6,
+            sll_addr,
+        }
+    }
+}
+
+/// Writes `sa` to the user buffer `addr_ptr` of `*addrlen` bytes, and sets
+/// `*addrlen` to the real size of the address. The address is truncated if
+/// the buffer is too small.
+unsafe fn write_addr<T>(sa: T, addr_ptr: *mut ctypes::sockaddr, addrlen: *mut ctypes::socklen_t) {
+    let len = (*addrlen as usize).min(size_of::<T>());
+    core::ptr::copy_nonoverlapping(&sa as *const T as *const u8, addr_ptr as *mut u8, len);
+    *addrlen = size_of::<T>() as _;
+}
+
+/// Writes `addr` to the user buffer `addr_ptr` of `*addrlen` bytes, and sets
+/// `*addrlen` to the real size of the address. The address is truncated if
+/// the buffer is too small.
+unsafe fn write_sockaddr(
+    addr: SocketAddr,
+    addr_ptr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) {
     debug!("    Sockaddr: {}", addr);
     match addr {
-        SocketAddr::V4(addr) => (
-            unsafe { *(&ctypes::sockaddr_in::from(addr) as *const _ as *const ctypes::sockaddr) },
-            size_of::<ctypes::sockaddr>() as _,
-        ),
-        SocketAddr::V6(_) => panic!("IPv6 is not supported"),
+        SocketAddr::V4(addr) => write_addr(ctypes::sockaddr_in::from(addr), addr_ptr, addrlen),
+        SocketAddr::V6(addr) => write_addr(ctypes::sockaddr_in6::from(addr), addr_ptr, addrlen),
     }
 }
 
+/// Writes the link-layer `addr` of a packet socket like [`write_sockaddr`].
+unsafe fn write_sockaddr_ll(
+    addr: LinkAddr,
+    addr_ptr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) {
+    debug!("    Sockaddr: {:?}", addr);
+    write_addr(ctypes::sockaddr_ll::from(addr), addr_ptr, addrlen)
+}
+
 fn from_sockaddr(
     addr: *const ctypes::sockaddr,
     addrlen: ctypes::socklen_t,
@@ -212,20 +321,45 @@ fn from_sockaddr(
     if addr.is_null() {
         return Err(LinuxError::EFAULT);
     }
-    if addrlen != size_of::<ctypes::sockaddr>() as _ {
-        return Err(LinuxError::EINVAL);
-    }
-
-    let mid = unsafe { *(addr as *const ctypes::sockaddr_in) };
-    if mid.sin_family != ctypes::AF_INET as u16 {
+    if (addrlen as usize) < size_of::<ctypes::sa_family_t>() {
         return Err(LinuxError::EINVAL);
     }
 
-    let res = SocketAddr::V4(mid.into());
+    let res = match unsafe { (*addr).sa_family } as u32 {
+        ctypes::AF_INET if addrlen as usize >= size_of::<ctypes::sockaddr_in>() => {
+            SocketAddr::V4(unsafe { *(addr as *const ctypes::sockaddr_in) }.into())
+        }
+        ctypes::AF_INET6 if addrlen as usize >= size_of::<ctypes::sockaddr_in6>() => {
+            SocketAddr::V6(unsafe { *(addr as *const ctypes::sockaddr_in6) }.into())
+        }
+        ctypes::AF_INET | ctypes::AF_INET6 => return Err(LinuxError::EINVAL),
+        _ => return Err(LinuxError::EAFNOSUPPORT),
+    };
     debug!("    load sockaddr:{:#x} => {:?}", addr as usize, res);
     Ok(res)
 }
 
+/// Loads the link-layer address of a packet socket, and returns the interface
+/// index and the protocol in host byte order.
+fn from_sockaddr_ll(
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> LinuxResult<(usize, u16)> {
+    if addr.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if (addrlen as usize) < size_of::<ctypes::sockaddr_ll>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let addr = unsafe { *(addr as *const ctypes::sockaddr_ll) };
+    if addr.sll_family as u32 != ctypes::AF_PACKET {
+        return Err(LinuxError::EINVAL);
+    }
+    let ifindex = usize::try_from(addr.sll_ifindex).map_err(|_| LinuxError::EINVAL)?;
+    debug!("    load sockaddr_ll: ifindex {}", ifindex);
+    Ok((ifindex, u16::from_be(addr.sll_protocol)))
+}
+
 /// Create an socket for communication.
 ///
 /// Return the socket file descriptor.
@@ -234,14 +368,26 @@ pub fn sys_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
     let (domain, socktype, protocol) = (domain as u32, socktype as u32, protocol as u32);
     syscall_body!(sys_socket, {
         match (domain, socktype, protocol) {
-            (ctypes::AF_INET, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
-            | (ctypes::AF_INET, ctypes::SOCK_STREAM, 0) => {
+            (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
+            | (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_STREAM, 0) => {
                 Socket::Tcp(Mutex::new(TcpSocket::new())).add_to_fd_table()
             }
-            (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
-            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => {
+            (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
+            | (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_DGRAM, 0) => {
                 Socket::Udp(Mutex::new(UdpSocket::new())).add_to_fd_table()
             }
+            // sending IP headers with `IPPROTO_RAW` is not supported
+            (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_RAW, 0 | ctypes::IPPROTO_RAW..) => {
+                Err(LinuxError::EPROTONOSUPPORT)
+            }
+            (ctypes::AF_INET | ctypes::AF_INET6, ctypes::SOCK_RAW, _) => {
+                let ipv6 = domain == ctypes::AF_INET6;
+                Socket::Raw(Mutex::new(RawSocket::new(ipv6, protocol as u8))).add_to_fd_table()
+            }
+            (ctypes::AF_PACKET, ctypes::SOCK_RAW, _) => {
+                let protocol = u16::from_be(protocol as u16);
+                Socket::Packet(Mutex::new(PacketSocket::new(protocol))).add_to_fd_table()
+            }
             _ => Err(LinuxError::EINVAL),
         }
     })
@@ -260,8 +406,14 @@ pub fn sys_bind(
         socket_fd, socket_addr as usize, addrlen
     );
     syscall_body!(sys_bind, {
+        let socket = Socket::from_fd(socket_fd)?;
+        if let Socket::Packet(packetsocket) = &*socket {
+            let (ifindex, protocol) = from_sockaddr_ll(socket_addr, addrlen)?;
+            packetsocket.lock().bind(ifindex, protocol)?;
+            return Ok(0);
+        }
         let addr = from_sockaddr(socket_addr, addrlen)?;
-        Socket::from_fd(socket_fd)?.bind(addr)?;
+        socket.bind(addr)?;
         Ok(0)
     })
 }
@@ -304,9 +456,14 @@ pub fn sys_sendto(
         if buf_ptr.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let addr = from_sockaddr(socket_addr, addrlen)?;
+        let socket = Socket::from_fd(socket_fd)?;
         let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, len) };
-        Socket::from_fd(socket_fd)?.sendto(buf, addr)
+        if let Socket::Packet(packetsocket) = &*socket {
+            let (ifindex, _) = from_sockaddr_ll(socket_addr, addrlen)?;
+            return Ok(packetsocket.lock().send_to(buf, ifindex)?);
+        }
+        let addr = from_sockaddr(socket_addr, addrlen)?;
+        socket.sendto(buf, addr)
     })
 }
 
@@ -354,11 +511,14 @@ pub unsafe fn sys_recvfrom(
         let socket = Socket::from_fd(socket_fd)?;
         let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
 
+        if let Socket::Packet(packetsocket) = &*socket {
+            let (len, addr) = packetsocket.lock().recv_from(buf)?;
+            unsafe { write_sockaddr_ll(addr, socket_addr, addrlen) };
+            return Ok(len);
+        }
         let res = socket.recvfrom(buf)?;
         if let Some(addr) = res.1 {
-            unsafe {
-                (*socket_addr, *addrlen) = into_sockaddr(addr);
-            }
+            unsafe { write_sockaddr(addr, socket_addr, addrlen) };
         }
         Ok(res.0)
     })
@@ -420,9 +580,7 @@ pub unsafe fn sys_accept(
         let new_socket = socket.accept()?;
         let addr = new_socket.peer_addr()?;
         let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
-        unsafe {
-            (*socket_addr, *socket_len) = into_sockaddr(addr);
-        }
+        unsafe { write_sockaddr(addr, socket_addr, socket_len) };
         Ok(new_fd)
     })
 }
@@ -443,14 +601,15 @@ pub fn sys_shutdown(
 
 /// Query addresses for a domain name.
 ///
-/// Only IPv4. Ports are always 0. Ignore servname and hint.
+/// Both IPv4 and IPv6 addresses are returned unless `ai_family` of the hint
+/// is `AF_INET` or `AF_INET6`. Other fields of the hint are ignored.
 /// Results' ai_flags and ai_canonname are 0 or NULL.
 ///
 /// Return address number if success.
 pub unsafe fn sys_getaddrinfo(
     nodename: *const c_char,
     servname: *const c_char,
-    _hints: *const ctypes::addrinfo,
+    hints: *const ctypes::addrinfo,
     res: *mut *mut ctypes::addrinfo,
 ) -> c_int {
     let name = char_ptr_to_str(nodename);
@@ -464,16 +623,35 @@ pub unsafe fn sys_getaddrinfo(
             return Err(LinuxError::EFAULT);
         }
 
+        let family = if hints.is_null() {
+            ctypes::AF_UNSPEC
+        } else {
+            unsafe { (*hints).ai_family as u32 }
+        };
+        if !matches!(
+            family,
+            ctypes::AF_UNSPEC | ctypes::AF_INET | ctypes::AF_INET6
+        ) {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+
         let port = port.map_or(0, |p| p.parse::<u16>().unwrap_or(0));
-        let ip_addrs = if let Ok(domain) = name {
+        let mut ip_addrs = if let Ok(domain) = name {
             if let Ok(a) = domain.parse::<IpAddr>() {
                 vec![a]
             } else {
                 axnet::dns_query(domain)?
             }
+        } else if family == ctypes::AF_INET6 {
+            vec![Ipv6Addr::LOCALHOST.into()]
         } else {
             vec![Ipv4Addr::LOCALHOST.into()]
         };
+        ip_addrs.retain(|ip| match family {
+            ctypes::AF_INET => ip.is_ipv4(),
+            ctypes::AF_INET6 => ip.is_ipv6(),
+            _ => true,
+        });
 
         let len = ip_addrs.len().min(ctypes::MAXADDRS as usize);
         if len == 0 {
@@ -482,31 +660,42 @@ pub unsafe fn sys_getaddrinfo(
 
         let mut out: Vec<ctypes::aibuf> = Vec::with_capacity(len);
         for (i, &ip) in ip_addrs.iter().enumerate().take(len) {
-            let buf = match ip {
-                IpAddr::V4(ip) => ctypes::aibuf {
-                    ai: ctypes::addrinfo {
-                        ai_family: ctypes::AF_INET as _,
-                        // TODO: This is a hard-code part, only return TCP parameters
-                        ai_socktype: ctypes::SOCK_STREAM as _,
-                        ai_protocol: ctypes::IPPROTO_TCP as _,
-                        ai_addrlen: size_of::<ctypes::sockaddr_in>() as _,
-                        ai_addr: core::ptr::null_mut(),
-                        ai_canonname: core::ptr::null_mut(),
-                        ai_next: core::ptr::null_mut(),
-                        ai_flags: 0,
-                    },
-                    sa: ctypes::aibuf_sa {
+            let (ai_family, ai_addrlen, sa) = match ip {
+                IpAddr::V4(ip) => (
+                    ctypes::AF_INET,
+                    size_of::<ctypes::sockaddr_in>(),
+                    ctypes::aibuf_sa {
                         sin: SocketAddrV4::new(ip, port).into(),
                     },
-                    slot: i as i16,
-                    lock: [0],
-                    ref_: 0,
+                ),
+                IpAddr::V6(ip) => (
+                    ctypes::AF_INET6,
+                    size_of::<ctypes::sockaddr_in6>(),
+                    ctypes::aibuf_sa {
+                        sin6: SocketAddrV6::new(ip, port, 0, 0).into(),
+                    },
+                ),
+            };
+            let buf = ctypes::aibuf {
+                ai: ctypes::addrinfo {
+                    ai_family: ai_family as _,
+                    // TODO: This is a hard-code part, only return TCP parameters
+                    ai_socktype: ctypes::SOCK_STREAM as _,
+                    ai_protocol: ctypes::IPPROTO_TCP as _,
+                    ai_addrlen: ai_addrlen as _,
+                    ai_addr: core::ptr::null_mut(),
+                    ai_canonname: core::ptr::null_mut(),
+                    ai_next: core::ptr::null_mut(),
+                    ai_flags: 0,
                 },
-                _ => panic!("IPv6 is not supported"),
+                sa,
+                slot: i as i16,
+                lock: [0],
+                ref_: 0,
             };
             out.push(buf);
             out[i].ai.ai_addr =
-                unsafe { core::ptr::addr_of_mut!(out[i].sa.sin) as *mut ctypes::sockaddr };
+                unsafe { core::ptr::addr_of_mut!(out[i].sa) as *mut ctypes::sockaddr };
             if i > 0 {
                 out[i - 1].ai.ai_next = core::ptr::addr_of_mut!(out[i].ai);
             }
@@ -546,12 +735,13 @@ pub unsafe fn sys_getsockname(
         if addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
-            return Err(LinuxError::EINVAL);
-        }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?);
+        let socket = Socket::from_fd(sock_fd)?;
+        if let Socket::Packet(packetsocket) = &*socket {
+            unsafe { write_sockaddr_ll(packetsocket.lock().local_addr(), addr, addrlen) };
+            return Ok(0);
         }
+        let sockaddr = socket.local_addr()?;
+        unsafe { write_sockaddr(sockaddr, addr, addrlen) };
         Ok(0)
     })
 }
@@ -570,12 +760,8 @@ pub unsafe fn sys_getpeername(
         if addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
-            return Err(LinuxError::EINVAL);
-        }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?);
-        }
+        let sockaddr = Socket::from_fd(sock_fd)?.peer_addr()?;
+        unsafe { write_sockaddr(sockaddr, addr, addrlen) };
         Ok(0)
     })
 }
@@ -57,6 +57,8 @@ impl Pthread {
             let ret = start_routine(arg.0);
             unsafe { *their_packet.result.get() = ret };
             drop(their_packet);
+            #[cfg(feature = "fs")]
+            crate::imp::file_lock::release_task_records();
         };
 
         let task_inner = axtask::spawn(main);
@@ -85,6 +87,8 @@ impl Pthread {
     fn exit_current(retval: *mut c_void) -> ! {
         let thread = Self::current().expect("fail to get current thread");
         unsafe { *thread.retval.result.get() = retval };
+        #[cfg(feature = "fs")]
+        crate::imp::file_lock::release_task_records();
         axtask::exit(0);
     }
 
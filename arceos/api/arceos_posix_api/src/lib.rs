@@ -39,7 +39,22 @@ pub use imp::time::{sys_clock_gettime, sys_nanosleep};
 #[cfg(feature = "fd")]
 pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl, get_file_like};
 #[cfg(feature = "fs")]
-pub use imp::fs::{sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat};
+pub use imp::file_lock::sys_flock;
+#[cfg(feature = "fs")]
+pub use imp::fs::{
+    sys_chmod, sys_chown, sys_fallocate, sys_fchmod, sys_fchown, sys_fdatasync, sys_fstat,
+    sys_fstatfs, sys_fsync, sys_getcwd, sys_getegid, sys_geteuid, sys_getgid, sys_getuid,
+    sys_lchown, sys_link, sys_lseek, sys_lstat, sys_mount, sys_open, sys_readlink, sys_rename,
+    sys_setgid, sys_setuid, sys_stat, sys_statfs, sys_symlink, sys_sync, sys_umount2,
+    sys_utimensat,
+};
+#[cfg(feature = "fs")]
+pub use imp::fs::{
+    sys_fgetxattr, sys_flistxattr, sys_fremovexattr, sys_fsetxattr, sys_getxattr, sys_lgetxattr,
+    sys_listxattr, sys_llistxattr, sys_lremovexattr, sys_lsetxattr, sys_removexattr, sys_setxattr,
+};
+#[cfg(feature = "fs")]
+pub use imp::inotify::{sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch};
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
 #[cfg(feature = "epoll")]
@@ -1,12 +1,16 @@
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::meta::{node_attrs, NodeAttrs};
+use crate::symlink::SymlinkNode;
+use crate::usage::NodeCharge;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -15,17 +19,26 @@ pub struct DirNode {
     this: Weak<DirNode>,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    attrs: NodeAttrs,
+    pub(crate) charge: NodeCharge,
 }
 
 impl DirNode {
-    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>, charge: NodeCharge) -> Arc<Self> {
         Arc::new_cyclic(|this| Self {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            attrs: NodeAttrs::new(VfsNodePerm::default_dir(), charge.usage().clone()),
+            charge,
         })
     }
 
+    /// Returns the ownership, permissions and timestamps of this directory.
+    pub fn attrs(&self) -> &NodeAttrs {
+        &self.attrs
+    }
+
     pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
         *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
     }
@@ -46,15 +59,58 @@ impl DirNode {
             log::error!("AlreadyExists {}", name);
             return Err(VfsError::AlreadyExists);
         }
+        let charge = || NodeCharge::new(self.charge.usage());
         let node: VfsNodeRef = match ty {
-            VfsNodeType::File => Arc::new(FileNode::new()),
-            VfsNodeType::Dir => Self::new(Some(self.this.clone())),
+            VfsNodeType::File => Arc::new(FileNode::new(charge()?)),
+            VfsNodeType::Dir => Self::new(Some(self.this.clone()), charge()?),
+            VfsNodeType::SymLink => Arc::new(SymlinkNode::new(charge()?)),
             _ => return Err(VfsError::Unsupported),
         };
+        if let Some(nlink) = link_count(&node) {
+            nlink.store(1, Ordering::Release);
+        }
         self.children.write().insert(name.into(), node);
+        self.attrs.touch_modify();
         Ok(())
     }
 
+    /// Adds a hard link to `node` with the given name in this directory.
+    ///
+    /// Only files and symbolic links of the RAM filesystem can be linked.
+    pub fn link(&self, name: &str, node: &VfsNodeRef) -> VfsResult {
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let Some(nlink) = link_count(node) else {
+            return Err(if node.get_attr()?.is_dir() {
+                VfsError::PermissionDenied
+            } else {
+                VfsError::Unsupported
+            });
+        };
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        nlink.fetch_add(1, Ordering::AcqRel);
+        children.insert(name.into(), node.clone());
+        if let Some(attrs) = node_attrs(node) {
+            attrs.touch_change();
+        }
+        self.attrs.touch_modify();
+        Ok(())
+    }
+
+    /// Returns the number of hard links to this directory, which are the entry
+    /// in its parent, its own `.` and the `..` of each subdirectory.
+    pub fn nlink(&self) -> usize {
+        let children = self.children.read();
+        2 + children
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>())
+            .count()
+    }
+
     /// Removes a node by the given name in this directory.
     pub fn remove_node(&self, name: &str) -> VfsResult {
         let mut children = self.children.write();
@@ -64,14 +120,26 @@ impl DirNode {
                 return Err(VfsError::DirectoryNotEmpty);
             }
         }
+        if let Some(nlink) = link_count(node) {
+            nlink.fetch_sub(1, Ordering::AcqRel);
+        }
+        if let Some(attrs) = node_attrs(node) {
+            attrs.touch_change();
+        }
         children.remove(name);
+        self.attrs.touch_modify();
         Ok(())
     }
 }
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        Ok(VfsNodeAttr::new(
+            self.attrs.perm(),
+            VfsNodeType::Dir,
+            4096,
+            0,
+        ))
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
@@ -99,6 +167,7 @@ impl VfsNodeOps for DirNode {
     }
 
     fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.attrs.touch_access();
         let children = self.children.read();
         let mut children = children.iter().skip(start_idx.max(2) - 2);
         for (i, ent) in dirents.iter_mut().enumerate() {
@@ -165,7 +234,35 @@ impl VfsNodeOps for DirNode {
         }
     }
 
-    axfs_vfs::impl_vfs_dir_default! {}
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // there is nothing to write back
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the link counter of a node that can be hard linked.
+fn link_count(node: &VfsNodeRef) -> Option<&AtomicUsize> {
+    let any = node.as_any();
+    if let Some(file) = any.downcast_ref::<FileNode>() {
+        Some(&file.nlink)
+    } else {
+        any.downcast_ref::<SymlinkNode>().map(|link| &link.nlink)
+    }
 }
 
 fn split_path(path: &str) -> (&str, Option<&str>) {
@@ -1,56 +1,270 @@
-use alloc::vec::Vec;
-use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
 use spin::RwLock;
 
+use crate::meta::NodeAttrs;
+use crate::usage::{NodeCharge, Usage};
+
+/// The size of the chunks the content of a file is stored in.
+pub const PAGE_SIZE: usize = 4096;
+
+type Page = Box<[u8; PAGE_SIZE]>;
+
+/// The content of a file, stored in pages indexed by their offsets divided
+/// by [`PAGE_SIZE`]. Missing pages are holes that read as zeros, and bytes
+/// after the end of the file are always zeros.
+///
+/// The pages are counted in the [`Usage`] of the filesystem.
+struct Content {
+    size: u64,
+    pages: BTreeMap<u64, Page>,
+    usage: Arc<Usage>,
+}
+
+impl Content {
+    /// Allocates the pages from `first` to `last` that are in holes, or
+    /// returns [`StorageFull`](VfsError::StorageFull) without allocating any
+    /// if the filesystem has no space for them.
+    fn reserve(&mut self, first: u64, last: u64) -> VfsResult {
+        let present = self.pages.range(first..=last).count() as u64;
+        let missing = last - first + 1 - present;
+        if missing == 0 {
+            return Ok(());
+        }
+        self.usage.charge_bytes(missing * PAGE_SIZE as u64)?;
+        for index in first..=last {
+            self.pages
+                .entry(index)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+        }
+        Ok(())
+    }
+
+    /// Frees the page at `index` if it is allocated.
+    fn remove_page(&mut self, index: u64) {
+        if self.pages.remove(&index).is_some() {
+            self.usage.release_bytes(PAGE_SIZE as u64);
+        }
+    }
+
+    /// Zeros the bytes in `[start, end)`, which must be within one page, and
+    /// frees the page if it has nothing but zeros left.
+    fn zero_range(&mut self, start: u64, end: u64) {
+        let index = start / PAGE_SIZE as u64;
+        let range = (start % PAGE_SIZE as u64) as usize..(end - index * PAGE_SIZE as u64) as usize;
+        if let Some(page) = self.pages.get_mut(&index) {
+            page[range].fill(0);
+            if page.iter().all(|&b| b == 0) {
+                self.remove_page(index);
+            }
+        }
+    }
+
+    /// Calls `f` with the offset and the range in the page of every piece of
+    /// `[start, end)` in a different page.
+    fn for_each_piece(start: u64, end: u64, mut f: impl FnMut(u64, core::ops::Range<usize>)) {
+        let mut pos = start;
+        while pos < end {
+            let page_off = (pos % PAGE_SIZE as u64) as usize;
+            let len = (PAGE_SIZE - page_off).min((end - pos) as usize);
+            f(pos, page_off..page_off + len);
+            pos += len as u64;
+        }
+    }
+}
+
+impl Drop for Content {
+    fn drop(&mut self) {
+        self.usage
+            .release_bytes((self.pages.len() * PAGE_SIZE) as u64);
+    }
+}
+
+/// Returns the end of the range at `offset` of `len` bytes, or an error if it
+/// overflows.
+fn range_end(offset: u64, len: u64) -> VfsResult<u64> {
+    offset
+        .checked_add(len)
+        .filter(|&end| end <= i64::MAX as u64)
+        .ok_or(VfsError::InvalidInput)
+}
+
 /// The file node in the RAM filesystem.
 ///
-/// It implements [`axfs_vfs::VfsNodeOps`].
+/// The content is stored in pages of [`PAGE_SIZE`] bytes allocated when they
+/// are written to, so a sparse file only costs the memory of its data. It
+/// implements [`axfs_vfs::VfsNodeOps`].
 pub struct FileNode {
-    content: RwLock<Vec<u8>>,
+    content: RwLock<Content>,
+    attrs: NodeAttrs,
+    pub(super) nlink: AtomicUsize,
+    pub(crate) charge: NodeCharge,
 }
 
 impl FileNode {
-    pub(super) const fn new() -> Self {
+    pub(super) fn new(charge: NodeCharge) -> Self {
         Self {
-            content: RwLock::new(Vec::new()),
+            content: RwLock::new(Content {
+                size: 0,
+                pages: BTreeMap::new(),
+                usage: charge.usage().clone(),
+            }),
+            attrs: NodeAttrs::new(VfsNodePerm::default_file(), charge.usage().clone()),
+            nlink: AtomicUsize::new(0),
+            charge,
+        }
+    }
+
+    /// Returns the ownership, permissions and timestamps of this file.
+    pub fn attrs(&self) -> &NodeAttrs {
+        &self.attrs
+    }
+
+    /// Returns the number of hard links to this file.
+    pub fn nlink(&self) -> usize {
+        self.nlink.load(Ordering::Acquire)
+    }
+
+    /// Allocates the pages of the range at `offset` of `len` bytes, like
+    /// `fallocate(2)`. The file is extended to the end of the range unless
+    /// `keep_size` is `true`.
+    pub fn allocate(&self, offset: u64, len: u64, keep_size: bool) -> VfsResult {
+        let end = range_end(offset, len)?;
+        if len == 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut content = self.content.write();
+        content.reserve(offset / PAGE_SIZE as u64, (end - 1) / PAGE_SIZE as u64)?;
+        if !keep_size && end > content.size {
+            content.size = end;
+            drop(content);
+            self.attrs.touch_modify();
+        }
+        Ok(())
+    }
+
+    /// Deallocates the range at `offset` of `len` bytes, which reads as zeros
+    /// afterwards. The size of the file is not changed.
+    pub fn punch_hole(&self, offset: u64, len: u64) -> VfsResult {
+        let end = range_end(offset, len)?;
+        if len == 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut content = self.content.write();
+        Content::for_each_piece(offset, end, |pos, range| {
+            if range.len() == PAGE_SIZE {
+                content.remove_page(pos / PAGE_SIZE as u64);
+            } else {
+                content.zero_range(pos, pos + range.len() as u64);
+            }
+        });
+        drop(content);
+        self.attrs.touch_modify();
+        Ok(())
+    }
+
+    /// Returns the offset of the first data at or after `offset`, like
+    /// `lseek(2)` with `SEEK_DATA`, or `None` if there is no more data. Data
+    /// and holes are found in whole pages.
+    pub fn seek_data(&self, offset: u64) -> Option<u64> {
+        let content = self.content.read();
+        if offset >= content.size {
+            return None;
+        }
+        let (&index, _) = content.pages.range(offset / PAGE_SIZE as u64..).next()?;
+        let data = offset.max(index * PAGE_SIZE as u64);
+        (data < content.size).then_some(data)
+    }
+
+    /// Returns the offset of the first hole at or after `offset`, like
+    /// `lseek(2)` with `SEEK_HOLE`, or `None` if it is after the end of the
+    /// file. The end of the file counts as a hole.
+    pub fn seek_hole(&self, offset: u64) -> Option<u64> {
+        let content = self.content.read();
+        if offset >= content.size {
+            return None;
+        }
+        let mut index = offset / PAGE_SIZE as u64;
+        for &next in content.pages.range(index..).map(|(index, _)| index) {
+            if next != index {
+                break;
+            }
+            index += 1;
         }
+        Some(offset.max(index * PAGE_SIZE as u64).min(content.size))
     }
 }
 
 impl VfsNodeOps for FileNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_file(self.content.read().len() as _, 0))
+        let content = self.content.read();
+        Ok(VfsNodeAttr::new(
+            self.attrs.perm(),
+            VfsNodeType::File,
+            content.size,
+            (content.pages.len() * PAGE_SIZE / 512) as _,
+        ))
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
+        range_end(size, 0)?;
         let mut content = self.content.write();
-        if size < content.len() as u64 {
-            content.truncate(size as _);
-        } else {
-            content.resize(size as _, 0);
+        if size < content.size {
+            let keep = size.div_ceil(PAGE_SIZE as u64);
+            let removed = content.pages.split_off(&keep).len();
+            content.usage.release_bytes((removed * PAGE_SIZE) as u64);
+            if size % PAGE_SIZE as u64 != 0 {
+                content.zero_range(size, keep * PAGE_SIZE as u64);
+            }
         }
+        content.size = size;
+        drop(content);
+        self.attrs.touch_modify();
         Ok(())
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
         let content = self.content.read();
-        let start = content.len().min(offset as usize);
-        let end = content.len().min(offset as usize + buf.len());
-        let src = &content[start..end];
-        buf[..src.len()].copy_from_slice(src);
-        Ok(src.len())
+        let start = content.size.min(offset);
+        let end = content.size.min(offset.saturating_add(buf.len() as u64));
+        Content::for_each_piece(start, end, |pos, range| {
+            let dst = &mut buf[(pos - start) as usize..][..range.len()];
+            match content.pages.get(&(pos / PAGE_SIZE as u64)) {
+                Some(page) => dst.copy_from_slice(&page[range]),
+                None => dst.fill(0),
+            }
+        });
+        drop(content);
+        self.attrs.touch_access();
+        Ok((end - start) as usize)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let offset = offset as usize;
-        let mut content = self.content.write();
-        if offset + buf.len() > content.len() {
-            content.resize(offset + buf.len(), 0);
+        let end = range_end(offset, buf.len() as u64)?;
+        if buf.is_empty() {
+            return Ok(0);
         }
-        let dst = &mut content[offset..offset + buf.len()];
-        dst.copy_from_slice(&buf[..dst.len()]);
+        let mut content = self.content.write();
+        content.reserve(offset / PAGE_SIZE as u64, (end - 1) / PAGE_SIZE as u64)?;
+        Content::for_each_piece(offset, end, |pos, range| {
+            let src = &buf[(pos - offset) as usize..][..range.len()];
+            let page = content.pages.get_mut(&(pos / PAGE_SIZE as u64)).unwrap();
+            page[range].copy_from_slice(src);
+        });
+        content.size = content.size.max(end);
+        drop(content);
+        self.attrs.touch_modify();
         Ok(buf.len())
     }
 
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // there is nothing to write back
+    }
+
     impl_vfs_non_dir_default! {}
 }
@@ -8,18 +8,42 @@ extern crate alloc;
 
 mod dir;
 mod file;
+mod meta;
+mod symlink;
+mod usage;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::dir::DirNode;
-pub use self::file::FileNode;
+pub use self::file::{FileNode, PAGE_SIZE};
+pub use self::meta::{node_attrs, NodeAttrs, NodeMeta, XATTR_SPACE};
+pub use self::symlink::SymlinkNode;
+pub use self::usage::{node_usage, RamLimits, RamUsage};
 
 use alloc::sync::Arc;
 use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use core::time::Duration;
 use spin::once::Once;
 
+static CLOCK: Once<fn() -> Duration> = Once::new();
+
+/// Sets the clock that timestamps of nodes are read from, which returns the
+/// time elapsed since the epoch.
+///
+/// Only the first call takes effect. All timestamps are zero until it is set.
+pub fn set_clock(clock: fn() -> Duration) {
+    CLOCK.call_once(|| clock);
+}
+
+fn now() -> Duration {
+    CLOCK.get().map_or(Duration::ZERO, |clock| clock())
+}
+
 /// A RAM filesystem that implements [`axfs_vfs::VfsOps`].
+///
+/// Creating files, directories and symbolic links and writing files fail with
+/// [`StorageFull`](axfs_vfs::VfsError::StorageFull) beyond its [`RamLimits`].
 pub struct RamFileSystem {
     parent: Once<VfsNodeRef>,
     root: Arc<DirNode>,
@@ -28,12 +52,23 @@ pub struct RamFileSystem {
 impl RamFileSystem {
     /// Create a new instance.
     pub fn new() -> Self {
+        Self::with_limits(RamLimits::default())
+    }
+
+    /// Create a new instance with the given limits.
+    pub fn with_limits(limits: RamLimits) -> Self {
+        let charge = usage::NodeCharge::root(usage::Usage::new(limits));
         Self {
             parent: Once::new(),
-            root: DirNode::new(None),
+            root: DirNode::new(None, charge),
         }
     }
 
+    /// Returns the current usage and the limits of the filesystem.
+    pub fn usage(&self) -> RamUsage {
+        self.root.charge.usage().get()
+    }
+
     /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
     pub fn root_dir_node(&self) -> Arc<DirNode> {
         self.root.clone()
@@ -0,0 +1,202 @@
+use alloc::collections::BTreeMap;
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::time::Duration;
+
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeRef, VfsResult};
+use spin::RwLock;
+
+use crate::usage::Usage;
+use crate::{DirNode, FileNode, SymlinkNode};
+
+/// Ownership, permissions and timestamps of a node.
+///
+/// Timestamps are durations since the epoch, read from the clock set by
+/// [`set_clock`](crate::set_clock).
+#[derive(Debug, Clone, Copy)]
+pub struct NodeMeta {
+    /// The permission bits.
+    pub perm: VfsNodePerm,
+    /// The user ID of the owner.
+    pub uid: u32,
+    /// The group ID of the owner.
+    pub gid: u32,
+    /// The time of the last access.
+    pub atime: Duration,
+    /// The time of the last modification of the content.
+    pub mtime: Duration,
+    /// The time of the last status change.
+    pub ctime: Duration,
+}
+
+/// The maximum total size of the names and values of the extended attributes
+/// of a node.
+pub const XATTR_SPACE: usize = 65536;
+
+/// The [`NodeMeta`] and the extended attributes of a node, which can be
+/// changed through a shared reference.
+///
+/// The names and values of the extended attributes are counted as bytes in the
+/// [`Usage`] of the filesystem.
+pub struct NodeAttrs {
+    meta: RwLock<NodeMeta>,
+    xattrs: RwLock<BTreeMap<String, Vec<u8>>>,
+    usage: Arc<Usage>,
+}
+
+impl NodeAttrs {
+    /// Creates the attributes of a node owned by root and created now.
+    pub(crate) fn new(perm: VfsNodePerm, usage: Arc<Usage>) -> Self {
+        let now = crate::now();
+        Self {
+            meta: RwLock::new(NodeMeta {
+                perm,
+                uid: 0,
+                gid: 0,
+                atime: now,
+                mtime: now,
+                ctime: now,
+            }),
+            xattrs: RwLock::new(BTreeMap::new()),
+            usage,
+        }
+    }
+
+    /// Returns a copy of the attributes.
+    pub fn get(&self) -> NodeMeta {
+        *self.meta.read()
+    }
+
+    /// Returns the permission bits.
+    pub fn perm(&self) -> VfsNodePerm {
+        self.meta.read().perm
+    }
+
+    /// Changes the permission bits.
+    pub fn set_perm(&self, perm: VfsNodePerm) {
+        let mut meta = self.meta.write();
+        meta.perm = perm;
+        meta.ctime = crate::now();
+    }
+
+    /// Changes the owner and the group, which are kept if `None`.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) {
+        let mut meta = self.meta.write();
+        if let Some(uid) = uid {
+            meta.uid = uid;
+        }
+        if let Some(gid) = gid {
+            meta.gid = gid;
+        }
+        meta.ctime = crate::now();
+    }
+
+    /// Changes the access and modification times, which are kept if `None`.
+    ///
+    /// The status change time is always set to now.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) {
+        let mut meta = self.meta.write();
+        if let Some(atime) = atime {
+            meta.atime = atime;
+        }
+        if let Some(mtime) = mtime {
+            meta.mtime = mtime;
+        }
+        meta.ctime = crate::now();
+    }
+
+    /// Updates the access time after the content is read.
+    pub(crate) fn touch_access(&self) {
+        self.meta.write().atime = crate::now();
+    }
+
+    /// Updates the modification and status change times after the content is
+    /// written.
+    pub(crate) fn touch_modify(&self) {
+        let now = crate::now();
+        let mut meta = self.meta.write();
+        meta.mtime = now;
+        meta.ctime = now;
+    }
+
+    /// Updates the status change time, e.g. after a link is added or removed.
+    pub(crate) fn touch_change(&self) {
+        self.meta.write().ctime = crate::now();
+    }
+
+    /// Returns the value of the extended attribute `name`, or `None` if there
+    /// is no such attribute.
+    pub fn get_xattr(&self, name: &str) -> Option<Vec<u8>> {
+        self.xattrs.read().get(name).cloned()
+    }
+
+    /// Returns the names of all extended attributes.
+    pub fn list_xattrs(&self) -> Vec<String> {
+        self.xattrs.read().keys().cloned().collect()
+    }
+
+    /// Sets the extended attribute `name` to `value`.
+    ///
+    /// If `exists` is `Some`, the attribute must already exist or not:
+    /// [`AlreadyExists`](VfsError::AlreadyExists) is returned if it exists
+    /// but must not, and [`NotFound`](VfsError::NotFound) if it is missing
+    /// but must exist. The names and values of a node take at most
+    /// [`XATTR_SPACE`] bytes and are charged to the filesystem, and it fails
+    /// with [`StorageFull`](VfsError::StorageFull) beyond either limit.
+    pub fn set_xattr(&self, name: &str, value: &[u8], exists: Option<bool>) -> VfsResult {
+        let mut xattrs = self.xattrs.write();
+        let old = xattrs.get(name);
+        match (exists, old.is_some()) {
+            (Some(false), true) => return Err(VfsError::AlreadyExists),
+            (Some(true), false) => return Err(VfsError::NotFound),
+            _ => {}
+        }
+        let used: usize = xattrs.iter().map(|(k, v)| k.len() + v.len()).sum();
+        let freed = old.map_or(0, |v| name.len() + v.len());
+        let needed = name.len() + value.len();
+        if used - freed + needed > XATTR_SPACE {
+            return Err(VfsError::StorageFull);
+        }
+        if needed > freed {
+            self.usage.charge_bytes((needed - freed) as u64)?;
+        } else {
+            self.usage.release_bytes((freed - needed) as u64);
+        }
+        xattrs.insert(name.into(), value.into());
+        drop(xattrs);
+        self.touch_change();
+        Ok(())
+    }
+
+    /// Removes the extended attribute `name`, or returns
+    /// [`NotFound`](VfsError::NotFound) if there is no such attribute.
+    pub fn remove_xattr(&self, name: &str) -> VfsResult {
+        let value = self.xattrs.write().remove(name).ok_or(VfsError::NotFound)?;
+        self.usage.release_bytes((name.len() + value.len()) as u64);
+        self.touch_change();
+        Ok(())
+    }
+}
+
+impl Drop for NodeAttrs {
+    fn drop(&mut self) {
+        let used: usize = self
+            .xattrs
+            .get_mut()
+            .iter()
+            .map(|(k, v)| k.len() + v.len())
+            .sum();
+        self.usage.release_bytes(used as u64);
+    }
+}
+
+/// Returns the attributes of `node` if it is a node of the RAM filesystem.
+pub fn node_attrs(node: &VfsNodeRef) -> Option<&NodeAttrs> {
+    let any = node.as_any();
+    if let Some(file) = any.downcast_ref::<FileNode>() {
+        Some(file.attrs())
+    } else if let Some(dir) = any.downcast_ref::<DirNode>() {
+        Some(dir.attrs())
+    } else {
+        any.downcast_ref::<SymlinkNode>().map(SymlinkNode::attrs)
+    }
+}
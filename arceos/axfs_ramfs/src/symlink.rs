@@ -0,0 +1,94 @@
+use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use axfs_vfs::{VfsNodeType, VfsResult};
+use spin::RwLock;
+
+use crate::meta::NodeAttrs;
+use crate::usage::NodeCharge;
+
+/// The symbolic link node in the RAM filesystem.
+///
+/// Its content is the path it points to. It implements
+/// [`axfs_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: RwLock<Vec<u8>>,
+    attrs: NodeAttrs,
+    pub(super) nlink: AtomicUsize,
+    pub(crate) charge: NodeCharge,
+}
+
+impl SymlinkNode {
+    pub(super) fn new(charge: NodeCharge) -> Self {
+        Self {
+            target: RwLock::new(Vec::new()),
+            attrs: NodeAttrs::new(
+                VfsNodePerm::from_bits_truncate(0o777),
+                charge.usage().clone(),
+            ),
+            nlink: AtomicUsize::new(0),
+            charge,
+        }
+    }
+
+    /// Returns the ownership and timestamps of this link. Its permissions are
+    /// not used.
+    pub fn attrs(&self) -> &NodeAttrs {
+        &self.attrs
+    }
+
+    /// Returns the path that the link points to.
+    pub fn target(&self) -> String {
+        String::from_utf8_lossy(&self.target.read()).into_owned()
+    }
+
+    /// Returns the number of hard links to this node.
+    pub fn nlink(&self) -> usize {
+        self.nlink.load(Ordering::Acquire)
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            self.attrs.perm(),
+            VfsNodeType::SymLink,
+            self.target.read().len() as _,
+            0,
+        ))
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.target.write().resize(size as _, 0);
+        self.attrs.touch_modify();
+        Ok(())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = self.target.read();
+        let start = target.len().min(offset as usize);
+        let end = target.len().min(offset as usize + buf.len());
+        let src = &target[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        self.attrs.touch_access();
+        Ok(src.len())
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let offset = offset as usize;
+        let mut target = self.target.write();
+        if offset + buf.len() > target.len() {
+            target.resize(offset + buf.len(), 0);
+        }
+        target[offset..offset + buf.len()].copy_from_slice(buf);
+        self.attrs.touch_modify();
+        Ok(buf.len())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // there is nothing to write back
+    }
+
+    impl_vfs_non_dir_default! {}
+}
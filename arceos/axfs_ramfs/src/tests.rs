@@ -1,6 +1,7 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -32,6 +33,7 @@ fn test_ramfs_ops(devfs: &RamFileSystem) -> VfsResult {
     assert_eq!(node.read_at(0, &mut buf)?, N);
     assert_eq!(buf[..N_HALF], [0; N_HALF]);
     assert_eq!(buf[N_HALF..], [1; N_HALF]);
+    assert_eq!(node.fsync(), Ok(()));
     assert_eq!(node.lookup("/").err(), Some(VfsError::NotADirectory));
 
     let foo = devfs.root_dir().lookup(".///.//././/.////foo")?;
@@ -40,6 +42,7 @@ fn test_ramfs_ops(devfs: &RamFileSystem) -> VfsResult {
         foo.read_at(10, &mut buf).err(),
         Some(VfsError::IsADirectory)
     );
+    assert_eq!(foo.fsync(), Ok(()));
     assert!(Arc::ptr_eq(
         &foo.clone().lookup("/f3")?,
         &devfs.root_dir().lookup(".//./foo///f3")?,
@@ -88,6 +91,127 @@ fn test_get_parent(devfs: &RamFileSystem) -> VfsResult {
     Ok(())
 }
 
+#[test]
+fn test_links() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir_node();
+    root.create("f1", VfsNodeType::File).unwrap();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/link", VfsNodeType::SymLink).unwrap();
+
+    let link = root.clone().lookup("foo/link").unwrap();
+    assert_eq!(link.write_at(0, b"../f1").unwrap(), 5);
+    assert!(link.get_attr().unwrap().file_type().is_symlink());
+    assert_eq!(link.get_attr().unwrap().size(), 5);
+    let link = link.as_any().downcast_ref::<SymlinkNode>().unwrap();
+    assert_eq!(link.target(), "../f1");
+    assert_eq!(link.nlink(), 1);
+
+    let f1 = root.clone().lookup("f1").unwrap();
+    f1.write_at(0, b"hello").unwrap();
+    let foo = root.clone().lookup("foo").unwrap();
+    let dir_foo = foo.as_any().downcast_ref::<DirNode>().unwrap();
+    dir_foo.link("f2", &f1).unwrap();
+    assert_eq!(dir_foo.link("f2", &f1).err(), Some(VfsError::AlreadyExists));
+    assert_eq!(
+        root.link("bar", &foo).err(),
+        Some(VfsError::PermissionDenied)
+    );
+
+    let f2 = root.clone().lookup("foo/f2").unwrap();
+    assert!(Arc::ptr_eq(&f1, &f2));
+    let file = f1.as_any().downcast_ref::<FileNode>().unwrap();
+    assert_eq!(file.nlink(), 2);
+    assert_eq!(root.nlink(), 3);
+
+    root.remove("f1").unwrap();
+    assert_eq!(file.nlink(), 1);
+    let mut buf = [0; 8];
+    assert_eq!(f2.read_at(0, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"hello");
+    root.remove("foo/f2").unwrap();
+    assert_eq!(file.nlink(), 0);
+}
+
+#[test]
+fn test_attrs() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir_node();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    let attrs = node_attrs(&f1).unwrap();
+    let meta = attrs.get();
+    assert_eq!(meta.perm.bits(), 0o666);
+    assert_eq!((meta.uid, meta.gid), (0, 0));
+
+    attrs.set_perm(VfsNodePerm::from_bits_truncate(0o600));
+    attrs.set_owner(Some(1000), None);
+    assert_eq!(f1.get_attr().unwrap().perm().bits(), 0o600);
+    assert_eq!((attrs.get().uid, attrs.get().gid), (1000, 0));
+    attrs.set_owner(None, Some(100));
+    assert_eq!((attrs.get().uid, attrs.get().gid), (1000, 100));
+
+    let (atime, mtime) = (Duration::from_secs(1), Duration::from_secs(2));
+    attrs.set_times(Some(atime), None);
+    assert_eq!(attrs.get().atime, atime);
+    assert_eq!(attrs.get().mtime, meta.mtime);
+    attrs.set_times(None, Some(mtime));
+    assert_eq!((attrs.get().atime, attrs.get().mtime), (atime, mtime));
+
+    let foo = root.clone().lookup("foo").unwrap();
+    let dir_attrs = node_attrs(&foo).unwrap();
+    dir_attrs.set_perm(VfsNodePerm::from_bits_truncate(0o700));
+    assert!(foo.get_attr().unwrap().is_dir());
+    assert_eq!(foo.get_attr().unwrap().perm().bits(), 0o700);
+    assert_eq!(root.attrs().perm().bits(), 0o755);
+}
+
+#[test]
+fn test_xattrs() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir_node();
+    root.create("f1", VfsNodeType::File).unwrap();
+    let f1 = root.clone().lookup("f1").unwrap();
+    let attrs = node_attrs(&f1).unwrap();
+    assert!(attrs.list_xattrs().is_empty());
+    assert_eq!(attrs.get_xattr("user.a"), None);
+
+    attrs.set_xattr("user.b", b"2", None).unwrap();
+    attrs.set_xattr("user.a", b"1", Some(false)).unwrap();
+    assert_eq!(attrs.list_xattrs(), ["user.a", "user.b"]);
+    assert_eq!(attrs.get_xattr("user.a").as_deref(), Some(&b"1"[..]));
+    assert_eq!(
+        attrs.set_xattr("user.a", b"x", Some(false)),
+        Err(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        attrs.set_xattr("user.c", b"x", Some(true)),
+        Err(VfsError::NotFound)
+    );
+    attrs.set_xattr("user.a", b"", Some(true)).unwrap();
+    assert_eq!(attrs.get_xattr("user.a").as_deref(), Some(&b""[..]));
+
+    attrs.remove_xattr("user.a").unwrap();
+    assert_eq!(attrs.remove_xattr("user.a"), Err(VfsError::NotFound));
+    assert_eq!(attrs.list_xattrs(), ["user.b"]);
+
+    let big = vec![0; XATTR_SPACE - "user.b".len() - 1];
+    attrs.set_xattr("user.b", &big, None).unwrap();
+    assert_eq!(
+        attrs.set_xattr("user.c", b"", None),
+        Err(VfsError::StorageFull)
+    );
+    attrs.set_xattr("user.b", &big[1..], None).unwrap();
+    assert_eq!(
+        attrs.set_xattr("user.c", b"", None),
+        Err(VfsError::StorageFull)
+    );
+    attrs.set_xattr("user.b", &big[6..], None).unwrap();
+    attrs.set_xattr("user.c", b"", None).unwrap();
+}
+
 #[test]
 fn test_ramfs() {
     // .
@@ -134,3 +258,134 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+#[test]
+fn test_sparse() {
+    const PAGE: u64 = PAGE_SIZE as u64;
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir_node();
+    root.create("f1", VfsNodeType::File).unwrap();
+    let node = root.clone().lookup("f1").unwrap();
+    let file = node.as_any().downcast_ref::<FileNode>().unwrap();
+
+    // only the written pages are allocated
+    assert_eq!(node.write_at(1 << 40, b"end").unwrap(), 3);
+    assert_eq!(node.get_attr().unwrap().size(), (1 << 40) + 3);
+    assert_eq!(node.get_attr().unwrap().blocks(), PAGE / 512);
+    let mut buf = [1; 8];
+    assert_eq!(node.read_at(1 << 30, &mut buf).unwrap(), 8);
+    assert_eq!(buf, [0; 8]);
+    assert_eq!(node.read_at((1 << 40) - 2, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"\0\0end");
+    assert_eq!(file.seek_data(0), Some(1 << 40));
+    assert_eq!(file.seek_hole(0), Some(0));
+    assert_eq!(file.seek_hole(1 << 40), Some((1 << 40) + 3));
+    assert_eq!(file.seek_data((1 << 40) + 3), None);
+
+    // a write across pages, then a hole punched in the middle of it
+    node.truncate(0).unwrap();
+    assert_eq!(node.get_attr().unwrap().blocks(), 0);
+    node.write_at(PAGE - 1, &[7; 2 * PAGE as usize + 2])
+        .unwrap();
+    assert_eq!(node.get_attr().unwrap().blocks(), 4 * PAGE / 512);
+    file.punch_hole(PAGE - 1, PAGE + 2).unwrap();
+    assert_eq!(node.get_attr().unwrap().size(), 3 * PAGE + 1);
+    assert_eq!(node.get_attr().unwrap().blocks(), 2 * PAGE / 512);
+    assert_eq!(file.seek_data(0), Some(2 * PAGE));
+    assert_eq!(file.seek_hole(2 * PAGE), Some(3 * PAGE + 1));
+    let mut buf = [1; 4];
+    node.read_at(2 * PAGE - 1, &mut buf).unwrap();
+    assert_eq!(buf, [0, 0, 7, 7]);
+
+    // the truncated tail reads as zeros after the file is extended again
+    node.truncate(2 * PAGE + 2).unwrap();
+    node.truncate(3 * PAGE).unwrap();
+    node.read_at(2 * PAGE, &mut buf).unwrap();
+    assert_eq!(buf, [0, 7, 0, 0]);
+
+    // preallocated pages are data, beyond the end or not
+    root.create("f2", VfsNodeType::File).unwrap();
+    let node = root.clone().lookup("f2").unwrap();
+    let file = node.as_any().downcast_ref::<FileNode>().unwrap();
+    file.allocate(PAGE, 1, false).unwrap();
+    assert_eq!(node.get_attr().unwrap().size(), PAGE + 1);
+    file.allocate(0, 3 * PAGE, true).unwrap();
+    assert_eq!(node.get_attr().unwrap().size(), PAGE + 1);
+    assert_eq!(node.get_attr().unwrap().blocks(), 3 * PAGE / 512);
+    assert_eq!(file.seek_hole(0), Some(PAGE + 1));
+    assert_eq!(file.allocate(0, 0, false), Err(VfsError::InvalidInput));
+    assert_eq!(file.punch_hole(u64::MAX, 1), Err(VfsError::InvalidInput));
+    assert_eq!(
+        node.write_at(u64::MAX - 1, b"xx").err(),
+        Some(VfsError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_limits() {
+    const PAGE: u64 = PAGE_SIZE as u64;
+    let ramfs = RamFileSystem::with_limits(RamLimits {
+        max_bytes: Some(4 * PAGE),
+        max_nodes: Some(4),
+    });
+    let root = ramfs.root_dir_node();
+    assert_eq!((ramfs.usage().bytes, ramfs.usage().nodes), (0, 1));
+    root.create("f1", VfsNodeType::File).unwrap();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/link", VfsNodeType::SymLink).unwrap();
+    assert_eq!(
+        root.create("f2", VfsNodeType::File),
+        Err(VfsError::StorageFull)
+    );
+    assert_eq!(ramfs.usage().nodes, 4);
+
+    let f1 = root.clone().lookup("f1").unwrap();
+    assert_eq!(f1.write_at(PAGE - 1, &[1; 2]).unwrap(), 2);
+    assert_eq!(f1.write_at(10 * PAGE, &[1]).unwrap(), 1);
+    assert_eq!(ramfs.usage().bytes, 3 * PAGE);
+    // nothing is written if it does not fit
+    assert_eq!(
+        f1.write_at(4 * PAGE, &[1; 2 * PAGE as usize]).err(),
+        Some(VfsError::StorageFull)
+    );
+    assert_eq!(f1.get_attr().unwrap().size(), 10 * PAGE + 1);
+    assert_eq!(ramfs.usage().bytes, 3 * PAGE);
+    f1.write_at(4 * PAGE, &[1; PAGE as usize]).unwrap();
+    assert_eq!(ramfs.usage().bytes, 4 * PAGE);
+    let file = f1.as_any().downcast_ref::<FileNode>().unwrap();
+    assert_eq!(file.allocate(5 * PAGE, 1, true), Err(VfsError::StorageFull));
+
+    // space is returned when content is dropped
+    file.punch_hole(4 * PAGE, PAGE).unwrap();
+    f1.truncate(PAGE).unwrap();
+    assert_eq!(node_usage(&f1).unwrap().bytes, PAGE);
+    root.remove("dir/link").unwrap();
+    root.remove("f1").unwrap();
+    assert_eq!(ramfs.usage().nodes, 3); // `f1` is still referenced
+    drop(f1);
+    assert_eq!((ramfs.usage().bytes, ramfs.usage().nodes), (0, 2));
+    let usage = node_usage(&root.clone().lookup("dir").unwrap()).unwrap();
+    assert_eq!(usage.limits.max_bytes, Some(4 * PAGE));
+
+    // extended attributes are charged by the bytes of their names and values
+    let dir = root.clone().lookup("dir").unwrap();
+    let attrs = node_attrs(&dir).unwrap();
+    attrs.set_xattr("user.a", &[0; 10], None).unwrap();
+    assert_eq!(ramfs.usage().bytes, 16);
+    attrs.set_xattr("user.a", &[0; 4], None).unwrap();
+    assert_eq!(ramfs.usage().bytes, 10);
+    let big = vec![0; 4 * PAGE as usize - 15];
+    assert_eq!(
+        attrs.set_xattr("user.b", &big, None),
+        Err(VfsError::StorageFull)
+    );
+    assert_eq!(attrs.get_xattr("user.b"), None);
+    assert_eq!(ramfs.usage().bytes, 10);
+    attrs.set_xattr("user.b", &big[1..], None).unwrap();
+    assert_eq!(ramfs.usage().bytes, 4 * PAGE);
+    attrs.remove_xattr("user.a").unwrap();
+    assert_eq!(ramfs.usage().bytes, 4 * PAGE - 10);
+    drop(dir);
+    root.remove("dir").unwrap();
+    assert_eq!((ramfs.usage().bytes, ramfs.usage().nodes), (0, 1));
+}
@@ -0,0 +1,122 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axfs_vfs::{VfsError, VfsNodeRef, VfsResult};
+
+use crate::{DirNode, FileNode, SymlinkNode};
+
+/// Limits on the memory and the number of nodes of a
+/// [`RamFileSystem`](crate::RamFileSystem), like the `size` and `nr_inodes`
+/// options of a Linux tmpfs. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RamLimits {
+    /// The maximum number of bytes of file content, which is allocated in
+    /// pages of [`PAGE_SIZE`](crate::PAGE_SIZE) bytes, and of the names and
+    /// values of extended attributes.
+    pub max_bytes: Option<u64>,
+    /// The maximum number of files, directories and symbolic links,
+    /// including the root directory.
+    pub max_nodes: Option<u64>,
+}
+
+/// The usage and limits of a [`RamFileSystem`](crate::RamFileSystem), see
+/// [`node_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamUsage {
+    /// The number of bytes of file content and extended attributes allocated.
+    pub bytes: u64,
+    /// The number of nodes.
+    pub nodes: u64,
+    /// The limits of the filesystem.
+    pub limits: RamLimits,
+}
+
+/// The counters of a filesystem, shared by all of its nodes.
+pub(crate) struct Usage {
+    bytes: AtomicU64,
+    nodes: AtomicU64,
+    limits: RamLimits,
+}
+
+impl Usage {
+    pub(crate) fn new(limits: RamLimits) -> Arc<Self> {
+        Arc::new(Self {
+            bytes: AtomicU64::new(0),
+            nodes: AtomicU64::new(0),
+            limits,
+        })
+    }
+
+    pub(crate) fn get(&self) -> RamUsage {
+        RamUsage {
+            bytes: self.bytes.load(Ordering::Acquire),
+            nodes: self.nodes.load(Ordering::Acquire),
+            limits: self.limits,
+        }
+    }
+
+    /// Adds `n` bytes of content or extended attributes, or returns
+    /// [`StorageFull`](VfsError::StorageFull) if it exceeds the limit.
+    pub(crate) fn charge_bytes(&self, n: u64) -> VfsResult {
+        charge(&self.bytes, n, self.limits.max_bytes)
+    }
+
+    /// Removes `n` bytes of content or extended attributes.
+    pub(crate) fn release_bytes(&self, n: u64) {
+        self.bytes.fetch_sub(n, Ordering::AcqRel);
+    }
+}
+
+fn charge(counter: &AtomicU64, n: u64, max: Option<u64>) -> VfsResult {
+    counter
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+            used.checked_add(n)
+                .filter(|&total| max.is_none_or(|max| total <= max))
+        })
+        .map(|_| ())
+        .map_err(|_| VfsError::StorageFull)
+}
+
+/// A node counted in the [`Usage`] of its filesystem until it is dropped.
+pub(crate) struct NodeCharge(Arc<Usage>);
+
+impl NodeCharge {
+    /// Counts a new node, or returns [`StorageFull`](VfsError::StorageFull)
+    /// if there are too many.
+    pub(crate) fn new(usage: &Arc<Usage>) -> VfsResult<Self> {
+        charge(&usage.nodes, 1, usage.limits.max_nodes)?;
+        Ok(Self(usage.clone()))
+    }
+
+    /// Counts the root directory of a new filesystem, regardless of the
+    /// limit.
+    pub(crate) fn root(usage: Arc<Usage>) -> Self {
+        usage.nodes.fetch_add(1, Ordering::AcqRel);
+        Self(usage)
+    }
+
+    /// Returns the counters of the filesystem of the node.
+    pub(crate) fn usage(&self) -> &Arc<Usage> {
+        &self.0
+    }
+}
+
+impl Drop for NodeCharge {
+    fn drop(&mut self) {
+        self.0.nodes.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Returns the usage of the RAM filesystem that `node` belongs to, or `None`
+/// if it is not a node of a RAM filesystem.
+pub fn node_usage(node: &VfsNodeRef) -> Option<RamUsage> {
+    let any = node.as_any();
+    let charge = if let Some(file) = any.downcast_ref::<FileNode>() {
+        &file.charge
+    } else if let Some(dir) = any.downcast_ref::<DirNode>() {
+        &dir.charge
+    } else {
+        &any.downcast_ref::<SymlinkNode>()?.charge
+    };
+    Some(charge.usage().get())
+}
@@ -0,0 +1,75 @@
+//! TCP and UDP clients talking to servers in the same image through the
+//! loopback interface, over both IPv4 and IPv6.
+//!
+//! No NIC is needed, so it is built with the `net-loopback` feature without
+//! any NIC driver:
+//!
+//! ```
+//! make A=examples/loopback run
+//! ```
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::io::{self, prelude::*};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener, TcpStream, UdpSocket};
+use std::thread;
+
+const PORT: u16 = 5555;
+const MESSAGE: &[u8] = b"Hello, loopback!";
+
+fn test_tcp(ip: IpAddr) -> io::Result<()> {
+    let listener = TcpListener::bind((ip, PORT))?;
+    let server = thread::spawn(move || -> io::Result<()> {
+        let (mut stream, peer) = listener.accept()?;
+        assert_eq!(peer.ip(), ip);
+        let mut buf = [0; MESSAGE.len()];
+        stream.read_exact(&mut buf)?;
+        stream.write_all(&buf)
+    });
+
+    let mut stream = TcpStream::connect((ip, PORT))?;
+    assert_eq!(stream.peer_addr()?.ip(), ip);
+    stream.write_all(MESSAGE)?;
+    let mut buf = [0; MESSAGE.len()];
+    stream.read_exact(&mut buf)?;
+    assert_eq!(buf, MESSAGE);
+    server.join()??;
+    println!("TCP over {} OK!", ip);
+    Ok(())
+}
+
+fn test_udp(ip: IpAddr) -> io::Result<()> {
+    let server = UdpSocket::bind((ip, PORT))?;
+    let client = UdpSocket::bind((ip, 0))?;
+    client.send_to(MESSAGE, (ip, PORT))?;
+
+    let mut buf = [0; 64];
+    let (n, peer) = server.recv_from(&mut buf)?;
+    assert_eq!(&buf[..n], MESSAGE);
+    assert_eq!(peer, client.local_addr()?);
+    server.send_to(&buf[..n], peer)?;
+
+    let (n, peer) = client.recv_from(&mut buf)?;
+    assert_eq!(&buf[..n], MESSAGE);
+    assert_eq!(peer, server.local_addr()?);
+    println!("UDP over {} OK!", ip);
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, loopback test!");
+    for ip in [
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ] {
+        test_tcp(ip).expect("test TCP over loopback failed");
+        test_udp(ip).expect("test UDP over loopback failed");
+    }
+    println!("Loopback tests run OK!");
+}
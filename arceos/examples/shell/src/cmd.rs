@@ -3,7 +3,7 @@ use std::io::{self, prelude::*};
 use std::{string::String, vec::Vec};
 
 #[cfg(all(not(feature = "axstd"), unix))]
-use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
 
 macro_rules! print_err {
     ($cmd: literal, $msg: expr) => {
@@ -19,11 +19,17 @@ type CmdHandler = fn(&str);
 const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("cat", do_cat),
     ("cd", do_cd),
+    #[cfg(feature = "axstd")]
+    ("df", do_df),
     ("echo", do_echo),
     ("exit", do_exit),
+    #[cfg(all(feature = "axstd", not(feature = "use-ramfs")))]
+    ("fsck", do_fsck),
     ("help", do_help),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    #[cfg(all(feature = "axstd", not(feature = "use-ramfs")))]
+    ("mkfs", do_mkfs),
     ("pwd", do_pwd),
     ("rm", do_rm),
     ("uname", do_uname),
@@ -66,6 +72,25 @@ const fn file_perm_to_rwx(mode: u32) -> [u8; 9] {
     perm
 }
 
+/// Converts seconds since the epoch to `(year, month, day, hour, minute)` in
+/// UTC.
+fn civil_time(secs: i64) -> (i64, u32, u32, u32, u32) {
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // Days to a date in the proleptic Gregorian calendar, see
+    // <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let (hour, minute) = ((secs / 3600) as u32, (secs % 3600 / 60) as u32);
+    (year, month, day, hour, minute)
+}
+
 fn do_ls(args: &str) {
     let current_dir = std::env::current_dir().unwrap();
     let args = if args.is_empty() {
@@ -82,7 +107,22 @@ fn do_ls(args: &str) {
         let file_type_char = file_type_to_char(file_type);
         let rwx = file_perm_to_rwx(metadata.permissions().mode());
         let rwx = unsafe { core::str::from_utf8_unchecked(&rwx) };
-        println!("{}{} {:>8} {}", file_type_char, rwx, size, entry);
+        let (year, month, day, hour, minute) = civil_time(metadata.mtime());
+        println!(
+            "{}{} {:>2} {:>5} {:>5} {:>8} {}-{:02}-{:02} {:02}:{:02} {}",
+            file_type_char,
+            rwx,
+            metadata.nlink(),
+            metadata.uid(),
+            metadata.gid(),
+            size,
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            entry
+        );
         Ok(())
     }
 
@@ -225,6 +265,111 @@ fn do_rm(args: &str) {
     }
 }
 
+#[cfg(all(feature = "axstd", not(feature = "use-ramfs")))]
+fn do_mkfs(args: &str) {
+    use std::os::arceos::modules::axfs::api::mkfs_vfat;
+
+    let (mut fat32, mut label, mut device, mut invalid) = (false, None, None, false);
+    let mut args = args.split_whitespace();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-F" if args.next() == Some("32") => fat32 = true,
+            "-n" => label = args.next(),
+            _ if device.is_none() && !arg.starts_with('-') => device = Some(arg),
+            _ => invalid = true,
+        }
+    }
+    let Some(device) = device.filter(|_| !invalid) else {
+        print_err!("mkfs", "usage: mkfs [-F 32] [-n LABEL] DEVICE");
+        return;
+    };
+    if let Err(e) = mkfs_vfat(device, label, fat32) {
+        print_err!("mkfs", device, e);
+    }
+}
+
+#[cfg(all(feature = "axstd", not(feature = "use-ramfs")))]
+fn do_fsck(args: &str) {
+    use std::os::arceos::modules::axfs::api::fsck_vfat;
+
+    let (repair, device) = match split_whitespace(args) {
+        ("-r", device) => (true, device),
+        (device, "") => (false, device),
+        _ => (false, ""),
+    };
+    if device.is_empty() || device.contains(char::is_whitespace) {
+        print_err!("fsck", "usage: fsck [-r] DEVICE");
+        return;
+    }
+    let report = match fsck_vfat(device, repair) {
+        Ok(report) => report,
+        Err(e) => {
+            print_err!("fsck", device, e);
+            return;
+        }
+    };
+    for problem in &report.problems {
+        println!("{}: {}", device, problem);
+    }
+    let status = if report.is_clean() {
+        "clean"
+    } else if report.repaired {
+        "repaired"
+    } else {
+        "NOT repaired, run `fsck -r` to repair"
+    };
+    println!(
+        "{}: FAT{}, {} files, {} directories, {}/{} clusters, {}",
+        device,
+        report.fat_bits,
+        report.files,
+        report.dirs,
+        report.used_clusters,
+        report.clusters,
+        status
+    );
+}
+
+#[cfg(feature = "axstd")]
+fn do_df(args: &str) {
+    use std::os::arceos::modules::axfs::api::{mounts, statfs};
+
+    if !args.is_empty() {
+        print_err!("df", "usage: df");
+        return;
+    }
+    // `-` if unknown or unlimited
+    let column = |total: u64, n: u64| match total {
+        0 => String::from("-"),
+        _ => n.to_string(),
+    };
+    println!(
+        "{:<12} {:<8} {:>10} {:>10} {:>10} {:>8} {:>8}  Mounted on",
+        "Filesystem", "Type", "1K-blocks", "Used", "Available", "Inodes", "IUsed"
+    );
+    for mount in mounts() {
+        let stat = match statfs(&mount.target) {
+            Ok(stat) => stat,
+            Err(e) => {
+                print_err!("df", mount.target, e);
+                continue;
+            }
+        };
+        let kib = |blocks: u64| column(stat.blocks, blocks * stat.block_size / 1024);
+        println!(
+            "{:<12} {:<8} {:>10} {:>10} {:>10} {:>8} {:>8}  {}",
+            mount.source,
+            mount.fstype,
+            kib(stat.blocks),
+            kib(stat.blocks - stat.blocks_free),
+            kib(stat.blocks_free),
+            column(stat.files, stat.files),
+            column(stat.files, stat.files - stat.files_free),
+            mount.target
+        );
+    }
+}
+
 fn do_cd(mut args: &str) {
     if args.is_empty() {
         args = "/";
@@ -8,8 +8,9 @@
 //! driver they want.
 //!
 //! For each device category (i.e., net, block, display, etc.), an unified type
-//! is used to represent all devices in that category. Currently, there are 3
-//! categories: [`AxNetDevice`], [`AxBlockDevice`], and [`AxDisplayDevice`].
+//! is used to represent all devices in that category. Currently, there are 4
+//! categories: [`AxNetDevice`], [`AxBlockDevice`], [`AxDisplayDevice`], and
+//! [`AxP9Device`] for the transports of the 9P filesystem protocol.
 //!
 //! # Concepts
 //!
@@ -34,6 +35,7 @@
 //! | Block | `virtio-blk` | VirtIO block device |
 //! | Network | `virtio-net` | VirtIO network device |
 //! | Display | `virtio-gpu` | VirtIO graphics device |
+//! | 9P | `virtio-9p` | VirtIO 9P transport, to mount directories shared by the host |
 //!
 //! # Other Cargo Features
 //!
@@ -42,7 +44,7 @@
 //! - `bus-pci`: use PCI bus to probe all PCI devices. This feature is
 //!    enabeld by default.
 //! - `virtio`: use VirtIO devices. This is enabled if any of `virtio-blk`,
-//!   `virtio-net` or `virtio-gpu` is enabled.
+//!   `virtio-net`, `virtio-gpu` or `virtio-9p` is enabled.
 //! - `net`: use network devices. This is enabled if any feature of network
 //!    devices is selected. If this feature is enabled without any network device
 //!    features, a dummy struct is used for [`AxNetDevice`].
@@ -74,6 +76,8 @@ mod structs;
 
 #[cfg(feature = "virtio")]
 mod virtio;
+#[cfg(feature = "virtio-9p")]
+mod virtio_9p;
 
 #[cfg(feature = "ixgbe")]
 mod ixgbe;
@@ -82,7 +86,7 @@ pub mod prelude;
 
 #[allow(unused_imports)]
 use self::prelude::*;
-pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
+pub use self::structs::{AxDeviceContainer, AxDeviceEnum, DeviceBus};
 
 #[cfg(feature = "block")]
 pub use self::structs::AxBlockDevice;
@@ -90,6 +94,8 @@ pub use self::structs::AxBlockDevice;
 pub use self::structs::AxDisplayDevice;
 #[cfg(feature = "net")]
 pub use self::structs::AxNetDevice;
+#[cfg(feature = "virtio-9p")]
+pub use self::structs::AxP9Device;
 
 /// A structure that contains all device drivers, organized by their category.
 #[derive(Default)]
@@ -103,6 +109,9 @@ pub struct AllDevices {
     /// All graphics device drivers.
     #[cfg(feature = "display")]
     pub display: AxDeviceContainer<AxDisplayDevice>,
+    /// All 9P transport drivers.
+    #[cfg(feature = "virtio-9p")]
+    pub p9: AxDeviceContainer<AxP9Device>,
 }
 
 impl AllDevices {
@@ -126,7 +135,7 @@ impl AllDevices {
                     dev.device_type(),
                     dev.device_name(),
                 );
-                self.add_device(dev);
+                self.add_device(dev, DeviceBus::Platform);
             }
         });
 
@@ -135,14 +144,16 @@ impl AllDevices {
 
     /// Adds one device into the corresponding container, according to its device category.
     #[allow(dead_code)]
-    fn add_device(&mut self, dev: AxDeviceEnum) {
+    fn add_device(&mut self, dev: AxDeviceEnum, bus: DeviceBus) {
         match dev {
             #[cfg(feature = "net")]
-            AxDeviceEnum::Net(dev) => self.net.push(dev),
+            AxDeviceEnum::Net(dev) => self.net.push(dev, bus),
             #[cfg(feature = "block")]
-            AxDeviceEnum::Block(dev) => self.block.push(dev),
+            AxDeviceEnum::Block(dev) => self.block.push(dev, bus),
             #[cfg(feature = "display")]
-            AxDeviceEnum::Display(dev) => self.display.push(dev),
+            AxDeviceEnum::Display(dev) => self.display.push(dev, bus),
+            #[cfg(feature = "virtio-9p")]
+            AxDeviceEnum::P9(dev) => self.p9.push(dev, bus),
         }
     }
 }
@@ -179,6 +190,13 @@ pub fn init_drivers() -> AllDevices {
             debug!("  graphics device {}: {:?}", i, dev.device_name());
         }
     }
+    #[cfg(feature = "virtio-9p")]
+    {
+        debug!("number of 9P devices: {}", all_devs.p9.len());
+        for (i, dev) in all_devs.p9.iter().enumerate() {
+            debug!("  9P device {}: {:?}", i, dev.mount_tag());
+        }
+    }
 
     all_devs
 }
@@ -2,6 +2,8 @@
 
 pub use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
 
+#[cfg(feature = "virtio-9p")]
+pub use crate::structs::AxP9Device;
 #[cfg(feature = "block")]
 pub use {crate::structs::AxBlockDevice, axdriver_block::BlockDriverOps};
 #[cfg(feature = "display")]
@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 
+use super::DeviceBus;
 use crate::prelude::*;
 use alloc::{boxed::Box, vec, vec::Vec};
 
@@ -37,7 +38,7 @@ impl super::AxDeviceEnum {
 ///
 /// If the feature `dyn` is enabled, the inner type is [`Vec<D>`]. Otherwise,
 /// the inner type is [`Option<D>`] and at most one device can be contained.
-pub struct AxDeviceContainer<D>(Vec<D>);
+pub struct AxDeviceContainer<D>(Vec<D>, Vec<DeviceBus>);
 
 impl<D> AxDeviceContainer<D> {
     /// Returns number of devices in this container.
@@ -55,19 +56,26 @@ impl<D> AxDeviceContainer<D> {
         if self.is_empty() {
             None
         } else {
+            self.1.remove(0);
             Some(self.0.remove(0))
         }
     }
 
     /// Constructs the container from one device.
     pub fn from_one(dev: D) -> Self {
-        Self(vec![dev])
+        Self(vec![dev], vec![DeviceBus::Platform])
+    }
+
+    /// Returns where each device is found, in the same order as the devices.
+    pub fn buses(&self) -> &[DeviceBus] {
+        &self.1
     }
 
     /// Adds one device into the container.
     #[allow(dead_code)]
-    pub(crate) fn push(&mut self, dev: D) {
+    pub(crate) fn push(&mut self, dev: D, bus: DeviceBus) {
         self.0.push(dev);
+        self.1.push(bus);
     }
 }
 
@@ -80,6 +88,6 @@ impl<D> core::ops::Deref for AxDeviceContainer<D> {
 
 impl<D> Default for AxDeviceContainer<D> {
     fn default() -> Self {
-        Self(Default::default())
+        Self(Default::default(), Default::default())
     }
 }
@@ -6,6 +6,45 @@ use axdriver_base::{BaseDriverOps, DeviceType};
 
 pub use imp::*;
 
+/// Where a device is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceBus {
+    /// A PCI device at the bus, device and function numbers.
+    Pci {
+        /// The bus number.
+        bus: u8,
+        /// The device number.
+        device: u8,
+        /// The function number.
+        function: u8,
+    },
+    /// A device mapped to the physical address range.
+    Mmio {
+        /// The base physical address.
+        base: usize,
+        /// The size of the range.
+        size: usize,
+    },
+    /// A device not on a bus, e.g. a RAM disk.
+    Platform,
+}
+
+impl DeviceBus {
+    /// Returns the name of the bus, e.g. `pci`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Pci { .. } => "pci",
+            Self::Mmio { .. } => "mmio",
+            Self::Platform => "platform",
+        }
+    }
+}
+
+/// The unified type of the 9P transports, the same in both device models as
+/// there is only one driver of them.
+#[cfg(feature = "virtio-9p")]
+pub type AxP9Device = crate::virtio::VirtIo9pDev;
+
 /// A unified enum that represents different categories of devices.
 #[allow(clippy::large_enum_variant)]
 pub enum AxDeviceEnum {
@@ -18,6 +57,17 @@ pub enum AxDeviceEnum {
     /// Graphic display device.
     #[cfg(feature = "display")]
     Display(AxDisplayDevice),
+    /// 9P transport device.
+    #[cfg(feature = "virtio-9p")]
+    P9(AxP9Device),
+}
+
+impl AxDeviceEnum {
+    /// Constructs a 9P transport device.
+    #[cfg(feature = "virtio-9p")]
+    pub const fn from_p9(dev: AxP9Device) -> Self {
+        Self::P9(dev)
+    }
 }
 
 impl BaseDriverOps for AxDeviceEnum {
@@ -31,6 +81,8 @@ impl BaseDriverOps for AxDeviceEnum {
             Self::Block(_) => DeviceType::Block,
             #[cfg(feature = "display")]
             Self::Display(_) => DeviceType::Display,
+            #[cfg(feature = "virtio-9p")]
+            Self::P9(dev) => dev.device_type(),
             _ => unreachable!(),
         }
     }
@@ -45,6 +97,8 @@ impl BaseDriverOps for AxDeviceEnum {
             Self::Block(dev) => dev.device_name(),
             #[cfg(feature = "display")]
             Self::Display(dev) => dev.device_name(),
+            #[cfg(feature = "virtio-9p")]
+            Self::P9(dev) => dev.device_name(),
             _ => unreachable!(),
         }
     }
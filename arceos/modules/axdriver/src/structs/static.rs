@@ -1,3 +1,5 @@
+use super::DeviceBus;
+
 #[cfg(feature = "block")]
 pub use crate::drivers::AxBlockDevice;
 #[cfg(feature = "display")]
@@ -29,7 +31,7 @@ impl super::AxDeviceEnum {
 ///
 /// If the feature `dyn` is enabled, the inner type is [`Vec<D>`]. Otherwise,
 /// the inner type is [`Option<D>`] and at most one device can be contained.
-pub struct AxDeviceContainer<D>(Option<D>);
+pub struct AxDeviceContainer<D>(Option<D>, Option<DeviceBus>);
 
 impl<D> AxDeviceContainer<D> {
     /// Returns number of devices in this container.
@@ -48,19 +50,26 @@ impl<D> AxDeviceContainer<D> {
 
     /// Takes one device out of the container (will remove it from the container).
     pub fn take_one(&mut self) -> Option<D> {
+        self.1 = None;
         self.0.take()
     }
 
     /// Constructs the container from one device.
     pub const fn from_one(dev: D) -> Self {
-        Self(Some(dev))
+        Self(Some(dev), Some(DeviceBus::Platform))
+    }
+
+    /// Returns where each device is found, in the same order as the devices.
+    pub fn buses(&self) -> &[DeviceBus] {
+        self.1.as_slice()
     }
 
     /// Adds one device into the container.
     #[allow(dead_code)]
-    pub(crate) fn push(&mut self, dev: D) {
+    pub(crate) fn push(&mut self, dev: D, bus: DeviceBus) {
         if self.0.is_none() {
             self.0 = Some(dev);
+            self.1 = Some(bus);
         }
     }
 }
@@ -74,6 +83,6 @@ impl<D> core::ops::Deref for AxDeviceContainer<D> {
 
 impl<D> Default for AxDeviceContainer<D> {
     fn default() -> Self {
-        Self(Default::default())
+        Self(Default::default(), Default::default())
     }
 }
@@ -73,6 +73,65 @@ cfg_if! {
     }
 }
 
+cfg_if! {
+    if #[cfg(feature = "virtio-9p")] {
+        /// The VirtIO 9P device on the transport of the bus.
+        pub type VirtIo9pDev = crate::virtio_9p::VirtIo9pDev<VirtIoHalImpl, VirtIoTransport>;
+
+        /// The driver of [`VirtIo9pDev`], which is probed without
+        /// `axdriver_virtio` as it does not know the device type.
+        pub struct VirtIo9pDriver;
+
+        impl DriverProbe for VirtIo9pDriver {
+            #[cfg(bus = "mmio")]
+            fn probe_mmio(mmio_base: usize, mmio_size: usize) -> Option<AxDeviceEnum> {
+                use virtio_drivers::transport::{mmio::VirtIOHeader, DeviceType, Transport};
+                let base_vaddr = phys_to_virt(mmio_base.into());
+                let header = NonNull::new(base_vaddr.as_mut_ptr() as *mut VirtIOHeader)?;
+                let transport = unsafe { VirtIoTransport::new(header) }.ok()?;
+                if transport.device_type() != DeviceType::_9P {
+                    return None;
+                }
+                match VirtIo9pDev::try_new(transport) {
+                    Ok(dev) => Some(AxDeviceEnum::from_p9(dev)),
+                    Err(e) => {
+                        warn!(
+                            "failed to initialize MMIO device at [PA:{:#x}, PA:{:#x}): {:?}",
+                            mmio_base,
+                            mmio_base + mmio_size,
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+
+            #[cfg(bus = "pci")]
+            fn probe_pci(
+                root: &mut PciRoot,
+                bdf: DeviceFunction,
+                dev_info: &DeviceFunctionInfo,
+            ) -> Option<AxDeviceEnum> {
+                // the transitional and the modern device IDs
+                if dev_info.vendor_id != 0x1af4 || !matches!(dev_info.device_id, 0x1009 | 0x1049) {
+                    return None;
+                }
+                let transport = VirtIoTransport::new::<VirtIoHalImpl>(root, bdf).ok()?;
+                match VirtIo9pDev::try_new(transport) {
+                    Ok(dev) => Some(AxDeviceEnum::from_p9(dev)),
+                    Err(e) => {
+                        warn!(
+                            "failed to initialize PCI device at {}({}): {:?}",
+                            bdf, dev_info, e
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// A common driver for all VirtIO devices that implements [`DriverProbe`].
 pub struct VirtIoDriver<D: VirtIoDevMeta + ?Sized>(PhantomData<D>);
 
@@ -0,0 +1,119 @@
+//! The VirtIO 9P transport, which carries the messages of the 9P protocol to
+//! a filesystem on the host, e.g. a directory shared by QEMU with
+//! `-fsdev local,id=fs0,path=<dir>,security_model=none` and
+//! `-device virtio-9p-pci,fsdev=fs0,mount_tag=<tag>`.
+//!
+//! `axdriver_virtio` does not support this device, so it is built on the
+//! transports and queues of `virtio-drivers` directly.
+
+use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+use virtio_drivers::{queue::VirtQueue, transport::Transport, Hal};
+
+/// The maximum length of the mount tag, which is limited by QEMU.
+const MAX_TAG_LEN: usize = 255;
+/// The only virtqueue of the device.
+const QUEUE_REQUESTS: u16 = 0;
+const QUEUE_SIZE: usize = 16;
+
+bitflags::bitflags! {
+    /// The feature bits of the device.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Features: u64 {
+        /// The mount tag is in the configuration space.
+        const MOUNT_TAG = 1 << 0;
+        /// Compliant with the VirtIO 1.0 specification.
+        const VERSION_1 = 1 << 32;
+    }
+}
+
+const fn as_dev_err(e: virtio_drivers::Error) -> DevError {
+    use virtio_drivers::Error::*;
+    match e {
+        QueueFull => DevError::BadState,
+        NotReady => DevError::Again,
+        AlreadyUsed => DevError::AlreadyExists,
+        InvalidParam => DevError::InvalidParam,
+        DmaError => DevError::NoMemory,
+        IoError => DevError::Io,
+        Unsupported => DevError::Unsupported,
+        _ => DevError::BadState,
+    }
+}
+
+/// A VirtIO 9P device, which sends 9P requests to the host and waits for
+/// their responses one at a time.
+pub struct VirtIo9pDev<H: Hal, T: Transport> {
+    transport: T,
+    queue: VirtQueue<H, QUEUE_SIZE>,
+    tag: [u8; MAX_TAG_LEN],
+    tag_len: usize,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIo9pDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIo9pDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIo9pDev<H, T> {
+    /// Initializes the device on `transport`.
+    pub fn try_new(mut transport: T) -> DevResult<Self> {
+        let features = transport.begin_init(Features::MOUNT_TAG | Features::VERSION_1);
+        if !features.contains(Features::MOUNT_TAG) {
+            return Err(DevError::Unsupported);
+        }
+
+        // `le16 tag_len` followed by the tag, without a NUL terminator
+        let config = transport.config_space::<u8>().map_err(as_dev_err)?;
+        let read = |offset: usize| unsafe { config.as_ptr().add(offset).read_volatile() };
+        let tag_len = u16::from_le_bytes([read(0), read(1)]) as usize;
+        if tag_len > MAX_TAG_LEN {
+            return Err(DevError::InvalidParam);
+        }
+        let mut tag = [0; MAX_TAG_LEN];
+        for (i, b) in tag[..tag_len].iter_mut().enumerate() {
+            *b = read(2 + i);
+        }
+
+        let queue =
+            VirtQueue::new(&mut transport, QUEUE_REQUESTS, false, false).map_err(as_dev_err)?;
+        transport.finish_init();
+        Ok(Self {
+            transport,
+            queue,
+            tag,
+            tag_len,
+        })
+    }
+
+    /// The tag that identifies the shared filesystem, which is the source to
+    /// mount it.
+    pub fn mount_tag(&self) -> &str {
+        core::str::from_utf8(&self.tag[..self.tag_len]).unwrap_or_default()
+    }
+
+    /// Sends the 9P message `req` and waits for the response, which is
+    /// received into `resp`. Returns the length of the response.
+    pub fn request(&mut self, req: &[u8], resp: &mut [u8]) -> DevResult<usize> {
+        let len = self
+            .queue
+            .add_notify_wait_pop(&[req], &mut [resp], &mut self.transport)
+            .map_err(as_dev_err)?;
+        Ok(len as usize)
+    }
+}
+
+impl<H: Hal, T: Transport> BaseDriverOps for VirtIo9pDev<H, T> {
+    fn device_name(&self) -> &str {
+        "virtio-9p"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        // a message transport, the closest to a character device
+        DeviceType::Char
+    }
+}
+
+impl<H: Hal, T: Transport> Drop for VirtIo9pDev<H, T> {
+    fn drop(&mut self) {
+        // the queue must be unset before it is freed
+        self.transport.queue_unset(QUEUE_REQUESTS);
+    }
+}
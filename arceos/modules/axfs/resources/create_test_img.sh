@@ -26,5 +26,47 @@ create_test_img() {
 	sudo umount mnt
 }
 
+# ext2 images are populated from a staging directory, no mounting needed
+create_ext2_img() {
+	local name=$1
+	local blkcount=$2
+	local stage=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$stage/long.txt"
+	done
+	echo "Rust is cool!" >>"$stage/short.txt"
+	mkdir -p "$stage/very/long/path"
+	echo "Rust is cool!" >>"$stage/very/long/path/test.txt"
+	mkdir -p "$stage/very-long-dir-name"
+	echo "Rust is cool!" >>"$stage/very-long-dir-name/very-long-file-name.txt"
+	echo "Read only!" >>"$stage/read-only.txt"
+	chmod 444 "$stage/read-only.txt"
+	ln -s short.txt "$stage/short-link"
+	rm -f "$name"
+	mke2fs -q -t ext2 -b 1024 -L "Test!" -d "$stage" "$name" $blkcount
+	rm -rf "$stage"
+}
+
+# a GPT disk with a FAT boot partition, a FAT data partition of the Linux
+# filesystem type and a swap partition, both FAT ones copied from fat16.img,
+# and an empty 40 MiB partition large enough to be formatted as FAT32
+create_part_img() {
+	local name=$1
+	local fat=$2
+	rm -f "$name"
+	dd if=/dev/zero of="$name" bs=1M count=49
+	sfdisk -q "$name" <<EOF
+label: gpt
+start=2048, size=5000, type=uefi, name="boot"
+start=8192, size=5000, type=linux, name="data"
+start=14336, size=1024, type=swap, name="swap"
+start=16384, size=81920, type=linux, name="scratch"
+EOF
+	dd if="$fat" of="$name" bs=512 seek=2048 conv=notrunc
+	dd if="$fat" of="$name" bs=512 seek=8192 conv=notrunc
+}
+
 create_test_img "$CUR_DIR/fat16.img" 2500 16
 create_test_img "$CUR_DIR/fat32.img" 34000 32
+create_ext2_img "$CUR_DIR/ext2.img" 4096
+create_part_img "$CUR_DIR/gpt.img" "$CUR_DIR/fat16.img"
@@ -1,5 +1,6 @@
 use axio::{prelude::*, Result, SeekFrom};
 use core::fmt;
+use core::time::Duration;
 
 use crate::fops;
 
@@ -15,8 +16,9 @@ pub struct File {
     inner: fops::File,
 }
 
-/// Metadata information about a file.
-pub struct Metadata(fops::FileAttr);
+/// Metadata information about a file, with its ownership, timestamps and
+/// number of hard links.
+pub struct Metadata(pub(super) fops::FileAttr, pub(super) fops::FileMeta);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -64,6 +66,20 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the option to open the file to be executed, which fails if its
+    /// filesystem is mounted `noexec`.
+    pub fn execute(&mut self, execute: bool) -> &mut Self {
+        self.0.execute(execute);
+        self
+    }
+
+    /// Sets the permission bits of the file if it is created, `0o666` by
+    /// default.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.0.mode(mode);
+        self
+    }
+
     /// Opens a file at `path` with the options specified by `self`.
     pub fn open(&self, path: &str) -> Result<File> {
         fops::File::open(path, &self.0).map(|inner| File { inner })
@@ -90,6 +106,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible for [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +132,37 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.1.nlink
+    }
+
+    /// Returns the user ID of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.1.uid
+    }
+
+    /// Returns the group ID of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.1.gid
+    }
+
+    /// Returns the time of the last access to the file, since the epoch.
+    pub const fn accessed(&self) -> Duration {
+        self.1.atime
+    }
+
+    /// Returns the time of the last modification of the file, since the epoch.
+    pub const fn modified(&self) -> Duration {
+        self.1.mtime
+    }
+
+    /// Returns the time of the last status change of the file, since the
+    /// epoch.
+    pub const fn changed(&self) -> Duration {
+        self.1.ctime
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +172,8 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("uid", &self.uid())
+            .field("gid", &self.gid())
             .finish_non_exhaustive()
     }
 }
@@ -160,7 +215,22 @@ impl File {
 
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
-        self.inner.get_attr().map(Metadata)
+        Ok(Metadata(self.inner.get_attr()?, self.inner.meta()?))
+    }
+
+    /// Changes the permissions of the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.set_perm(perm)
+    }
+
+    /// Attempts to sync all data and metadata of the file to the device.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    /// Same as [`sync_all`](Self::sync_all), metadata is always synced.
+    pub fn sync_data(&self) -> Result<()> {
+        self.inner.flush()
     }
 }
 
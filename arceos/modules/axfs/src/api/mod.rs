@@ -5,9 +5,14 @@ mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::fops::XattrMode;
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+pub use crate::fs::fatck::FsckReport;
+pub use crate::root::{FsStat, MountFlags, MountInfo};
 
 use alloc::{string::String, vec::Vec};
 use axio::{self as io, prelude::*};
+use core::time::Duration;
 
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
@@ -17,7 +22,7 @@ pub fn read_dir(path: &str) -> io::Result<ReadDir> {
 /// Returns the canonical, absolute form of a path with all intermediate
 /// components normalized.
 pub fn canonicalize(path: &str) -> io::Result<String> {
-    crate::root::absolute_path(path)
+    crate::root::absolute_path(&crate::root::resolve(None, path, true)?)
 }
 
 /// Returns the current working directory as a [`String`].
@@ -56,7 +61,14 @@ pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub fn metadata(path: &str) -> io::Result<Metadata> {
-    File::open(path)?.metadata()
+    let (attr, meta) = crate::root::metadata(path, true)?;
+    Ok(Metadata(attr, meta))
+}
+
+/// Queries the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    let (attr, meta) = crate::root::metadata(path, false)?;
+    Ok(Metadata(attr, meta))
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -87,3 +99,212 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
+
+/// Creates a new symbolic link `link` that points to `original`.
+///
+/// `original` is not checked, it can be a path relative to the directory of
+/// `link` or not exist at all.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::symlink(original, link)
+}
+
+/// Creates a new hard link `link` to the file `original`.
+///
+/// Both must be in the same mounted filesystem, and only files and symbolic
+/// links can be linked.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::link(original, link)
+}
+
+/// Reads the target of a symbolic link.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(path)
+}
+
+/// Changes the permissions of a file or directory, which only its owner and
+/// root can do.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    crate::root::set_perm(path, perm)
+}
+
+/// Changes the owner and the group of a file or directory, which are kept if
+/// `None`.
+///
+/// Only root can change the owner. The owner can change the group to its own
+/// group.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::root::set_owner(path, uid, gid, true)
+}
+
+/// Same as [`chown`], but does not follow symbolic links.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::root::set_owner(path, uid, gid, false)
+}
+
+/// Changes the access and modification times of a file or directory, which
+/// are kept if `None`. Only its owner and root can do it.
+///
+/// Times are durations since the epoch.
+pub fn set_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> io::Result<()> {
+    crate::root::set_times(path, accessed, modified)
+}
+
+/// Returns the value of the extended attribute `name` of a file or directory,
+/// or `None` if there is no such attribute.
+///
+/// Only the RAM filesystem supports extended attributes, others fail with
+/// [`OperationNotSupported`](io::Error::OperationNotSupported).
+pub fn get_xattr(path: &str, name: &str) -> io::Result<Option<Vec<u8>>> {
+    crate::root::get_xattr(path, name, true)
+}
+
+/// Same as [`get_xattr`], but does not follow symbolic links.
+pub fn lget_xattr(path: &str, name: &str) -> io::Result<Option<Vec<u8>>> {
+    crate::root::get_xattr(path, name, false)
+}
+
+/// Returns the names of the extended attributes of a file or directory.
+pub fn list_xattrs(path: &str) -> io::Result<Vec<String>> {
+    crate::root::list_xattrs(path, true)
+}
+
+/// Same as [`list_xattrs`], but does not follow symbolic links.
+pub fn llist_xattrs(path: &str) -> io::Result<Vec<String>> {
+    crate::root::list_xattrs(path, false)
+}
+
+/// Sets the extended attribute `name` of a file or directory to `value`.
+///
+/// Returns `false` without setting it if `mode` is [`XattrMode::Replace`]
+/// and there is no such attribute.
+pub fn set_xattr(path: &str, name: &str, value: &[u8], mode: XattrMode) -> io::Result<bool> {
+    crate::root::set_xattr(path, name, value, mode, true)
+}
+
+/// Same as [`set_xattr`], but does not follow symbolic links.
+pub fn lset_xattr(path: &str, name: &str, value: &[u8], mode: XattrMode) -> io::Result<bool> {
+    crate::root::set_xattr(path, name, value, mode, false)
+}
+
+/// Removes the extended attribute `name` of a file or directory. Returns
+/// `false` if there is no such attribute.
+pub fn remove_xattr(path: &str, name: &str) -> io::Result<bool> {
+    crate::root::remove_xattr(path, name, true)
+}
+
+/// Same as [`remove_xattr`], but does not follow symbolic links.
+pub fn lremove_xattr(path: &str, name: &str) -> io::Result<bool> {
+    crate::root::remove_xattr(path, name, false)
+}
+
+/// Returns the user ID that files are accessed with.
+pub fn current_uid() -> u32 {
+    crate::perm::current_ids().0
+}
+
+/// Returns the group ID that files are accessed with.
+pub fn current_gid() -> u32 {
+    crate::perm::current_ids().1
+}
+
+/// Sets the user ID that files are accessed with and created by.
+///
+/// There is no privilege check, as all tasks share the same files.
+pub fn set_current_uid(uid: u32) {
+    crate::perm::set_current_uid(uid)
+}
+
+/// Sets the group ID that files are accessed with and created by.
+pub fn set_current_gid(gid: u32) {
+    crate::perm::set_current_gid(gid)
+}
+
+/// Mounts the filesystem of type `fstype` on `source` at `target` with
+/// `flags`.
+///
+/// `source` is the name of a block device (e.g. `blk1`, `/dev/blk1` or the
+/// partition `blk0p2`) for disk filesystems such as `vfat`, and is ignored by
+/// `ramfs`. A partition may also be given by its GPT name or unique GUID as
+/// `PARTLABEL=<name>` or `PARTUUID=<uuid>`, where MBR partitions have UUIDs
+/// like `12345678-01` as in Linux. For an `overlay`, `source` is the path of
+/// the read-only lower directory, see [`overlayfs`](crate::overlayfs). The
+/// target directory is created if it does not exist.
+pub fn mount(source: &str, target: &str, fstype: &str, flags: MountFlags) -> io::Result<()> {
+    crate::root::mount(source, target, fstype, flags, "")
+}
+
+/// Mounts the filesystem like [`mount`], with the comma-separated `options`
+/// of the filesystem.
+///
+/// A `ramfs` takes the limits `size=<bytes>` and `nr_inodes=<count>` like a
+/// Linux tmpfs, where the numbers may end with `k`, `m` or `g` and `0` means
+/// unlimited. It is limited to half of the memory by default, beyond which
+/// writes fail with [`StorageFull`](io::Error::StorageFull). Options of other
+/// filesystems are ignored.
+pub fn mount_with_options(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+    options: &str,
+) -> io::Result<()> {
+    crate::root::mount(source, target, fstype, flags, options)
+}
+
+/// Changes the flags of the filesystem mounted at `target`, which can be the
+/// root filesystem.
+pub fn remount(target: &str, flags: MountFlags) -> io::Result<()> {
+    crate::root::remount(target, flags)
+}
+
+/// Unmounts the filesystem mounted at `target`.
+///
+/// Fails with [`ResourceBusy`](io::Error::ResourceBusy) if any file or
+/// directory in it is still in use.
+pub fn umount(target: &str) -> io::Result<()> {
+    crate::root::umount(target)
+}
+
+/// Writes all cached filesystem data back to the block devices.
+pub fn sync() -> io::Result<()> {
+    crate::dev::sync_all()
+}
+
+/// Formats the block device `source` as a FAT filesystem with the volume
+/// label `label`, like `mkfs.vfat`.
+///
+/// `source` is a block device as in [`mount`], which must not be mounted.
+/// The filesystem is FAT32 if `fat32` is set, which needs at least 32 MiB,
+/// otherwise its type is chosen by the size of the device.
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+pub fn mkfs_vfat(source: &str, label: Option<&str>, fat32: bool) -> io::Result<()> {
+    let mut disk = crate::dev::Disk::open(source)?;
+    crate::fs::fatfs::FatFileSystem::format(&mut disk, label, fat32)
+}
+
+/// Checks the consistency of the FAT filesystem on the block device
+/// `source`, like `fsck.vfat`, and repairs it if `repair` is set.
+///
+/// The chains of clusters of all files and directories are walked to find
+/// lost clusters, cross-links and files of wrong sizes, see [`FsckReport`].
+/// `source` is a block device as in [`mount`], which must not be mounted.
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+pub fn fsck_vfat(source: &str, repair: bool) -> io::Result<FsckReport> {
+    let mut disk = crate::dev::Disk::open(source)?;
+    crate::fs::fatck::check(&mut disk, repair)
+}
+
+/// Returns the mount table, starting with the root filesystem.
+pub fn mounts() -> Vec<MountInfo> {
+    crate::root::mount_infos()
+}
+
+/// Returns the size and the usage of the filesystem that the file at `path`
+/// lives in, like `statfs`.
+pub fn statfs(path: &str) -> io::Result<FsStat> {
+    crate::root::statfs(path)
+}
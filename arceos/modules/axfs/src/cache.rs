@@ -0,0 +1,215 @@
+//! A write-back cache of disk blocks.
+//!
+//! Each block device has its own cache, shared by all filesystems and disks
+//! opened on it. Blocks are evicted in LRU order, dirty blocks are written
+//! back when evicted, when [`BlockCache::flush`] is called, and at most
+//! [`WRITEBACK_INTERVAL`] after they were written.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use core::time::Duration;
+
+use axdriver::prelude::*;
+
+/// The maximum number of blocks in a cache.
+const CACHE_BLOCKS: usize = 2048;
+/// The maximum number of blocks read ahead on a sequential read.
+const READ_AHEAD_MAX: usize = 64;
+/// The maximum number of blocks written back in one request.
+const WRITEBACK_BATCH: usize = 64;
+
+/// How long a dirty block may stay in the cache before it is written back.
+pub const WRITEBACK_INTERVAL: Duration = Duration::from_secs(5);
+
+struct CachedBlock {
+    data: Box<[u8]>,
+    dirty: bool,
+    /// The time of the last access, the key in [`BlockCache::lru`].
+    stamp: u64,
+}
+
+/// A cache of the blocks of one device.
+pub(crate) struct BlockCache {
+    dev: AxBlockDevice,
+    block_size: usize,
+    num_blocks: u64,
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// Cached block IDs ordered by their last access.
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    dirty: usize,
+    /// The block following the last read from the device.
+    next_sequential: u64,
+    /// The current read-ahead window, grows on sequential reads.
+    read_ahead: usize,
+    last_writeback: Duration,
+}
+
+impl BlockCache {
+    pub fn new(dev: AxBlockDevice) -> Self {
+        Self {
+            block_size: dev.block_size(),
+            num_blocks: dev.num_blocks(),
+            dev,
+            blocks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            dirty: 0,
+            next_sequential: u64::MAX,
+            read_ahead: 0,
+            last_writeback: axhal::time::monotonic_time(),
+        }
+    }
+
+    /// Copies `buf.len()` bytes at `offset` in block `block_id` into `buf`.
+    pub fn read(&mut self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        let block = self.get(block_id, true)?;
+        buf.copy_from_slice(&block.data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    /// Copies `buf` to `offset` in block `block_id`, which becomes dirty.
+    pub fn write(&mut self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        let block = if offset == 0 && buf.len() == self.block_size {
+            // overwritten entirely, no need to read it
+            self.get_uninit(block_id)?
+        } else {
+            self.get(block_id, false)?
+        };
+        block.data[offset..offset + buf.len()].copy_from_slice(buf);
+        if !block.dirty {
+            block.dirty = true;
+            self.dirty += 1;
+        }
+        if axhal::time::monotonic_time() >= self.last_writeback + WRITEBACK_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes all dirty blocks back and flushes the device.
+    pub fn flush(&mut self) -> DevResult {
+        let dirty = if self.dirty > 0 {
+            self.blocks
+                .iter()
+                .filter(|(_, block)| block.dirty)
+                .map(|(&id, _)| id)
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+        let mut i = 0;
+        while i < dirty.len() {
+            // merge consecutive blocks into one request
+            let mut n = 1;
+            while i + n < dirty.len() && n < WRITEBACK_BATCH && dirty[i + n] == dirty[i] + n as u64
+            {
+                n += 1;
+            }
+            self.write_back(&dirty[i..i + n])?;
+            i += n;
+        }
+        self.dev.flush()?;
+        self.last_writeback = axhal::time::monotonic_time();
+        Ok(())
+    }
+
+    /// Writes back the consecutive blocks `ids`.
+    fn write_back(&mut self, ids: &[u64]) -> DevResult {
+        let mut buf = Vec::with_capacity(ids.len() * self.block_size);
+        for id in ids {
+            buf.extend_from_slice(&self.blocks[id].data);
+        }
+        self.dev.write_block(ids[0], &buf)?;
+        for id in ids {
+            self.blocks.get_mut(id).unwrap().dirty = false;
+        }
+        self.dirty -= ids.len();
+        Ok(())
+    }
+
+    /// Returns the cached block `block_id`, reading it from the device if
+    /// needed.
+    fn get(&mut self, block_id: u64, read_ahead: bool) -> DevResult<&mut CachedBlock> {
+        if self.blocks.contains_key(&block_id) {
+            return Ok(self.touch(block_id));
+        }
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+
+        let count = if read_ahead {
+            self.read_ahead = if block_id == self.next_sequential {
+                (self.read_ahead * 2).clamp(4, READ_AHEAD_MAX)
+            } else {
+                0
+            };
+            // stop at the end of the device or at the next cached block
+            let mut count = 1;
+            while count <= self.read_ahead
+                && block_id + (count as u64) < self.num_blocks
+                && !self.blocks.contains_key(&(block_id + count as u64))
+            {
+                count += 1;
+            }
+            count
+        } else {
+            1
+        };
+
+        let mut buf = vec![0; count * self.block_size];
+        self.dev.read_block(block_id, &mut buf)?;
+        self.next_sequential = block_id + count as u64;
+        // insert the requested block last so it is the most recently used
+        for (i, data) in buf.chunks(self.block_size).enumerate().rev() {
+            self.insert(block_id + i as u64, data.into())?;
+        }
+        Ok(self.touch(block_id))
+    }
+
+    /// Returns the cached block `block_id` without reading it from the device.
+    fn get_uninit(&mut self, block_id: u64) -> DevResult<&mut CachedBlock> {
+        if !self.blocks.contains_key(&block_id) {
+            if block_id >= self.num_blocks {
+                return Err(DevError::InvalidParam);
+            }
+            self.insert(block_id, vec![0; self.block_size].into())?;
+        }
+        Ok(self.touch(block_id))
+    }
+
+    fn insert(&mut self, block_id: u64, data: Box<[u8]>) -> DevResult {
+        while self.blocks.len() >= CACHE_BLOCKS {
+            self.evict()?;
+        }
+        self.clock += 1;
+        self.lru.insert(self.clock, block_id);
+        let block = CachedBlock {
+            data,
+            dirty: false,
+            stamp: self.clock,
+        };
+        self.blocks.insert(block_id, block);
+        Ok(())
+    }
+
+    /// Marks the block as the most recently used.
+    fn touch(&mut self, block_id: u64) -> &mut CachedBlock {
+        let block = self.blocks.get_mut(&block_id).unwrap();
+        self.clock += 1;
+        self.lru.remove(&block.stamp);
+        self.lru.insert(self.clock, block_id);
+        block.stamp = self.clock;
+        block
+    }
+
+    /// Drops the least recently used block, writing it back if dirty.
+    fn evict(&mut self) -> DevResult {
+        let (_, &block_id) = self.lru.first_key_value().unwrap();
+        if self.blocks[&block_id].dirty {
+            self.write_back(&[block_id])?;
+        }
+        self.lru.pop_first();
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+}
@@ -1,18 +1,183 @@
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use axdriver::prelude::*;
+use axerrno::{ax_err, AxResult};
+use axsync::Mutex;
+
+use crate::cache::BlockCache;
+use crate::partition::{self, Partition};
 
 const BLOCK_SIZE: usize = 512;
 
+/// A block device known to the filesystem module, either a whole device or a
+/// partition of it.
+///
+/// It can be opened as at most one [`Disk`] at a time, so two filesystems
+/// never share the same device. A whole device can not be opened while any
+/// of its partitions is opened, and vice versa.
+pub(crate) struct BlockDevice {
+    name: String,
+    /// Shared by the whole device and its partitions.
+    cache: Arc<Mutex<BlockCache>>,
+    /// The first block on the whole device, 0 if not a partition.
+    start: u64,
+    num_blocks: u64,
+    partition: Option<Partition>,
+    in_use: AtomicBool,
+}
+
+/// All block devices found at boot, named `blk0`, `blk1`, ..., followed by
+/// their partitions named `blk0p1`, `blk0p2`, ...
+static BLOCK_DEVICES: Mutex<Vec<Arc<BlockDevice>>> = Mutex::new(Vec::new());
+
+impl BlockDevice {
+    fn new(name: String, dev: AxBlockDevice) -> Self {
+        assert_eq!(BLOCK_SIZE, dev.block_size());
+        Self {
+            name,
+            num_blocks: dev.num_blocks(),
+            cache: Arc::new(Mutex::new(BlockCache::new(dev))),
+            start: 0,
+            partition: None,
+            in_use: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates the device of `part` on this whole device.
+    fn partition(&self, part: Partition) -> Self {
+        Self {
+            name: format!("{}p{}", self.name, part.index),
+            cache: self.cache.clone(),
+            start: part.start,
+            num_blocks: part.num_blocks,
+            partition: Some(part),
+            in_use: AtomicBool::new(false),
+        }
+    }
+
+    /// The name of the device, e.g. `blk0` or `blk0p1`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether `source` refers to this device, see [`Disk::open`].
+    fn matches(&self, source: &str) -> bool {
+        if let Some(label) = source.strip_prefix("PARTLABEL=") {
+            (self.partition.as_ref()).is_some_and(|p| p.label.as_deref() == Some(label))
+        } else if let Some(uuid) = source.strip_prefix("PARTUUID=") {
+            (self.partition.as_ref()).is_some_and(|p| p.uuid.eq_ignore_ascii_case(uuid))
+        } else {
+            self.name == source.strip_prefix("/dev/").unwrap_or(source)
+        }
+    }
+
+    /// Whether this device and `other` are on the same whole device and
+    /// overlap, i.e. one of them is the whole device.
+    fn overlaps(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cache, &other.cache)
+            && (self.partition.is_none() || other.partition.is_none())
+    }
+}
+
+/// Registers a block device and its partitions, returns its name.
+pub(crate) fn register_device(dev: AxBlockDevice) -> String {
+    let mut devices = BLOCK_DEVICES.lock();
+    let index = devices.iter().filter(|dev| dev.partition.is_none()).count();
+    let name = format!("blk{}", index);
+    info!("  block device {}: {:?}", name, dev.device_name());
+    let dev = Arc::new(BlockDevice::new(name.clone(), dev));
+    let partitions = {
+        let mut cache = dev.cache.lock();
+        partition::read_partitions(dev.num_blocks, |block_id, buf| cache.read(block_id, 0, buf))
+    };
+    let partitions = partitions.unwrap_or_else(|e| {
+        warn!("failed to read the partition table of {}: {:?}", name, e);
+        Vec::new()
+    });
+    devices.push(dev.clone());
+    for part in partitions {
+        let part = dev.partition(part);
+        let info = part.partition.as_ref().unwrap();
+        info!(
+            "    partition {}: {} sectors at {}, {:?}, label {:?}, uuid {}",
+            part.name, part.num_blocks, part.start, info.ty, info.label, info.uuid
+        );
+        devices.push(Arc::new(part));
+    }
+    name
+}
+
+/// Returns the name of the device of the root filesystem: the first Linux
+/// filesystem partition of `blk0`, or its first partition other than swap if
+/// none, or `blk0` itself if it is not partitioned.
+pub(crate) fn root_device() -> Option<String> {
+    let devices = BLOCK_DEVICES.lock();
+    let disk = devices.first()?;
+    let partitions: Vec<_> = devices
+        .iter()
+        .filter_map(|dev| dev.partition.as_ref().filter(|_| dev.overlaps(disk)))
+        .collect();
+    let root = (partitions.iter())
+        .find(|p| p.ty.is_linux_fs())
+        .or_else(|| partitions.iter().find(|p| !p.ty.is_swap()));
+    Some(match root {
+        Some(p) => format!("{}p{}", disk.name, p.index),
+        None => disk.name.clone(),
+    })
+}
+
+/// Writes the cached changes of all block devices back to them.
+pub(crate) fn sync_all() -> AxResult {
+    let devices = BLOCK_DEVICES.lock().clone();
+    // partitions share the cache of their whole device
+    for dev in devices.iter().filter(|dev| dev.partition.is_none()) {
+        if let Err(e) = dev.cache.lock().flush() {
+            warn!("failed to flush block device {}: {:?}", dev.name, e);
+            return ax_err!(Io);
+        }
+    }
+    Ok(())
+}
+
 /// A disk device with a cursor.
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    dev: Arc<BlockDevice>,
 }
 
 impl Disk {
     /// Create a new disk.
     pub fn new(dev: AxBlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+        let name = String::from(dev.device_name());
+        Self::from_device(Arc::new(BlockDevice::new(name, dev)))
+    }
+
+    /// Opens a registered block device as a disk.
+    ///
+    /// `source` is the name of the device (optionally prefixed with `/dev/`),
+    /// e.g. `blk0` or its first partition `blk0p1`, or a partition given by
+    /// `PARTLABEL=<label>` or `PARTUUID=<uuid>`, see [`Partition`].
+    ///
+    /// Returns [`ResourceBusy`](axerrno::AxError::ResourceBusy) if the device
+    /// or an overlapping one is already opened, e.g. by a mounted filesystem.
+    pub(crate) fn open(source: &str) -> AxResult<Self> {
+        let devices = BLOCK_DEVICES.lock();
+        let Some(dev) = devices.iter().find(|dev| dev.matches(source)) else {
+            return ax_err!(NotFound, "no such block device");
+        };
+        let busy = |other: &Arc<BlockDevice>| {
+            other.in_use.load(Ordering::Acquire) && (Arc::ptr_eq(other, dev) || other.overlaps(dev))
+        };
+        if devices.iter().any(busy) {
+            return ax_err!(ResourceBusy, "block device is in use");
+        }
+        dev.in_use.store(true, Ordering::Release);
+        Ok(Self::from_device(dev.clone()))
+    }
+
+    fn from_device(dev: Arc<BlockDevice>) -> Self {
         Self {
             block_id: 0,
             offset: 0,
@@ -20,9 +185,14 @@ impl Disk {
         }
     }
 
+    /// The name of the underlying block device.
+    pub fn device_name(&self) -> &str {
+        self.dev.name()
+    }
+
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.dev.num_blocks * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -38,55 +208,80 @@ impl Disk {
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        let block_id = self.device_block()?;
+        self.dev
+            .cache
+            .lock()
+            .read(block_id, self.offset, &mut buf[..count])?;
+        self.advance(count);
+        Ok(count)
+    }
+
+    /// Reads exactly `buf.len()` bytes starting at `pos`.
+    pub fn read_exact_at(&mut self, pos: u64, mut buf: &mut [u8]) -> DevResult {
+        self.set_position(pos);
+        while !buf.is_empty() {
+            let n = self.read_one(buf)?;
+            buf = &mut buf[n..];
+        }
+        Ok(())
+    }
+
+    /// Writes all of `buf` starting at `pos`.
+    pub fn write_all_at(&mut self, pos: u64, mut buf: &[u8]) -> DevResult {
+        self.set_position(pos);
+        while !buf.is_empty() {
+            let n = self.write_one(buf)?;
+            buf = &buf[n..];
+        }
+        Ok(())
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        let block_id = self.device_block()?;
+        self.dev
+            .cache
+            .lock()
+            .write(block_id, self.offset, &buf[..count])?;
+        self.advance(count);
+        Ok(count)
+    }
+
+    /// Returns the block at the cursor on the whole device, checking that it
+    /// does not go beyond the end of a partition.
+    fn device_block(&self) -> DevResult<u64> {
+        if self.block_id >= self.dev.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(self.dev.start + self.block_id)
+    }
+
+    fn advance(&mut self, count: usize) {
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
             self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(write_size)
+            self.offset -= BLOCK_SIZE;
+        }
+    }
+
+    /// Writes all cached changes of the device back to it.
+    pub fn flush(&mut self) -> DevResult {
+        self.dev.cache.lock().flush()
+    }
+}
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!(
+                "failed to flush block device {}: {:?}",
+                self.device_name(),
+                e
+            );
+        }
+        self.dev.in_use.store(false, Ordering::Release);
     }
 }
@@ -1,10 +1,15 @@
 //! Low-level filesystem operations.
 
+use alloc::{string::String, sync::Arc, vec::Vec};
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use cap_access::{Cap, WithCap};
 use core::fmt;
+use core::time::Duration;
+
+use crate::notify::WatchMask;
+use crate::root::{BaseDir, FsStat, MountFlags, MountPoint};
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -20,9 +25,84 @@ pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// Attributes of a file that are not in [`FileAttr`].
+///
+/// Filesystems that do not record owners or timestamps report root as the
+/// owner and zero timestamps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileMeta {
+    /// The number of hard links.
+    pub nlink: u64,
+    /// The user ID of the owner.
+    pub uid: u32,
+    /// The group ID of the owner.
+    pub gid: u32,
+    /// The time of the last access, since the epoch.
+    pub atime: Duration,
+    /// The time of the last modification, since the epoch.
+    pub mtime: Duration,
+    /// The time of the last status change, since the epoch.
+    pub ctime: Duration,
+}
+
+/// Identifies the file an opened [`File`] refers to, which is the same for
+/// all opened files of it as long as any of them is open.
+///
+/// It is used to share state between the opened files of a file, e.g. locks.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileId {
+    mount: usize,
+    key: FileKey,
+}
+
+/// Identifies a file in its filesystem, see [`crate::root::file_key`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum FileKey {
+    /// The address of the node, for filesystems that keep a single node for
+    /// each file.
+    Node(usize),
+    /// The inode number.
+    Ino(u64),
+    /// The absolute path, for filesystems that create a new node on each
+    /// lookup and have no inode numbers.
+    Path(String),
+}
+
+/// How [`File::allocate`] changes the space of a range of a file, like the
+/// modes of `fallocate(2)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocMode {
+    /// Allocates the range, extending the file to its end.
+    Allocate,
+    /// Allocates the range, keeping the size of the file.
+    KeepSize,
+    /// Deallocates the range, which reads as zeros afterwards, keeping the
+    /// size of the file.
+    PunchHole,
+}
+
+/// How [`File::set_xattr`] sets an extended attribute, like the flags of
+/// `setxattr(2)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XattrMode {
+    /// Creates the attribute or replaces its value.
+    Set,
+    /// Creates the attribute, failing with
+    /// [`AlreadyExists`](AxError::AlreadyExists) if it exists.
+    Create,
+    /// Replaces the value of an existing attribute.
+    Replace,
+}
+
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
+    /// Keeps the filesystem from being unmounted while the file is opened.
+    mount: Arc<MountPoint>,
+    id: FileId,
+    /// The absolute path the file is opened at, to report its changes to the
+    /// watchers, or `None` if it is opened relative to a directory.
+    path: Option<String>,
     is_append: bool,
     offset: u64,
 }
@@ -31,6 +111,11 @@ pub struct File {
 /// [`read_dir`](Directory::read_dir).
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
+    /// Keeps the filesystem from being unmounted while the directory is opened.
+    mount: Arc<MountPoint>,
+    /// The absolute path the directory is opened at, to find the mount points
+    /// of the paths relative to it.
+    path: String,
     entry_idx: usize,
 }
 
@@ -44,9 +129,10 @@ pub struct OpenOptions {
     truncate: bool,
     create: bool,
     create_new: bool,
+    execute: bool,
     // system-specific
     _custom_flags: i32,
-    _mode: u32,
+    mode: u32,
 }
 
 impl OpenOptions {
@@ -60,9 +146,10 @@ impl OpenOptions {
             truncate: false,
             create: false,
             create_new: false,
+            execute: false,
             // system-specific
             _custom_flags: 0,
-            _mode: 0o666,
+            mode: 0o666,
         }
     }
     /// Sets the option for read access.
@@ -89,6 +176,15 @@ impl OpenOptions {
     pub fn create_new(&mut self, create_new: bool) {
         self.create_new = create_new;
     }
+    /// Sets the option to open a file to be executed, which fails if its
+    /// filesystem is mounted `noexec`.
+    pub fn execute(&mut self, execute: bool) {
+        self.execute = execute;
+    }
+    /// Sets the permission bits of the file if it is created.
+    pub fn mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
 
     const fn is_valid(&self) -> bool {
         if !self.read && !self.write && !self.append {
@@ -116,29 +212,41 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(base: BaseDir, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
+        let path = &crate::root::resolve(base.map(|d| d.0), path, true)?;
+        let mount = crate::root::mount_point_of(base.map(|d| d.1), path)?;
+        if opts.write || opts.append || opts.truncate {
+            mount.check_writable()?;
+        }
+        if opts.execute {
+            mount.check_executable()?;
+        }
+        let dir = base.map(|d| d.0);
 
         let node_option = crate::root::lookup(dir, path);
-        let node = if opts.create || opts.create_new {
+        let (node, created) = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
                     // already exists
                     if opts.create_new {
                         return ax_err!(AlreadyExists);
                     }
-                    node
+                    (node, false)
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => {
+                    let perm = FilePerm::from_bits_truncate(opts.mode as u16);
+                    (crate::root::create_file(base, path, perm)?, true)
+                }
                 Err(e) => return Err(e),
             }
         } else {
             // just open the existing
-            node_option?
+            (node_option?, false)
         };
 
         let attr = node.get_attr()?;
@@ -148,16 +256,31 @@ impl File {
             return ax_err!(IsADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
-            return ax_err!(PermissionDenied);
+        if !created {
+            // a new file can be opened regardless of the permissions it is given
+            crate::perm::check_access(&node, &attr, access_cap)?;
         }
 
         node.open()?;
+        let path = match base {
+            Some(_) if !path.starts_with('/') => None,
+            _ => Some(crate::root::absolute_path(path)?),
+        };
         if opts.truncate {
             node.truncate(0)?;
+            if let (Some(path), false) = (&path, created) {
+                crate::notify::notify(path, WatchMask::MODIFY, false);
+            }
         }
+        let id = FileId {
+            mount: Arc::as_ptr(&mount) as usize,
+            key: crate::root::file_key(&node, path.as_deref()),
+        };
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            mount,
+            id,
+            path,
             is_append: opts.append,
             offset: 0,
         })
@@ -169,9 +292,47 @@ impl File {
         Self::_open_at(None, path, opts)
     }
 
+    fn access_node_writable(&self) -> AxResult<&VfsNodeRef> {
+        let node = self.access_node(Cap::WRITE)?;
+        self.mount.check_writable()?; // it may be remounted read-only
+        Ok(node)
+    }
+
+    /// Flushes the written data if the filesystem is mounted `sync`.
+    fn sync_if_needed(&self, node: &VfsNodeRef) -> AxResult {
+        if self.mount.flags().contains(MountFlags::SYNC) {
+            self.mount.fsync(node)?;
+        }
+        Ok(())
+    }
+
+    /// Reports the event `mask` of the file to the watchers.
+    fn notify(&self, mask: WatchMask) {
+        // checking the type of the file is not free, e.g. on every write
+        if !crate::notify::has_watches() {
+            return;
+        }
+        if let (Some(path), Ok(node)) = (&self.path, self.access_node(Cap::empty())) {
+            crate::notify::notify(path, mask, crate::root::is_dir(node));
+        }
+    }
+
+    /// Returns the identity of the file, shared by all opened files of it.
+    pub fn file_id(&self) -> &FileId {
+        &self.id
+    }
+
+    /// Returns the flags of the filesystem the file lives in.
+    pub fn mount_flags(&self) -> MountFlags {
+        self.mount.flags()
+    }
+
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node_writable()?;
+        node.truncate(size)?;
+        self.sync_if_needed(node)?;
+        self.notify(WatchMask::MODIFY);
         Ok(())
     }
 
@@ -206,9 +367,11 @@ impl File {
         } else {
             self.offset
         };
-        let node = self.access_node(Cap::WRITE)?;
+        let node = self.access_node_writable()?;
         let write_len = node.write_at(offset, buf)?;
+        self.sync_if_needed(node)?;
         self.offset = offset + write_len as u64;
+        self.notify(WatchMask::MODIFY);
         Ok(write_len)
     }
 
@@ -217,15 +380,16 @@ impl File {
     ///
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::WRITE)?;
+        let node = self.access_node_writable()?;
         let write_len = node.write_at(offset, buf)?;
+        self.sync_if_needed(node)?;
+        self.notify(WatchMask::MODIFY);
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        self.mount.fsync(self.access_node(Cap::WRITE)?)
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
@@ -242,10 +406,123 @@ impl File {
         Ok(new_offset)
     }
 
+    /// Moves the cursor to the first data at or after `offset`, like `lseek`
+    /// with `SEEK_DATA`. Returns the new position, or `None` without moving
+    /// the cursor if `offset` is at or after the end of the file.
+    ///
+    /// Files on filesystems without holes are all data.
+    pub fn seek_data(&mut self, offset: u64) -> AxResult<Option<u64>> {
+        let node = self.access_node(Cap::empty())?;
+        let pos = crate::root::seek_data_or_hole(node, offset, false)?;
+        self.offset = pos.unwrap_or(self.offset);
+        Ok(pos)
+    }
+
+    /// Moves the cursor to the first hole at or after `offset`, like `lseek`
+    /// with `SEEK_HOLE`, where the end of the file counts as a hole. Returns
+    /// the new position, or `None` without moving the cursor if `offset` is
+    /// at or after the end of the file.
+    pub fn seek_hole(&mut self, offset: u64) -> AxResult<Option<u64>> {
+        let node = self.access_node(Cap::empty())?;
+        let pos = crate::root::seek_data_or_hole(node, offset, true)?;
+        self.offset = pos.unwrap_or(self.offset);
+        Ok(pos)
+    }
+
+    /// Allocates or deallocates the space of the range at `offset` of `len`
+    /// bytes, like `fallocate`.
+    ///
+    /// Only files in the RAM filesystem support it, for which unwritten
+    /// ranges take no memory.
+    pub fn allocate(&self, offset: u64, len: u64, mode: AllocMode) -> AxResult {
+        let node = self.access_node_writable()?;
+        crate::root::allocate(node, offset, len, mode)?;
+        self.notify(WatchMask::MODIFY);
+        Ok(())
+    }
+
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.access_node(Cap::empty())?.get_attr()
     }
+
+    /// Returns the usage of the filesystem the file lives in, like
+    /// `fstatfs`.
+    pub fn statfs(&self) -> AxResult<FsStat> {
+        let node = self.access_node(Cap::empty())?;
+        Ok(crate::root::statfs_of(node, &self.mount))
+    }
+
+    /// Returns the number of hard links to the file.
+    pub fn nlink(&self) -> AxResult<u64> {
+        Ok(crate::root::nlink_of(self.access_node(Cap::empty())?))
+    }
+
+    /// Returns the ownership, timestamps and number of hard links of the file.
+    pub fn meta(&self) -> AxResult<FileMeta> {
+        Ok(crate::perm::meta_of(self.access_node(Cap::empty())?))
+    }
+
+    /// Changes the permissions of the file, which only its owner and root can
+    /// do.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        self.mount.check_writable()?;
+        crate::perm::set_perm(self.access_node(Cap::empty())?, perm)?;
+        self.notify(WatchMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Changes the owner and the group of the file, which are kept if `None`.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        self.mount.check_writable()?;
+        crate::perm::set_owner(self.access_node(Cap::empty())?, uid, gid)?;
+        self.notify(WatchMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Changes the access and modification times of the file, which are kept
+    /// if `None`.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+        self.mount.check_writable()?;
+        crate::perm::set_times(self.access_node(Cap::empty())?, atime, mtime)?;
+        self.notify(WatchMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Returns the value of the extended attribute `name` of the file, or
+    /// `None` if there is no such attribute.
+    pub fn get_xattr(&self, name: &str) -> AxResult<Option<Vec<u8>>> {
+        crate::xattr::get(self.access_node(Cap::empty())?, name)
+    }
+
+    /// Returns the names of the extended attributes of the file.
+    pub fn list_xattrs(&self) -> AxResult<Vec<String>> {
+        crate::xattr::list(self.access_node(Cap::empty())?)
+    }
+
+    /// Sets the extended attribute `name` of the file to `value`.
+    ///
+    /// Returns `false` without setting it if `mode` is
+    /// [`XattrMode::Replace`] and there is no such attribute.
+    pub fn set_xattr(&self, name: &str, value: &[u8], mode: XattrMode) -> AxResult<bool> {
+        self.mount.check_writable()?;
+        let set = crate::xattr::set(self.access_node(Cap::empty())?, name, value, mode)?;
+        if set {
+            self.notify(WatchMask::ATTRIB);
+        }
+        Ok(set)
+    }
+
+    /// Removes the extended attribute `name` of the file. Returns `false` if
+    /// there is no such attribute.
+    pub fn remove_xattr(&self, name: &str) -> AxResult<bool> {
+        self.mount.check_writable()?;
+        let removed = crate::xattr::remove(self.access_node(Cap::empty())?, name)?;
+        if removed {
+            self.notify(WatchMask::ATTRIB);
+        }
+        Ok(removed)
+    }
 }
 
 impl Directory {
@@ -253,7 +530,7 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(base: BaseDir, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -261,20 +538,22 @@ impl Directory {
         if opts.create || opts.create_new || opts.write || opts.append || opts.truncate {
             return ax_err!(InvalidInput);
         }
-
-        let node = crate::root::lookup(dir, path)?;
+        let path = &crate::root::resolve(base.map(|d| d.0), path, true)?;
+        let abs_path = crate::root::absolute_path_at(base.map(|d| d.1), path)?;
+        let mount = crate::root::mount_point_of(None, &abs_path)?;
+        let node = crate::root::lookup(base.map(|d| d.0), path)?;
         let attr = node.get_attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
-            return ax_err!(PermissionDenied);
-        }
+        crate::perm::check_access(&node, &attr, access_cap)?;
 
         node.open()?;
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            mount,
+            path: abs_path,
             entry_idx: 0,
         })
     }
@@ -293,36 +572,40 @@ impl Directory {
         Self::_open_dir_at(None, path, opts)
     }
 
+    fn base_at(&self, path: &str) -> AxResult<BaseDir> {
+        Ok(self.access_at(path)?.map(|node| (node, self.path.as_str())))
+    }
+
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        Self::_open_dir_at(self.base_at(path)?, path, opts)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        File::_open_at(self.base_at(path)?, path, opts)
     }
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        crate::root::create_file(self.base_at(path)?, path, FilePerm::default_file())
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        crate::root::create_dir(self.base_at(path)?, path)
     }
 
     /// Removes a file at the path relative to this directory.
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        crate::root::remove_file(self.base_at(path)?, path)
     }
 
     /// Removes a directory at the path relative to this directory.
     pub fn remove_dir(&self, path: &str) -> AxResult {
-        crate::root::remove_dir(self.access_at(path)?, path)
+        crate::root::remove_dir(self.base_at(path)?, path)
     }
 
     /// Reads directory entries starts from the current position into the
@@ -380,6 +663,7 @@ impl fmt::Debug for OpenOptions {
         fmt_opt!(truncate, "TRUNC");
         fmt_opt!(create, "CREATE");
         fmt_opt!(create_new, "CREATE_NEW");
+        fmt_opt!(execute, "EXECUTE");
         Ok(())
     }
 }
@@ -396,17 +680,3 @@ impl From<&OpenOptions> for Cap {
         cap
     }
 }
-
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
-    }
-    cap
-}
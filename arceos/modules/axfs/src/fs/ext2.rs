@@ -0,0 +1,1948 @@
+//! The ext2 filesystem, which can also mount ext3 and ext4.
+//!
+//! ext2 is fully supported. ext3 and ext4 are supported without journaling:
+//! the journal is neither replayed nor written, so a filesystem that needs
+//! recovery is mounted read-only. Files with extent trees can be read at any
+//! depth, but new blocks can only be added to extents stored in the inode.
+//!
+//! Filesystems with metadata checksums (`metadata_csum`, `uninit_bg`) or other
+//! unknown read-only compatible features are mounted read-only. Create them
+//! with `mkfs.ext4 -O ^metadata_csum` to get a writable ext4.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use core::any::Any;
+use core::time::Duration;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+use crate::dev::Disk;
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xEF53;
+const ROOT_INO: u32 = 2;
+const GOOD_OLD_INODE_SIZE: usize = 128;
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const NDIR_BLOCKS: u64 = 12;
+const MAX_NAME_LEN: usize = 255;
+const FAST_SYMLINK_MAX: usize = 60;
+const MAX_LINKS: u16 = 65000;
+
+const COMPAT_HAS_JOURNAL: u32 = 0x4;
+
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const INCOMPAT_RECOVER: u32 = 0x4;
+const INCOMPAT_EXTENTS: u32 = 0x40;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+const INCOMPAT_LARGEDIR: u32 = 0x4000;
+const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+const RO_COMPAT_BTREE_DIR: u32 = 0x4;
+const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+/// Read-only compatible features that are kept consistent by our writes.
+const RO_COMPAT_WRITABLE: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_BTREE_DIR
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE;
+
+const INODE_FLAG_INDEX: u32 = 0x1000;
+const INODE_FLAG_HUGE_FILE: u32 = 0x4_0000;
+const INODE_FLAG_EXTENTS: u32 = 0x8_0000;
+
+const EXTENT_MAGIC: u16 = 0xF30A;
+const EXTENT_MAX_LEN: u32 = 32768;
+const EXTENT_ROOT_MAX: u16 = 4;
+
+// offsets in a group descriptor, (low, high) halves
+const GD_BLOCK_BITMAP: (usize, usize) = (0x0, 0x20);
+const GD_INODE_BITMAP: (usize, usize) = (0x4, 0x24);
+const GD_INODE_TABLE: (usize, usize) = (0x8, 0x28);
+const GD_FREE_BLOCKS: (usize, usize) = (0xC, 0x2C);
+const GD_FREE_INODES: (usize, usize) = (0xE, 0x2E);
+const GD_USED_DIRS: (usize, usize) = (0x10, 0x30);
+
+fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn set_u16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+fn set_u32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn now() -> u32 {
+    axhal::time::wall_time().as_secs() as u32
+}
+
+/// The space a directory entry with a name of `name_len` bytes takes.
+const fn dirent_len(name_len: usize) -> usize {
+    (8 + name_len + 3) & !3
+}
+
+const fn mode_type_bits(ty: VfsNodeType) -> u16 {
+    (ty as u16) << 12
+}
+
+fn type_from_mode(mode: u16) -> VfsNodeType {
+    match mode >> 12 {
+        0o1 => VfsNodeType::Fifo,
+        0o2 => VfsNodeType::CharDevice,
+        0o4 => VfsNodeType::Dir,
+        0o6 => VfsNodeType::BlockDevice,
+        0o12 => VfsNodeType::SymLink,
+        0o14 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+const fn dirent_type(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+const fn type_from_dirent(ty: u8) -> Option<VfsNodeType> {
+    match ty {
+        1 => Some(VfsNodeType::File),
+        2 => Some(VfsNodeType::Dir),
+        3 => Some(VfsNodeType::CharDevice),
+        4 => Some(VfsNodeType::BlockDevice),
+        5 => Some(VfsNodeType::Fifo),
+        6 => Some(VfsNodeType::Socket),
+        7 => Some(VfsNodeType::SymLink),
+        _ => None,
+    }
+}
+
+/// Splits `path` into its parent directory and the last component.
+fn split_path(path: &str) -> VfsResult<(&str, &str)> {
+    let path = path.trim_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    match name {
+        "" | "." | ".." => Err(VfsError::InvalidInput),
+        _ if name.len() > MAX_NAME_LEN => Err(VfsError::InvalidInput),
+        _ => Ok((parent, name)),
+    }
+}
+
+/// An on-disk inode, kept in its raw form so unknown fields are preserved.
+struct Inode {
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    fn mode(&self) -> u16 {
+        get_u16(&self.raw, 0x0)
+    }
+
+    fn file_type(&self) -> VfsNodeType {
+        type_from_mode(self.mode())
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type() == VfsNodeType::Dir
+    }
+
+    fn size(&self) -> u64 {
+        get_u32(&self.raw, 0x4) as u64 | (get_u32(&self.raw, 0x6C) as u64) << 32
+    }
+
+    fn set_size(&mut self, size: u64) {
+        set_u32(&mut self.raw, 0x4, size as u32);
+        set_u32(&mut self.raw, 0x6C, (size >> 32) as u32);
+    }
+
+    fn set_perm(&mut self, perm: u16) {
+        let mode = self.mode() & !0o7777 | perm & 0o7777;
+        set_u16(&mut self.raw, 0x0, mode);
+    }
+
+    /// The owner, with the high 16 bits kept in `osd2`.
+    fn uid(&self) -> u32 {
+        get_u16(&self.raw, 0x2) as u32 | (get_u16(&self.raw, 0x78) as u32) << 16
+    }
+
+    fn set_uid(&mut self, uid: u32) {
+        set_u16(&mut self.raw, 0x2, uid as u16);
+        set_u16(&mut self.raw, 0x78, (uid >> 16) as u16);
+    }
+
+    fn gid(&self) -> u32 {
+        get_u16(&self.raw, 0x18) as u32 | (get_u16(&self.raw, 0x7A) as u32) << 16
+    }
+
+    fn set_gid(&mut self, gid: u32) {
+        set_u16(&mut self.raw, 0x18, gid as u16);
+        set_u16(&mut self.raw, 0x7A, (gid >> 16) as u16);
+    }
+
+    /// The access, modification and status change times in seconds.
+    fn times(&self) -> (u32, u32, u32) {
+        (
+            get_u32(&self.raw, 0x8),
+            get_u32(&self.raw, 0x10),
+            get_u32(&self.raw, 0xC),
+        )
+    }
+
+    fn links(&self) -> u16 {
+        get_u16(&self.raw, 0x1A)
+    }
+
+    fn set_links(&mut self, links: u16) {
+        set_u16(&mut self.raw, 0x1A, links);
+    }
+
+    fn flags(&self) -> u32 {
+        get_u32(&self.raw, 0x20)
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        set_u32(&mut self.raw, 0x20, flags);
+    }
+
+    fn has_extents(&self) -> bool {
+        self.flags() & INODE_FLAG_EXTENTS != 0
+    }
+
+    /// The 60-byte `i_block` area: block pointers, an extent tree root, or
+    /// the target of a fast symlink.
+    fn i_block(&self) -> &[u8] {
+        &self.raw[0x28..0x64]
+    }
+
+    fn i_block_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[0x28..0x64]
+    }
+
+    fn block_ptr(&self, idx: usize) -> u32 {
+        get_u32(self.i_block(), idx * 4)
+    }
+
+    fn set_block_ptr(&mut self, idx: usize, blk: u32) {
+        set_u32(self.i_block_mut(), idx * 4, blk);
+    }
+
+    fn file_acl(&self) -> u64 {
+        get_u32(&self.raw, 0x68) as u64 | (get_u16(&self.raw, 0x76) as u64) << 32
+    }
+
+    fn touch(&mut self, atime: bool, mtime: bool) {
+        let now = now();
+        if atime {
+            set_u32(&mut self.raw, 0x8, now);
+        }
+        set_u32(&mut self.raw, 0xC, now); // ctime
+        if mtime {
+            set_u32(&mut self.raw, 0x10, now);
+        }
+    }
+
+    /// The deletion time, or the next inode in the orphan list if it is an
+    /// orphan.
+    fn dtime(&self) -> u32 {
+        get_u32(&self.raw, 0x14)
+    }
+
+    fn set_dtime(&mut self, dtime: u32) {
+        set_u32(&mut self.raw, 0x14, dtime);
+    }
+
+    fn init_extents(&mut self) {
+        self.set_flags(self.flags() | INODE_FLAG_EXTENTS);
+        let root = self.i_block_mut();
+        root.fill(0);
+        set_u16(root, 0, EXTENT_MAGIC);
+        set_u16(root, 4, EXTENT_ROOT_MAX);
+    }
+}
+
+/// A leaf entry of an extent tree.
+#[derive(Clone, Copy)]
+struct Extent {
+    block: u64,
+    len: u32,
+    start: u64,
+    uninit: bool,
+}
+
+impl Extent {
+    fn parse(entry: &[u8]) -> Self {
+        let raw_len = get_u16(entry, 4) as u32;
+        let (len, uninit) = if raw_len > EXTENT_MAX_LEN {
+            (raw_len - EXTENT_MAX_LEN, true)
+        } else {
+            (raw_len, false)
+        };
+        Self {
+            block: get_u32(entry, 0) as u64,
+            len,
+            start: get_u32(entry, 8) as u64 | (get_u16(entry, 6) as u64) << 32,
+            uninit,
+        }
+    }
+
+    fn store(&self, entry: &mut [u8]) {
+        let raw_len = if self.uninit {
+            self.len + EXTENT_MAX_LEN
+        } else {
+            self.len
+        };
+        set_u32(entry, 0, self.block as u32);
+        set_u16(entry, 4, raw_len as u16);
+        set_u16(entry, 6, (self.start >> 32) as u16);
+        set_u32(entry, 8, self.start as u32);
+    }
+
+    fn contains(&self, lblk: u64) -> bool {
+        lblk >= self.block && lblk < self.block + self.len as u64
+    }
+}
+
+/// Parses an extent tree node, returns the number of entries and its depth.
+fn extent_header(node: &[u8]) -> VfsResult<(usize, u16)> {
+    if get_u16(node, 0) != EXTENT_MAGIC {
+        return Err(VfsError::InvalidData);
+    }
+    let entries = get_u16(node, 2) as usize;
+    if 12 + entries * 12 > node.len() {
+        return Err(VfsError::InvalidData);
+    }
+    Ok((entries, get_u16(node, 6)))
+}
+
+fn extent_index_child(entry: &[u8]) -> u64 {
+    get_u32(entry, 4) as u64 | (get_u16(entry, 8) as u64) << 32
+}
+
+/// A mounted ext2 filesystem. All operations are serialized by the lock
+/// around it.
+struct Ext2 {
+    disk: Disk,
+    sb: Vec<u8>,
+    gdt: Vec<u8>,
+    block_size: usize,
+    inode_size: usize,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    blocks_per_group: u32,
+    first_data_block: u64,
+    blocks_count: u64,
+    first_ino: u32,
+    group_count: u32,
+    desc_size: usize,
+    incompat: u32,
+    ro_compat: u32,
+    read_only: bool,
+    fstype: &'static str,
+    /// The number of [`Ext2Node`]s alive of each inode.
+    open_nodes: BTreeMap<u32, usize>,
+}
+
+impl Ext2 {
+    fn open(mut disk: Disk) -> VfsResult<Self> {
+        let mut sb = vec![0; SUPERBLOCK_SIZE];
+        disk.read_exact_at(SUPERBLOCK_OFFSET, &mut sb)
+            .map_err(|_| VfsError::Io)?;
+        if get_u16(&sb, 0x38) != EXT2_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+
+        let rev_level = get_u32(&sb, 0x4C);
+        let (inode_size, first_ino) = if rev_level == 0 {
+            (GOOD_OLD_INODE_SIZE, GOOD_OLD_FIRST_INO)
+        } else {
+            (get_u16(&sb, 0x58) as usize, get_u32(&sb, 0x54))
+        };
+        let (compat, incompat, ro_compat) = if rev_level == 0 {
+            (0, 0, 0)
+        } else {
+            (get_u32(&sb, 0x5C), get_u32(&sb, 0x60), get_u32(&sb, 0x64))
+        };
+        let log_block_size = get_u32(&sb, 0x18);
+        if log_block_size > 6 || inode_size < GOOD_OLD_INODE_SIZE {
+            return Err(VfsError::InvalidData);
+        }
+        if incompat & !INCOMPAT_SUPPORTED != 0 {
+            warn!(
+                "ext2: unsupported incompatible features {:#x}",
+                incompat & !INCOMPAT_SUPPORTED
+            );
+            return Err(VfsError::Unsupported);
+        }
+        let mut read_only = false;
+        if ro_compat & !RO_COMPAT_WRITABLE != 0 {
+            warn!(
+                "ext2: read-only compatible features {:#x} are not supported, mount read-only",
+                ro_compat & !RO_COMPAT_WRITABLE
+            );
+            read_only = true;
+        }
+        if incompat & INCOMPAT_RECOVER != 0 {
+            warn!("ext2: the journal needs recovery, mount read-only");
+            read_only = true;
+        }
+
+        let is_64bit = incompat & INCOMPAT_64BIT != 0;
+        let block_size = 1024 << log_block_size;
+        let blocks_per_group = get_u32(&sb, 0x20);
+        let inodes_per_group = get_u32(&sb, 0x28);
+        let first_data_block = get_u32(&sb, 0x14) as u64;
+        let mut blocks_count = get_u32(&sb, 0x4) as u64;
+        if is_64bit {
+            blocks_count |= (get_u32(&sb, 0x150) as u64) << 32;
+        }
+        let desc_size = if is_64bit {
+            (get_u16(&sb, 0xFE) as usize).max(32)
+        } else {
+            32
+        };
+        if blocks_per_group == 0 || inodes_per_group == 0 || blocks_count <= first_data_block {
+            return Err(VfsError::InvalidData);
+        }
+        let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group as u64);
+
+        let mut gdt = vec![0; group_count as usize * desc_size];
+        disk.read_exact_at((first_data_block + 1) * block_size as u64, &mut gdt)
+            .map_err(|_| VfsError::Io)?;
+
+        let fstype = if incompat & (INCOMPAT_EXTENTS | INCOMPAT_64BIT | INCOMPAT_FLEX_BG) != 0 {
+            "ext4"
+        } else if compat & COMPAT_HAS_JOURNAL != 0 {
+            "ext3"
+        } else {
+            "ext2"
+        };
+        let inodes_count = get_u32(&sb, 0x0);
+        let mut fs = Self {
+            disk,
+            sb,
+            gdt,
+            block_size,
+            inode_size,
+            inodes_count,
+            inodes_per_group,
+            blocks_per_group,
+            first_data_block,
+            blocks_count,
+            first_ino,
+            group_count: group_count as u32,
+            desc_size,
+            incompat,
+            ro_compat,
+            read_only,
+            fstype,
+            open_nodes: BTreeMap::new(),
+        };
+        if !fs.read_only {
+            // free the files removed while opened when it was last mounted
+            while fs.last_orphan() != 0 {
+                let ino = fs.last_orphan();
+                if let Err(e) = fs.release_orphan(ino) {
+                    warn!("ext2: failed to free orphan inode {}: {:?}", ino, e);
+                    break;
+                }
+            }
+        }
+        Ok(fs)
+    }
+
+    fn bs(&self) -> u64 {
+        self.block_size as u64
+    }
+
+    fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_bytes(&mut self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        self.disk.read_exact_at(pos, buf).map_err(|_| VfsError::Io)
+    }
+
+    fn write_bytes(&mut self, pos: u64, buf: &[u8]) -> VfsResult {
+        self.check_writable()?;
+        self.disk.write_all_at(pos, buf).map_err(|_| VfsError::Io)
+    }
+
+    fn read_block(&mut self, blk: u64, buf: &mut [u8]) -> VfsResult {
+        if blk >= self.blocks_count {
+            return Err(VfsError::InvalidData);
+        }
+        self.read_bytes(blk * self.bs(), buf)
+    }
+
+    fn write_block(&mut self, blk: u64, buf: &[u8]) -> VfsResult {
+        self.write_bytes(blk * self.bs(), buf)
+    }
+
+    fn write_superblock(&mut self) -> VfsResult {
+        self.write_bytes(SUPERBLOCK_OFFSET, &self.sb.clone())
+    }
+
+    fn sb_add_free_blocks(&mut self, delta: i64) {
+        let mut free = get_u32(&self.sb, 0xC) as u64;
+        if self.incompat & INCOMPAT_64BIT != 0 {
+            free |= (get_u32(&self.sb, 0x158) as u64) << 32;
+        }
+        let free = free.wrapping_add_signed(delta);
+        set_u32(&mut self.sb, 0xC, free as u32);
+        if self.incompat & INCOMPAT_64BIT != 0 {
+            set_u32(&mut self.sb, 0x158, (free >> 32) as u32);
+        }
+    }
+
+    fn sb_add_free_inodes(&mut self, delta: i32) {
+        let free = get_u32(&self.sb, 0x10).wrapping_add_signed(delta);
+        set_u32(&mut self.sb, 0x10, free);
+    }
+
+    /// The head of the list of orphans, the inodes without links that are
+    /// still opened, chained by their `dtime`.
+    fn last_orphan(&self) -> u32 {
+        get_u32(&self.sb, 0xE8)
+    }
+
+    fn desc(&self, group: u32) -> &[u8] {
+        let off = group as usize * self.desc_size;
+        &self.gdt[off..off + self.desc_size]
+    }
+
+    fn gd_block(&self, group: u32, (lo, hi): (usize, usize)) -> u64 {
+        let desc = self.desc(group);
+        let mut blk = get_u32(desc, lo) as u64;
+        if self.desc_size >= 64 {
+            blk |= (get_u32(desc, hi) as u64) << 32;
+        }
+        blk
+    }
+
+    fn gd_count(&self, group: u32, (lo, hi): (usize, usize)) -> u32 {
+        let desc = self.desc(group);
+        let mut count = get_u16(desc, lo) as u32;
+        if self.desc_size >= 64 {
+            count |= (get_u16(desc, hi) as u32) << 16;
+        }
+        count
+    }
+
+    fn gd_add_count(&mut self, group: u32, field: (usize, usize), delta: i32) {
+        let count = self.gd_count(group, field).wrapping_add_signed(delta);
+        let off = group as usize * self.desc_size;
+        set_u16(&mut self.gdt, off + field.0, count as u16);
+        if self.desc_size >= 64 {
+            set_u16(&mut self.gdt, off + field.1, (count >> 16) as u16);
+        }
+    }
+
+    fn write_group_desc(&mut self, group: u32) -> VfsResult {
+        let pos =
+            (self.first_data_block + 1) * self.bs() + (group as usize * self.desc_size) as u64;
+        let desc = self.desc(group).to_vec();
+        self.write_bytes(pos, &desc)
+    }
+
+    fn blocks_in_group(&self, group: u32) -> u64 {
+        let start = group as u64 * self.blocks_per_group as u64;
+        (self.blocks_count - self.first_data_block - start).min(self.blocks_per_group as u64)
+    }
+
+    fn group_first_block(&self, group: u32) -> u64 {
+        self.first_data_block + group as u64 * self.blocks_per_group as u64
+    }
+
+    /// Finds the first zero bit in `bitmap[from..end]`.
+    fn find_zero_bit(bitmap: &[u8], from: u64, end: u64) -> Option<u64> {
+        let mut bit = from;
+        while bit < end {
+            let byte = bitmap[(bit / 8) as usize];
+            if byte == 0xFF && bit % 8 == 0 {
+                bit += 8;
+                continue;
+            }
+            if byte & (1 << (bit % 8)) == 0 {
+                return Some(bit);
+            }
+            bit += 1;
+        }
+        None
+    }
+
+    /// Allocates a zeroed block, as close after `goal` as possible.
+    fn alloc_block(&mut self, goal: u64) -> VfsResult<u64> {
+        self.check_writable()?;
+        let goal = if goal >= self.first_data_block && goal < self.blocks_count {
+            goal - self.first_data_block
+        } else {
+            0
+        };
+        let bpg = self.blocks_per_group as u64;
+        let start_group = (goal / bpg) as u32;
+        let mut bitmap = vec![0; self.block_size];
+        // the start group is visited twice, to search before the goal at last
+        for i in 0..=self.group_count {
+            let group = (start_group + i) % self.group_count;
+            if self.gd_count(group, GD_FREE_BLOCKS) == 0 {
+                continue;
+            }
+            let from = if i == 0 { goal % bpg } else { 0 };
+            let bitmap_blk = self.gd_block(group, GD_BLOCK_BITMAP);
+            self.read_block(bitmap_blk, &mut bitmap)?;
+            let Some(bit) = Self::find_zero_bit(&bitmap, from, self.blocks_in_group(group)) else {
+                continue;
+            };
+            bitmap[(bit / 8) as usize] |= 1 << (bit % 8);
+            self.write_block(bitmap_blk, &bitmap)?;
+            self.gd_add_count(group, GD_FREE_BLOCKS, -1);
+            self.write_group_desc(group)?;
+            self.sb_add_free_blocks(-1);
+            self.write_superblock()?;
+
+            let blk = self.group_first_block(group) + bit;
+            bitmap.fill(0);
+            self.write_block(blk, &bitmap)?;
+            return Ok(blk);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_block(&mut self, blk: u64) -> VfsResult {
+        if blk < self.first_data_block || blk >= self.blocks_count {
+            return Err(VfsError::InvalidData);
+        }
+        let group = ((blk - self.first_data_block) / self.blocks_per_group as u64) as u32;
+        let bit = (blk - self.first_data_block) % self.blocks_per_group as u64;
+        let mut bitmap = vec![0; self.block_size];
+        let bitmap_blk = self.gd_block(group, GD_BLOCK_BITMAP);
+        self.read_block(bitmap_blk, &mut bitmap)?;
+        if bitmap[(bit / 8) as usize] & (1 << (bit % 8)) == 0 {
+            warn!("ext2: freeing free block {}", blk);
+            return Err(VfsError::InvalidData);
+        }
+        bitmap[(bit / 8) as usize] &= !(1 << (bit % 8));
+        self.write_block(bitmap_blk, &bitmap)?;
+        self.gd_add_count(group, GD_FREE_BLOCKS, 1);
+        self.write_group_desc(group)?;
+        self.sb_add_free_blocks(1);
+        self.write_superblock()
+    }
+
+    /// Allocates an inode number, preferring the group of its parent.
+    fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> VfsResult<u32> {
+        self.check_writable()?;
+        let start_group = (parent - 1) / self.inodes_per_group;
+        let mut bitmap = vec![0; self.block_size];
+        for i in 0..self.group_count {
+            let group = (start_group + i) % self.group_count;
+            if self.gd_count(group, GD_FREE_INODES) == 0 {
+                continue;
+            }
+            let bitmap_blk = self.gd_block(group, GD_INODE_BITMAP);
+            self.read_block(bitmap_blk, &mut bitmap)?;
+            let first_ino = group * self.inodes_per_group + 1;
+            let from = self.first_ino.saturating_sub(first_ino) as u64;
+            let end = self.inodes_per_group as u64;
+            let Some(bit) = Self::find_zero_bit(&bitmap, from, end) else {
+                continue;
+            };
+            bitmap[(bit / 8) as usize] |= 1 << (bit % 8);
+            self.write_block(bitmap_blk, &bitmap)?;
+            self.gd_add_count(group, GD_FREE_INODES, -1);
+            if is_dir {
+                self.gd_add_count(group, GD_USED_DIRS, 1);
+            }
+            self.write_group_desc(group)?;
+            self.sb_add_free_inodes(-1);
+            self.write_superblock()?;
+            return Ok(first_ino + bit as u32);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let group = (ino - 1) / self.inodes_per_group;
+        let bit = (ino - 1) % self.inodes_per_group;
+        let mut bitmap = vec![0; self.block_size];
+        let bitmap_blk = self.gd_block(group, GD_INODE_BITMAP);
+        self.read_block(bitmap_blk, &mut bitmap)?;
+        bitmap[(bit / 8) as usize] &= !(1 << (bit % 8));
+        self.write_block(bitmap_blk, &bitmap)?;
+        self.gd_add_count(group, GD_FREE_INODES, 1);
+        if is_dir {
+            self.gd_add_count(group, GD_USED_DIRS, -1);
+        }
+        self.write_group_desc(group)?;
+        self.sb_add_free_inodes(1);
+        self.write_superblock()
+    }
+
+    fn inode_pos(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.inodes_count {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (ino - 1) / self.inodes_per_group;
+        let index = (ino - 1) % self.inodes_per_group;
+        let table = self.gd_block(group, GD_INODE_TABLE);
+        Ok(table * self.bs() + index as u64 * self.inode_size as u64)
+    }
+
+    fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let pos = self.inode_pos(ino)?;
+        let mut raw = vec![0; self.inode_size];
+        self.read_bytes(pos, &mut raw)?;
+        Ok(Inode { raw })
+    }
+
+    fn write_inode(&mut self, ino: u32, inode: &Inode) -> VfsResult {
+        let pos = self.inode_pos(ino)?;
+        self.write_bytes(pos, &inode.raw)
+    }
+
+    /// The number of 512-byte sectors allocated to the inode.
+    fn inode_sectors(&self, inode: &Inode) -> u64 {
+        let mut blocks = get_u32(&inode.raw, 0x1C) as u64;
+        if self.ro_compat & RO_COMPAT_HUGE_FILE != 0 {
+            blocks |= (get_u16(&inode.raw, 0x74) as u64) << 32;
+            if inode.flags() & INODE_FLAG_HUGE_FILE != 0 {
+                return blocks * (self.bs() / 512);
+            }
+        }
+        blocks
+    }
+
+    /// Accounts `delta` filesystem blocks to the inode.
+    fn inode_add_blocks(&self, inode: &mut Inode, delta: i64) {
+        let unit = if inode.flags() & INODE_FLAG_HUGE_FILE != 0 {
+            1
+        } else {
+            self.bs() / 512
+        };
+        let mut blocks = get_u32(&inode.raw, 0x1C) as u64;
+        if self.ro_compat & RO_COMPAT_HUGE_FILE != 0 {
+            blocks |= (get_u16(&inode.raw, 0x74) as u64) << 32;
+        }
+        let blocks = blocks.wrapping_add_signed(delta * unit as i64);
+        set_u32(&mut inode.raw, 0x1C, blocks as u32);
+        if self.ro_compat & RO_COMPAT_HUGE_FILE != 0 {
+            set_u16(&mut inode.raw, 0x74, (blocks >> 32) as u16);
+        }
+    }
+
+    fn set_inode_size(&mut self, inode: &mut Inode, size: u64) -> VfsResult {
+        if size > i32::MAX as u64 && self.ro_compat & RO_COMPAT_LARGE_FILE == 0 {
+            self.ro_compat |= RO_COMPAT_LARGE_FILE;
+            set_u32(&mut self.sb, 0x64, self.ro_compat);
+            self.write_superblock()?;
+        }
+        inode.set_size(size);
+        Ok(())
+    }
+
+    fn new_inode(&mut self, parent: u32, ty: VfsNodeType) -> VfsResult<(u32, Inode)> {
+        let perm = match ty {
+            VfsNodeType::Dir => 0o755,
+            VfsNodeType::SymLink => 0o777,
+            _ => 0o644,
+        };
+        let is_dir = ty == VfsNodeType::Dir;
+        let ino = self.alloc_inode(parent, is_dir)?;
+        let mut inode = Inode {
+            raw: vec![0; self.inode_size],
+        };
+        set_u16(&mut inode.raw, 0x0, mode_type_bits(ty) | perm);
+        inode.set_links(if is_dir { 2 } else { 1 });
+        inode.touch(true, true);
+        if self.inode_size >= GOOD_OLD_INODE_SIZE + 32 {
+            set_u16(&mut inode.raw, 0x80, 32); // i_extra_isize
+        }
+        // fast symlinks store the target in `i_block` instead
+        if ty != VfsNodeType::SymLink && self.incompat & INCOMPAT_EXTENTS != 0 {
+            inode.init_extents();
+        }
+        Ok((ino, inode))
+    }
+
+    /// Frees everything the inode owns, after its last link is removed.
+    fn release_inode(&mut self, ino: u32, mut inode: Inode) -> VfsResult {
+        if !self.is_fast_symlink(&inode) {
+            self.free_blocks_from(&mut inode, 0)?;
+        }
+        let acl = inode.file_acl();
+        if acl != 0 {
+            // extended attribute block, shared by a reference count
+            let mut buf = vec![0; self.block_size];
+            self.read_block(acl, &mut buf)?;
+            let refcount = get_u32(&buf, 4);
+            if refcount <= 1 {
+                self.free_block(acl)?;
+            } else {
+                set_u32(&mut buf, 4, refcount - 1);
+                self.write_block(acl, &buf)?;
+            }
+            set_u32(&mut inode.raw, 0x68, 0);
+            set_u16(&mut inode.raw, 0x76, 0);
+        }
+        inode.set_links(0);
+        inode.set_dtime(now().max(1));
+        let is_dir = inode.is_dir();
+        self.write_inode(ino, &inode)?;
+        self.free_inode(ino, is_dir)
+    }
+
+    /// Keeps an inode whose last link is removed while it is still opened in
+    /// the orphan list, until [`release_orphan`](Self::release_orphan).
+    fn add_orphan(&mut self, ino: u32, mut inode: Inode) -> VfsResult {
+        inode.set_links(0);
+        inode.set_dtime(self.last_orphan());
+        inode.touch(false, false);
+        self.write_inode(ino, &inode)?;
+        set_u32(&mut self.sb, 0xE8, ino);
+        self.write_superblock()
+    }
+
+    /// Removes an inode from the orphan list and frees it.
+    fn release_orphan(&mut self, ino: u32) -> VfsResult {
+        let inode = self.read_inode(ino)?;
+        let next = inode.dtime();
+        if self.last_orphan() == ino {
+            set_u32(&mut self.sb, 0xE8, next);
+            self.write_superblock()?;
+        } else {
+            let mut cur = self.last_orphan();
+            for _ in 0..self.inodes_count {
+                if cur == 0 {
+                    break;
+                }
+                let mut prev = self.read_inode(cur)?;
+                if prev.dtime() == ino {
+                    prev.set_dtime(next);
+                    self.write_inode(cur, &prev)?;
+                    break;
+                }
+                cur = prev.dtime();
+            }
+        }
+        self.release_inode(ino, inode)
+    }
+
+    fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let ea_sectors = if inode.file_acl() != 0 {
+            self.bs() / 512
+        } else {
+            0
+        };
+        inode.file_type() == VfsNodeType::SymLink
+            && inode.size() < FAST_SYMLINK_MAX as u64
+            && self.inode_sectors(inode) == ea_sectors
+    }
+
+    /// Returns the physical block of logical block `lblk`, or 0 for a hole.
+    ///
+    /// If `alloc` is given, a hole is filled with a new block allocated
+    /// near the goal `alloc`.
+    fn map_block(&mut self, inode: &mut Inode, lblk: u64, alloc: Option<u64>) -> VfsResult<u64> {
+        if inode.has_extents() {
+            let pblk = self.extent_lookup(inode, lblk)?;
+            match alloc {
+                Some(goal) if pblk == 0 => self.extent_alloc(inode, lblk, goal),
+                _ => Ok(pblk),
+            }
+        } else {
+            self.blockmap_map(inode, lblk, alloc)
+        }
+    }
+
+    /// The slot in `i_block` and the indices in each level of indirect
+    /// blocks that lead to logical block `lblk`.
+    fn blockmap_path(&self, lblk: u64) -> VfsResult<(usize, Vec<usize>)> {
+        let ptrs = self.bs() / 4;
+        if lblk < NDIR_BLOCKS {
+            return Ok((lblk as usize, Vec::new()));
+        }
+        let mut l = lblk - NDIR_BLOCKS;
+        if l < ptrs {
+            return Ok((12, vec![l as usize]));
+        }
+        l -= ptrs;
+        if l < ptrs * ptrs {
+            return Ok((13, vec![(l / ptrs) as usize, (l % ptrs) as usize]));
+        }
+        l -= ptrs * ptrs;
+        if l < ptrs * ptrs * ptrs {
+            let path = vec![
+                (l / ptrs / ptrs) as usize,
+                (l / ptrs % ptrs) as usize,
+                (l % ptrs) as usize,
+            ];
+            return Ok((14, path));
+        }
+        Err(VfsError::InvalidInput) // file too large
+    }
+
+    fn blockmap_map(&mut self, inode: &mut Inode, lblk: u64, alloc: Option<u64>) -> VfsResult<u64> {
+        let (slot, path) = self.blockmap_path(lblk)?;
+        let mut blk = inode.block_ptr(slot) as u64;
+        if blk == 0 {
+            let Some(goal) = alloc else {
+                return Ok(0);
+            };
+            blk = self.alloc_block(goal)?;
+            inode.set_block_ptr(slot, blk as u32);
+            self.inode_add_blocks(inode, 1);
+        }
+        let mut buf = vec![0; self.block_size];
+        for idx in path {
+            self.read_block(blk, &mut buf)?;
+            let mut next = get_u32(&buf, idx * 4) as u64;
+            if next == 0 {
+                let Some(goal) = alloc else {
+                    return Ok(0);
+                };
+                next = self.alloc_block(goal)?;
+                self.write_bytes(
+                    blk * self.bs() + idx as u64 * 4,
+                    &(next as u32).to_le_bytes(),
+                )?;
+                self.inode_add_blocks(inode, 1);
+            }
+            blk = next;
+        }
+        Ok(blk)
+    }
+
+    /// Frees an indirect block tree of `level` levels (0 for a data block).
+    fn blockmap_free_tree(&mut self, inode: &mut Inode, blk: u64, level: u32) -> VfsResult {
+        if level > 0 {
+            let mut buf = vec![0; self.block_size];
+            self.read_block(blk, &mut buf)?;
+            for idx in 0..self.block_size / 4 {
+                let child = get_u32(&buf, idx * 4) as u64;
+                if child != 0 {
+                    self.blockmap_free_tree(inode, child, level - 1)?;
+                }
+            }
+        }
+        self.free_block(blk)?;
+        self.inode_add_blocks(inode, -1);
+        Ok(())
+    }
+
+    /// Frees the blocks of logical blocks `from..` under an indirect block of
+    /// `level` levels. Returns `true` if the indirect block becomes empty.
+    fn blockmap_free_partial(
+        &mut self,
+        inode: &mut Inode,
+        blk: u64,
+        level: u32,
+        from: u64,
+    ) -> VfsResult<bool> {
+        let ptrs = self.bs() / 4;
+        let span = ptrs.pow(level - 1);
+        let mut buf = vec![0; self.block_size];
+        self.read_block(blk, &mut buf)?;
+        let mut empty = true;
+        for idx in 0..ptrs as usize {
+            let child = get_u32(&buf, idx * 4) as u64;
+            if child == 0 {
+                continue;
+            }
+            let start = idx as u64 * span;
+            if start >= from {
+                self.blockmap_free_tree(inode, child, level - 1)?;
+                set_u32(&mut buf, idx * 4, 0);
+            } else if level > 1
+                && start + span > from
+                && self.blockmap_free_partial(inode, child, level - 1, from - start)?
+            {
+                self.free_block(child)?;
+                self.inode_add_blocks(inode, -1);
+                set_u32(&mut buf, idx * 4, 0);
+            } else {
+                empty = false;
+            }
+        }
+        self.write_block(blk, &buf)?;
+        Ok(empty)
+    }
+
+    fn blockmap_free_from(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        for slot in from.min(NDIR_BLOCKS) as usize..NDIR_BLOCKS as usize {
+            let blk = inode.block_ptr(slot) as u64;
+            if blk != 0 {
+                self.free_block(blk)?;
+                self.inode_add_blocks(inode, -1);
+                inode.set_block_ptr(slot, 0);
+            }
+        }
+        let ptrs = self.bs() / 4;
+        let mut base = NDIR_BLOCKS;
+        for (slot, level) in [(12, 1), (13, 2), (14, 3)] {
+            let span = ptrs.pow(level);
+            let blk = inode.block_ptr(slot) as u64;
+            if blk != 0 {
+                if from <= base {
+                    self.blockmap_free_tree(inode, blk, level)?;
+                    inode.set_block_ptr(slot, 0);
+                } else if from < base + span
+                    && self.blockmap_free_partial(inode, blk, level, from - base)?
+                {
+                    self.free_block(blk)?;
+                    self.inode_add_blocks(inode, -1);
+                    inode.set_block_ptr(slot, 0);
+                }
+            }
+            base += span;
+        }
+        Ok(())
+    }
+
+    fn extent_lookup(&mut self, inode: &Inode, lblk: u64) -> VfsResult<u64> {
+        let mut node = inode.i_block().to_vec();
+        loop {
+            let (entries, depth) = extent_header(&node)?;
+            if depth == 0 {
+                for i in 0..entries {
+                    let ext = Extent::parse(&node[12 + i * 12..]);
+                    if ext.contains(lblk) {
+                        // uninitialized extents read as zeros
+                        return Ok(if ext.uninit {
+                            0
+                        } else {
+                            ext.start + lblk - ext.block
+                        });
+                    }
+                }
+                return Ok(0);
+            }
+            let mut child = None;
+            for i in 0..entries {
+                let entry = &node[12 + i * 12..];
+                if get_u32(entry, 0) as u64 > lblk {
+                    break;
+                }
+                child = Some(extent_index_child(entry));
+            }
+            let Some(child) = child else {
+                return Ok(0);
+            };
+            node = vec![0; self.block_size];
+            self.read_block(child, &mut node)?;
+        }
+    }
+
+    /// Maps a hole at `lblk` of an inode whose extents are all in the inode.
+    fn extent_alloc(&mut self, inode: &mut Inode, lblk: u64, goal: u64) -> VfsResult<u64> {
+        let (entries, depth) = extent_header(inode.i_block())?;
+        if depth != 0 || lblk > u32::MAX as u64 {
+            return Err(VfsError::Unsupported);
+        }
+        let extents: Vec<Extent> = (0..entries)
+            .map(|i| Extent::parse(&inode.i_block()[12 + i * 12..]))
+            .collect();
+        if extents.iter().any(|ext| ext.contains(lblk)) {
+            return Err(VfsError::Unsupported); // an uninitialized extent
+        }
+        let pos = extents.iter().take_while(|ext| ext.block < lblk).count();
+
+        // try to grow the previous extent
+        let prev = pos.checked_sub(1).map(|i| extents[i]);
+        let goal = match prev {
+            Some(ext) if ext.block + ext.len as u64 == lblk => ext.start + ext.len as u64,
+            _ => goal,
+        };
+        let pblk = self.alloc_block(goal)?;
+        let root = inode.i_block_mut();
+        match prev {
+            Some(mut ext)
+                if !ext.uninit
+                    && ext.len < EXTENT_MAX_LEN
+                    && ext.block + ext.len as u64 == lblk
+                    && ext.start + ext.len as u64 == pblk =>
+            {
+                ext.len += 1;
+                ext.store(&mut root[12 + (pos - 1) * 12..]);
+            }
+            _ => {
+                if entries >= get_u16(root, 4) as usize {
+                    self.free_block(pblk)?;
+                    return Err(VfsError::Unsupported); // the extent tree must grow
+                }
+                root.copy_within(12 + pos * 12..12 + entries * 12, 12 + (pos + 1) * 12);
+                let ext = Extent {
+                    block: lblk,
+                    len: 1,
+                    start: pblk,
+                    uninit: false,
+                };
+                ext.store(&mut root[12 + pos * 12..]);
+                set_u16(root, 2, entries as u16 + 1);
+            }
+        }
+        self.inode_add_blocks(inode, 1);
+        Ok(pblk)
+    }
+
+    fn free_block_range(&mut self, inode: &mut Inode, start: u64, len: u64) -> VfsResult {
+        for blk in start..start + len {
+            self.free_block(blk)?;
+        }
+        self.inode_add_blocks(inode, -(len as i64));
+        Ok(())
+    }
+
+    /// Frees all blocks under an extent tree node, but not the node itself.
+    fn extent_free_node(&mut self, inode: &mut Inode, node: &[u8]) -> VfsResult {
+        let (entries, depth) = extent_header(node)?;
+        for i in 0..entries {
+            let entry = &node[12 + i * 12..];
+            if depth == 0 {
+                let ext = Extent::parse(entry);
+                self.free_block_range(inode, ext.start, ext.len as u64)?;
+            } else {
+                let child = extent_index_child(entry);
+                let mut buf = vec![0; self.block_size];
+                self.read_block(child, &mut buf)?;
+                self.extent_free_node(inode, &buf)?;
+                self.free_block_range(inode, child, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn extent_free_from(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        let (entries, depth) = extent_header(inode.i_block())?;
+        if depth != 0 {
+            if from != 0 {
+                return Err(VfsError::Unsupported);
+            }
+            let root = inode.i_block().to_vec();
+            self.extent_free_node(inode, &root)?;
+            let max = get_u16(&root, 4);
+            inode.init_extents();
+            set_u16(inode.i_block_mut(), 4, max);
+            return Ok(());
+        }
+        let mut kept = 0;
+        for i in 0..entries {
+            let mut ext = Extent::parse(&inode.i_block()[12 + i * 12..]);
+            if ext.block >= from {
+                self.free_block_range(inode, ext.start, ext.len as u64)?;
+                continue;
+            }
+            let end = ext.block + ext.len as u64;
+            if end > from {
+                let keep = from - ext.block;
+                self.free_block_range(inode, ext.start + keep, end - from)?;
+                ext.len = keep as u32;
+            }
+            ext.store(&mut inode.i_block_mut()[12 + kept * 12..]);
+            kept += 1;
+        }
+        set_u16(inode.i_block_mut(), 2, kept as u16);
+        Ok(())
+    }
+
+    /// Frees the blocks of logical blocks `from..`.
+    fn free_blocks_from(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        if inode.has_extents() {
+            self.extent_free_from(inode, from)
+        } else {
+            self.blockmap_free_from(inode, from)
+        }
+    }
+
+    fn read_data(&mut self, inode: &mut Inode, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = inode.size();
+        if self.is_fast_symlink(inode) {
+            let target = &inode.i_block()[..size as usize];
+            let start = (offset as usize).min(target.len());
+            let len = buf.len().min(target.len() - start);
+            buf[..len].copy_from_slice(&target[start..start + len]);
+            return Ok(len);
+        }
+        if offset >= size {
+            return Ok(0);
+        }
+        let end = size.min(offset + buf.len() as u64);
+        let mut pos = offset;
+        while pos < end {
+            let lblk = pos / self.bs();
+            let off = pos % self.bs();
+            let len = (self.bs() - off).min(end - pos);
+            let dst = &mut buf[(pos - offset) as usize..(pos - offset + len) as usize];
+            match self.map_block(inode, lblk, None)? {
+                0 => dst.fill(0), // a hole
+                pblk => self.read_bytes(pblk * self.bs() + off, dst)?,
+            }
+            pos += len;
+        }
+        Ok((end - offset) as usize)
+    }
+
+    fn write_data(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        offset: u64,
+        buf: &[u8],
+    ) -> VfsResult<usize> {
+        self.check_writable()?;
+        if inode.file_type() == VfsNodeType::SymLink && inode.size() == 0 && offset == 0 {
+            if buf.len() < FAST_SYMLINK_MAX && self.inode_sectors(inode) == 0 {
+                inode.i_block_mut()[..buf.len()].copy_from_slice(buf);
+                inode.set_size(buf.len() as u64);
+                inode.touch(false, true);
+                self.write_inode(ino, inode)?;
+                return Ok(buf.len());
+            } else if self.incompat & INCOMPAT_EXTENTS != 0 && !inode.has_extents() {
+                inode.init_extents();
+            }
+        }
+
+        let group = (ino - 1) / self.inodes_per_group;
+        let mut goal = self.group_first_block(group);
+        let end = offset + buf.len() as u64;
+        let mut pos = offset;
+        let mut res = Ok(());
+        while pos < end {
+            let lblk = pos / self.bs();
+            let off = pos % self.bs();
+            let len = (self.bs() - off).min(end - pos);
+            let src = &buf[(pos - offset) as usize..(pos - offset + len) as usize];
+            res = self.map_block(inode, lblk, Some(goal)).and_then(|pblk| {
+                goal = pblk + 1;
+                self.write_bytes(pblk * self.bs() + off, src)
+            });
+            if res.is_err() {
+                break;
+            }
+            pos += len;
+        }
+
+        // keep what has been written even on errors
+        if pos > inode.size() {
+            self.set_inode_size(inode, pos)?;
+        }
+        inode.touch(false, true);
+        self.write_inode(ino, inode)?;
+        match res {
+            Err(e) if pos == offset => Err(e),
+            _ => Ok((pos - offset) as usize),
+        }
+    }
+
+    fn truncate(&mut self, ino: u32, size: u64) -> VfsResult {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        if size < inode.size() {
+            let first_free = size.div_ceil(self.bs());
+            self.free_blocks_from(&mut inode, first_free)?;
+            // zero the tail of the last block, it may be exposed by a later extend
+            let off = size % self.bs();
+            if off != 0 {
+                let pblk = self.map_block(&mut inode, size / self.bs(), None)?;
+                if pblk != 0 {
+                    let zeros = vec![0; (self.bs() - off) as usize];
+                    self.write_bytes(pblk * self.bs() + off, &zeros)?;
+                }
+            }
+        }
+        self.set_inode_size(&mut inode, size)?;
+        inode.touch(false, true);
+        self.write_inode(ino, &inode)
+    }
+
+    fn dirent_name_len(&self, block: &[u8], off: usize) -> usize {
+        if self.incompat & INCOMPAT_FILETYPE != 0 {
+            block[off + 6] as usize
+        } else {
+            get_u16(block, off + 6) as usize
+        }
+    }
+
+    fn put_dirent(
+        &self,
+        block: &mut [u8],
+        off: usize,
+        rec_len: usize,
+        ino: u32,
+        name: &[u8],
+        ty: VfsNodeType,
+    ) {
+        set_u32(block, off, ino);
+        set_u16(block, off + 4, rec_len as u16);
+        block[off + 6] = name.len() as u8;
+        block[off + 7] = if self.incompat & INCOMPAT_FILETYPE != 0 {
+            dirent_type(ty)
+        } else {
+            0
+        };
+        block[off + 8..off + 8 + name.len()].copy_from_slice(name);
+    }
+
+    /// Calls `f` with each directory block and its physical block number,
+    /// until `f` returns `Some`.
+    fn scan_dir<T>(
+        &mut self,
+        dir: &mut Inode,
+        mut f: impl FnMut(&mut Self, u64, &mut [u8]) -> VfsResult<Option<T>>,
+    ) -> VfsResult<Option<T>> {
+        if !dir.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let mut block = vec![0; self.block_size];
+        for lblk in 0..dir.size() / self.bs() {
+            let pblk = self.map_block(dir, lblk, None)?;
+            if pblk == 0 {
+                continue;
+            }
+            self.read_block(pblk, &mut block)?;
+            if let Some(res) = f(self, pblk, &mut block)? {
+                return Ok(Some(res));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates the entries in a directory block: `(offset, inode, rec_len)`.
+    fn block_dirents(&self, block: &[u8]) -> VfsResult<Vec<(usize, u32, usize)>> {
+        let mut entries = Vec::new();
+        let mut off = 0;
+        while off + 8 <= block.len() {
+            let rec_len = get_u16(block, off + 4) as usize;
+            if rec_len < 8 || off + rec_len > block.len() {
+                return Err(VfsError::InvalidData);
+            }
+            entries.push((off, get_u32(block, off), rec_len));
+            off += rec_len;
+        }
+        Ok(entries)
+    }
+
+    /// Returns all entries in a directory: `(name, inode, dirent type)`.
+    fn dir_entries(&mut self, dir: &mut Inode) -> VfsResult<Vec<(Vec<u8>, u32, u8)>> {
+        let mut result = Vec::new();
+        self.scan_dir(dir, |fs, _, block| {
+            for (off, ino, _) in fs.block_dirents(block)? {
+                if ino != 0 {
+                    let name_len = fs.dirent_name_len(block, off);
+                    let name = block[off + 8..off + 8 + name_len].to_vec();
+                    result.push((name, ino, block[off + 7]));
+                }
+            }
+            Ok(None::<()>)
+        })?;
+        Ok(result)
+    }
+
+    fn find_entry(&mut self, dir: &mut Inode, name: &[u8]) -> VfsResult<Option<u32>> {
+        self.scan_dir(dir, |fs, _, block| {
+            for (off, ino, _) in fs.block_dirents(block)? {
+                let name_len = fs.dirent_name_len(block, off);
+                if ino != 0 && &block[off + 8..off + 8 + name_len] == name {
+                    return Ok(Some(ino));
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    /// Updates a modified directory, which is no longer hash-indexed.
+    fn dir_modified(&mut self, dir_ino: u32, dir: &mut Inode) -> VfsResult {
+        dir.set_flags(dir.flags() & !INODE_FLAG_INDEX);
+        dir.touch(false, true);
+        self.write_inode(dir_ino, dir)
+    }
+
+    fn add_entry(
+        &mut self,
+        dir_ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+        ino: u32,
+        ty: VfsNodeType,
+    ) -> VfsResult {
+        if dir.links() == 0 {
+            // removed but still opened
+            return Err(VfsError::NotFound);
+        }
+        let need = dirent_len(name.len());
+        let added = self.scan_dir(dir, |fs, pblk, block| {
+            for (off, e_ino, rec_len) in fs.block_dirents(block)? {
+                let used = if e_ino == 0 {
+                    0
+                } else {
+                    dirent_len(fs.dirent_name_len(block, off))
+                };
+                if rec_len - used >= need {
+                    let new_off = if used == 0 {
+                        off
+                    } else {
+                        set_u16(block, off + 4, used as u16);
+                        off + used
+                    };
+                    fs.put_dirent(block, new_off, rec_len - used, ino, name, ty);
+                    fs.write_block(pblk, block)?;
+                    return Ok(Some(()));
+                }
+            }
+            Ok(None)
+        })?;
+        if added.is_none() {
+            // append a new block
+            let mut block = vec![0; self.block_size];
+            self.put_dirent(&mut block, 0, self.block_size, ino, name, ty);
+            let size = dir.size();
+            self.write_data(dir_ino, dir, size, &block)?;
+        }
+        self.dir_modified(dir_ino, dir)
+    }
+
+    /// Removes the entry `name` from a directory, returns its inode.
+    fn remove_entry(&mut self, dir_ino: u32, dir: &mut Inode, name: &[u8]) -> VfsResult<u32> {
+        let removed = self.scan_dir(dir, |fs, pblk, block| {
+            let mut prev = None;
+            for (off, ino, rec_len) in fs.block_dirents(block)? {
+                let name_len = fs.dirent_name_len(block, off);
+                if ino != 0 && &block[off + 8..off + 8 + name_len] == name {
+                    match prev {
+                        // merge into the previous entry
+                        Some(prev) => {
+                            let prev_len = get_u16(block, prev + 4) as usize;
+                            set_u16(block, prev + 4, (prev_len + rec_len) as u16);
+                        }
+                        None => set_u32(block, off, 0),
+                    }
+                    fs.write_block(pblk, block)?;
+                    return Ok(Some(ino));
+                }
+                prev = Some(off);
+            }
+            Ok(None)
+        })?;
+        let ino = removed.ok_or(VfsError::NotFound)?;
+        self.dir_modified(dir_ino, dir)?;
+        Ok(ino)
+    }
+
+    /// Points the entry `name` of a directory to another inode.
+    fn set_entry(&mut self, dir: &mut Inode, name: &[u8], new_ino: u32) -> VfsResult {
+        let found = self.scan_dir(dir, |fs, pblk, block| {
+            for (off, ino, _) in fs.block_dirents(block)? {
+                let name_len = fs.dirent_name_len(block, off);
+                if ino != 0 && &block[off + 8..off + 8 + name_len] == name {
+                    set_u32(block, off, new_ino);
+                    fs.write_block(pblk, block)?;
+                    return Ok(Some(()));
+                }
+            }
+            Ok(None)
+        })?;
+        found.ok_or(VfsError::NotFound)
+    }
+
+    fn add_dir_link(&mut self, dir: &mut Inode) -> VfsResult {
+        match dir.links() {
+            1 => {} // already "many" links
+            n if n >= MAX_LINKS => {
+                if self.ro_compat & RO_COMPAT_DIR_NLINK == 0 {
+                    return Err(VfsError::StorageFull);
+                }
+                dir.set_links(1);
+            }
+            n => dir.set_links(n + 1),
+        }
+        Ok(())
+    }
+
+    fn remove_dir_link(dir: &mut Inode) {
+        if dir.links() > 2 {
+            dir.set_links(dir.links() - 1);
+        }
+    }
+
+    fn lookup(&mut self, mut ino: u32, path: &str) -> VfsResult<u32> {
+        for name in path.split('/') {
+            if name.is_empty() || name == "." {
+                continue;
+            } else if name == ".." && ino == ROOT_INO {
+                // like other filesystems, `..` of the root is left to the VFS
+                return Err(VfsError::NotFound);
+            }
+            let mut dir = self.read_inode(ino)?;
+            ino = self
+                .find_entry(&mut dir, name.as_bytes())?
+                .ok_or(VfsError::NotFound)?;
+        }
+        Ok(ino)
+    }
+
+    /// Resolves the parent directory of `path`, returns it with the last
+    /// component of `path`.
+    fn lookup_parent<'a>(&mut self, ino: u32, path: &'a str) -> VfsResult<(u32, Inode, &'a str)> {
+        let (parent, name) = split_path(path)?;
+        let parent_ino = self.lookup(ino, parent)?;
+        let parent = self.read_inode(parent_ino)?;
+        if !parent.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok((parent_ino, parent, name))
+    }
+
+    fn create(&mut self, dir_ino: u32, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.check_writable()?;
+        let (parent_ino, mut parent, name) = self.lookup_parent(dir_ino, path)?;
+        if self.find_entry(&mut parent, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        if !matches!(
+            ty,
+            VfsNodeType::File | VfsNodeType::Dir | VfsNodeType::SymLink
+        ) {
+            return Err(VfsError::Unsupported);
+        }
+        if ty == VfsNodeType::Dir {
+            self.add_dir_link(&mut parent)?;
+        }
+
+        let (ino, mut inode) = self.new_inode(parent_ino, ty)?;
+        let res = if ty == VfsNodeType::Dir {
+            let mut block = vec![0; self.block_size];
+            self.put_dirent(&mut block, 0, 12, ino, b".", ty);
+            self.put_dirent(&mut block, 12, self.block_size - 12, parent_ino, b"..", ty);
+            self.write_data(ino, &mut inode, 0, &block).map(|_| ())
+        } else {
+            self.write_inode(ino, &inode)
+        };
+        if let Err(e) =
+            res.and_then(|_| self.add_entry(parent_ino, &mut parent, name.as_bytes(), ino, ty))
+        {
+            let inode = self.read_inode(ino)?;
+            self.release_inode(ino, inode)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, dir_ino: u32, path: &str) -> VfsResult {
+        self.check_writable()?;
+        let (parent_ino, mut parent, name) = self.lookup_parent(dir_ino, path)?;
+        let ino = self
+            .find_entry(&mut parent, name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        let mut inode = self.read_inode(ino)?;
+        let is_dir = inode.is_dir();
+        if is_dir {
+            let entries = self.dir_entries(&mut inode)?;
+            if entries
+                .iter()
+                .any(|(name, ..)| name != b"." && name != b"..")
+            {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            Self::remove_dir_link(&mut parent);
+        }
+        self.remove_entry(parent_ino, &mut parent, name.as_bytes())?;
+
+        let links = if is_dir {
+            0
+        } else {
+            inode.links().saturating_sub(1)
+        };
+        if links == 0 && self.open_nodes.contains_key(&ino) {
+            self.add_orphan(ino, inode)
+        } else if links == 0 {
+            self.release_inode(ino, inode)
+        } else {
+            inode.set_links(links);
+            inode.touch(false, false);
+            self.write_inode(ino, &inode)
+        }
+    }
+
+    /// Adds the entry `name` in directory `dir_ino` for the existing inode
+    /// `ino`, which must not be a directory.
+    fn link(&mut self, dir_ino: u32, name: &str, ino: u32) -> VfsResult {
+        self.check_writable()?;
+        let mut dir = self.read_inode(dir_ino)?;
+        if !dir.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        if self.find_entry(&mut dir, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        if inode.links() == u16::MAX {
+            return Err(VfsError::StorageFull);
+        }
+        self.add_entry(dir_ino, &mut dir, name.as_bytes(), ino, inode.file_type())?;
+        inode.set_links(inode.links() + 1);
+        inode.touch(false, false);
+        self.write_inode(ino, &inode)
+    }
+
+    fn rename(&mut self, dir_ino: u32, src_path: &str, dst_path: &str) -> VfsResult {
+        self.check_writable()?;
+        let (src_parent_ino, mut src_parent, src_name) = self.lookup_parent(dir_ino, src_path)?;
+        let ino = self
+            .find_entry(&mut src_parent, src_name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        // absolute destinations are relative to the root of this filesystem
+        let dst_base = if dst_path.starts_with('/') {
+            ROOT_INO
+        } else {
+            dir_ino
+        };
+        let (dst_parent_ino, mut dst_parent, dst_name) = self.lookup_parent(dst_base, dst_path)?;
+        if self
+            .find_entry(&mut dst_parent, dst_name.as_bytes())?
+            .is_some()
+        {
+            return Err(VfsError::AlreadyExists);
+        }
+
+        let mut inode = self.read_inode(ino)?;
+        let ty = inode.file_type();
+        let moves_dir = ty == VfsNodeType::Dir && src_parent_ino != dst_parent_ino;
+        if moves_dir {
+            // a directory cannot be moved into itself
+            let mut cur = dst_parent_ino;
+            while cur != ROOT_INO {
+                if cur == ino {
+                    return Err(VfsError::InvalidInput);
+                }
+                cur = self.lookup(cur, "..")?;
+            }
+            self.add_dir_link(&mut dst_parent)?;
+        }
+
+        self.add_entry(
+            dst_parent_ino,
+            &mut dst_parent,
+            dst_name.as_bytes(),
+            ino,
+            ty,
+        )?;
+        let mut src_parent = self.read_inode(src_parent_ino)?;
+        if moves_dir {
+            Self::remove_dir_link(&mut src_parent);
+            self.set_entry(&mut inode, b"..", dst_parent_ino)?;
+        }
+        self.remove_entry(src_parent_ino, &mut src_parent, src_name.as_bytes())?;
+        inode.touch(false, false);
+        self.write_inode(ino, &inode)
+    }
+}
+
+/// A file, directory or other node in an ext2 filesystem.
+pub struct Ext2Node {
+    fs: Arc<Mutex<Ext2>>,
+    ino: u32,
+}
+
+impl Ext2Node {
+    fn new(fs: Arc<Mutex<Ext2>>, ino: u32) -> Arc<Self> {
+        *fs.lock().open_nodes.entry(ino).or_default() += 1;
+        Arc::new(Self { fs, ino })
+    }
+
+    /// The inode number.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Fills the block size, and the total and free numbers of blocks and
+    /// inodes of the filesystem in `stat`.
+    pub(crate) fn fill_statfs(&self, stat: &mut crate::root::FsStat) {
+        let fs = self.fs.lock();
+        let mut blocks_free = get_u32(&fs.sb, 0xC) as u64;
+        if fs.incompat & INCOMPAT_64BIT != 0 {
+            blocks_free |= (get_u32(&fs.sb, 0x158) as u64) << 32;
+        }
+        stat.block_size = fs.bs();
+        stat.blocks = fs.blocks_count;
+        stat.blocks_free = blocks_free;
+        stat.files = fs.inodes_count as u64;
+        stat.files_free = get_u32(&fs.sb, 0x10) as u64;
+    }
+
+    /// Returns the number of hard links to this node.
+    pub fn nlink(&self) -> VfsResult<u64> {
+        Ok(self.fs.lock().read_inode(self.ino)?.links() as u64)
+    }
+
+    /// Returns the ownership, timestamps and number of hard links of this
+    /// node.
+    pub(crate) fn meta(&self) -> VfsResult<crate::fops::FileMeta> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        let (atime, mtime, ctime) = inode.times();
+        Ok(crate::fops::FileMeta {
+            nlink: inode.links() as u64,
+            uid: inode.uid(),
+            gid: inode.gid(),
+            atime: Duration::from_secs(atime as u64),
+            mtime: Duration::from_secs(mtime as u64),
+            ctime: Duration::from_secs(ctime as u64),
+        })
+    }
+
+    /// Returns the owner and the group of this node.
+    pub fn owner(&self) -> VfsResult<(u32, u32)> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        Ok((inode.uid(), inode.gid()))
+    }
+
+    /// Changes the permission bits.
+    pub fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        self.update_inode(|inode| inode.set_perm(perm.bits()))
+    }
+
+    /// Changes the owner and the group, which are kept if `None`.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult {
+        self.update_inode(|inode| {
+            if let Some(uid) = uid {
+                inode.set_uid(uid);
+            }
+            if let Some(gid) = gid {
+                inode.set_gid(gid);
+            }
+        })
+    }
+
+    /// Changes the access and modification times, which are kept if `None`.
+    /// They are stored in whole seconds.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.update_inode(|inode| {
+            if let Some(atime) = atime {
+                set_u32(&mut inode.raw, 0x8, atime.as_secs() as u32);
+            }
+            if let Some(mtime) = mtime {
+                set_u32(&mut inode.raw, 0x10, mtime.as_secs() as u32);
+            }
+        })
+    }
+
+    /// Changes the inode with `f` and sets its status change time to now.
+    fn update_inode(&self, f: impl FnOnce(&mut Inode)) -> VfsResult {
+        let mut fs = self.fs.lock();
+        fs.check_writable()?;
+        let mut inode = fs.read_inode(self.ino)?;
+        f(&mut inode);
+        inode.touch(false, false);
+        fs.write_inode(self.ino, &inode)
+    }
+
+    /// Adds a hard link to `node` named `name` in this directory.
+    ///
+    /// Both nodes must be in the same filesystem, and `node` must not be a
+    /// directory.
+    pub fn link(&self, name: &str, node: &Ext2Node) -> VfsResult {
+        if !Arc::ptr_eq(&self.fs, &node.fs) {
+            return Err(VfsError::InvalidInput);
+        }
+        if name.is_empty() || name.contains('/') {
+            return Err(VfsError::InvalidInput);
+        }
+        self.fs.lock().link(self.ino, name, node.ino)
+    }
+}
+
+impl Drop for Ext2Node {
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        let count = fs.open_nodes.get_mut(&self.ino).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        fs.open_nodes.remove(&self.ino);
+        // free the inode if it was removed while opened
+        let orphan = fs
+            .read_inode(self.ino)
+            .is_ok_and(|inode| inode.links() == 0);
+        if orphan && !fs.read_only {
+            if let Err(e) = fs.release_orphan(self.ino) {
+                warn!("ext2: failed to free orphan inode {}: {:?}", self.ino, e);
+            }
+        }
+    }
+}
+
+impl VfsNodeOps for Ext2Node {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut fs = self.fs.lock();
+        let inode = fs.read_inode(self.ino)?;
+        let perm = VfsNodePerm::from_bits_truncate(inode.mode() & 0o777);
+        let blocks = fs.inode_sectors(&inode);
+        Ok(VfsNodeAttr::new(
+            perm,
+            inode.file_type(),
+            inode.size(),
+            blocks,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        let mut inode = fs.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        fs.read_data(&mut inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        let mut inode = fs.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        fs.write_data(self.ino, &mut inode, offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        // metadata is written through to the disk, so this is enough
+        self.fs.lock().disk.flush().map_err(|_| VfsError::Io)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.fs.lock().truncate(self.ino, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let ino = self.fs.lock().lookup(self.ino, "..").ok()?;
+        Some(Self::new(self.fs.clone(), ino))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext2: {}", path);
+        let ino = self.fs.lock().lookup(self.ino, path)?;
+        if ino == self.ino {
+            Ok(self)
+        } else {
+            Ok(Self::new(self.fs.clone(), ino))
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext2: {}", ty, path);
+        self.fs.lock().create(self.ino, path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext2: {}", path);
+        self.fs.lock().remove(self.ino, path)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        let mut dir = fs.read_inode(self.ino)?;
+        let entries = fs.dir_entries(&mut dir)?;
+        let mut count = 0;
+        for ((name, ino, ty), out) in entries.iter().skip(start_idx).zip(dirents.iter_mut()) {
+            let ty = match type_from_dirent(*ty) {
+                Some(ty) => ty,
+                None => fs.read_inode(*ino)?.file_type(),
+            };
+            *out = VfsDirEntry::new(&String::from_utf8_lossy(name), ty);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!(
+            "rename at ext2, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        self.fs.lock().rename(self.ino, src_path, dst_path)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// An ext2, ext3 or ext4 filesystem on a disk.
+pub struct Ext2FileSystem {
+    inner: Arc<Mutex<Ext2>>,
+    fstype: &'static str,
+}
+
+impl Ext2FileSystem {
+    /// Returns `true` if the disk contains an ext2/3/4 filesystem.
+    pub fn probe(disk: &mut Disk) -> bool {
+        let mut magic = [0; 2];
+        let found = disk
+            .read_exact_at(SUPERBLOCK_OFFSET + 0x38, &mut magic)
+            .is_ok()
+            && u16::from_le_bytes(magic) == EXT2_MAGIC;
+        disk.set_position(0);
+        found
+    }
+
+    /// Opens the filesystem on `disk`.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let fs = Ext2::open(disk)?;
+        info!(
+            "  ext2: {} filesystem, {} blocks of {} bytes{}",
+            fs.fstype,
+            fs.blocks_count,
+            fs.block_size,
+            if fs.read_only { ", read-only" } else { "" }
+        );
+        Ok(Self {
+            fstype: fs.fstype,
+            inner: Arc::new(Mutex::new(fs)),
+        })
+    }
+
+    /// The type of the filesystem: `ext2`, `ext3` or `ext4`.
+    pub fn fstype(&self) -> &'static str {
+        self.fstype
+    }
+}
+
+impl VfsOps for Ext2FileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        Ext2Node::new(self.inner.clone(), ROOT_INO)
+    }
+}
@@ -0,0 +1,623 @@
+//! Consistency check of FAT filesystems, like `fsck.fat`.
+//!
+//! The check walks the cluster chains of all files and directories from the
+//! root directory, and finds:
+//!
+//! - chains that lead to free, bad or out of range clusters;
+//! - clusters in more than one chain (cross-links) or in a loop;
+//! - files whose sizes do not match the lengths of their chains;
+//! - clusters allocated in the FAT but in no chain (lost clusters);
+//! - FAT copies that differ, a wrong free cluster count in the FSInfo sector
+//!   of FAT32, and the dirty flag left by a filesystem not unmounted cleanly.
+//!
+//! Repairs only make the filesystem consistent, the data of broken files is
+//! not recovered: chains are cut before the first bad link, so a cross-linked
+//! cluster is kept by the first file that reaches it, file sizes are fitted
+//! to their chains, entries of directories without valid chains are deleted
+//! and lost clusters are freed.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use crate::dev::Disk;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LFN: u8 = 0x0f;
+const DELETED: u8 = 0xe5;
+/// The flag in the boot sector set by `fatfs` while the filesystem is
+/// mounted, and cleared when it is unmounted.
+const DIRTY_FLAG: u8 = 0x01;
+const FSINFO_LEAD_SIG: u32 = 0x4161_5252;
+const FSINFO_STRUC_SIG: u32 = 0x6141_7272;
+
+/// The result of [`check`].
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// The type of the FAT: 12, 16 or 32.
+    pub fat_bits: u8,
+    /// The number of files.
+    pub files: u32,
+    /// The number of directories, not counting the root directory.
+    pub dirs: u32,
+    /// The number of data clusters.
+    pub clusters: u32,
+    /// The number of clusters in use by files and directories.
+    pub used_clusters: u32,
+    /// The number of lost clusters, allocated but not in use.
+    pub lost_clusters: u32,
+    /// The number of chains of the lost clusters.
+    pub lost_chains: u32,
+    /// The number of cross-links and loops.
+    pub cross_links: u32,
+    /// The problems found, one per line.
+    pub problems: Vec<String>,
+    /// Whether the problems are repaired.
+    pub repaired: bool,
+}
+
+impl FsckReport {
+    /// Whether no problem is found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn io_err<T>(_: T) -> VfsError {
+    VfsError::Io
+}
+
+/// The name of a short directory entry, e.g. `README.TXT`.
+fn short_name(entry: &[u8]) -> String {
+    let mut name = entry[..11].to_vec();
+    if name[0] == 0x05 {
+        name[0] = DELETED; // a name starting with 0xe5
+    }
+    let base = String::from_utf8_lossy(&name[..8]);
+    let ext = String::from_utf8_lossy(&name[8..]);
+    match ext.trim_end() {
+        "" => String::from(base.trim_end()),
+        ext => format!("{}.{}", base.trim_end(), ext),
+    }
+}
+
+/// The checksum of the short name stored in its long name entries.
+fn short_name_checksum(entry: &[u8]) -> u8 {
+    (entry[..11])
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Collects the long name of the next short entry from its long name
+/// entries, which come before it in reverse order.
+#[derive(Default)]
+struct LongName {
+    chars: Vec<u16>,
+    checksum: u8,
+    /// The number of the next entry expected, 0 if none.
+    next: u8,
+}
+
+impl LongName {
+    fn push(&mut self, entry: &[u8]) {
+        let ord = entry[0];
+        if ord & 0x40 != 0 && ord & 0x1f != 0 {
+            self.chars.clear();
+            self.checksum = entry[13];
+            self.next = ord & 0x1f;
+        } else if ord != self.next || ord == 0 || entry[13] != self.checksum {
+            self.chars.clear();
+            self.next = 0;
+            return;
+        }
+        // 13 UCS-2 characters in 3 parts
+        let offsets = (1..11)
+            .step_by(2)
+            .chain((14..26).step_by(2))
+            .chain([28, 30]);
+        let mut part = Vec::with_capacity(13);
+        for off in offsets {
+            match get_u16(entry, off) {
+                0 | 0xffff => break,
+                c => part.push(c),
+            }
+        }
+        part.append(&mut self.chars);
+        self.chars = part;
+        self.next -= 1;
+    }
+
+    /// Returns the long name of the short entry `entry`, or its short name if
+    /// it has none.
+    fn take(&mut self, entry: &[u8]) -> String {
+        let matched = self.next == 0 && self.checksum == short_name_checksum(entry);
+        let chars = core::mem::take(&mut self.chars);
+        self.next = 0;
+        if !matched || chars.is_empty() {
+            return short_name(entry);
+        }
+        char::decode_utf16(chars)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+struct Checker<'a> {
+    disk: &'a mut Disk,
+    repair: bool,
+    report: FsckReport,
+    /// The contents of the first FAT.
+    fat: Vec<u8>,
+    fat_changed: bool,
+    /// Whether any copy of the FAT differs from the first one.
+    copies_differ: bool,
+    /// Whether each cluster is in a chain already.
+    used: Vec<bool>,
+    fat_offset: u64,
+    fat_size: usize,
+    num_fats: u32,
+    cluster_size: usize,
+    data_offset: u64,
+    /// The fixed root directory of FAT12 and FAT16: its offset and size.
+    root_region: (u64, usize),
+    root_cluster: u32,
+    fsinfo_offset: Option<u64>,
+    /// The offset of the dirty flag in the boot sector.
+    flags_offset: u64,
+}
+
+impl<'a> Checker<'a> {
+    fn new(disk: &'a mut Disk, repair: bool) -> VfsResult<Self> {
+        let mut bs = [0; 512];
+        disk.read_exact_at(0, &mut bs).map_err(io_err)?;
+        let bytes_per_sector = get_u16(&bs, 11) as u64;
+        let sectors_per_cluster = bs[13] as u64;
+        let reserved = get_u16(&bs, 14) as u64;
+        let num_fats = bs[16] as u32;
+        let root_entries = get_u16(&bs, 17) as u64;
+        let total = match get_u16(&bs, 19) {
+            0 => get_u32(&bs, 32) as u64,
+            n => n as u64,
+        };
+        let fat_sectors = match get_u16(&bs, 22) {
+            0 => get_u32(&bs, 36) as u64,
+            n => n as u64,
+        };
+        if get_u16(&bs, 510) != 0xaa55
+            || !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved == 0
+            || num_fats == 0
+            || fat_sectors == 0
+        {
+            return Err(VfsError::InvalidData);
+        }
+
+        let root_sectors = (root_entries * DIR_ENTRY_SIZE as u64).div_ceil(bytes_per_sector);
+        let data_start = reserved + num_fats as u64 * fat_sectors + root_sectors;
+        if total <= data_start || total * bytes_per_sector > disk.size() {
+            return Err(VfsError::InvalidData);
+        }
+        let clusters = (total - data_start) / sectors_per_cluster;
+        let fat_bits = match clusters {
+            0..4085 => 12,
+            4085..65525 => 16,
+            _ => 32,
+        };
+        let fat_size = (fat_sectors * bytes_per_sector) as usize;
+        if fat_size * 8 < (clusters as usize + 2) * fat_bits as usize {
+            return Err(VfsError::InvalidData);
+        }
+
+        let fat_offset = reserved * bytes_per_sector;
+        let mut fat = vec![0; fat_size];
+        disk.read_exact_at(fat_offset, &mut fat).map_err(io_err)?;
+        let mut copies_differ = false;
+        let mut copy = vec![0; fat_size];
+        for i in 1..num_fats as u64 {
+            let offset = fat_offset + i * fat_size as u64;
+            disk.read_exact_at(offset, &mut copy).map_err(io_err)?;
+            copies_differ |= copy != fat;
+        }
+        let fsinfo_offset = match get_u16(&bs, 48) {
+            0 | 0xffff => None,
+            n if fat_bits == 32 => Some(n as u64 * bytes_per_sector),
+            _ => None,
+        };
+        Ok(Self {
+            disk,
+            repair,
+            report: FsckReport {
+                fat_bits,
+                clusters: clusters as u32,
+                ..Default::default()
+            },
+            fat,
+            fat_changed: false,
+            copies_differ,
+            used: vec![false; clusters as usize + 2],
+            fat_offset,
+            fat_size,
+            num_fats,
+            cluster_size: (sectors_per_cluster * bytes_per_sector) as usize,
+            data_offset: data_start * bytes_per_sector,
+            root_region: (
+                (reserved + num_fats as u64 * fat_sectors) * bytes_per_sector,
+                (root_entries as usize) * DIR_ENTRY_SIZE,
+            ),
+            root_cluster: get_u32(&bs, 44),
+            fsinfo_offset,
+            flags_offset: if fat_bits == 32 { 0x41 } else { 0x25 },
+        })
+    }
+
+    fn problem(&mut self, msg: String) {
+        debug!("fsck: {}", msg);
+        self.report.problems.push(msg);
+    }
+
+    /// The first value of the FAT entries of the last cluster in a chain.
+    fn eoc(&self) -> u32 {
+        match self.report.fat_bits {
+            12 => 0xff8,
+            16 => 0xfff8,
+            _ => 0x0fff_fff8,
+        }
+    }
+
+    fn is_cluster(&self, cluster: u32) -> bool {
+        (2..self.used.len() as u32).contains(&cluster)
+    }
+
+    fn get(&self, cluster: u32) -> u32 {
+        let n = cluster as usize;
+        match self.report.fat_bits {
+            12 => {
+                let v = get_u16(&self.fat, n * 3 / 2) as u32;
+                if n % 2 == 0 {
+                    v & 0xfff
+                } else {
+                    v >> 4
+                }
+            }
+            16 => get_u16(&self.fat, n * 2) as u32,
+            _ => get_u32(&self.fat, n * 4) & 0x0fff_ffff,
+        }
+    }
+
+    fn set(&mut self, cluster: u32, value: u32) {
+        let n = cluster as usize;
+        match self.report.fat_bits {
+            12 => {
+                let off = n * 3 / 2;
+                let old = get_u16(&self.fat, off);
+                let new = if n % 2 == 0 {
+                    (old & 0xf000) | value as u16
+                } else {
+                    (old & 0x000f) | (value as u16) << 4
+                };
+                self.fat[off..off + 2].copy_from_slice(&new.to_le_bytes());
+            }
+            16 => self.fat[n * 2..n * 2 + 2].copy_from_slice(&(value as u16).to_le_bytes()),
+            _ => {
+                // the upper 4 bits are reserved
+                let value = (get_u32(&self.fat, n * 4) & 0xf000_0000) | value;
+                self.fat[n * 4..n * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        self.fat_changed = true;
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_offset + (cluster as u64 - 2) * self.cluster_size as u64
+    }
+
+    /// Follows the chain starting at `first` of the file or directory at
+    /// `path`, and marks its clusters as used.
+    ///
+    /// Returns `None` if `first` can not start a chain. Otherwise returns the
+    /// clusters before the first bad link, where the chain is cut if
+    /// repairing.
+    fn follow_chain(&mut self, path: &str, first: u32) -> Option<Vec<u32>> {
+        let eoc = self.eoc();
+        if !self.is_cluster(first) {
+            self.problem(format!("{}: starts with invalid cluster {}", path, first));
+            return None;
+        } else if self.used[first as usize] {
+            self.problem(format!("{}: cross-linked at cluster {}", path, first));
+            self.report.cross_links += 1;
+            return None;
+        } else if self.get(first) == 0 {
+            self.problem(format!("{}: starts with free cluster {}", path, first));
+            return None;
+        }
+
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        loop {
+            self.used[cluster as usize] = true;
+            chain.push(cluster);
+            let next = self.get(cluster);
+            if next >= eoc {
+                return Some(chain);
+            }
+            let msg = if !self.is_cluster(next) {
+                format!("{}: invalid cluster {} in chain", path, next)
+            } else if self.used[next as usize] {
+                self.report.cross_links += 1;
+                format!("{}: cross-linked at cluster {}", path, next)
+            } else if self.get(next) == 0 {
+                format!("{}: free cluster {} in chain", path, next)
+            } else {
+                cluster = next;
+                continue;
+            };
+            self.problem(msg);
+            if self.repair {
+                self.set(cluster, eoc);
+            }
+            return Some(chain);
+        }
+    }
+
+    /// Writes the directory entry at `offset` back to the disk.
+    fn write_entry(&mut self, offset: u64, entry: &[u8]) -> VfsResult {
+        self.disk.write_all_at(offset, entry).map_err(io_err)
+    }
+
+    /// Checks the entries in the directory at `path`, which is stored in the
+    /// regions `(offset, size)`. Returns the subdirectories in it, along with
+    /// their chains.
+    fn check_dir(
+        &mut self,
+        path: &str,
+        regions: &[(u64, usize)],
+    ) -> VfsResult<Vec<(String, Vec<u32>)>> {
+        let mut subdirs = Vec::new();
+        let mut long_name = LongName::default();
+        for &(region_offset, size) in regions {
+            let mut buf = vec![0; size];
+            self.disk
+                .read_exact_at(region_offset, &mut buf)
+                .map_err(io_err)?;
+            for (i, entry) in buf.chunks_exact_mut(DIR_ENTRY_SIZE).enumerate() {
+                let offset = region_offset + (i * DIR_ENTRY_SIZE) as u64;
+                let attr = entry[11];
+                if entry[0] == 0 {
+                    return Ok(subdirs); // the end of the directory
+                } else if entry[0] == DELETED {
+                    continue;
+                } else if attr & ATTR_LFN == ATTR_LFN {
+                    long_name.push(entry);
+                    continue;
+                } else if attr & ATTR_VOLUME_ID != 0 || entry[0] == b'.' {
+                    continue; // the volume label, `.` and `..`
+                }
+
+                let name = long_name.take(entry);
+                let path = format!("{}/{}", path.trim_end_matches('/'), name);
+                let first = (get_u16(entry, 20) as u32) << 16 | get_u16(entry, 26) as u32;
+                let first = if self.report.fat_bits == 32 {
+                    first
+                } else {
+                    first & 0xffff
+                };
+                if attr & ATTR_DIRECTORY != 0 {
+                    self.report.dirs += 1;
+                    if let Some(chain) = self.follow_chain(&path, first) {
+                        subdirs.push((path, chain));
+                    } else if self.repair {
+                        entry[0] = DELETED;
+                        self.write_entry(offset, entry)?;
+                    }
+                    continue;
+                }
+
+                self.report.files += 1;
+                let size = get_u32(entry, 28) as u64;
+                let chain = match first {
+                    0 => Vec::new(),
+                    _ => match self.follow_chain(&path, first) {
+                        Some(chain) => chain,
+                        None => {
+                            if self.repair {
+                                entry[20..22].fill(0);
+                                entry[26..28].fill(0);
+                                entry[28..32].fill(0);
+                                self.write_entry(offset, entry)?;
+                            }
+                            continue;
+                        }
+                    },
+                };
+                let needed = size.div_ceil(self.cluster_size as u64) as usize;
+                if chain.len() < needed {
+                    let new_size = (chain.len() * self.cluster_size) as u32;
+                    self.problem(format!(
+                        "{}: size {} is larger than its {} clusters",
+                        path,
+                        size,
+                        chain.len()
+                    ));
+                    if self.repair {
+                        entry[28..32].copy_from_slice(&new_size.to_le_bytes());
+                        if chain.is_empty() {
+                            entry[20..22].fill(0);
+                            entry[26..28].fill(0);
+                        }
+                        self.write_entry(offset, entry)?;
+                    }
+                } else if chain.len() > needed {
+                    self.problem(format!(
+                        "{}: {} clusters beyond its size {}",
+                        path,
+                        chain.len() - needed,
+                        size
+                    ));
+                    if self.repair {
+                        for &cluster in &chain[needed..] {
+                            self.set(cluster, 0);
+                            self.used[cluster as usize] = false;
+                        }
+                        if needed == 0 {
+                            entry[20..22].fill(0);
+                            entry[26..28].fill(0);
+                            self.write_entry(offset, entry)?;
+                        } else {
+                            let eoc = self.eoc();
+                            self.set(chain[needed - 1], eoc);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(subdirs)
+    }
+
+    /// Returns the regions of the clusters in `chain`.
+    fn chain_regions(&self, chain: &[u32]) -> Vec<(u64, usize)> {
+        (chain.iter())
+            .map(|&c| (self.cluster_offset(c), self.cluster_size))
+            .collect()
+    }
+
+    /// Walks the directory tree from the root.
+    fn check_tree(&mut self) -> VfsResult {
+        let root_regions = if self.report.fat_bits == 32 {
+            let root = self.root_cluster;
+            match self.follow_chain("/", root) {
+                Some(chain) => self.chain_regions(&chain),
+                None => return Err(VfsError::InvalidData),
+            }
+        } else {
+            vec![self.root_region]
+        };
+        let mut dirs = self.check_dir("/", &root_regions)?;
+        while let Some((path, chain)) = dirs.pop() {
+            let regions = self.chain_regions(&chain);
+            dirs.extend(self.check_dir(&path, &regions)?);
+        }
+        Ok(())
+    }
+
+    /// Finds the lost clusters, and frees them if repairing.
+    fn check_lost(&mut self) {
+        let bad = self.eoc() - 1;
+        let mut lost = vec![false; self.used.len()];
+        for c in 2..self.used.len() as u32 {
+            let value = self.get(c);
+            lost[c as usize] = !self.used[c as usize] && value != 0 && value != bad;
+        }
+        // the lost clusters that no other lost cluster links to
+        let mut heads = lost.clone();
+        for c in 2..self.used.len() as u32 {
+            let next = self.get(c);
+            if lost[c as usize] && self.is_cluster(next) {
+                heads[next as usize] = false;
+            }
+        }
+        let lost_clusters = lost.iter().filter(|&&l| l).count() as u32;
+        if lost_clusters == 0 {
+            return;
+        }
+        let lost_chains = heads.iter().filter(|&&h| h).count().max(1) as u32;
+        self.report.lost_clusters = lost_clusters;
+        self.report.lost_chains = lost_chains;
+        self.problem(format!(
+            "{} lost clusters in {} chains",
+            lost_clusters, lost_chains
+        ));
+        if self.repair {
+            for c in 2..self.used.len() as u32 {
+                if lost[c as usize] {
+                    self.set(c, 0);
+                }
+            }
+        }
+    }
+
+    /// Reports the FAT copies that differ, checks the FSInfo sector and the
+    /// dirty flag, and writes the repairs back to the disk.
+    fn finish(&mut self) -> VfsResult {
+        if self.copies_differ {
+            self.problem(String::from("FAT copies differ, the first one is used"));
+        }
+
+        let free = (2..self.used.len() as u32)
+            .filter(|&c| self.get(c) == 0)
+            .count() as u32;
+        self.report.used_clusters = self.used.iter().filter(|&&u| u).count() as u32;
+        let mut fsinfo = [0; 512];
+        if let Some(offset) = self.fsinfo_offset {
+            self.disk
+                .read_exact_at(offset, &mut fsinfo)
+                .map_err(io_err)?;
+            let count = get_u32(&fsinfo, 488);
+            let valid =
+                get_u32(&fsinfo, 0) == FSINFO_LEAD_SIG && get_u32(&fsinfo, 484) == FSINFO_STRUC_SIG;
+            if valid && count != 0xffff_ffff && count != free {
+                self.problem(format!(
+                    "free cluster count is {} in FSInfo, but {} actually",
+                    count, free
+                ));
+                fsinfo[488..492].copy_from_slice(&free.to_le_bytes());
+            }
+        }
+
+        let mut flags = [0];
+        (self.disk)
+            .read_exact_at(self.flags_offset, &mut flags)
+            .map_err(io_err)?;
+        let dirty = flags[0] & DIRTY_FLAG != 0;
+        if dirty {
+            self.problem(String::from("the filesystem was not unmounted cleanly"));
+        }
+
+        if !self.repair || self.report.is_clean() {
+            return Ok(());
+        }
+        if self.fat_changed || self.copies_differ {
+            for i in 0..self.num_fats {
+                let offset = self.fat_offset + (i as usize * self.fat_size) as u64;
+                self.disk.write_all_at(offset, &self.fat).map_err(io_err)?;
+            }
+        }
+        if let Some(offset) = self.fsinfo_offset {
+            if get_u32(&fsinfo, 488) == free {
+                self.disk.write_all_at(offset, &fsinfo).map_err(io_err)?;
+            }
+        }
+        if dirty {
+            flags[0] &= !DIRTY_FLAG;
+            (self.disk)
+                .write_all_at(self.flags_offset, &flags)
+                .map_err(io_err)?;
+        }
+        self.disk.flush().map_err(io_err)?;
+        self.report.repaired = true;
+        Ok(())
+    }
+}
+
+/// Checks the FAT filesystem on `disk`, and repairs the problems found if
+/// `repair` is set.
+///
+/// Returns [`InvalidData`](VfsError::InvalidData) if the boot sector or the
+/// root directory is too broken to check the filesystem.
+pub fn check(disk: &mut Disk, repair: bool) -> VfsResult<FsckReport> {
+    let mut checker = Checker::new(disk, repair)?;
+    checker.check_tree()?;
+    checker.check_lost();
+    checker.finish()?;
+    Ok(checker.report)
+}
@@ -48,6 +48,39 @@ impl FatFileSystem {
         }
     }
 
+    /// Opens an existing FAT filesystem on `disk` without formatting it.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new()).map_err(as_vfs_err)?;
+        Ok(Self {
+            inner,
+            root_dir: UnsafeCell::new(None),
+        })
+    }
+
+    /// Formats `disk` as a FAT filesystem with the volume label `label`, like
+    /// `mkfs.vfat`.
+    ///
+    /// The filesystem is FAT32 if `fat32` is set, which needs at least 32 MiB,
+    /// otherwise FAT12, FAT16 or FAT32 is chosen by the size of the disk.
+    pub fn format(disk: &mut Disk, label: Option<&str>, fat32: bool) -> VfsResult {
+        // the serial number of the volume, from the time like `mkfs.vfat`
+        let volume_id = axhal::time::wall_time().as_secs() as u32;
+        let mut opts = fatfs::FormatVolumeOptions::new().volume_id(volume_id);
+        if let Some(label) = label {
+            if label.len() > 11 || !label.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+                return Err(VfsError::InvalidInput);
+            }
+            let mut volume_label = [b' '; 11];
+            volume_label[..label.len()].copy_from_slice(label.to_ascii_uppercase().as_bytes());
+            opts = opts.volume_label(volume_label);
+        }
+        if fat32 {
+            opts = opts.fat_type(fatfs::FatType::Fat32);
+        }
+        fatfs::format_volume(disk, opts).map_err(as_vfs_err)?;
+        disk.flush().map_err(|_| VfsError::Io)
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
         unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
@@ -90,6 +123,11 @@ impl VfsNodeOps for FileWrapper<'static> {
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
         file.truncate().map_err(as_vfs_err)
     }
+
+    fn fsync(&self) -> VfsResult {
+        // also flushes the whole disk
+        self.0.lock().flush().map_err(as_vfs_err)
+    }
 }
 
 impl VfsNodeOps for DirWrapper<'static> {
@@ -243,7 +281,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
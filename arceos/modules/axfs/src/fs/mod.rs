@@ -2,12 +2,24 @@ cfg_if::cfg_if! {
     if #[cfg(feature = "myfs")] {
         pub mod myfs;
     } else if #[cfg(feature = "fatfs")] {
+        pub mod fatck;
         pub mod fatfs;
     }
 }
 
+#[cfg(all(feature = "ext2", not(feature = "myfs")))]
+pub mod ext2;
+
+#[cfg(feature = "9p")]
+pub mod v9fs;
+
+pub mod pseudofs;
+
 #[cfg(feature = "devfs")]
 pub use axfs_devfs as devfs;
 
+#[cfg(feature = "overlayfs")]
+pub mod overlayfs;
+
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
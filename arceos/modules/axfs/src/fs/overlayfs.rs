@@ -0,0 +1,618 @@
+//! An overlay filesystem, which merges a read-only lower directory with a
+//! writable upper [RAM filesystem](axfs_ramfs).
+//!
+//! Lookups see the files of both layers, and the upper one takes precedence.
+//! A file of the lower layer is copied up before it is changed, and a removed
+//! one is hidden by a whiteout, an empty file named `.wh.<name>` in the upper
+//! layer as in AUFS. A directory created in place of a removed one is marked
+//! opaque by a `.wh..wh..opq` file in it, so the lower one does not show
+//! through. A renamed file is copied up and linked at the new name, and a
+//! renamed directory is rebuilt there with its entries moved into it, before
+//! the old name is removed. A file or an empty directory at the new name is
+//! removed first, the same as a remove. The lower layer is never written, and
+//! all changes are lost when the filesystem is unmounted.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+use core::any::Any;
+
+use axfs_ramfs::{DirNode, RamFileSystem};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsOps, VfsResult};
+use axsync::Mutex;
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE: &str = ".wh..wh..opq";
+
+/// Keeps the lower layer alive as long as the overlay, e.g. the mount point
+/// it lives in.
+pub type LowerGuard = Box<dyn Any + Send + Sync>;
+
+/// The state shared by all nodes of an overlay.
+struct Overlay {
+    /// The parent of the mount point, for `..` of the root.
+    parent: Mutex<Option<VfsNodeRef>>,
+    /// The nodes in use by their paths, so that all users of a file see it
+    /// after it is copied up.
+    nodes: Mutex<BTreeMap<String, Weak<OverlayNode>>>,
+    _guard: Option<LowerGuard>,
+}
+
+impl Overlay {
+    fn cached(&self, path: &str) -> Option<Arc<OverlayNode>> {
+        self.nodes.lock().get(path).and_then(Weak::upgrade)
+    }
+
+    /// Caches `node` unless another node of the same path is cached first,
+    /// which is returned instead.
+    fn insert(&self, node: Arc<OverlayNode>) -> Arc<OverlayNode> {
+        let mut nodes = self.nodes.lock();
+        if let Some(old) = nodes.get(&node.path).and_then(Weak::upgrade) {
+            drop(nodes);
+            return old;
+        }
+        nodes.insert(node.path.clone(), Arc::downgrade(&node));
+        node
+    }
+
+    fn forget(&self, path: &str) {
+        self.nodes.lock().remove(path);
+    }
+}
+
+/// The nodes of a file in each layer.
+#[derive(Clone)]
+struct Layers {
+    upper: Option<VfsNodeRef>,
+    lower: Option<VfsNodeRef>,
+}
+
+/// A file or directory of an overlay.
+pub struct OverlayNode {
+    this: Weak<OverlayNode>,
+    fs: Arc<Overlay>,
+    /// The path from the root, without the leading `/`.
+    path: String,
+    /// The parent directory, `None` for the root.
+    parent: Option<Arc<OverlayNode>>,
+    layers: Mutex<Layers>,
+}
+
+impl OverlayNode {
+    fn layers(&self) -> Layers {
+        self.layers.lock().clone()
+    }
+
+    /// Returns the node in the top layer that the file lives in.
+    pub fn real(&self) -> VfsNodeRef {
+        let layers = self.layers();
+        layers.upper.or(layers.lower).unwrap()
+    }
+
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.real().get_attr().is_ok_and(|attr| attr.is_dir())
+    }
+
+    fn root(&self) -> Arc<OverlayNode> {
+        match &self.parent {
+            Some(parent) => parent.root(),
+            None => self.this.upgrade().unwrap(),
+        }
+    }
+
+    /// Looks up the parent directory of `path` from this directory, returns it
+    /// along with the last component of `path`.
+    fn lookup_parent<'a>(&self, path: &'a str) -> VfsResult<(Arc<OverlayNode>, &'a str)> {
+        let path = path.trim_matches('/');
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => {
+                let dir = self.this.upgrade().unwrap().lookup(dir)?;
+                let dir = dir.as_any().downcast_ref::<OverlayNode>();
+                // `..` of the root leaves the overlay
+                let dir = dir.ok_or(VfsError::CrossesDevices)?;
+                (dir.this.upgrade().unwrap(), name)
+            }
+            None => (self.this.upgrade().unwrap(), path),
+        };
+        if !dir.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok((dir, name))
+    }
+
+    /// Looks up the entry `name` of this directory in both layers.
+    fn child(&self, name: &str) -> VfsResult<Arc<OverlayNode>> {
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::NotFound);
+        }
+        let path = if self.path.is_empty() {
+            name.into()
+        } else {
+            format!("{}/{}", self.path, name)
+        };
+        if let Some(node) = self.fs.cached(&path) {
+            return Ok(node);
+        }
+
+        let layers = self.layers();
+        let (mut upper, mut hide_lower) = (None, false);
+        if let Some(dir) = &layers.upper {
+            upper = lookup_entry(dir, name)?;
+            hide_lower = lookup_entry(dir, &whiteout(name))?.is_some()
+                || lookup_entry(dir, OPAQUE)?.is_some();
+        }
+        let mut lower = match &layers.lower {
+            Some(dir) if !hide_lower => lookup_entry(dir, name)?,
+            _ => None,
+        };
+        // only directories are merged, other files are replaced
+        if let (Some(upper), Some(l)) = (&upper, &lower) {
+            if !is_dir_node(upper)? || !is_dir_node(l)? {
+                lower = None;
+            }
+        }
+        if upper.is_none() && lower.is_none() {
+            return Err(VfsError::NotFound);
+        }
+        let node = Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            fs: self.fs.clone(),
+            path,
+            parent: self.this.upgrade(),
+            layers: Mutex::new(Layers { upper, lower }),
+        });
+        Ok(self.fs.insert(node))
+    }
+
+    /// Returns the entries of this directory merged from both layers, except
+    /// `.` and `..`.
+    fn entries(&self) -> VfsResult<Vec<(String, VfsNodeType)>> {
+        let layers = self.layers();
+        let mut entries = BTreeMap::new();
+        let (mut hidden, mut opaque) = (BTreeSet::new(), false);
+        if let Some(dir) = &layers.upper {
+            for (name, ty) in read_entries(dir)? {
+                if name == OPAQUE {
+                    opaque = true;
+                } else if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+                    hidden.insert(String::from(name));
+                } else {
+                    entries.insert(name, ty);
+                }
+            }
+        }
+        if let Some(dir) = layers.lower.filter(|_| !opaque) {
+            for (name, ty) in read_entries(&dir)? {
+                if !hidden.contains(&name) && !name.starts_with(WHITEOUT_PREFIX) {
+                    entries.entry(name).or_insert(ty);
+                }
+            }
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    /// Copies the file up to the upper layer if it is only in the lower one,
+    /// along with its parent directories, and returns its upper node.
+    pub fn copy_up(&self) -> VfsResult<VfsNodeRef> {
+        let mut layers = self.layers.lock();
+        if let Some(upper) = &layers.upper {
+            return Ok(upper.clone());
+        }
+        let lower = layers.lower.clone().unwrap();
+        // the root always has an upper layer
+        let dir = self.parent.as_ref().unwrap().copy_up()?;
+        let name = self.name();
+        let attr = lower.get_attr()?;
+        debug!("overlayfs: copy up {}", self.path);
+        dir.create(name, attr.file_type())?;
+        let upper = dir.clone().lookup(name)?;
+        if let Err(e) = copy_data(&lower, &upper, &attr) {
+            dir.remove(name).ok();
+            return Err(e);
+        }
+        copy_meta(&lower, &upper, &attr);
+        layers.upper = Some(upper.clone());
+        Ok(upper)
+    }
+
+    /// Adds a hard link to `node` named `name` in this directory, both of
+    /// which are copied up.
+    pub fn link(&self, name: &str, node: &OverlayNode) -> VfsResult {
+        if !Arc::ptr_eq(&self.fs, &node.fs) {
+            return Err(VfsError::InvalidInput);
+        }
+        match self.child(name) {
+            Ok(_) => return Err(VfsError::AlreadyExists),
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        let target = node.copy_up()?;
+        let dir = self.copy_up()?;
+        let dir_node = dir.as_any().downcast_ref::<DirNode>().unwrap();
+        dir_node.link(name, &target)?;
+        if lookup_entry(&dir, &whiteout(name))?.is_some() {
+            dir.remove(&whiteout(name))?;
+        }
+        Ok(())
+    }
+
+    /// Moves `src` into this directory as `name`, which must not exist. A file
+    /// is linked, and a directory is created with its entries moved into it.
+    fn move_tree(&self, src: &OverlayNode, name: &str) -> VfsResult {
+        if !src.is_dir() {
+            return self.link(name, src);
+        }
+        let real = src.real();
+        let attr = real.get_attr()?;
+        self.create(name, VfsNodeType::Dir)?;
+        let dst = self.child(name)?;
+        for (entry, _) in src.entries()? {
+            dst.move_tree(&src.child(&entry)?, &entry)?;
+        }
+        copy_meta(&real, &dst.copy_up()?, &attr);
+        Ok(())
+    }
+
+    /// Removes the entry `name` of this directory along with its entries,
+    /// which are hidden by whiteouts if they are in the lower layer.
+    fn remove_tree(&self, name: &str) -> VfsResult {
+        let node = self.child(name)?;
+        if node.is_dir() {
+            for (entry, _) in node.entries()? {
+                node.remove_tree(&entry)?;
+            }
+        }
+        self.remove(name)
+    }
+}
+
+impl Drop for OverlayNode {
+    fn drop(&mut self) {
+        let mut nodes = self.fs.nodes.lock();
+        if nodes
+            .get(&self.path)
+            .is_some_and(|node| node.ptr_eq(&self.this))
+        {
+            nodes.remove(&self.path);
+        }
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.real().get_attr()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.real().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.copy_up()?.write_at(offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        // the lower layer is never written
+        match self.layers().upper {
+            Some(upper) => upper.fsync(),
+            None => Ok(()),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.copy_up()?.truncate(size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if !self.is_dir() {
+            return None;
+        }
+        match &self.parent {
+            Some(parent) => Some(parent.clone()),
+            None => self.fs.parent.lock().clone(),
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.child(name).map(|node| node as VfsNodeRef),
+        }?;
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            return match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => self.child(name)?.create(rest, ty),
+            };
+        } else if name.is_empty() || name == "." || name == ".." {
+            return Ok(()); // already exists
+        } else if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::InvalidInput);
+        }
+        match self.child(name) {
+            Ok(_) => return Err(VfsError::AlreadyExists),
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let dir = self.copy_up()?;
+        let whiteout = whiteout(name);
+        let replaces_lower = lookup_entry(&dir, &whiteout)?.is_some();
+        dir.create(name, ty)?;
+        if replaces_lower {
+            dir.remove(&whiteout)?;
+            if ty == VfsNodeType::Dir {
+                dir.create(&format!("{}/{}", name, OPAQUE), VfsNodeType::File)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            return match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => self.child(name)?.remove(rest),
+            };
+        } else if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+
+        let node = self.child(name)?;
+        let is_dir = node.is_dir();
+        if is_dir && !node.entries()?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        let in_lower = match &self.layers().lower {
+            Some(lower) => lookup_entry(lower, name)?.is_some(),
+            None => false,
+        };
+        let dir = self.copy_up()?;
+        if let Some(upper) = node.layers().upper {
+            // the whiteouts in a directory are removed along with it
+            if is_dir {
+                for (name, _) in read_entries(&upper)? {
+                    if name.starts_with(WHITEOUT_PREFIX) {
+                        upper.remove(&name)?;
+                    }
+                }
+            }
+            dir.remove(name)?;
+        }
+        if in_lower {
+            dir.create(&whiteout(name), VfsNodeType::File)?;
+        }
+        self.fs.forget(&node.path);
+        Ok(())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at overlayfs: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.lookup_parent(src_path)?;
+        // absolute destinations are relative to the root of the overlay
+        let dst_base = if dst_path.starts_with('/') {
+            self.root()
+        } else {
+            self.this.upgrade().unwrap()
+        };
+        let (dst_dir, dst_name) = dst_base.lookup_parent(dst_path)?;
+        if dst_name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::InvalidInput);
+        }
+        let node = src_dir.child(src_name)?;
+        // a directory cannot be moved into itself
+        if dst_dir.path == node.path || dst_dir.path.starts_with(&format!("{}/", node.path)) {
+            return Err(VfsError::InvalidInput);
+        }
+        match dst_dir.child(dst_name) {
+            Ok(old) if Arc::ptr_eq(&old, &node) => return Ok(()),
+            Ok(old) => {
+                match (node.is_dir(), old.is_dir()) {
+                    (true, false) => return Err(VfsError::NotADirectory),
+                    (false, true) => return Err(VfsError::IsADirectory),
+                    _ => {}
+                }
+                // hides it if it is in the lower layer, fails on a non-empty
+                // directory
+                dst_dir.remove(dst_name)?;
+            }
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        dst_dir.move_tree(&node, dst_name)?;
+        src_dir.remove_tree(src_name)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries()?;
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = entries.next() {
+                        *ent = VfsDirEntry::new(name, *ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// An overlay of a read-only lower directory and a RAM filesystem.
+pub struct OverlayFileSystem {
+    root: Arc<OverlayNode>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay of the directory `lower` and an empty RAM
+    /// filesystem, whose root has the same attributes as `lower`.
+    ///
+    /// `guard` is dropped along with the overlay.
+    pub fn new(lower: VfsNodeRef, guard: Option<LowerGuard>) -> VfsResult<Self> {
+        let attr = lower.get_attr()?;
+        if !attr.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let upper: VfsNodeRef = RamFileSystem::new().root_dir_node();
+        copy_meta(&lower, &upper, &attr);
+        let fs = Arc::new(Overlay {
+            parent: Mutex::new(None),
+            nodes: Mutex::new(BTreeMap::new()),
+            _guard: guard,
+        });
+        let root = Arc::new_cyclic(|this| OverlayNode {
+            this: this.clone(),
+            fs,
+            path: String::new(),
+            parent: None,
+            layers: Mutex::new(Layers {
+                upper: Some(upper),
+                lower: Some(lower),
+            }),
+        });
+        Ok(Self { root })
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        *self.root.fs.parent.lock() = mount_point.parent();
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+/// Returns the node of the layer that `node` lives in if it is in an
+/// overlay, or `node` itself.
+pub(crate) fn real_node(node: &VfsNodeRef) -> VfsNodeRef {
+    match node.as_any().downcast_ref::<OverlayNode>() {
+        Some(node) => node.real(),
+        None => node.clone(),
+    }
+}
+
+/// Returns the upper node of `node`, copied up first, if it is in an
+/// overlay, or `node` itself.
+pub(crate) fn upper_node(node: &VfsNodeRef) -> VfsResult<VfsNodeRef> {
+    match node.as_any().downcast_ref::<OverlayNode>() {
+        Some(node) => node.copy_up(),
+        None => Ok(node.clone()),
+    }
+}
+
+fn whiteout(name: &str) -> String {
+    format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+fn is_dir_node(node: &VfsNodeRef) -> VfsResult<bool> {
+    Ok(node.get_attr()?.is_dir())
+}
+
+/// Looks up the entry `name` in `dir`, returns `None` if it does not exist.
+fn lookup_entry(dir: &VfsNodeRef, name: &str) -> VfsResult<Option<VfsNodeRef>> {
+    match dir.clone().lookup(name) {
+        Ok(node) => Ok(Some(node)),
+        Err(VfsError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads all entries of `dir` except `.` and `..`.
+fn read_entries(dir: &VfsNodeRef) -> VfsResult<Vec<(String, VfsNodeType)>> {
+    let mut entries = Vec::new();
+    let mut dirents: [VfsDirEntry; 16] = core::array::from_fn(|_| VfsDirEntry::default());
+    let mut idx = 0;
+    loop {
+        let n = dir.read_dir(idx, &mut dirents)?;
+        if n == 0 {
+            break;
+        }
+        idx += n;
+        for ent in &dirents[..n] {
+            let name =
+                core::str::from_utf8(ent.name_as_bytes()).map_err(|_| VfsError::InvalidData)?;
+            if name != "." && name != ".." {
+                entries.push((String::from(name), ent.entry_type()));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Copies the content of the file or symbolic link `src` to `dst`.
+fn copy_data(src: &VfsNodeRef, dst: &VfsNodeRef, attr: &VfsNodeAttr) -> VfsResult {
+    if attr.is_dir() {
+        return Ok(());
+    }
+    let mut buf = vec![0; 4096];
+    let mut offset = 0;
+    loop {
+        let n = src.read_at(offset, &mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        dst.write_at(offset, &buf[..n])?;
+        offset += n as u64;
+    }
+}
+
+/// Copies the permissions of `src` with attributes `attr` to the RAM
+/// filesystem node `dst`, along with the extended attributes, the owner and
+/// the timestamps if `src` records them.
+fn copy_meta(src: &VfsNodeRef, dst: &VfsNodeRef, attr: &VfsNodeAttr) {
+    let Some(attrs) = axfs_ramfs::node_attrs(dst) else {
+        return;
+    };
+    attrs.set_perm(attr.perm());
+    if let Some(src) = axfs_ramfs::node_attrs(src) {
+        for name in src.list_xattrs() {
+            if let Some(value) = src.get_xattr(&name) {
+                // both nodes have the same space for extended attributes
+                let _ = attrs.set_xattr(&name, &value, None);
+            }
+        }
+        let meta = src.get();
+        attrs.set_owner(Some(meta.uid), Some(meta.gid));
+        attrs.set_times(Some(meta.atime), Some(meta.mtime));
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,368 @@
+//! Pseudo filesystems whose files are generated from kernel state, like
+//! `procfs` and `sysfs`.
+//!
+//! The content of a [`PseudoFile`] is rendered by a callback every time it is
+//! read, and a write is passed to another callback. Nodes are added to a
+//! [`PseudoDir`] at runtime, and subdirectories may also be generated on every
+//! lookup by a callback, e.g. one directory per task.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsOps, VfsResult};
+use axsync::Mutex;
+
+type ReadFn = Box<dyn Fn() -> String + Send + Sync>;
+type WriteFn = Box<dyn Fn(&str) -> VfsResult + Send + Sync>;
+type EntriesFn = Arc<dyn Fn() -> Vec<(String, Arc<PseudoDir>)> + Send + Sync>;
+
+/// A file whose content is rendered on every read.
+pub struct PseudoFile {
+    read: ReadFn,
+    write: Option<WriteFn>,
+}
+
+impl PseudoFile {
+    /// Creates a read-only file whose content is returned by `read`.
+    pub fn new<R>(read: R) -> Arc<Self>
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: None,
+        })
+    }
+
+    /// Creates a file whose content is returned by `read`, and whose new
+    /// content is passed to `write`, e.g. to change a kernel parameter.
+    ///
+    /// `write` receives the written data without surrounding whitespace.
+    pub fn new_writable<R, W>(read: R, write: W) -> Arc<Self>
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+        W: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: Some(Box::new(write)),
+        })
+    }
+}
+
+impl VfsNodeOps for PseudoFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = if self.write.is_some() { 0o644 } else { 0o444 };
+        // the content is only rendered when read, the size is 0 like Linux
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(perm),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        if offset != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidData)?;
+        write(value.trim())?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // opening with `O_TRUNC` before a write is fine
+        if self.write.is_some() {
+            Ok(())
+        } else {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // the writes take effect at once
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A directory of a pseudo filesystem.
+///
+/// Besides the nodes added to it, it may contain subdirectories generated on
+/// every lookup, see [`PseudoDir::set_entries`].
+pub struct PseudoDir {
+    this: Weak<PseudoDir>,
+    parent: Mutex<Weak<dyn VfsNodeOps>>,
+    children: Mutex<BTreeMap<String, Child>>,
+    entries: Mutex<Option<EntriesFn>>,
+}
+
+/// A node added to a [`PseudoDir`]. Subdirectories are kept in their own type
+/// so that [`PseudoDir::dir`] can return them.
+#[derive(Clone)]
+enum Child {
+    Dir(Arc<PseudoDir>),
+    Node(VfsNodeRef),
+}
+
+impl Child {
+    fn node(&self) -> VfsNodeRef {
+        match self {
+            Self::Dir(dir) => dir.clone(),
+            Self::Node(node) => node.clone(),
+        }
+    }
+}
+
+impl PseudoDir {
+    /// Creates an empty directory.
+    pub fn new() -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(Weak::<Self>::new()),
+            children: Mutex::new(BTreeMap::new()),
+            entries: Mutex::new(None),
+        })
+    }
+
+    /// Makes the directory also contain the subdirectories returned by
+    /// `entries`, which is called on every lookup and listing.
+    ///
+    /// Nodes added with [`add`](Self::add) take precedence over generated ones
+    /// with the same name.
+    pub fn set_entries<E>(&self, entries: E)
+    where
+        E: Fn() -> Vec<(String, Arc<PseudoDir>)> + Send + Sync + 'static,
+    {
+        *self.entries.lock() = Some(Arc::new(entries));
+    }
+
+    fn set_parent(&self, parent: Weak<dyn VfsNodeOps>) {
+        *self.parent.lock() = parent;
+    }
+
+    /// Adds `node` named `name` to this directory, replacing the node with the
+    /// same name if any.
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        if let Some(dir) = node.as_any().downcast_ref::<PseudoDir>() {
+            dir.set_parent(self.this.clone());
+        }
+        self.children.lock().insert(name.into(), Child::Node(node));
+    }
+
+    /// Adds the directory `dir` named `name`, e.g. one built before the
+    /// filesystem is mounted. Unlike [`add`](Self::add), it can be returned
+    /// by [`dir`](Self::dir) later.
+    pub fn add_dir(&self, name: &str, dir: Arc<PseudoDir>) {
+        dir.set_parent(self.this.clone());
+        self.children.lock().insert(name.into(), Child::Dir(dir));
+    }
+
+    /// Adds a read-only file named `name`, see [`PseudoFile::new`].
+    pub fn add_file<R>(&self, name: &str, read: R)
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+    {
+        self.add(name, PseudoFile::new(read));
+    }
+
+    /// Adds a writable file named `name`, see [`PseudoFile::new_writable`].
+    pub fn add_writable_file<R, W>(&self, name: &str, read: R, write: W)
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+        W: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        self.add(name, PseudoFile::new_writable(read, write));
+    }
+
+    /// Returns the subdirectory at `path`, creating it and all missing parent
+    /// directories.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component of `path` exists but is not a [`PseudoDir`].
+    pub fn dir(&self, path: &str) -> Arc<PseudoDir> {
+        let (name, rest) = split_path(path);
+        let dir = if name.is_empty() {
+            self.this.upgrade().unwrap()
+        } else {
+            let mut children = self.children.lock();
+            match children.get(name) {
+                Some(Child::Dir(dir)) => dir.clone(),
+                Some(Child::Node(_)) => panic!("{} is not a directory", name),
+                None => {
+                    let dir = Self::new();
+                    dir.set_parent(self.this.clone());
+                    children.insert(name.into(), Child::Dir(dir.clone()));
+                    dir
+                }
+            }
+        };
+        match rest {
+            Some(rest) => dir.dir(rest),
+            None => dir,
+        }
+    }
+
+    /// Returns all entries, the added ones first.
+    fn all_entries(&self) -> Vec<(String, VfsNodeRef)> {
+        let mut all: Vec<_> = self
+            .children
+            .lock()
+            .iter()
+            .map(|(name, child)| (name.clone(), child.node()))
+            .collect();
+        let entries = self.entries.lock().clone();
+        if let Some(entries) = entries {
+            for (name, dir) in entries() {
+                if !all.iter().any(|(n, _)| *n == name) {
+                    dir.set_parent(self.this.clone());
+                    all.push((name, dir));
+                }
+            }
+        }
+        all
+    }
+
+    fn child(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(child) = self.children.lock().get(name) {
+            return Some(child.node());
+        }
+        self.all_entries()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, node)| node)
+    }
+}
+
+impl VfsNodeOps for PseudoDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.child(name).ok_or(VfsError::NotFound),
+        }?;
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.all_entries();
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, node)) = entries.next() {
+                        let ty = node.get_attr().map_or(VfsNodeType::File, |a| a.file_type());
+                        *ent = VfsDirEntry::new(name, ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::IsADirectory)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// A pseudo filesystem, whose tree is built with [`PseudoDir`]s.
+pub struct PseudoFileSystem {
+    parent: Mutex<Option<VfsNodeRef>>,
+    root: Arc<PseudoDir>,
+}
+
+impl PseudoFileSystem {
+    /// Creates a filesystem with an empty root directory.
+    pub fn new() -> Self {
+        Self {
+            parent: Mutex::new(None),
+            root: PseudoDir::new(),
+        }
+    }
+
+    /// Returns the root directory.
+    pub fn root_dir_node(&self) -> Arc<PseudoDir> {
+        self.root.clone()
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        // keep the parent of the mount point alive for `..` of the root
+        let mut parent = self.parent.lock();
+        *parent = mount_point.parent();
+        self.root.set_parent(match parent.as_ref() {
+            Some(parent) => Arc::downgrade(parent),
+            None => Weak::<PseudoDir>::new(),
+        });
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl Default for PseudoFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,741 @@
+//! A client of the 9P2000.L protocol, which mounts the directories shared by
+//! the host through 9P transports, e.g. a QEMU `virtio-9p` device.
+//!
+//! The source of a mount is the mount tag of the device, and only one
+//! filesystem can be mounted on each device at a time. Files are read and
+//! written through the host without caching, so changes made on either side
+//! are seen by the other at once.
+//!
+//! All requests are made as root, so the access to the files is decided by
+//! the host, as the user running QEMU. Symbolic links can be read but not
+//! created.
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use core::any::Any;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use axdriver::prelude::*;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+const VERSION: &str = "9P2000.L";
+/// The maximum size of a message we send or receive, which may be lowered by
+/// the host.
+const MSIZE: u32 = 0x10000;
+/// The size of the headers of `Tread`, `Rread`, `Twrite` and `Rwrite`.
+const IOHDRSZ: u32 = 24;
+/// The maximum number of names in a `Twalk`.
+const MAXWELEM: usize = 16;
+const NOTAG: u16 = !0;
+const NOFID: u32 = !0;
+/// The fid of the root directory, attached when mounted.
+const ROOT_FID: u32 = 0;
+
+const RLERROR: u8 = 7;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+const QTDIR: u8 = 0x80;
+const QTSYMLINK: u8 = 0x02;
+
+/// `mode`, `nlink`, `size` and `blocks` of `Tgetattr`.
+const GETATTR_BASIC: u64 = 0x7ff;
+/// `size` of `Tsetattr`.
+const SETATTR_SIZE: u32 = 0x8;
+
+const O_RDONLY: u32 = 0;
+const O_RDWR: u32 = 2;
+const O_CREAT: u32 = 0o100;
+const O_EXCL: u32 = 0o200;
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// Converts an errno of Linux in `Rlerror` to [`VfsError`].
+fn errno_to_err(errno: u32) -> VfsError {
+    match errno {
+        1 | 13 | 30 => VfsError::PermissionDenied, // EPERM, EACCES, EROFS
+        2 => VfsError::NotFound,
+        12 => VfsError::NoMemory,
+        16 => VfsError::ResourceBusy,
+        17 => VfsError::AlreadyExists,
+        20 => VfsError::NotADirectory,
+        21 => VfsError::IsADirectory,
+        22 | 36 => VfsError::InvalidInput, // EINVAL, ENAMETOOLONG
+        27 | 28 | 122 => VfsError::StorageFull, // EFBIG, ENOSPC, EDQUOT
+        38 | 95 => VfsError::Unsupported,  // ENOSYS, EOPNOTSUPP
+        39 => VfsError::DirectoryNotEmpty,
+        _ => VfsError::Io,
+    }
+}
+
+fn type_from_mode(mode: u32) -> VfsNodeType {
+    match mode & 0o170000 {
+        0o010000 => VfsNodeType::Fifo,
+        0o020000 => VfsNodeType::CharDevice,
+        0o040000 => VfsNodeType::Dir,
+        0o060000 => VfsNodeType::BlockDevice,
+        0o120000 => VfsNodeType::SymLink,
+        0o140000 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// Converts a `DT_*` type in `Rreaddir` to [`VfsNodeType`].
+fn type_from_dirent(ty: u8) -> VfsNodeType {
+    match ty {
+        1 => VfsNodeType::Fifo,
+        2 => VfsNodeType::CharDevice,
+        4 => VfsNodeType::Dir,
+        6 => VfsNodeType::BlockDevice,
+        10 => VfsNodeType::SymLink,
+        12 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// Splits `path` into its parent directory and its last component.
+fn split_path(path: &str) -> VfsResult<(&str, &str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(VfsError::InvalidInput);
+    }
+    if parent.is_empty() && path.starts_with('/') {
+        return Ok(("/", name));
+    }
+    Ok((parent, name))
+}
+
+/// The unique identification of a file on the host.
+#[derive(Debug, Clone, Copy, Default)]
+struct Qid {
+    ty: u8,
+    path: u64,
+}
+
+/// A request being built, in little endian.
+struct Msg(Vec<u8>);
+
+impl Msg {
+    fn new(ty: u8) -> Self {
+        // `size[4]`, filled when sent, `type[1]` and `tag[2]`
+        let tag = if ty == TVERSION { NOTAG } else { 0 };
+        Self(vec![0, 0, 0, 0, ty]).u16(tag)
+    }
+
+    fn u16(mut self, val: u16) -> Self {
+        self.0.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, val: u32) -> Self {
+        self.0.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, val: u64) -> Self {
+        self.0.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn bytes(mut self, data: &[u8]) -> Self {
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    fn str(self, s: &str) -> Self {
+        self.u16(s.len() as u16).bytes(s.as_bytes())
+    }
+}
+
+/// A reader of the body of a response.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(VfsError::InvalidData);
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> VfsResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> VfsResult<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> VfsResult<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> VfsResult<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> VfsResult<&'a str> {
+        let len = self.u16()? as usize;
+        core::str::from_utf8(self.bytes(len)?).map_err(|_| VfsError::InvalidData)
+    }
+
+    fn qid(&mut self) -> VfsResult<Qid> {
+        let ty = self.u8()?;
+        let _version = self.u32()?;
+        Ok(Qid {
+            ty,
+            path: self.u64()?,
+        })
+    }
+}
+
+/// A 9P transport found at boot.
+struct Device {
+    tag: String,
+    dev: Mutex<AxP9Device>,
+    /// Whether a filesystem is mounted on it.
+    in_use: AtomicBool,
+}
+
+static DEVICES: Mutex<Vec<Arc<Device>>> = Mutex::new(Vec::new());
+
+/// Registers a 9P transport, whose filesystem can be mounted by its tag.
+pub(crate) fn register_device(dev: AxP9Device) {
+    info!(
+        "  9P device {}: mount tag {:?}",
+        dev.device_name(),
+        dev.mount_tag()
+    );
+    DEVICES.lock().push(Arc::new(Device {
+        tag: dev.mount_tag().into(),
+        dev: Mutex::new(dev),
+        in_use: AtomicBool::new(false),
+    }));
+}
+
+/// A session with the host on a device.
+struct Client {
+    device: Arc<Device>,
+    msize: u32,
+    /// The buffer of the response, locked while a request is in flight.
+    resp: Mutex<Vec<u8>>,
+    next_fid: AtomicU32,
+    root_qid: Qid,
+}
+
+impl Client {
+    /// Starts a session on `device` and attaches the root directory.
+    fn connect(device: Arc<Device>) -> VfsResult<Self> {
+        let mut client = Self {
+            device,
+            msize: MSIZE,
+            resp: Mutex::new(vec![0; MSIZE as usize]),
+            next_fid: AtomicU32::new(ROOT_FID + 1),
+            root_qid: Qid::default(),
+        };
+        let body = client.call(Msg::new(TVERSION).u32(MSIZE).str(VERSION))?;
+        let mut r = Reader(&body);
+        let msize = r.u32()?;
+        if r.str()? != VERSION {
+            return Err(VfsError::Unsupported);
+        }
+        client.msize = msize.min(MSIZE);
+
+        let body = client.call(
+            Msg::new(TATTACH)
+                .u32(ROOT_FID)
+                .u32(NOFID)
+                .str("root")
+                .str("")
+                .u32(0),
+        )?;
+        client.root_qid = Reader(&body).qid()?;
+        Ok(client)
+    }
+
+    /// Sends the request `msg` and waits for the response, returns its body.
+    fn call(&self, msg: Msg) -> VfsResult<Vec<u8>> {
+        let mut req = msg.0;
+        let ty = req[4];
+        let len = req.len() as u32;
+        req[..4].copy_from_slice(&len.to_le_bytes());
+
+        let mut resp = self.resp.lock();
+        let len = self
+            .device
+            .dev
+            .lock()
+            .request(&req, &mut resp)
+            .map_err(|_| VfsError::Io)?;
+        let mut r = Reader(&resp[..len]);
+        let size = r.u32()? as usize;
+        let resp_ty = r.u8()?;
+        let _tag = r.u16()?;
+        if size < 7 || size > len {
+            return Err(VfsError::InvalidData);
+        }
+        let mut body = Reader(&resp[7..size]);
+        if resp_ty == RLERROR {
+            return Err(errno_to_err(body.u32()?));
+        } else if resp_ty != ty + 1 {
+            return Err(VfsError::InvalidData);
+        }
+        Ok(body.0.to_vec())
+    }
+
+    /// The maximum number of bytes in a `Tread` or `Twrite`.
+    fn iounit(&self) -> usize {
+        (self.msize - IOHDRSZ) as usize
+    }
+
+    fn alloc_fid(&self) -> u32 {
+        self.next_fid.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Walks from `fid` through `names` to a new fid, returns it with the qid
+    /// of the last name, which is `None` if `names` is empty.
+    fn walk(&self, fid: u32, names: &[&str]) -> VfsResult<(u32, Option<Qid>)> {
+        let newfid = self.alloc_fid();
+        let mut from = fid;
+        let mut qid = None;
+        let mut start = 0;
+        loop {
+            let end = (start + MAXWELEM).min(names.len());
+            let chunk = &names[start..end];
+            let msg = chunk.iter().fold(
+                Msg::new(TWALK)
+                    .u32(from)
+                    .u32(newfid)
+                    .u16(chunk.len() as u16),
+                |msg, name| msg.str(name),
+            );
+            let res = self.call(msg).and_then(|body| {
+                let mut r = Reader(&body);
+                let nwqid = r.u16()? as usize;
+                if nwqid < chunk.len() {
+                    return Err(VfsError::NotFound);
+                }
+                for _ in 0..nwqid {
+                    qid = Some(r.qid()?);
+                }
+                Ok(())
+            });
+            if let Err(e) = res {
+                if from == newfid {
+                    self.clunk(newfid);
+                }
+                return Err(e);
+            }
+            from = newfid;
+            start = end;
+            if start >= names.len() {
+                return Ok((newfid, qid));
+            }
+        }
+    }
+
+    /// Opens a clone of `fid` with the Linux open `flags`.
+    fn open(&self, fid: u32, flags: u32) -> VfsResult<u32> {
+        let (fid, _) = self.walk(fid, &[])?;
+        if let Err(e) = self.call(Msg::new(TLOPEN).u32(fid).u32(flags)) {
+            self.clunk(fid);
+            return Err(e);
+        }
+        Ok(fid)
+    }
+
+    fn clunk(&self, fid: u32) {
+        if let Err(e) = self.call(Msg::new(TCLUNK).u32(fid)) {
+            warn!("9p: failed to clunk fid {}: {:?}", fid, e);
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.device.in_use.store(false, Ordering::Release);
+    }
+}
+
+/// A file, directory or other node in a 9P filesystem.
+pub struct V9fsNode {
+    client: Arc<Client>,
+    fid: u32,
+    qid: Qid,
+    /// A clone of `fid` opened for I/O on the first read or write, with
+    /// whether it is writable.
+    io: Mutex<Option<(u32, bool)>>,
+}
+
+/// The attributes of a node on the host.
+struct Stat {
+    mode: u32,
+    nlink: u64,
+    size: u64,
+    blocks: u64,
+}
+
+impl V9fsNode {
+    fn new(client: Arc<Client>, fid: u32, qid: Qid) -> Arc<Self> {
+        Arc::new(Self {
+            client,
+            fid,
+            qid,
+            io: Mutex::new(None),
+        })
+    }
+
+    /// The unique number of the file on the host.
+    pub fn ino(&self) -> u64 {
+        self.qid.path
+    }
+
+    /// Returns the number of hard links to this node.
+    pub fn nlink(&self) -> VfsResult<u64> {
+        Ok(self.stat()?.nlink)
+    }
+
+    fn is_root(&self) -> bool {
+        self.qid.path == self.client.root_qid.path
+    }
+
+    fn stat(&self) -> VfsResult<Stat> {
+        let body = self
+            .client
+            .call(Msg::new(TGETATTR).u32(self.fid).u64(GETATTR_BASIC))?;
+        let mut r = Reader(&body);
+        let _valid = r.u64()?;
+        let _qid = r.qid()?;
+        let mode = r.u32()?;
+        let _uid_gid = r.u64()?;
+        let nlink = r.u64()?;
+        let _rdev = r.u64()?;
+        let size = r.u64()?;
+        let _blksize = r.u64()?;
+        let blocks = r.u64()?;
+        Ok(Stat {
+            mode,
+            nlink,
+            size,
+            blocks,
+        })
+    }
+
+    /// Walks to the node at `path`, which is relative to the root of the
+    /// filesystem if absolute.
+    fn walk(&self, path: &str) -> VfsResult<Arc<Self>> {
+        let (from, base_qid) = if path.starts_with('/') {
+            (ROOT_FID, self.client.root_qid)
+        } else {
+            (self.fid, self.qid)
+        };
+        let names: Vec<_> = path
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .collect();
+        if names.first() == Some(&"..") && (from == ROOT_FID || self.is_root()) {
+            // like other filesystems, `..` of the root is left to the VFS
+            return Err(VfsError::NotFound);
+        }
+        let (fid, qid) = self.client.walk(from, &names)?;
+        Ok(Self::new(self.client.clone(), fid, qid.unwrap_or(base_qid)))
+    }
+
+    /// Returns the fid opened for I/O, which is opened for writing if `write`
+    /// is `true`.
+    fn io_fid(&self, write: bool) -> VfsResult<u32> {
+        let mut io = self.io.lock();
+        if let Some((fid, writable)) = *io {
+            if writable || !write {
+                return Ok(fid);
+            }
+        }
+        let (fid, writable) = if self.qid.ty & QTDIR != 0 {
+            (self.client.open(self.fid, O_RDONLY)?, false)
+        } else {
+            match self.client.open(self.fid, O_RDWR) {
+                Ok(fid) => (fid, true),
+                Err(VfsError::PermissionDenied) if !write => {
+                    (self.client.open(self.fid, O_RDONLY)?, false)
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        if let Some((old, _)) = io.replace((fid, writable)) {
+            self.client.clunk(old);
+        }
+        Ok(fid)
+    }
+
+    fn read_link(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let body = self.client.call(Msg::new(TREADLINK).u32(self.fid))?;
+        let target = Reader(&body).str()?.as_bytes();
+        let start = (offset as usize).min(target.len());
+        let len = buf.len().min(target.len() - start);
+        buf[..len].copy_from_slice(&target[start..start + len]);
+        Ok(len)
+    }
+}
+
+impl VfsNodeOps for V9fsNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let stat = self.stat()?;
+        let perm = VfsNodePerm::from_bits_truncate(stat.mode as u16 & 0o777);
+        Ok(VfsNodeAttr::new(
+            perm,
+            type_from_mode(stat.mode),
+            stat.size,
+            stat.blocks,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.qid.ty & QTDIR != 0 {
+            return Err(VfsError::IsADirectory);
+        } else if self.qid.ty & QTSYMLINK != 0 {
+            return self.read_link(offset, buf);
+        }
+        let fid = self.io_fid(false)?;
+        let mut read = 0;
+        while read < buf.len() {
+            let count = (buf.len() - read).min(self.client.iounit());
+            let body = self.client.call(
+                Msg::new(TREAD)
+                    .u32(fid)
+                    .u64(offset + read as u64)
+                    .u32(count as u32),
+            )?;
+            let mut r = Reader(&body);
+            let len = r.u32()? as usize;
+            let data = r.bytes(len.min(count))?;
+            buf[read..read + data.len()].copy_from_slice(data);
+            read += data.len();
+            if data.len() < count {
+                break; // end of file
+            }
+        }
+        Ok(read)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if self.qid.ty & QTDIR != 0 {
+            return Err(VfsError::IsADirectory);
+        }
+        let fid = self.io_fid(true)?;
+        let mut written = 0;
+        while written < buf.len() {
+            let count = (buf.len() - written).min(self.client.iounit());
+            let body = self.client.call(
+                Msg::new(TWRITE)
+                    .u32(fid)
+                    .u64(offset + written as u64)
+                    .u32(count as u32)
+                    .bytes(&buf[written..written + count]),
+            )?;
+            let len = Reader(&body).u32()? as usize;
+            if len == 0 {
+                break;
+            }
+            written += len.min(count);
+        }
+        Ok(written)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match *self.io.lock() {
+            Some((fid, true)) => self
+                .client
+                .call(Msg::new(TFSYNC).u32(fid).u32(0))
+                .map(|_| ()),
+            _ => Ok(()), // nothing written through this node
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let msg = Msg::new(TSETATTR)
+            .u32(self.fid)
+            .u32(SETATTR_SIZE)
+            .u32(0) // mode
+            .u32(0) // uid
+            .u32(0) // gid
+            .u64(size)
+            .u64(0) // atime
+            .u64(0)
+            .u64(0) // mtime
+            .u64(0);
+        self.client.call(msg).map(|_| ())
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.is_root() {
+            return None;
+        }
+        let (fid, qid) = self.client.walk(self.fid, &[".."]).ok()?;
+        Some(Self::new(self.client.clone(), fid, qid?))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at 9p: {}", path);
+        if path.split('/').all(|name| name.is_empty() || name == ".") {
+            return Ok(self);
+        }
+        Ok(self.walk(path)?)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at 9p: {}", ty, path);
+        let (parent, name) = split_path(path)?;
+        let dir = self.walk(parent)?;
+        match ty {
+            VfsNodeType::File => {
+                // `Tlcreate` turns the fid into the created file
+                let (fid, _) = self.client.walk(dir.fid, &[])?;
+                let msg = Msg::new(TLCREATE)
+                    .u32(fid)
+                    .str(name)
+                    .u32(O_RDWR | O_CREAT | O_EXCL)
+                    .u32(0o644)
+                    .u32(0); // gid
+                let res = self.client.call(msg);
+                self.client.clunk(fid);
+                res.map(|_| ())
+            }
+            VfsNodeType::Dir => {
+                let msg = Msg::new(TMKDIR).u32(dir.fid).str(name).u32(0o755).u32(0);
+                self.client.call(msg).map(|_| ())
+            }
+            _ => Err(VfsError::Unsupported),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at 9p: {}", path);
+        let (parent, name) = split_path(path)?;
+        let dir = self.walk(parent)?;
+        let node = dir.walk(name)?;
+        let flags = if node.qid.ty & QTDIR != 0 {
+            AT_REMOVEDIR
+        } else {
+            0
+        };
+        drop(node);
+        let msg = Msg::new(TUNLINKAT).u32(dir.fid).str(name).u32(flags);
+        self.client.call(msg).map(|_| ())
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let fid = self.io_fid(false)?;
+        let (mut offset, mut idx, mut count) = (0, 0, 0);
+        while count < dirents.len() {
+            let body = self.client.call(
+                Msg::new(TREADDIR)
+                    .u32(fid)
+                    .u64(offset)
+                    .u32(self.client.iounit() as u32),
+            )?;
+            let mut r = Reader(&body);
+            let len = r.u32()? as usize;
+            if len == 0 {
+                break;
+            }
+            let mut entries = Reader(r.bytes(len)?);
+            while !entries.0.is_empty() && count < dirents.len() {
+                let _qid = entries.qid()?;
+                offset = entries.u64()?;
+                let ty = entries.u8()?;
+                let name = entries.str()?;
+                if idx >= start_idx {
+                    dirents[count] = VfsDirEntry::new(name, type_from_dirent(ty));
+                    count += 1;
+                }
+                idx += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!(
+            "rename at 9p, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        let (src_parent, src_name) = split_path(src_path)?;
+        let (dst_parent, dst_name) = split_path(dst_path)?;
+        let src_dir = self.walk(src_parent)?;
+        let dst_dir = self.walk(dst_parent)?;
+        let msg = Msg::new(TRENAMEAT)
+            .u32(src_dir.fid)
+            .str(src_name)
+            .u32(dst_dir.fid)
+            .str(dst_name);
+        self.client.call(msg).map(|_| ())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Drop for V9fsNode {
+    fn drop(&mut self) {
+        if let Some((fid, _)) = self.io.lock().take() {
+            self.client.clunk(fid);
+        }
+        self.client.clunk(self.fid);
+    }
+}
+
+/// A filesystem shared by the host through a 9P transport.
+pub struct V9fsFileSystem {
+    root: Arc<V9fsNode>,
+}
+
+impl V9fsFileSystem {
+    /// Connects to the host through the device with the mount tag `tag`.
+    ///
+    /// Fails with [`ResourceBusy`](VfsError::ResourceBusy) if a filesystem is
+    /// already mounted on it.
+    pub fn open(tag: &str) -> VfsResult<Self> {
+        let device = DEVICES
+            .lock()
+            .iter()
+            .find(|dev| dev.tag == tag)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        if device.in_use.swap(true, Ordering::Acquire) {
+            return Err(VfsError::ResourceBusy);
+        }
+        let client = Arc::new(Client::connect(device)?);
+        info!(
+            "  9p: mounted {:?} with messages of {} bytes",
+            tag, client.msize
+        );
+        let qid = client.root_qid;
+        Ok(Self {
+            root: V9fsNode::new(client, ROOT_FID, qid),
+        })
+    }
+}
+
+impl VfsOps for V9fsFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
@@ -0,0 +1,145 @@
+//! Unpacks an initramfs, a cpio archive in the `newc` format, into the root
+//! RAM filesystem.
+
+use alloc::{collections::BTreeMap, format, string::String};
+use core::time::Duration;
+
+use axerrno::{ax_err, AxError, AxResult};
+
+use crate::fops::FilePerm;
+use crate::root;
+
+const HEADER_LEN: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// The fields of an entry header, each written as 8 hex digits.
+struct Header {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    file_size: usize,
+    name_size: usize,
+}
+
+impl Header {
+    fn parse(header: &[u8]) -> AxResult<Self> {
+        // `070702` only adds a checksum of the data, which is not verified
+        if header.len() < HEADER_LEN || !matches!(&header[..6], b"070701" | b"070702") {
+            return ax_err!(InvalidData, "bad cpio header");
+        }
+        let field = |i: usize| {
+            let hex = core::str::from_utf8(&header[6 + i * 8..][..8]).ok();
+            hex.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(AxError::InvalidData)
+        };
+        Ok(Self {
+            ino: field(0)?,
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            file_size: field(6)? as usize,
+            name_size: field(11)? as usize,
+        })
+    }
+}
+
+/// Returns `offset` rounded up to a multiple of 4, where names and data are
+/// aligned to.
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// Unpacks the cpio `archive` into the root directory, and returns the number
+/// of entries created.
+///
+/// Directories, regular files, symbolic links and hard links are created with
+/// their permissions, owners and modification times. Other types of files are
+/// skipped. Existing directories, e.g. mount points, are kept.
+pub(crate) fn unpack(archive: &[u8]) -> AxResult<usize> {
+    // the first path of each file with hard links
+    let mut links = BTreeMap::<u32, String>::new();
+    let mut count = 0;
+    let mut offset = 0;
+    loop {
+        let header = Header::parse(&archive[offset.min(archive.len())..])?;
+        let name_start = offset + HEADER_LEN;
+        let data_start = align4(name_start + header.name_size);
+        let data_end = data_start + header.file_size;
+        if header.name_size == 0 || data_end > archive.len() {
+            return ax_err!(InvalidData, "truncated cpio archive");
+        }
+        // the name ends with a NUL
+        let name = &archive[name_start..name_start + header.name_size - 1];
+        let name = core::str::from_utf8(name).map_err(|_| AxError::InvalidData)?;
+        if name == TRAILER {
+            return Ok(count);
+        }
+        offset = align4(data_end);
+
+        let path = name.trim_start_matches("./").trim_start_matches('/');
+        let path = format!("/{}", path.trim_end_matches('/'));
+        let data = &archive[data_start..data_end];
+        match unpack_entry(&path, &header, data, &mut links) {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(e) => warn!("initramfs: failed to unpack {}: {:?}", path, e),
+        }
+    }
+}
+
+/// Creates the file at `path`, returns whether it is created.
+fn unpack_entry(
+    path: &str,
+    header: &Header,
+    data: &[u8],
+    links: &mut BTreeMap<u32, String>,
+) -> AxResult<bool> {
+    let perm = FilePerm::from_bits_truncate(header.mode as u16);
+    match header.mode & S_IFMT {
+        S_IFDIR => match root::create_dir(None, path) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        },
+        S_IFREG if header.nlink > 1 && links.contains_key(&header.ino) => {
+            // the data is stored with the last link
+            root::link(&links[&header.ino], path)?;
+            if !data.is_empty() {
+                let node = root::lookup(None, path)?;
+                node.truncate(0)?;
+                node.write_at(0, data)?;
+            }
+        }
+        S_IFREG => {
+            let node = root::create_file(None, path, perm)?;
+            node.write_at(0, data)?;
+            if header.nlink > 1 {
+                links.insert(header.ino, path.into());
+            }
+        }
+        S_IFLNK => {
+            let target = core::str::from_utf8(data).map_err(|_| AxError::InvalidData)?;
+            root::symlink(target, path)?;
+            root::set_owner(path, Some(header.uid), Some(header.gid), false)?;
+            return Ok(true);
+        }
+        _ => {
+            warn!("initramfs: skip {} of mode {:#o}", path, header.mode);
+            return Ok(false);
+        }
+    }
+    let mtime = Some(Duration::from_secs(header.mtime as u64));
+    root::set_perm(path, perm)?;
+    root::set_owner(path, Some(header.uid), Some(header.gid), true)?;
+    root::set_times(path, mtime, mtime)?;
+    Ok(true)
+}
@@ -6,15 +6,63 @@
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
+//! - `ext2`: Support ext2, ext3 and ext4 filesystems. The main filesystem is
+//!    mounted as ext2/3/4 if the disk contains one, otherwise as FAT. They can
+//!    also be mounted at runtime with type `ext2`, `ext3` or `ext4`. Journaling
+//!    is not supported. This feature is **disabled** by default.
+//! - `9p`: Support directories shared by the host through 9P transports like
+//!    QEMU `virtio-9p`, which can be mounted at runtime with type `9p` and the
+//!    mount tag of the device as the source, see [`register_9p_devices`].
+//!    This feature is **disabled** by default.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!    **enabled** by default.
-//! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
-//!    **enabled** by default.
+//! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`, limited to half
+//!    of the memory unless mounted with other limits by
+//!    [`api::mount_with_options`]. This feature is **enabled** by default.
+//! - `procfs`: Mount a [pseudo filesystem](pseudofs) on `/proc`, whose files
+//!    are generated from kernel state. Other modules add their files with
+//!    [`procfs_root`]. This feature is **enabled** by default.
+//! - `sysfs`: Mount a [pseudo filesystem](pseudofs) on `/sys`, to which other
+//!    modules add files about devices and the system with [`sysfs_root`].
+//!    This feature is **enabled** by default.
+//! - `overlayfs`: Support [overlay filesystems](overlayfs) of type
+//!    `overlay`, which keep the changes to a read-only directory in memory.
+//!    This feature is **enabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
 //!    by default, but it will override other filesystem selection features if
 //!    both are enabled.
+//! - `initramfs`: Allow the root to be a RAM filesystem unpacked from a cpio
+//!    archive, see [`init_filesystems_initramfs`]. This feature is
+//!    **disabled** by default.
+//! - `rootfs-ro`: Mount the main filesystem read-only. It can be remounted
+//!    writable with [`api::remount`]. This feature is **disabled** by default.
+//! - `rootfs-overlay`: Mount an overlay of the main filesystem on `/`, so the
+//!    main filesystem is never written and all changes are lost on reboot.
+//!    This feature is **disabled** by default.
+//!
+//! Other block devices are registered as `blk1`, `blk2`, ... and can be
+//! mounted at runtime with [`api::mount`]. Partitions in the MBR or GPT
+//! partition table of a device are registered as `blk0p1`, `blk0p2`, ... The
+//! main filesystem is on the first Linux filesystem partition of `blk0` (or
+//! its first partition other than swap), or on the whole `blk0` if it is not
+//! partitioned.
+//! Devices that are not mounted can be formatted as FAT with
+//! [`api::mkfs_vfat`], and FAT filesystems on them checked and repaired with
+//! [`api::fsck_vfat`].
+//!
+//! Files are accessed as the user set by [`api::set_current_uid`], root by
+//! default, and their permission bits are checked against it. Owners and
+//! timestamps are only recorded by [`axfs_ramfs`] filesystems, files of other
+//! filesystems are owned by root.
+//!
+//! Changes to files and directories can be watched with a
+//! [`notify::Watcher`].
+//!
+//! Blocks of all devices are cached in memory. Written blocks are written back
+//! within [`WRITEBACK_INTERVAL`], on [`api::sync`] and when the filesystem is
+//! unmounted.
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
@@ -26,21 +74,90 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
 mod dev;
 mod fs;
+#[cfg(feature = "initramfs")]
+mod initramfs;
 mod mounts;
+mod partition;
+mod perm;
 mod root;
+mod xattr;
 
 pub mod api;
 pub mod fops;
+pub mod notify;
+
+pub use self::cache::WRITEBACK_INTERVAL;
+#[cfg(feature = "overlayfs")]
+pub use self::fs::overlayfs;
+pub use self::fs::pseudofs;
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
+/// Returns the root directory of the procfs mounted on `/proc`, to which
+/// other modules add files about their state.
+///
+/// Returns `None` if the filesystems are not initialized.
+#[cfg(feature = "procfs")]
+pub fn procfs_root() -> Option<alloc::sync::Arc<pseudofs::PseudoDir>> {
+    mounts::PROCFS_ROOT.get().cloned()
+}
+
+/// Returns the root directory of the sysfs mounted on `/sys`, to which other
+/// modules add files about devices and the system.
+///
+/// Returns `None` if the filesystems are not initialized.
+#[cfg(feature = "sysfs")]
+pub fn sysfs_root() -> Option<alloc::sync::Arc<pseudofs::PseudoDir>> {
+    mounts::SYSFS_ROOT.get().cloned()
+}
+
 /// Initializes filesystems by block devices.
-pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
+pub fn init_filesystems(blk_devs: AxDeviceContainer<AxBlockDevice>) {
+    info!("Initialize filesystems...");
+    register_devices(blk_devs);
+    let root = self::dev::root_device().expect("No block device found!");
+    let disk = self::dev::Disk::open(&root).expect("failed to open the root device");
+    info!(
+        "  use block device {} as the root filesystem",
+        disk.device_name()
+    );
+    self::root::init_rootfs(disk);
+}
+
+/// Initializes filesystems with a RAM filesystem as the root, populated from
+/// `archive`, a cpio archive in the `newc` format.
+///
+/// The block devices are not used by the root filesystem, but can be mounted
+/// later with [`api::mount`].
+#[cfg(feature = "initramfs")]
+pub fn init_filesystems_initramfs(blk_devs: AxDeviceContainer<AxBlockDevice>, archive: &[u8]) {
     info!("Initialize filesystems...");
+    register_devices(blk_devs);
+    info!(
+        "  use the initramfs of {} bytes as the root filesystem",
+        archive.len()
+    );
+    self::root::init_rootfs_initramfs(archive);
+}
+
+/// Registers the 9P transports, whose shared directories can then be mounted
+/// with [`api::mount`] as type `9p`, with the mount tag of the device as the
+/// source.
+#[cfg(feature = "9p")]
+pub fn register_9p_devices(mut p9_devs: AxDeviceContainer<AxP9Device>) {
+    while let Some(dev) = p9_devs.take_one() {
+        self::fs::v9fs::register_device(dev);
+    }
+}
+
+fn register_devices(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
+    #[cfg(feature = "ramfs")]
+    axfs_ramfs::set_clock(axhal::time::wall_time);
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    while let Some(dev) = blk_devs.take_one() {
+        self::dev::register_device(dev);
+    }
 }
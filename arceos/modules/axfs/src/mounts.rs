@@ -1,8 +1,47 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{ax_err, AxError, AxResult};
+use axfs_vfs::VfsOps;
 
 use crate::fs;
 
+/// Opens the main filesystem on `disk`, detecting its type.
+///
+/// An ext2/3/4 filesystem is used if found, otherwise the disk is treated as FAT.
+#[cfg(all(any(feature = "fatfs", feature = "ext2"), not(feature = "myfs")))]
+pub(crate) fn rootfs(disk: crate::dev::Disk) -> (&'static str, Arc<dyn VfsOps>) {
+    #[cfg(feature = "ext2")]
+    let disk = {
+        let mut disk = disk;
+        if fs::ext2::Ext2FileSystem::probe(&mut disk) {
+            let fs = fs::ext2::Ext2FileSystem::open(disk).expect("failed to open ext2 filesystem");
+            return (fs.fstype(), Arc::new(fs));
+        }
+        disk
+    };
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "fatfs")] {
+            use lazyinit::LazyInit;
+            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
+            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
+            FAT_FS.init();
+            ("vfat", FAT_FS.clone())
+        } else {
+            panic!("no supported filesystem found on {}", disk.device_name());
+        }
+    }
+}
+
+/// Creates an overlay of the main filesystem, which is then never written.
+#[cfg(feature = "rootfs-overlay")]
+pub(crate) fn overlay_rootfs(main_fs: Arc<dyn VfsOps>) -> Arc<dyn VfsOps> {
+    let lower = main_fs.root_dir();
+    let guard = alloc::boxed::Box::new(main_fs);
+    let overlay = fs::overlayfs::OverlayFileSystem::new(lower, Some(guard))
+        .expect("failed to create the overlay of the root filesystem");
+    Arc::new(overlay)
+}
+
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     let null = fs::devfs::NullDev;
@@ -16,65 +55,160 @@ pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     Arc::new(devfs)
 }
 
+/// Returns the default limits of a RAM filesystem, half of the physical
+/// memory and an inode for each page of it like a Linux tmpfs, so that
+/// filling it returns `ENOSPC` before the heap is exhausted.
+///
+/// There is no limit if the size of the memory is unknown.
+#[cfg(feature = "ramfs")]
+pub(crate) fn default_ramfs_limits() -> axfs_ramfs::RamLimits {
+    let max_bytes = axconfig::PHYS_MEMORY_SIZE as u64 / 2;
+    if max_bytes == 0 {
+        return axfs_ramfs::RamLimits::default();
+    }
+    axfs_ramfs::RamLimits {
+        max_bytes: Some(max_bytes),
+        max_nodes: Some(max_bytes / axfs_ramfs::PAGE_SIZE as u64),
+    }
+}
+
+/// Parses the mount options of a RAM filesystem, `size=<bytes>` and
+/// `nr_inodes=<count>` separated by commas like a Linux tmpfs, where the
+/// numbers may end with `k`, `m` or `g` and `0` means unlimited.
+#[cfg(feature = "ramfs")]
+fn parse_ramfs_options(options: &str) -> AxResult<axfs_ramfs::RamLimits> {
+    let mut limits = default_ramfs_limits();
+    for opt in options.split(',').filter(|opt| !opt.is_empty()) {
+        let (name, value) = opt.split_once('=').unwrap_or((opt, ""));
+        let (num, shift) = match value.as_bytes().last() {
+            Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+            Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+            Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+        let value = num
+            .parse::<u64>()
+            .ok()
+            .and_then(|num| num.checked_mul(1 << shift))
+            .ok_or(AxError::InvalidInput)?;
+        let value = (value != 0).then_some(value);
+        match name {
+            "size" => limits.max_bytes = value,
+            "nr_inodes" => limits.max_nodes = value,
+            _ => return ax_err!(InvalidInput, "unknown ramfs option"),
+        }
+    }
+    Ok(limits)
+}
+
+/// Creates a RAM filesystem with `limits`, whose root directory can be
+/// written by everyone like `/tmp`.
 #[cfg(feature = "ramfs")]
-pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
-    Arc::new(fs::ramfs::RamFileSystem::new())
+pub(crate) fn ramfs(limits: axfs_ramfs::RamLimits) -> Arc<fs::ramfs::RamFileSystem> {
+    let ramfs = fs::ramfs::RamFileSystem::with_limits(limits);
+    let perm = crate::fops::FilePerm::from_bits_truncate(0o777);
+    ramfs.root_dir_node().attrs().set_perm(perm);
+    Arc::new(ramfs)
 }
 
+/// The root directory of the procfs, see [`procfs_root`](crate::procfs_root).
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
-
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
-
-    // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
-
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
-
-    Ok(Arc::new(procfs))
+pub(crate) static PROCFS_ROOT: lazyinit::LazyInit<Arc<fs::pseudofs::PseudoDir>> =
+    lazyinit::LazyInit::new();
+
+/// Creates the procfs with the files about filesystems. Files about other
+/// kernel state are added by other modules through
+/// [`procfs_root`](crate::procfs_root).
+#[cfg(feature = "procfs")]
+pub(crate) fn procfs() -> Arc<fs::pseudofs::PseudoFileSystem> {
+    let procfs = fs::pseudofs::PseudoFileSystem::new();
+    let proc_root = procfs.root_dir_node();
+
+    // The mount table in the format of Linux.
+    proc_root.add_file("mounts", || {
+        crate::root::mount_infos()
+            .iter()
+            .map(|m| {
+                let opts = m.flags.options();
+                alloc::format!("{} {} {} {} 0 0\n", m.source, m.target, m.fstype, opts)
+            })
+            .collect()
+    });
+
+    // Memory is never overcommitted.
+    proc_root
+        .dir("sys/vm")
+        .add_file("overcommit_memory", || "0\n".into());
+
+    PROCFS_ROOT.init_once(proc_root);
+    Arc::new(procfs)
 }
 
+/// The root directory of the sysfs, see [`sysfs_root`](crate::sysfs_root).
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
-
-    // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
-
-    // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
-
-    Ok(Arc::new(sysfs))
+pub(crate) static SYSFS_ROOT: lazyinit::LazyInit<Arc<fs::pseudofs::PseudoDir>> =
+    lazyinit::LazyInit::new();
+
+/// Creates the sysfs with the memory management settings. Devices and other
+/// system information are added by other modules through
+/// [`sysfs_root`](crate::sysfs_root).
+#[cfg(feature = "sysfs")]
+pub(crate) fn sysfs() -> Arc<fs::pseudofs::PseudoFileSystem> {
+    let sysfs = fs::pseudofs::PseudoFileSystem::new();
+    let sys_root = sysfs.root_dir_node();
+
+    // The transparent huge page setting in the format of Linux.
+    sys_root
+        .dir("kernel/mm/transparent_hugepage")
+        .add_file("enabled", || "always [madvise] never\n".into());
+
+    SYSFS_ROOT.init_once(sys_root);
+    Arc::new(sysfs)
+}
+
+/// Creates a filesystem of type `fstype` on `source` to be mounted at runtime.
+///
+/// Returns the canonical name of the filesystem type along with it. The
+/// source of an `overlay` is the path of its lower directory, which is kept
+/// busy until the overlay is unmounted. The source of a `9p` filesystem is
+/// the mount tag of its device. `options` are only used by `ramfs`, see
+/// [`parse_ramfs_options`], and ignored by other filesystems.
+pub(crate) fn new_fs(
+    source: &str,
+    fstype: &str,
+    options: &str,
+) -> AxResult<(&'static str, Arc<dyn VfsOps>)> {
+    let _ = source;
+    if !options.is_empty() && !matches!(fstype, "ramfs" | "tmpfs") {
+        warn!("mount options {:?} of {} are ignored", options, fstype);
+    }
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => Ok(("ramfs", ramfs(parse_ramfs_options(options)?))),
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" | "fat" | "msdos" => {
+            let disk = crate::dev::Disk::open(source)?;
+            let fs = Arc::new(fs::fatfs::FatFileSystem::open(disk)?);
+            // SAFETY: the nodes of a FAT filesystem borrow the filesystem
+            // itself. A mount point can only be detached when no file or
+            // directory in it is opened, so they never outlive `fs`.
+            unsafe { (*Arc::as_ptr(&fs)).init() };
+            Ok(("vfat", fs))
+        }
+        #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => {
+            let fs = fs::ext2::Ext2FileSystem::open(crate::dev::Disk::open(source)?)?;
+            Ok((fs.fstype(), Arc::new(fs)))
+        }
+        #[cfg(feature = "9p")]
+        "9p" => Ok(("9p", Arc::new(fs::v9fs::V9fsFileSystem::open(source)?))),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => {
+            let (lower, mount) = crate::root::lookup_lower_dir(source)?;
+            let guard = alloc::boxed::Box::new(mount);
+            let fs = fs::overlayfs::OverlayFileSystem::new(lower, Some(guard))?;
+            Ok(("overlay", Arc::new(fs)))
+        }
+        _ => ax_err!(Unsupported, "unknown filesystem type"),
+    }
 }
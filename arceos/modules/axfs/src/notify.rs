@@ -0,0 +1,321 @@
+//! Notification of changes to files and directories, like Linux `inotify`.
+//!
+//! A [`Watcher`] watches files and directories by path, and queues an event
+//! each time one of them is changed. A watched directory also gets the events
+//! of the entries in it, along with their names. Watches follow the watched
+//! files when they are renamed, and are removed when they are deleted.
+//!
+//! Only the changes made through axfs are reported, except the ones made at
+//! paths relative to a [`Directory`](crate::fops::Directory) other than the
+//! current directory.
+
+use alloc::{collections::VecDeque, format, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+use axerrno::{ax_err, AxResult};
+use axsync::Mutex;
+
+/// The maximum number of events queued in a [`Watcher`], after which new
+/// events are dropped and an [`OVERFLOW`](WatchMask::OVERFLOW) event is
+/// queued.
+pub const MAX_QUEUED_EVENTS: usize = 16384;
+
+bitflags::bitflags! {
+    /// Types of events, with the same values as Linux `IN_*` flags.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct WatchMask: u32 {
+        /// The file is written or truncated.
+        const MODIFY = 0x2;
+        /// The permissions, owner or timestamps of the file are changed.
+        const ATTRIB = 0x4;
+        /// An entry is renamed from the directory.
+        const MOVED_FROM = 0x40;
+        /// An entry is renamed to the directory.
+        const MOVED_TO = 0x80;
+        /// An entry is created in the directory.
+        const CREATE = 0x100;
+        /// An entry is removed from the directory.
+        const DELETE = 0x200;
+        /// The watched file itself is removed.
+        const DELETE_SELF = 0x400;
+        /// The watched file itself is renamed.
+        const MOVE_SELF = 0x800;
+
+        /// The event queue overflowed, so events are lost.
+        const OVERFLOW = 0x4000;
+        /// The watch is removed, explicitly or because the file is removed.
+        const IGNORED = 0x8000;
+        /// The subject of the event is a directory.
+        const ISDIR = 0x4000_0000;
+
+        /// All the events that can be watched.
+        const ALL_EVENTS = 0xfc6;
+    }
+}
+
+/// An event queued in a [`Watcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    /// The watch descriptor returned by [`Watcher::add_watch`], or `-1` for
+    /// [`OVERFLOW`](WatchMask::OVERFLOW).
+    pub wd: i32,
+    /// The type of the event.
+    pub mask: WatchMask,
+    /// Connects the [`MOVED_FROM`](WatchMask::MOVED_FROM) and
+    /// [`MOVED_TO`](WatchMask::MOVED_TO) events of a rename, zero for other
+    /// events.
+    pub cookie: u32,
+    /// The name of the entry, for events of the entries in a watched
+    /// directory.
+    pub name: Option<String>,
+}
+
+struct EventQueue {
+    events: Mutex<VecDeque<WatchEvent>>,
+    next_wd: AtomicI32,
+}
+
+impl EventQueue {
+    fn push(&self, event: WatchEvent) {
+        let mut events = self.events.lock();
+        if events.back() == Some(&event) {
+            return; // coalesce identical events, like Linux
+        }
+        if events.len() + 1 >= MAX_QUEUED_EVENTS {
+            let overflow = WatchEvent {
+                wd: -1,
+                mask: WatchMask::OVERFLOW,
+                cookie: 0,
+                name: None,
+            };
+            if events.back() != Some(&overflow) {
+                events.push_back(overflow);
+            }
+            return;
+        }
+        events.push_back(event);
+    }
+}
+
+struct Watch {
+    wd: i32,
+    /// The absolute path of the watched file.
+    path: String,
+    mask: WatchMask,
+    queue: Arc<EventQueue>,
+}
+
+impl Watch {
+    fn send(&self, mask: WatchMask, cookie: u32, name: Option<&str>) {
+        let kind = mask & WatchMask::ALL_EVENTS;
+        if kind.is_empty() || self.mask.intersects(kind) {
+            self.queue.push(WatchEvent {
+                wd: self.wd,
+                mask,
+                cookie,
+                name: name.map(String::from),
+            });
+        }
+    }
+}
+
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// A queue of events of the files and directories it watches.
+///
+/// All its watches are removed when it is dropped.
+pub struct Watcher {
+    queue: Arc<EventQueue>,
+}
+
+impl Watcher {
+    /// Creates a watcher without any watch.
+    pub fn new() -> Self {
+        Self {
+            queue: Arc::new(EventQueue {
+                events: Mutex::new(VecDeque::new()),
+                next_wd: AtomicI32::new(1),
+            }),
+        }
+    }
+
+    /// Watches the file or directory at `path` for the events in `mask`, and
+    /// returns the watch descriptor in the events of it.
+    ///
+    /// If the file is already watched by this watcher, the mask of the watch
+    /// is replaced and the same watch descriptor is returned.
+    pub fn add_watch(&self, path: &str, mask: WatchMask) -> AxResult<i32> {
+        let mask = mask & WatchMask::ALL_EVENTS;
+        if mask.is_empty() {
+            return ax_err!(InvalidInput);
+        }
+        let path = &crate::root::resolve(None, path, true)?;
+        crate::root::lookup(None, path)?;
+        let path = normalize(crate::root::absolute_path(path)?);
+
+        let mut watches = WATCHES.lock();
+        if let Some(watch) = watches
+            .iter_mut()
+            .find(|w| Arc::ptr_eq(&w.queue, &self.queue) && w.path == path)
+        {
+            watch.mask = mask;
+            return Ok(watch.wd);
+        }
+        let wd = self.queue.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            wd,
+            path,
+            mask,
+            queue: self.queue.clone(),
+        });
+        Ok(wd)
+    }
+
+    /// Removes the watch `wd`, and queues an [`IGNORED`](WatchMask::IGNORED)
+    /// event of it.
+    pub fn remove_watch(&self, wd: i32) -> AxResult {
+        let mut watches = WATCHES.lock();
+        let Some(idx) = watches
+            .iter()
+            .position(|w| Arc::ptr_eq(&w.queue, &self.queue) && w.wd == wd)
+        else {
+            return ax_err!(InvalidInput, "no such watch");
+        };
+        watches.swap_remove(idx).send(WatchMask::IGNORED, 0, None);
+        Ok(())
+    }
+
+    /// Returns whether any event is queued.
+    pub fn has_events(&self) -> bool {
+        !self.queue.events.lock().is_empty()
+    }
+
+    /// Takes the queued events in order, as long as `take` returns `true` for
+    /// them.
+    pub fn take_events(&self, mut take: impl FnMut(&WatchEvent) -> bool) -> Vec<WatchEvent> {
+        let mut events = self.queue.events.lock();
+        let mut taken = Vec::new();
+        while let Some(event) = events.front() {
+            if !take(event) {
+                break;
+            }
+            taken.extend(events.pop_front());
+        }
+        taken
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        WATCHES
+            .lock()
+            .retain(|w| !Arc::ptr_eq(&w.queue, &self.queue));
+    }
+}
+
+/// Removes the trailing slash of the absolute path `path`.
+fn normalize(mut path: String) -> String {
+    while path.len() > 1 && path.ends_with('/') {
+        path.pop();
+    }
+    path
+}
+
+/// Splits the absolute path `path` into its parent and its name.
+fn split(path: &str) -> Option<(&str, &str)> {
+    match path.rsplit_once('/')? {
+        (_, "") => None,
+        ("", name) => Some(("/", name)),
+        (parent, name) => Some((parent, name)),
+    }
+}
+
+/// Whether `path` is `prefix` or in the directory `prefix`.
+fn is_under(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Returns whether any file or directory is watched, so that the callers of
+/// [`notify`] can skip working out the event when none is.
+pub(crate) fn has_watches() -> bool {
+    !WATCHES.lock().is_empty()
+}
+
+/// Reports the event `mask` of the file at the absolute path `path`.
+///
+/// Events of entries are sent to the watches of the parent directory, and
+/// [`MODIFY`](WatchMask::MODIFY) and [`ATTRIB`](WatchMask::ATTRIB) are sent
+/// to the watches of the file too. A [`DELETE`](WatchMask::DELETE) removes
+/// the watches of the file after sending them
+/// [`DELETE_SELF`](WatchMask::DELETE_SELF).
+pub(crate) fn notify(path: &str, mask: WatchMask, is_dir: bool) {
+    let mut watches = WATCHES.lock();
+    if watches.is_empty() {
+        return;
+    }
+    let path = &normalize(path.into());
+    let dir_flag = if is_dir {
+        WatchMask::ISDIR
+    } else {
+        WatchMask::empty()
+    };
+    if let Some((parent, name)) = split(path) {
+        for w in watches.iter().filter(|w| w.path == parent) {
+            w.send(mask | dir_flag, 0, Some(name));
+        }
+    }
+    if mask.intersects(WatchMask::MODIFY | WatchMask::ATTRIB) {
+        for w in watches.iter().filter(|w| &w.path == path) {
+            w.send(mask | dir_flag, 0, None);
+        }
+    } else if mask.contains(WatchMask::DELETE) {
+        watches.retain(|w| {
+            if &w.path != path {
+                return true;
+            }
+            w.send(WatchMask::DELETE_SELF, 0, None);
+            w.send(WatchMask::IGNORED, 0, None);
+            false
+        });
+    }
+}
+
+/// Reports the rename of the file at the absolute path `old` to `new`, and
+/// moves the watches of it and of the files in it to the new paths.
+pub(crate) fn notify_rename(old: &str, new: &str, is_dir: bool) {
+    let mut watches = WATCHES.lock();
+    if watches.is_empty() {
+        return;
+    }
+    let (old, new) = (&normalize(old.into()), &normalize(new.into()));
+    let dir_flag = if is_dir {
+        WatchMask::ISDIR
+    } else {
+        WatchMask::empty()
+    };
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    if let (Some((old_parent, old_name)), Some((new_parent, new_name))) = (split(old), split(new)) {
+        for w in watches.iter().filter(|w| w.path == old_parent) {
+            w.send(WatchMask::MOVED_FROM | dir_flag, cookie, Some(old_name));
+        }
+        for w in watches.iter().filter(|w| w.path == new_parent) {
+            w.send(WatchMask::MOVED_TO | dir_flag, cookie, Some(new_name));
+        }
+    }
+    for w in watches.iter_mut().filter(|w| is_under(&w.path, old)) {
+        if &w.path == old {
+            w.send(WatchMask::MOVE_SELF, 0, None);
+        }
+        w.path = normalize(format!("{}{}", new, &w.path[old.len()..]));
+    }
+}
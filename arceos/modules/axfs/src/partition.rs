@@ -0,0 +1,242 @@
+//! MBR and GPT partition tables.
+//!
+//! Each partition found on a block device is registered as another block
+//! device covering a range of it, e.g. `blk0p1`, see [`crate::dev`].
+
+use alloc::{format, string::String, vec::Vec};
+
+use axdriver::prelude::*;
+
+const SECTOR_SIZE: usize = 512;
+
+/// The MBR system IDs of extended partitions, which contain logical ones.
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// The MBR system ID of the protective partition of a GPT disk.
+const MBR_GPT_PROTECTIVE: u8 = 0xee;
+
+/// The type GUID `0FC63DAF-8483-4772-8E79-3D69D8477DE4` in the on-disk order.
+const GPT_LINUX_FS: [u8; 16] = [
+    0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4,
+];
+/// The type GUID `0657FD6D-A4AB-43C4-84E5-0933C84B4F4F` in the on-disk order.
+const GPT_LINUX_SWAP: [u8; 16] = [
+    0x6d, 0xfd, 0x57, 0x06, 0xab, 0xa4, 0xc4, 0x43, 0x84, 0xe5, 0x09, 0x33, 0xc8, 0x4b, 0x4f, 0x4f,
+];
+
+/// The maximum number of logical partitions in an extended partition, to
+/// stop at a loop in the chain.
+const MAX_LOGICAL: usize = 64;
+/// The maximum number of GPT entries read.
+const MAX_GPT_ENTRIES: u32 = 256;
+
+/// The type of a partition in the partition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionType {
+    /// The system ID of an MBR partition.
+    Mbr(u8),
+    /// The type GUID of a GPT partition, in the on-disk order.
+    Gpt([u8; 16]),
+}
+
+impl PartitionType {
+    /// Whether it is a Linux filesystem partition.
+    pub fn is_linux_fs(&self) -> bool {
+        *self == Self::Mbr(0x83) || *self == Self::Gpt(GPT_LINUX_FS)
+    }
+
+    /// Whether it is a Linux swap partition.
+    pub fn is_swap(&self) -> bool {
+        *self == Self::Mbr(0x82) || *self == Self::Gpt(GPT_LINUX_SWAP)
+    }
+}
+
+/// A partition of a block device.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    /// The partition number, starting from 1. Logical partitions in an MBR
+    /// extended partition start from 5, as in Linux.
+    pub index: usize,
+    /// The first sector on the device.
+    pub start: u64,
+    /// The number of sectors.
+    pub num_blocks: u64,
+    /// The partition type.
+    pub ty: PartitionType,
+    /// The name of a GPT partition, `PARTLABEL` in Linux.
+    pub label: Option<String>,
+    /// `PARTUUID` in Linux: the unique GUID of a GPT partition, or the disk
+    /// signature followed by the partition number for MBR, e.g.
+    /// `12345678-01`.
+    pub uuid: String,
+}
+
+struct MbrEntry {
+    status: u8,
+    ty: u8,
+    start: u64,
+    len: u64,
+}
+
+impl MbrEntry {
+    fn parse(sector: &[u8], i: usize) -> Self {
+        let entry = &sector[446 + i * 16..][..16];
+        Self {
+            status: entry[0],
+            ty: entry[4],
+            start: u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64,
+            len: u32::from_le_bytes(entry[12..16].try_into().unwrap()) as u64,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ty == 0 || self.len == 0
+    }
+}
+
+/// Reads the partition table of a device with `num_blocks` sectors, where
+/// `read` reads a sector.
+///
+/// Returns no partition if the device is not partitioned, e.g. a filesystem
+/// occupies the whole device.
+pub(crate) fn read_partitions<R>(num_blocks: u64, mut read: R) -> DevResult<Vec<Partition>>
+where
+    R: FnMut(u64, &mut [u8]) -> DevResult,
+{
+    let mut mbr = [0; SECTOR_SIZE];
+    read(0, &mut mbr)?;
+    if mbr[510..] != [0x55, 0xaa] || is_fat_boot_sector(&mbr) {
+        return Ok(Vec::new());
+    }
+    let entries: Vec<_> = (0..4).map(|i| MbrEntry::parse(&mbr, i)).collect();
+    // the protective partition may cover more than the device
+    if entries.iter().any(|e| e.ty == MBR_GPT_PROTECTIVE) {
+        return read_gpt(num_blocks, read);
+    }
+    let valid = entries.iter().all(|e| {
+        (e.status == 0 || e.status == 0x80)
+            && (e.is_empty() || (e.start > 0 && e.start + e.len <= num_blocks))
+    });
+    if !valid || entries.iter().all(MbrEntry::is_empty) {
+        return Ok(Vec::new());
+    }
+
+    let signature = u32::from_le_bytes(mbr[440..444].try_into().unwrap());
+    let partition = |index: usize, start: u64, e: &MbrEntry| Partition {
+        index,
+        start,
+        num_blocks: e.len,
+        ty: PartitionType::Mbr(e.ty),
+        label: None,
+        uuid: format!("{:08x}-{:02x}", signature, index),
+    };
+    let mut partitions = Vec::new();
+    let mut extended = None;
+    for (i, e) in entries.iter().enumerate() {
+        if MBR_EXTENDED.contains(&e.ty) {
+            extended = extended.or(Some(e.start));
+        } else if !e.is_empty() {
+            partitions.push(partition(i + 1, e.start, e));
+        }
+    }
+
+    // Logical partitions are chained by the extended boot records, whose
+    // first entry is relative to the record and second to the extended one.
+    let Some(base) = extended else {
+        return Ok(partitions);
+    };
+    let mut ebr_start = base;
+    for index in 5..5 + MAX_LOGICAL {
+        let mut ebr = [0; SECTOR_SIZE];
+        read(ebr_start, &mut ebr)?;
+        if ebr[510..] != [0x55, 0xaa] {
+            break;
+        }
+        let (logical, next) = (MbrEntry::parse(&ebr, 0), MbrEntry::parse(&ebr, 1));
+        let start = ebr_start + logical.start;
+        if logical.is_empty() || start + logical.len > num_blocks {
+            break;
+        }
+        partitions.push(partition(index, start, &logical));
+        if !MBR_EXTENDED.contains(&next.ty) {
+            break;
+        }
+        ebr_start = base + next.start;
+    }
+    Ok(partitions)
+}
+
+/// Whether the sector is the boot sector of a FAT filesystem occupying the
+/// whole device, which also ends with the MBR signature.
+fn is_fat_boot_sector(sector: &[u8]) -> bool {
+    matches!(sector[0], 0xeb | 0xe9) && (sector[54..57] == *b"FAT" || sector[82..87] == *b"FAT32")
+}
+
+fn read_gpt<R>(num_blocks: u64, mut read: R) -> DevResult<Vec<Partition>>
+where
+    R: FnMut(u64, &mut [u8]) -> DevResult,
+{
+    let mut header = [0; SECTOR_SIZE];
+    read(1, &mut header)?;
+    if header[..8] != *b"EFI PART" {
+        warn!("invalid GPT header");
+        return Ok(Vec::new());
+    }
+    let entries_lba = u64::from_le_bytes(header[72..80].try_into().unwrap());
+    let num_entries = u32::from_le_bytes(header[80..84].try_into().unwrap());
+    let entry_size = u32::from_le_bytes(header[84..88].try_into().unwrap()) as usize;
+    if entry_size < 128 || SECTOR_SIZE % entry_size != 0 {
+        warn!("unsupported GPT entry size {}", entry_size);
+        return Ok(Vec::new());
+    }
+
+    let mut partitions = Vec::new();
+    let mut sector = [0; SECTOR_SIZE];
+    let mut sector_lba = None;
+    for i in 0..num_entries.min(MAX_GPT_ENTRIES) as usize {
+        let lba = entries_lba + (i * entry_size / SECTOR_SIZE) as u64;
+        if sector_lba != Some(lba) {
+            read(lba, &mut sector)?;
+            sector_lba = Some(lba);
+        }
+        let entry = &sector[i * entry_size % SECTOR_SIZE..][..entry_size];
+        let ty: [u8; 16] = entry[..16].try_into().unwrap();
+        let first = u64::from_le_bytes(entry[32..40].try_into().unwrap());
+        let last = u64::from_le_bytes(entry[40..48].try_into().unwrap());
+        if ty == [0; 16] || first == 0 || last < first || last >= num_blocks {
+            continue;
+        }
+        let name = entry[56..128]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0);
+        let label: String = char::decode_utf16(name)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        partitions.push(Partition {
+            index: i + 1,
+            start: first,
+            num_blocks: last - first + 1,
+            ty: PartitionType::Gpt(ty),
+            label: (!label.is_empty()).then_some(label),
+            uuid: guid_string(entry[16..32].try_into().unwrap()),
+        });
+    }
+    Ok(partitions)
+}
+
+/// Formats a GUID stored in the mixed-endian on-disk order.
+fn guid_string(guid: &[u8; 16]) -> String {
+    let mut s = format!(
+        "{:08x}-{:04x}-{:04x}-",
+        u32::from_le_bytes(guid[..4].try_into().unwrap()),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+    );
+    for (i, b) in guid[8..].iter().enumerate() {
+        if i == 2 {
+            s.push('-');
+        }
+        s += &format!("{:02x}", b);
+    }
+    s
+}
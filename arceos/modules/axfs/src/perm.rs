@@ -0,0 +1,230 @@
+//! Ownership of files and permission checks.
+//!
+//! The RAM filesystem and ext2 record owners and timestamps. Files of other
+//! filesystems are owned by root and their timestamps are zero. A file in an
+//! overlay has the metadata of the layer it lives in, and is copied up before
+//! its metadata is changed.
+//!
+//! Files are accessed with the IDs set by [`set_current_uid`] and
+//! [`set_current_gid`]. Root (uid 0) is treated as the owner of every file, so
+//! the owner bits still apply to it.
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsNodeRef;
+use cap_access::Cap;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+use crate::fops::{FileAttr, FileMeta, FilePerm};
+#[cfg(all(feature = "ext2", not(feature = "myfs")))]
+use crate::fs::ext2::Ext2Node;
+
+/// Returns the node holding the metadata of `node`, see
+/// [`overlayfs::real_node`](crate::fs::overlayfs::real_node).
+#[cfg(any(feature = "ramfs", all(feature = "ext2", not(feature = "myfs"))))]
+pub(crate) fn meta_node(node: &VfsNodeRef) -> VfsNodeRef {
+    #[cfg(feature = "overlayfs")]
+    return crate::fs::overlayfs::real_node(node);
+    #[cfg(not(feature = "overlayfs"))]
+    node.clone()
+}
+
+/// Returns the node holding the metadata of `node` to be changed, see
+/// [`overlayfs::upper_node`](crate::fs::overlayfs::upper_node).
+#[cfg(any(feature = "ramfs", all(feature = "ext2", not(feature = "myfs"))))]
+pub(crate) fn meta_node_mut(node: &VfsNodeRef) -> AxResult<VfsNodeRef> {
+    #[cfg(feature = "overlayfs")]
+    return crate::fs::overlayfs::upper_node(node);
+    #[cfg(not(feature = "overlayfs"))]
+    Ok(node.clone())
+}
+
+static CURRENT_UID: AtomicU32 = AtomicU32::new(0);
+static CURRENT_GID: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the user and group IDs that files are accessed with.
+pub(crate) fn current_ids() -> (u32, u32) {
+    (
+        CURRENT_UID.load(Ordering::Acquire),
+        CURRENT_GID.load(Ordering::Acquire),
+    )
+}
+
+pub(crate) fn set_current_uid(uid: u32) {
+    CURRENT_UID.store(uid, Ordering::Release);
+}
+
+pub(crate) fn set_current_gid(gid: u32) {
+    CURRENT_GID.store(gid, Ordering::Release);
+}
+
+/// Returns the owner and the group of `node`.
+#[allow(unused_variables)]
+fn owner_of(node: &VfsNodeRef) -> (u32, u32) {
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node(node)) {
+        let meta = attrs.get();
+        return (meta.uid, meta.gid);
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node(node).as_any().downcast_ref::<Ext2Node>() {
+        return node.owner().unwrap_or((0, 0));
+    }
+    (0, 0)
+}
+
+/// Returns the accesses that the permission bits of `node` grant to the
+/// current user.
+fn granted_cap(node: &VfsNodeRef, perm: FilePerm) -> Cap {
+    let (uid, gid) = current_ids();
+    let (owner, group) = owner_of(node);
+    let mode = perm.bits();
+    let bits = if uid == 0 || uid == owner {
+        mode >> 6
+    } else if gid == group {
+        mode >> 3
+    } else {
+        mode
+    };
+    let mut cap = Cap::empty();
+    if bits & 0o4 != 0 {
+        cap |= Cap::READ;
+    }
+    if bits & 0o2 != 0 {
+        cap |= Cap::WRITE;
+    }
+    if bits & 0o1 != 0 {
+        cap |= Cap::EXECUTE;
+    }
+    cap
+}
+
+/// Checks that the current user can access `node`, whose attributes are
+/// `attr`, with `cap`.
+pub(crate) fn check_access(node: &VfsNodeRef, attr: &FileAttr, cap: Cap) -> AxResult {
+    if granted_cap(node, attr.perm()).contains(cap) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Checks that the current user is root or the owner of `node`.
+fn check_owner(node: &VfsNodeRef) -> AxResult {
+    let uid = current_ids().0;
+    if uid == 0 || uid == owner_of(node).0 {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Returns the ownership, timestamps and number of hard links of `node`.
+#[allow(unused_variables)]
+pub(crate) fn meta_of(node: &VfsNodeRef) -> FileMeta {
+    let nlink = crate::root::nlink_of(node);
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node(node)) {
+        let meta = attrs.get();
+        return FileMeta {
+            nlink,
+            uid: meta.uid,
+            gid: meta.gid,
+            atime: meta.atime,
+            mtime: meta.mtime,
+            ctime: meta.ctime,
+        };
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node(node).as_any().downcast_ref::<Ext2Node>() {
+        if let Ok(meta) = node.meta() {
+            return meta;
+        }
+    }
+    FileMeta {
+        nlink,
+        ..Default::default()
+    }
+}
+
+/// Makes the current user the owner of the newly created `node` and sets its
+/// permissions to `perm`.
+#[allow(unused_variables)]
+pub(crate) fn init_node(node: &VfsNodeRef, perm: FilePerm) -> AxResult {
+    let (uid, gid) = current_ids();
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node(node)) {
+        attrs.set_owner(Some(uid), Some(gid));
+        attrs.set_perm(perm);
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node(node).as_any().downcast_ref::<Ext2Node>() {
+        node.set_owner(Some(uid), Some(gid))?;
+        node.set_perm(perm)?;
+    }
+    Ok(())
+}
+
+/// Changes the permissions of `node`, which only its owner and root can do.
+#[allow(unused_variables)]
+pub(crate) fn set_perm(node: &VfsNodeRef, perm: FilePerm) -> AxResult {
+    check_owner(node)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node_mut(node)?) {
+        attrs.set_perm(perm);
+        return Ok(());
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node_mut(node)?.as_any().downcast_ref::<Ext2Node>() {
+        return node.set_perm(perm);
+    }
+    ax_err!(Unsupported, "the filesystem does not support permissions")
+}
+
+/// Changes the owner and the group of `node`, which are kept if `None`.
+///
+/// Only root can change the owner. The owner can change the group to its own
+/// group.
+#[allow(unused_variables)]
+pub(crate) fn set_owner(node: &VfsNodeRef, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+    let (cur_uid, cur_gid) = current_ids();
+    let (owner, group) = owner_of(node);
+    if cur_uid != 0
+        && (cur_uid != owner
+            || uid.is_some_and(|uid| uid != owner)
+            || gid.is_some_and(|gid| gid != group && gid != cur_gid))
+    {
+        return ax_err!(PermissionDenied);
+    }
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node_mut(node)?) {
+        attrs.set_owner(uid, gid);
+        return Ok(());
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node_mut(node)?.as_any().downcast_ref::<Ext2Node>() {
+        return node.set_owner(uid, gid);
+    }
+    ax_err!(Unsupported, "the filesystem does not support owners")
+}
+
+/// Changes the access and modification times of `node`, which are kept if
+/// `None`. Only its owner and root can do it.
+#[allow(unused_variables)]
+pub(crate) fn set_times(
+    node: &VfsNodeRef,
+    atime: Option<Duration>,
+    mtime: Option<Duration>,
+) -> AxResult {
+    check_owner(node)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&meta_node_mut(node)?) {
+        attrs.set_times(atime, mtime);
+        return Ok(());
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = meta_node_mut(node)?.as_any().downcast_ref::<Ext2Node>() {
+        return node.set_times(atime, mtime);
+    }
+    ax_err!(Unsupported, "the filesystem does not support timestamps")
+}
@@ -2,32 +2,176 @@
 //!
 //! TODO: it doesn't work very well if the mount points have containment relationships.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axsync::Mutex;
+use cap_access::Cap;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
 use lazyinit::LazyInit;
 
-use crate::{api::FileType, fs, mounts};
+use crate::fops::{AllocMode, FileAttr, FileKey, FileMeta, FilePerm, XattrMode};
+use crate::notify::WatchMask;
+use crate::{api::FileType, mounts, perm, xattr};
+
+/// The maximum number of symbolic links followed when resolving a path.
+const MAX_SYMLINKS: usize = 40;
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
 static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
+/// The mount point of the current directory, keeps it from being unmounted.
+static CURRENT_MOUNT: Mutex<Option<Arc<MountPoint>>> = Mutex::new(None);
+
+bitflags::bitflags! {
+    /// Per-mount flags, with the same values as Linux `MS_*` flags.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct MountFlags: u32 {
+        /// Mount read-only, no file can be created, removed or written.
+        const RDONLY = 1;
+        /// Do not allow executables to be loaded from the filesystem.
+        const NOEXEC = 8;
+        /// Flush every write to the device before it returns.
+        const SYNC = 16;
+    }
+}
+
+impl MountFlags {
+    /// Formats the flags as the options field of `/proc/mounts`.
+    pub(crate) fn options(&self) -> String {
+        let mut opts = String::from(if self.contains(Self::RDONLY) {
+            "ro"
+        } else {
+            "rw"
+        });
+        for (flag, name) in [(Self::NOEXEC, ",noexec"), (Self::SYNC, ",sync")] {
+            if self.contains(flag) {
+                opts += name;
+            }
+        }
+        opts
+    }
+}
 
-struct MountPoint {
-    path: &'static str,
+/// A filesystem mounted on a directory.
+///
+/// Opened files and directories hold a reference to the mount point they
+/// live in, so a mount point is busy as long as anyone else refers to it.
+pub(crate) struct MountPoint {
+    path: String,
+    source: String,
+    fstype: &'static str,
+    flags: AtomicU32,
     fs: Arc<dyn VfsOps>,
 }
 
+/// An entry of the mount table, see [`mounts`](crate::api::mounts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// The device or pseudo source of the filesystem, e.g. `blk1` or `tmpfs`.
+    pub source: String,
+    /// The absolute path where the filesystem is mounted.
+    pub target: String,
+    /// The type of the filesystem, e.g. `vfat` or `ramfs`.
+    pub fstype: &'static str,
+    /// The mount flags.
+    pub flags: MountFlags,
+}
+
+/// The usage of a filesystem, see [`statfs`](crate::api::statfs).
+///
+/// The sizes are zero if they are unknown or unlimited, as for a RAM
+/// filesystem mounted without limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsStat {
+    /// The type of the filesystem, as in [`MountInfo`].
+    pub fstype: &'static str,
+    /// The mount flags.
+    pub flags: MountFlags,
+    /// The size of a block in bytes.
+    pub block_size: u64,
+    /// The number of blocks the filesystem can hold.
+    pub blocks: u64,
+    /// The number of free blocks.
+    pub blocks_free: u64,
+    /// The number of files the filesystem can hold.
+    pub files: u64,
+    /// The number of free files.
+    pub files_free: u64,
+}
+
 struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    main_fs: Arc<MountPoint>,
+    mounts: Mutex<Vec<Arc<MountPoint>>>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(
+        path: String,
+        source: &str,
+        fstype: &'static str,
+        flags: MountFlags,
+        fs: Arc<dyn VfsOps>,
+    ) -> Self {
+        Self {
+            path,
+            source: source.into(),
+            fstype,
+            flags: AtomicU32::new(flags.bits()),
+            fs,
+        }
+    }
+
+    pub fn flags(&self) -> MountFlags {
+        MountFlags::from_bits_truncate(self.flags.load(Ordering::Acquire))
+    }
+
+    /// Returns [`ReadOnlyFilesystem`](AxError::ReadOnlyFilesystem) if the
+    /// filesystem is mounted read-only.
+    pub fn check_writable(&self) -> AxResult {
+        if self.flags().contains(MountFlags::RDONLY) {
+            ax_err!(ReadOnlyFilesystem, "read-only filesystem")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns [`PermissionDenied`](AxError::PermissionDenied) if executables
+    /// cannot be loaded from the filesystem.
+    pub fn check_executable(&self) -> AxResult {
+        if self.flags().contains(MountFlags::NOEXEC) {
+            ax_err!(PermissionDenied, "filesystem is mounted noexec")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes the changes of `node` in the filesystem back to the device.
+    pub fn fsync(&self, node: &VfsNodeRef) -> AxResult {
+        match node.fsync() {
+            // the device files hold no data to write back, but do not support
+            // it
+            Err(AxError::InvalidInput) if self.fstype == "devfs" => Ok(()),
+            res => res,
+        }
+    }
+
+    fn info(&self) -> MountInfo {
+        MountInfo {
+            source: self.source.clone(),
+            target: self.path.clone(),
+            fstype: self.fstype,
+            flags: self.flags(),
+        }
+    }
+
+    /// Returns `true` if `path` (relative to `/`) is in this mount point.
+    fn contains(&self, path: &str) -> bool {
+        let mp = &self.path[1..]; // skip the first '/'
+        path.strip_prefix(mp)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
     }
 }
 
@@ -38,36 +182,136 @@ impl Drop for MountPoint {
 }
 
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub fn new(main_fs: MountPoint) -> Self {
         Self {
-            main_fs,
-            mounts: Vec::new(),
+            main_fs: Arc::new(main_fs),
+            mounts: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
+    pub fn mount(
+        self: &Arc<Self>,
+        path: &str,
+        source: &str,
+        fstype: &'static str,
+        flags: MountFlags,
+        fs: Arc<dyn VfsOps>,
+    ) -> AxResult {
+        let path = path.trim_end_matches('/');
+        if path.is_empty() {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
+        if self.contains(path) {
+            return ax_err!(InvalidInput, "mount point already exists");
+        }
+        // create the mount point in its parent filesystem if it does not exist
+        let mount_dir = match self.clone().lookup(path) {
+            Err(AxError::NotFound) => {
+                self.create(path, FileType::Dir)?;
+                self.clone().lookup(path)?
+            }
+            res => res?,
+        };
+        if !mount_dir.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        fs.mount(path, mount_dir)?;
+
+        let mut mounts = self.mounts.lock();
+        if mounts.iter().any(|mp| mp.path == path) {
             return ax_err!(InvalidInput, "mount point already exists");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        mounts.push(Arc::new(MountPoint::new(
+            path.into(),
+            source,
+            fstype,
+            flags,
+            fs,
+        )));
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    /// Detaches the filesystem mounted at `path`.
+    ///
+    /// Fails with [`ResourceBusy`](AxError::ResourceBusy) if any file or
+    /// directory in it is still opened, it contains the current directory, or
+    /// another filesystem is mounted inside it.
+    pub fn umount(&self, path: &str) -> AxResult {
+        let path = path.trim_end_matches('/');
+        if path.is_empty() {
+            return ax_err!(ResourceBusy, "cannot unmount root filesystem");
+        }
+        let mut mounts = self.mounts.lock();
+        let idx = mounts
+            .iter()
+            .position(|mp| mp.path == path)
+            .ok_or(AxError::InvalidInput)?;
+        if mounts
+            .iter()
+            .any(|mp| mp.path.len() > path.len() && mounts[idx].contains(&mp.path[1..]))
+        {
+            return ax_err!(ResourceBusy, "another filesystem is mounted inside");
+        }
+        // only the mount table refers to it, all new references are created
+        // with the table locked
+        if Arc::strong_count(&mounts[idx]) > 1 {
+            return ax_err!(ResourceBusy, "mount point is in use");
+        }
+        let mp = mounts.remove(idx);
+        drop(mounts);
+        drop(mp);
+        Ok(())
+    }
+
+    /// Changes the flags of the filesystem mounted at `path`.
+    pub fn remount(&self, path: &str, flags: MountFlags) -> AxResult {
+        let path = path.trim_end_matches('/');
+        let mp = if path.is_empty() {
+            self.main_fs.clone()
+        } else {
+            self.mounts
+                .lock()
+                .iter()
+                .find(|mp| mp.path == path)
+                .cloned()
+                .ok_or(AxError::InvalidInput)?
+        };
+        mp.flags.store(flags.bits(), Ordering::Release);
+        Ok(())
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        let path = path.trim_end_matches('/');
+        self.mounts.lock().iter().any(|mp| mp.path == path)
+    }
+
+    /// Returns the mount table, starting with the root filesystem.
+    pub fn mount_infos(&self) -> Vec<MountInfo> {
+        let mut infos = Vec::from([self.main_fs.info()]);
+        infos.extend(self.mounts.lock().iter().map(|mp| mp.info()));
+        infos
+    }
+
+    /// Finds the mount point that has the longest mounted path match.
+    fn mount_point_of(&self, path: &str) -> (Arc<MountPoint>, usize) {
+        let mut found: Option<&Arc<MountPoint>> = None;
+        let mut max_len = 0;
+
+        // TODO: more efficient, e.g. trie
+        let mounts = self.mounts.lock();
+        for mp in mounts.iter() {
+            if mp.contains(path) && mp.path.len() - 1 > max_len {
+                max_len = mp.path.len() - 1;
+                found = Some(mp);
+            }
+        }
+        match found {
+            Some(mp) => (mp.clone(), max_len),
+            None => (self.main_fs.clone(), 0), // not matched any mount point
+        }
     }
 
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
@@ -80,24 +324,8 @@ impl RootDirectory {
             return self.lookup_mounted_fs(rest, f);
         }
 
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
-        }
-
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+        let (mp, max_len) = self.mount_point_of(path);
+        f(mp.fs.clone(), &path[max_len..])
     }
 }
 
@@ -105,7 +333,7 @@ impl VfsNodeOps for RootDirectory {
     axfs_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+        self.main_fs.fs.root_dir().get_attr()
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -133,57 +361,128 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        // the destination is passed relative to the root of the filesystem
+        let dst_path = dst_path.trim_matches('/');
+        let (dst_mp, dst_len) = self.mount_point_of(dst_path);
+        let dst_rest = format!("/{}", dst_path[dst_len..].trim_start_matches('/'));
         self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
+            if rest_path.is_empty() || dst_rest == "/" {
                 ax_err!(PermissionDenied) // cannot rename mount points
+            } else if !Arc::ptr_eq(&fs, &dst_mp.fs) {
+                ax_err!(CrossesDevices)
             } else {
-                fs.root_dir().rename(rest_path, dst_path)
+                fs.root_dir().rename(rest_path, &dst_rest)
             }
         })
     }
 }
 
 pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+    let source = String::from(disk.device_name());
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+            let main_fs = crate::fs::myfs::new_myfs(disk);
+            let fstype = "myfs";
+        } else if #[cfg(any(feature = "fatfs", feature = "ext2"))] {
+            let (fstype, main_fs) = mounts::rootfs(disk);
         }
     }
+    #[cfg(feature = "rootfs-overlay")]
+    let (fstype, main_fs) = {
+        info!(
+            "  keep changes to the {} root filesystem in an overlay",
+            fstype
+        );
+        ("overlay", mounts::overlay_rootfs(main_fs))
+    };
+    init_root_dir(&source, fstype, main_fs);
+    set_root_flags();
+}
+
+/// Initializes the root directory with a RAM filesystem populated from the
+/// initramfs `archive`.
+#[cfg(feature = "initramfs")]
+pub(crate) fn init_rootfs_initramfs(archive: &[u8]) {
+    let limits = mounts::default_ramfs_limits();
+    let main_fs = Arc::new(crate::fs::ramfs::RamFileSystem::with_limits(limits));
+    init_root_dir("rootfs", "ramfs", main_fs);
+    match crate::initramfs::unpack(archive) {
+        Ok(count) => info!("  unpacked {} files from the initramfs", count),
+        Err(e) => error!("failed to unpack the initramfs: {:?}", e),
+    }
+    set_root_flags();
+}
 
-    let mut root_dir = RootDirectory::new(main_fs);
+/// Initializes the root directory with `main_fs` mounted on `/`, and the
+/// pseudo filesystems mounted on their directories.
+fn init_root_dir(source: &str, fstype: &'static str, main_fs: Arc<dyn VfsOps>) {
+    let main_fs = MountPoint::new("/".into(), source, fstype, MountFlags::empty(), main_fs);
+    let root_dir = Arc::new(RootDirectory::new(main_fs));
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount(
+            "/dev",
+            "devfs",
+            "devfs",
+            MountFlags::empty(),
+            mounts::devfs(),
+        )
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount(
+            "/tmp",
+            "ramfs",
+            "ramfs",
+            MountFlags::empty(),
+            mounts::ramfs(mounts::default_ramfs_limits()),
+        )
         .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
+    // Mount a pseudo filesystem as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount(
+            "/proc",
+            "proc",
+            "proc",
+            MountFlags::empty(),
+            mounts::procfs(),
+        )
         .expect("fail to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
+    // Mount a pseudo filesystem as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount(
+            "/sys",
+            "sysfs",
+            "sysfs",
+            MountFlags::empty(),
+            mounts::sysfs(),
+        )
         .expect("fail to mount sysfs at /sys");
 
-    ROOT_DIR.init_once(Arc::new(root_dir));
+    ROOT_DIR.init_once(root_dir);
     CURRENT_DIR.init_once(Mutex::new(ROOT_DIR.clone()));
     *CURRENT_DIR_PATH.lock() = "/".into();
 }
 
+/// Makes the root filesystem read-only if `rootfs-ro` is enabled, after the
+/// mount points and the initramfs are created.
+fn set_root_flags() {
+    let root_flags = if cfg!(feature = "rootfs-ro") {
+        MountFlags::RDONLY
+    } else {
+        MountFlags::empty()
+    };
+    ROOT_DIR
+        .remount("/", root_flags)
+        .expect("failed to set flags of the root filesystem");
+}
+
 fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     if path.starts_with('/') {
         ROOT_DIR.clone()
@@ -201,6 +500,303 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
+/// The directory that relative paths are resolved in, along with its
+/// absolute path. `None` means the current directory.
+pub(crate) type BaseDir<'a> = Option<(&'a VfsNodeRef, &'a str)>;
+
+/// Returns the absolute path of `path`, which is relative to the directory
+/// at the absolute path `dir_path` if given, or the current directory.
+pub(crate) fn absolute_path_at(dir_path: Option<&str>, path: &str) -> AxResult<String> {
+    match dir_path {
+        Some(dir) if !path.starts_with('/') => {
+            Ok(axfs_vfs::path::canonicalize(&format!("{}/{}", dir, path)))
+        }
+        _ => absolute_path(path),
+    }
+}
+
+/// Returns the mount point that `path` lives in, found by its absolute path,
+/// see [`absolute_path_at`].
+pub(crate) fn mount_point_of(dir_path: Option<&str>, path: &str) -> AxResult<Arc<MountPoint>> {
+    let path = absolute_path_at(dir_path, path)?;
+    Ok(ROOT_DIR.mount_point_of(path.trim_matches('/')).0)
+}
+
+/// Appends the component `name` to `path`.
+fn push_component(path: &mut String, name: &str) {
+    if !path.is_empty() && !path.ends_with('/') {
+        path.push('/');
+    }
+    *path += name;
+}
+
+/// Removes the last component of `path` for a `..`, which is kept if there is
+/// nothing left to remove in a relative path.
+fn pop_component(path: &mut String) {
+    if path == "/" {
+        return; // `..` of the root is itself
+    }
+    let (parent_len, last) = match path.rsplit_once('/') {
+        Some(("", last)) => (1, last), // keep the leading '/'
+        Some((parent, last)) => (parent.len(), last),
+        None => (0, path.as_str()),
+    };
+    if last.is_empty() || last == ".." {
+        push_component(path, "..");
+    } else {
+        path.truncate(parent_len);
+    }
+}
+
+/// Reads the target of the symbolic link `node`.
+fn read_link_node(node: &VfsNodeRef) -> AxResult<String> {
+    let attr = node.get_attr()?;
+    if !attr.file_type().is_symlink() {
+        return ax_err!(InvalidInput, "not a symbolic link");
+    }
+    let mut buf = vec![0; attr.size() as usize];
+    let len = node.read_at(0, &mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Resolves the symbolic links in `path`, which is relative to `dir` or the
+/// current directory.
+///
+/// Returns a path to the same file without symbolic links, except the last
+/// component if `follow` is `false`. `..` is resolved after the links before
+/// it are followed. The components from the first one that does not exist are
+/// kept as is, so the result can still be used to create the file.
+///
+/// Fails with [`FilesystemLoop`](AxError::FilesystemLoop) if more than
+/// [`MAX_SYMLINKS`] links are followed, which usually means a loop.
+pub(crate) fn resolve(dir: Option<&VfsNodeRef>, path: &str, follow: bool) -> AxResult<String> {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let follow = follow || path.ends_with('/');
+    let mut resolved = String::from(if path.starts_with('/') { "/" } else { "" });
+    // the components left to resolve, in reverse order
+    let mut pending: Vec<String> = path.split('/').rev().map(String::from).collect();
+    let mut links = 0;
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "" | "." => continue,
+            ".." => {
+                pop_component(&mut resolved);
+                continue;
+            }
+            _ => {}
+        }
+        let mut next = resolved.clone();
+        push_component(&mut next, &name);
+        if !follow && pending.iter().all(|name| name.is_empty() || name == ".") {
+            resolved = next;
+            break;
+        }
+        let node = match parent_node_of(dir, &next).lookup(&next) {
+            Ok(node) => node,
+            Err(AxError::NotFound) => {
+                resolved = next;
+                while let Some(name) = pending.pop() {
+                    push_component(&mut resolved, &name);
+                }
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if !node.get_attr()?.file_type().is_symlink() {
+            resolved = next;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return ax_err!(FilesystemLoop, "too many levels of symbolic links");
+        }
+        let target = read_link_node(&node)?;
+        if target.is_empty() {
+            return ax_err!(NotFound);
+        } else if target.starts_with('/') {
+            resolved = "/".into();
+        }
+        pending.extend(target.split('/').rev().map(String::from));
+    }
+    if resolved.is_empty() {
+        resolved = ".".into();
+    }
+    if path.ends_with('/') && !resolved.ends_with('/') {
+        resolved.push('/');
+    }
+    Ok(resolved)
+}
+
+/// Returns the number of hard links to `node`, or 1 if its filesystem does
+/// not count them.
+#[allow(unused_variables)]
+pub(crate) fn nlink_of(node: &VfsNodeRef) -> u64 {
+    #[cfg(feature = "overlayfs")]
+    let node = &crate::fs::overlayfs::real_node(node);
+    let any = node.as_any();
+    #[cfg(feature = "ramfs")]
+    {
+        use axfs_ramfs::{DirNode, FileNode, SymlinkNode};
+        if let Some(file) = any.downcast_ref::<FileNode>() {
+            return file.nlink() as u64;
+        } else if let Some(dir) = any.downcast_ref::<DirNode>() {
+            return dir.nlink() as u64;
+        } else if let Some(link) = any.downcast_ref::<SymlinkNode>() {
+            return link.nlink() as u64;
+        }
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = any.downcast_ref::<crate::fs::ext2::Ext2Node>() {
+        return node.nlink().unwrap_or(1);
+    }
+    #[cfg(feature = "9p")]
+    if let Some(node) = any.downcast_ref::<crate::fs::v9fs::V9fsNode>() {
+        return node.nlink().unwrap_or(1);
+    }
+    1
+}
+
+/// Returns the key that identifies the file of `node` in its filesystem, the
+/// same for every lookup of it while `node` is alive.
+///
+/// `path` is the absolute path of the node, if known. It is only used for
+/// filesystems that create a new node on each lookup and number no inodes, in
+/// which case a file opened by a relative path is identified by its node.
+#[allow(unused_variables)]
+pub(crate) fn file_key(node: &VfsNodeRef, path: Option<&str>) -> FileKey {
+    let any = node.as_any();
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = any.downcast_ref::<crate::fs::ext2::Ext2Node>() {
+        return FileKey::Ino(node.ino() as u64);
+    }
+    #[cfg(feature = "9p")]
+    if let Some(node) = any.downcast_ref::<crate::fs::v9fs::V9fsNode>() {
+        return FileKey::Ino(node.ino());
+    }
+    #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+    {
+        use crate::fs::fatfs::{DirWrapper, FileWrapper};
+        if let (Some(path), true) = (
+            path,
+            any.is::<FileWrapper<'static>>() || any.is::<DirWrapper<'static>>(),
+        ) {
+            // FAT names are case-insensitive
+            return FileKey::Path(path.to_ascii_lowercase());
+        }
+    }
+    FileKey::Node(Arc::as_ptr(node) as *const () as usize)
+}
+
+/// Changes the space of the range at `offset` of `len` bytes of the file
+/// `node`, see [`File::allocate`](crate::fops::File::allocate).
+#[allow(unused_variables)]
+pub(crate) fn allocate(node: &VfsNodeRef, offset: u64, len: u64, mode: AllocMode) -> AxResult {
+    #[cfg(feature = "overlayfs")]
+    let node = &crate::fs::overlayfs::upper_node(node)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(file) = node.as_any().downcast_ref::<axfs_ramfs::FileNode>() {
+        return match mode {
+            AllocMode::Allocate => file.allocate(offset, len, false),
+            AllocMode::KeepSize => file.allocate(offset, len, true),
+            AllocMode::PunchHole => file.punch_hole(offset, len),
+        };
+    }
+    ax_err!(Unsupported, "allocating space is not supported")
+}
+
+/// Returns the offset of the first hole if `hole` is `true`, or data
+/// otherwise, at or after `offset` in the file `node`, or `None` if `offset`
+/// is at or after the end of the file.
+pub(crate) fn seek_data_or_hole(
+    node: &VfsNodeRef,
+    offset: u64,
+    hole: bool,
+) -> AxResult<Option<u64>> {
+    #[cfg(feature = "overlayfs")]
+    let node = &crate::fs::overlayfs::real_node(node);
+    #[cfg(feature = "ramfs")]
+    if let Some(file) = node.as_any().downcast_ref::<axfs_ramfs::FileNode>() {
+        return Ok(if hole {
+            file.seek_hole(offset)
+        } else {
+            file.seek_data(offset)
+        });
+    }
+    // other filesystems have no holes but the end of the file
+    let size = node.get_attr()?.size();
+    Ok((offset < size).then_some(if hole { size } else { offset }))
+}
+
+/// Adds a hard link to `node` named `name` in the directory `dir`.
+#[allow(unused_variables)]
+fn link_node(dir: &VfsNodeRef, name: &str, node: &VfsNodeRef) -> AxResult {
+    let any = dir.as_any();
+    #[cfg(feature = "ramfs")]
+    if let Some(dir) = any.downcast_ref::<axfs_ramfs::DirNode>() {
+        return dir.link(name, node);
+    }
+    #[cfg(feature = "overlayfs")]
+    {
+        use crate::fs::overlayfs::OverlayNode;
+        if let (Some(dir), Some(node)) = (
+            any.downcast_ref::<OverlayNode>(),
+            node.as_any().downcast_ref::<OverlayNode>(),
+        ) {
+            return dir.link(name, node);
+        }
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    {
+        use crate::fs::ext2::Ext2Node;
+        if let (Some(dir), Some(node)) = (
+            any.downcast_ref::<Ext2Node>(),
+            node.as_any().downcast_ref::<Ext2Node>(),
+        ) {
+            return dir.link(name, node);
+        }
+    }
+    ax_err!(Unsupported, "hard links are not supported")
+}
+
+/// Returns whether `node` is a directory.
+pub(crate) fn is_dir(node: &VfsNodeRef) -> bool {
+    node.get_attr().is_ok_and(|attr| attr.is_dir())
+}
+
+/// Splits `path` into the path of its parent directory and its last
+/// component.
+fn split_parent(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => (".", path),
+    }
+}
+
+/// Checks that the current user can add or remove entries in the directory
+/// containing `path`.
+fn check_parent_writable(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+    let parent = lookup(dir, split_parent(path).0)?;
+    perm::check_access(&parent, &parent.get_attr()?, Cap::WRITE | Cap::EXECUTE)
+}
+
+/// Reports the event `mask` of the file at `path` relative to `dir` or the
+/// current directory to the watchers, which is skipped for a relative path in
+/// `dir`.
+pub(crate) fn notify(dir: Option<&VfsNodeRef>, path: &str, mask: WatchMask, is_dir: bool) {
+    if dir.is_none() || path.starts_with('/') {
+        if let Ok(path) = absolute_path(path) {
+            crate::notify::notify(&path, mask, is_dir);
+        }
+    }
+}
+
+/// Looks up the node at `path` relative to `dir` or the current directory,
+/// which must have been resolved by [`resolve`].
 pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
@@ -213,38 +809,62 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+pub(crate) fn create_file(dir: BaseDir, path: &str, perm: FilePerm) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
+    let path = &resolve(dir.map(|d| d.0), path, true)?;
+    mount_point_of(dir.map(|d| d.1), path)?.check_writable()?;
+    check_parent_writable(dir.map(|d| d.0), path)?;
+    let parent = parent_node_of(dir.map(|d| d.0), path);
     parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let node = parent.lookup(path)?;
+    perm::init_node(&node, perm)?;
+    notify(dir.map(|d| d.0), path, WatchMask::CREATE, false);
+    Ok(node)
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+pub(crate) fn create_dir(dir: BaseDir, path: &str) -> AxResult {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let path = &resolve(dir.map(|d| d.0), path, false)?;
+    match lookup(dir.map(|d| d.0), path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            mount_point_of(dir.map(|d| d.1), path)?.check_writable()?;
+            check_parent_writable(dir.map(|d| d.0), path)?;
+            let parent = parent_node_of(dir.map(|d| d.0), path);
+            parent.create(path, VfsNodeType::Dir)?;
+            perm::init_node(&parent.lookup(path)?, FilePerm::default_dir())?;
+            notify(dir.map(|d| d.0), path, WatchMask::CREATE, true);
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+pub(crate) fn remove_file(dir: BaseDir, path: &str) -> AxResult {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let path = &resolve(dir.map(|d| d.0), path, false)?;
+    let node = lookup(dir.map(|d| d.0), path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        mount_point_of(dir.map(|d| d.1), path)?.check_writable()?;
+        check_parent_writable(dir.map(|d| d.0), path)?;
+        parent_node_of(dir.map(|d| d.0), path).remove(path)?;
+        notify(dir.map(|d| d.0), path, WatchMask::DELETE, false);
+        Ok(())
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(dir: BaseDir, path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -258,18 +878,21 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
+    let path = &resolve(dir.map(|d| d.0), path, false)?;
     if ROOT_DIR.contains(&absolute_path(path)?) {
         return ax_err!(PermissionDenied);
     }
 
-    let node = lookup(dir, path)?;
+    let node = lookup(dir.map(|d| d.0), path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        mount_point_of(dir.map(|d| d.1), path)?.check_writable()?;
+        check_parent_writable(dir.map(|d| d.0), path)?;
+        parent_node_of(dir.map(|d| d.0), path).remove(path)?;
+        notify(dir.map(|d| d.0), path, WatchMask::DELETE, true);
+        Ok(())
     }
 }
 
@@ -278,13 +901,14 @@ pub(crate) fn current_dir() -> AxResult<String> {
 }
 
 pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
+    let mut abs_path = absolute_path(&resolve(None, path, true)?)?;
     if !abs_path.ends_with('/') {
         abs_path += "/";
     }
     if abs_path == "/" {
         *CURRENT_DIR.lock() = ROOT_DIR.clone();
         *CURRENT_DIR_PATH.lock() = "/".into();
+        *CURRENT_MOUNT.lock() = None;
         return Ok(());
     }
 
@@ -292,19 +916,258 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
-        ax_err!(PermissionDenied)
     } else {
+        perm::check_access(&node, &attr, Cap::EXECUTE)?;
+        let mount = mount_point_of(None, &abs_path)?;
         *CURRENT_DIR.lock() = node;
         *CURRENT_DIR_PATH.lock() = abs_path;
+        *CURRENT_MOUNT.lock() = Some(mount);
         Ok(())
     }
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
-        warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+    let old = &resolve(None, old, false)?;
+    let new = &resolve(None, new, false)?;
+    mount_point_of(None, old)?.check_writable()?;
+    mount_point_of(None, new)?.check_writable()?;
+    check_parent_writable(None, old)?;
+    check_parent_writable(None, new)?;
+    let is_dir = is_dir(&lookup(None, old)?);
+    let parent = parent_node_of(None, old);
+    match parent.clone().rename(old, new) {
+        // the filesystem cannot replace the destination itself
+        Err(AxError::AlreadyExists) if !is_dir => {
+            warn!("dst file already exist, now remove it");
+            remove_file(None, new)?;
+            parent.rename(old, new)?;
+        }
+        res => res?,
+    }
+    crate::notify::notify_rename(&absolute_path(old)?, &absolute_path(new)?, is_dir);
+    Ok(())
+}
+
+/// Creates a symbolic link at `path` that points to `target`.
+pub(crate) fn symlink(target: &str, path: &str) -> AxResult {
+    if target.is_empty() || path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let path = &resolve(None, path, false)?;
+    match lookup(None, path) {
+        Ok(_) => return ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+    mount_point_of(None, path)?.check_writable()?;
+    check_parent_writable(None, path)?;
+    let parent = parent_node_of(None, path);
+    parent.create(path, VfsNodeType::SymLink)?;
+    let res = parent.clone().lookup(path).and_then(|node| {
+        node.write_at(0, target.as_bytes())?;
+        perm::init_node(&node, FilePerm::from_bits_truncate(0o777))?;
+        Ok(())
+    });
+    if let Err(e) = res {
+        parent.remove(path).ok();
+        return Err(e);
+    }
+    notify(None, path, WatchMask::CREATE, false);
+    Ok(())
+}
+
+/// Creates a hard link at `new` to the file at `old`, which is not followed
+/// if it is a symbolic link.
+///
+/// Both paths must be in the same mounted filesystem, which must support hard
+/// links.
+pub(crate) fn link(old: &str, new: &str) -> AxResult {
+    let old = &resolve(None, old, false)?;
+    let new = &resolve(None, new, false)?;
+    let node = lookup(None, old)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied, "cannot link a directory");
+    }
+    match lookup(None, new) {
+        Ok(_) => return ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+    let mount = mount_point_of(None, new)?;
+    if !Arc::ptr_eq(&mount, &mount_point_of(None, old)?) {
+        return ax_err!(CrossesDevices, "cannot link across filesystems");
+    }
+    mount.check_writable()?;
+    check_parent_writable(None, new)?;
+
+    let (parent, name) = split_parent(new);
+    let parent = lookup(None, parent)?;
+    link_node(&parent, name, &node)?;
+    notify(None, new, WatchMask::CREATE, false);
+    Ok(())
+}
+
+/// Looks up the directory at `path` to be the lower layer of an overlay,
+/// along with the mount point it lives in.
+#[cfg(feature = "overlayfs")]
+pub(crate) fn lookup_lower_dir(path: &str) -> AxResult<(VfsNodeRef, Arc<MountPoint>)> {
+    let path = &resolve(None, path, true)?;
+    let node = lookup(None, path)?;
+    if !node.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    Ok((node, mount_point_of(None, path)?))
+}
+
+/// Returns the target of the symbolic link at `path`.
+pub(crate) fn read_link(path: &str) -> AxResult<String> {
+    let path = &resolve(None, path, false)?;
+    read_link_node(&lookup(None, path)?)
+}
+
+/// Returns the attributes, ownership, timestamps and number of hard links of
+/// the file at `path`, which is not followed if `follow` is `false` and it is
+/// a symbolic link.
+pub(crate) fn metadata(path: &str, follow: bool) -> AxResult<(FileAttr, FileMeta)> {
+    let path = &resolve(None, path, follow)?;
+    let node = lookup(None, path)?;
+    Ok((node.get_attr()?, perm::meta_of(&node)))
+}
+
+/// Changes the metadata of the file at `path` with `change`, which requires
+/// its filesystem to be writable.
+fn change_meta(path: &str, follow: bool, change: impl FnOnce(&VfsNodeRef) -> AxResult) -> AxResult {
+    let path = &resolve(None, path, follow)?;
+    let node = lookup(None, path)?;
+    mount_point_of(None, path)?.check_writable()?;
+    change(&node)?;
+    notify(None, path, WatchMask::ATTRIB, is_dir(&node));
+    Ok(())
+}
+
+/// Changes the permissions of the file at `path`.
+pub(crate) fn set_perm(path: &str, perm: FilePerm) -> AxResult {
+    change_meta(path, true, |node| perm::set_perm(node, perm))
+}
+
+/// Changes the owner and the group of the file at `path`, which is not
+/// followed if `follow` is `false` and it is a symbolic link.
+pub(crate) fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>, follow: bool) -> AxResult {
+    change_meta(path, follow, |node| perm::set_owner(node, uid, gid))
+}
+
+/// Changes the access and modification times of the file at `path`.
+pub(crate) fn set_times(path: &str, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+    change_meta(path, true, |node| perm::set_times(node, atime, mtime))
+}
+
+/// Changes the extended attributes of the file at `path` with `change`, which
+/// requires its filesystem to be writable and returns whether anything was
+/// changed.
+fn change_xattrs(
+    path: &str,
+    follow: bool,
+    change: impl FnOnce(&VfsNodeRef) -> AxResult<bool>,
+) -> AxResult<bool> {
+    let path = &resolve(None, path, follow)?;
+    let node = lookup(None, path)?;
+    mount_point_of(None, path)?.check_writable()?;
+    let changed = change(&node)?;
+    if changed {
+        notify(None, path, WatchMask::ATTRIB, is_dir(&node));
+    }
+    Ok(changed)
+}
+
+/// Returns the value of the extended attribute `name` of the file at `path`,
+/// which is not followed if `follow` is `false` and it is a symbolic link.
+pub(crate) fn get_xattr(path: &str, name: &str, follow: bool) -> AxResult<Option<Vec<u8>>> {
+    let path = &resolve(None, path, follow)?;
+    xattr::get(&lookup(None, path)?, name)
+}
+
+/// Returns the names of the extended attributes of the file at `path`.
+pub(crate) fn list_xattrs(path: &str, follow: bool) -> AxResult<Vec<String>> {
+    let path = &resolve(None, path, follow)?;
+    xattr::list(&lookup(None, path)?)
+}
+
+/// Sets the extended attribute `name` of the file at `path` to `value`, see
+/// [`File::set_xattr`](crate::fops::File::set_xattr).
+pub(crate) fn set_xattr(
+    path: &str,
+    name: &str,
+    value: &[u8],
+    mode: XattrMode,
+    follow: bool,
+) -> AxResult<bool> {
+    change_xattrs(path, follow, |node| xattr::set(node, name, value, mode))
+}
+
+/// Removes the extended attribute `name` of the file at `path`. Returns
+/// `false` if there is no such attribute.
+pub(crate) fn remove_xattr(path: &str, name: &str, follow: bool) -> AxResult<bool> {
+    change_xattrs(path, follow, |node| xattr::remove(node, name))
+}
+
+pub(crate) fn mount(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+    options: &str,
+) -> AxResult {
+    let target = absolute_path(&resolve(None, target, true)?)?;
+    let (fstype, fs) = mounts::new_fs(source, fstype, options)?;
+    ROOT_DIR.mount(&target, source, fstype, flags, fs)
+}
+
+pub(crate) fn remount(target: &str, flags: MountFlags) -> AxResult {
+    ROOT_DIR.remount(&absolute_path(target)?, flags)
+}
+
+pub(crate) fn umount(target: &str) -> AxResult {
+    ROOT_DIR.umount(&absolute_path(target)?)
+}
+
+pub(crate) fn mount_infos() -> Vec<MountInfo> {
+    ROOT_DIR.mount_infos()
+}
+
+/// Returns the usage of the filesystem that the file at `path` lives in.
+pub(crate) fn statfs(path: &str) -> AxResult<FsStat> {
+    let path = &resolve(None, path, true)?;
+    let node = lookup(None, path)?;
+    Ok(statfs_of(&node, &mount_point_of(None, path)?))
+}
+
+/// Returns the usage of the filesystem mounted at `mount` that `node` lives
+/// in.
+#[allow(unused_mut, unused_variables)]
+pub(crate) fn statfs_of(node: &VfsNodeRef, mount: &MountPoint) -> FsStat {
+    let mut stat = FsStat {
+        fstype: mount.fstype,
+        flags: mount.flags(),
+        block_size: 4096,
+        blocks: 0,
+        blocks_free: 0,
+        files: 0,
+        files_free: 0,
+    };
+    #[cfg(feature = "overlayfs")]
+    let node = &crate::fs::overlayfs::real_node(node);
+    #[cfg(feature = "ramfs")]
+    if let Some(usage) = axfs_ramfs::node_usage(node) {
+        let page = axfs_ramfs::PAGE_SIZE as u64;
+        stat.block_size = page;
+        stat.blocks = usage.limits.max_bytes.unwrap_or(0) / page;
+        stat.blocks_free = stat.blocks.saturating_sub(usage.bytes / page);
+        stat.files = usage.limits.max_nodes.unwrap_or(0);
+        stat.files_free = stat.files.saturating_sub(usage.nodes);
+    }
+    #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+    if let Some(node) = node.as_any().downcast_ref::<crate::fs::ext2::Ext2Node>() {
+        node.fill_statfs(&mut stat);
     }
-    parent_node_of(None, old).rename(old, new)
+    stat
 }
@@ -0,0 +1,139 @@
+//! Extended attributes of files.
+//!
+//! Only the RAM filesystem stores extended attributes. The operations fail
+//! with [`OperationNotSupported`](axerrno::AxError::OperationNotSupported) on
+//! other filesystems, e.g. FAT. A file in an overlay has the extended
+//! attributes of the layer it lives in, and is copied up before they are
+//! changed.
+//!
+//! Names are in one of the namespaces of Linux:
+//!
+//! - `user.`: read with the read permission of the file, and changed with its
+//!   write permission. Only regular files and directories can have them.
+//! - `trusted.`: only visible to root.
+//! - `security.`: visible to everyone, and only changed by root.
+
+use alloc::{string::String, vec::Vec};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsNodeRef;
+use cap_access::Cap;
+
+use crate::fops::XattrMode;
+use crate::perm::{check_access, current_ids};
+
+/// The maximum length of the name of an extended attribute.
+pub(crate) const XATTR_NAME_MAX: usize = 255;
+/// The maximum size of the value of an extended attribute.
+pub(crate) const XATTR_SIZE_MAX: usize = 65536;
+
+/// Checks that the current user can access the extended attribute `name` of
+/// `node`, for reading if `write` is `false`.
+fn check_name(node: &VfsNodeRef, name: &str, write: bool) -> AxResult {
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return ax_err!(OutOfRange, "invalid extended attribute name");
+    }
+    let is_root = current_ids().0 == 0;
+    if let Some(suffix) = name.strip_prefix("user.") {
+        if suffix.is_empty() {
+            return ax_err!(InvalidInput);
+        }
+        let attr = node.get_attr()?;
+        if write && !attr.is_file() && !attr.is_dir() {
+            return ax_err!(OperationNotPermitted);
+        }
+        check_access(node, &attr, if write { Cap::WRITE } else { Cap::READ })
+    } else if name.starts_with("trusted.") {
+        if is_root {
+            Ok(())
+        } else {
+            ax_err!(OperationNotPermitted)
+        }
+    } else if name.starts_with("security.") {
+        if !write || is_root {
+            Ok(())
+        } else {
+            ax_err!(OperationNotPermitted)
+        }
+    } else {
+        ax_err!(
+            OperationNotSupported,
+            "unknown extended attribute namespace"
+        )
+    }
+}
+
+/// Returns the value of the extended attribute `name` of `node`, or `None` if
+/// there is no such attribute.
+pub(crate) fn get(node: &VfsNodeRef, name: &str) -> AxResult<Option<Vec<u8>>> {
+    check_name(node, name, false)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&crate::perm::meta_node(node)) {
+        return Ok(attrs.get_xattr(name));
+    }
+    ax_err!(
+        OperationNotSupported,
+        "the filesystem does not support extended attributes"
+    )
+}
+
+/// Returns the names of the extended attributes of `node` that the current
+/// user can see.
+#[allow(unused_variables)]
+pub(crate) fn list(node: &VfsNodeRef) -> AxResult<Vec<String>> {
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&crate::perm::meta_node(node)) {
+        let is_root = current_ids().0 == 0;
+        let mut names = attrs.list_xattrs();
+        names.retain(|name| is_root || !name.starts_with("trusted."));
+        return Ok(names);
+    }
+    ax_err!(
+        OperationNotSupported,
+        "the filesystem does not support extended attributes"
+    )
+}
+
+/// Sets the extended attribute `name` of `node` to `value`.
+///
+/// Returns `false` without setting it if `mode` is [`XattrMode::Replace`]
+/// and there is no such attribute.
+#[allow(unused_variables)]
+pub(crate) fn set(node: &VfsNodeRef, name: &str, value: &[u8], mode: XattrMode) -> AxResult<bool> {
+    if value.len() > XATTR_SIZE_MAX {
+        return ax_err!(ArgumentListTooLong, "extended attribute value too large");
+    }
+    check_name(node, name, true)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&crate::perm::meta_node_mut(node)?) {
+        let exists = match mode {
+            XattrMode::Set => None,
+            XattrMode::Create => Some(false),
+            XattrMode::Replace => Some(true),
+        };
+        return match attrs.set_xattr(name, value, exists) {
+            Err(axfs_vfs::VfsError::NotFound) => Ok(false),
+            res => res.map(|_| true),
+        };
+    }
+    ax_err!(
+        OperationNotSupported,
+        "the filesystem does not support extended attributes"
+    )
+}
+
+/// Removes the extended attribute `name` of `node`. Returns `false` if there
+/// is no such attribute.
+pub(crate) fn remove(node: &VfsNodeRef, name: &str) -> AxResult<bool> {
+    check_name(node, name, true)?;
+    #[cfg(feature = "ramfs")]
+    if let Some(attrs) = axfs_ramfs::node_attrs(&crate::perm::meta_node_mut(node)?) {
+        return match attrs.remove_xattr(name) {
+            Err(axfs_vfs::VfsError::NotFound) => Ok(false),
+            res => res.map(|_| true),
+        };
+    }
+    ax_err!(
+        OperationNotSupported,
+        "the filesystem does not support extended attributes"
+    )
+}
@@ -1,7 +1,8 @@
 use axfs::api as fs;
 use axio as io;
+use std::time::Duration;
 
-use fs::{File, FileType, OpenOptions};
+use fs::{File, FileType, MountFlags, OpenOptions, Permissions};
 use io::{prelude::*, Error, Result};
 
 macro_rules! assert_err {
@@ -252,6 +253,724 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_links() -> Result<()> {
+    println!("test symbolic and hard links ...");
+
+    fs::create_dir("/tmp/links")?;
+    fs::write("/tmp/links/file.txt", "link test")?;
+
+    // symbolic links are followed, except by `symlink_metadata` and `read_link`
+    fs::symlink("file.txt", "/tmp/links/sym")?;
+    fs::symlink("/tmp/links", "/tmp/dir-sym")?;
+    assert_eq!(fs::read_link("/tmp/links/sym")?, "file.txt");
+    assert_eq!(fs::read_to_string("/tmp/links/sym")?, "link test");
+    assert_eq!(fs::read_to_string("/tmp/dir-sym/sym")?, "link test");
+    assert!(fs::symlink_metadata("/tmp/links/sym")?.is_symlink());
+    assert!(fs::metadata("/tmp/links/sym")?.is_file());
+    assert!(fs::metadata("/tmp/dir-sym/")?.is_dir());
+    assert_eq!(fs::read_dir("/tmp/dir-sym")?.count(), 2);
+    assert_err!(fs::read_link("/tmp/links/file.txt"), InvalidInput);
+    assert_err!(fs::symlink("file.txt", "/tmp/links/sym"), AlreadyExists);
+
+    // relative targets start from the directory of the link
+    fs::symlink("../links/file.txt", "/tmp/links/up")?;
+    assert_eq!(fs::read_to_string("/tmp/dir-sym/up")?, "link test");
+    assert_eq!(fs::read_to_string("/tmp/dir-sym/../links/up")?, "link test");
+
+    // writing through a dangling link creates its target
+    fs::symlink("new.txt", "/tmp/links/dangling")?;
+    assert_err!(fs::metadata("/tmp/links/dangling"), NotFound);
+    fs::write("/tmp/links/dangling", "created")?;
+    assert_eq!(fs::read_to_string("/tmp/links/new.txt")?, "created");
+
+    // loops are detected, with `ELOOP`
+    fs::symlink("loop2", "/tmp/links/loop1")?;
+    fs::symlink("loop1", "/tmp/links/loop2")?;
+    assert_err!(fs::read("/tmp/links/loop1"), FilesystemLoop);
+
+    // hard links share the file until the last one is removed
+    fs::hard_link("/tmp/links/file.txt", "/tmp/hard.txt")?;
+    assert_eq!(fs::metadata("/tmp/hard.txt")?.nlink(), 2);
+    assert_err!(
+        fs::hard_link("/tmp/links", "/tmp/dir-link"),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::hard_link("/tmp/hard.txt", "/tmp/links/up"),
+        AlreadyExists
+    );
+    // with `EXDEV` across filesystems
+    assert_err!(fs::hard_link("/tmp/hard.txt", "/hard.txt"), CrossesDevices);
+    fs::remove_file("/tmp/links/file.txt")?;
+    assert_eq!(fs::metadata("/tmp/hard.txt")?.nlink(), 1);
+    assert_eq!(fs::read_to_string("/tmp/hard.txt")?, "link test");
+    assert_err!(fs::read("/tmp/links/sym"), NotFound);
+
+    // removing a link leaves its target alone
+    fs::remove_file("/tmp/dir-sym")?;
+    assert!(fs::metadata("/tmp/links")?.is_dir());
+    for name in ["sym", "up", "dangling", "new.txt", "loop1", "loop2"] {
+        fs::remove_file(&format!("/tmp/links/{}", name))?;
+    }
+    fs::remove_dir("/tmp/links")?;
+    fs::remove_file("/tmp/hard.txt")?;
+    assert_eq!(fs::read_dir("/tmp")?.count(), 0);
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_ownership() -> Result<()> {
+    println!("test ownership and permissions ...");
+
+    // new files are owned by the current user
+    fs::create_dir("/tmp/perm")?;
+    fs::write("/tmp/perm/file.txt", "secret")?;
+    let meta = fs::metadata("/tmp/perm/file.txt")?;
+    assert_eq!((meta.uid(), meta.gid()), (0, 0));
+    assert_eq!(meta.permissions().mode(), 0o666);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open("/tmp/perm/private.txt")?;
+    let meta = fs::metadata("/tmp/perm/private.txt")?;
+    assert_eq!(meta.permissions().mode(), 0o600);
+
+    let (atime, mtime) = (Duration::from_secs(1), Duration::from_secs(2));
+    fs::set_times("/tmp/perm/file.txt", Some(atime), Some(mtime))?;
+    let meta = fs::metadata("/tmp/perm/file.txt")?;
+    assert_eq!((meta.accessed(), meta.modified()), (atime, mtime));
+    fs::read("/tmp/perm/file.txt")?;
+    assert_eq!(fs::metadata("/tmp/perm/file.txt")?.modified(), mtime);
+
+    fs::set_permissions("/tmp/perm/file.txt", Permissions::from_bits_truncate(0o640))?;
+    fs::chown("/tmp/perm/file.txt", Some(1000), Some(100))?;
+    let meta = fs::metadata("/tmp/perm/file.txt")?;
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+    assert_eq!(meta.permissions().mode(), 0o640);
+
+    // a member of the group
+    fs::set_current_uid(2000);
+    fs::set_current_gid(100);
+    assert_eq!(fs::read_to_string("/tmp/perm/file.txt")?, "secret");
+    assert_err!(fs::write("/tmp/perm/file.txt", "test"), PermissionDenied);
+    assert_err!(fs::read("/tmp/perm/private.txt"), PermissionDenied);
+    assert_err!(fs::write("/tmp/perm/new.txt", "test"), PermissionDenied);
+    assert_err!(fs::remove_file("/tmp/perm/file.txt"), PermissionDenied);
+    assert_err!(
+        fs::set_permissions("/tmp/perm/file.txt", Permissions::default_file()),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::set_times("/tmp/perm/file.txt", None, None),
+        PermissionDenied
+    );
+
+    // the owner
+    fs::set_current_uid(1000);
+    fs::write("/tmp/perm/file.txt", "public")?;
+    assert_err!(
+        fs::chown("/tmp/perm/file.txt", Some(0), None),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/perm/file.txt", None, Some(0)),
+        PermissionDenied
+    );
+    fs::set_permissions("/tmp/perm/file.txt", Permissions::from_bits_truncate(0o200))?;
+    assert_err!(fs::read("/tmp/perm/file.txt"), PermissionDenied);
+
+    // `/tmp` can be written by everyone
+    fs::write("/tmp/mine.txt", "test")?;
+    assert_eq!(fs::metadata("/tmp/mine.txt")?.uid(), 1000);
+    fs::remove_file("/tmp/mine.txt")?;
+
+    // root is the owner of every file
+    fs::set_current_uid(0);
+    fs::set_current_gid(0);
+    assert_err!(fs::read("/tmp/perm/file.txt"), PermissionDenied);
+    fs::set_permissions("/tmp/perm/file.txt", Permissions::from_bits_truncate(0o600))?;
+    assert_eq!(fs::read_to_string("/tmp/perm/file.txt")?, "public");
+
+    fs::remove_file("/tmp/perm/file.txt")?;
+    fs::remove_file("/tmp/perm/private.txt")?;
+    fs::remove_dir("/tmp/perm")?;
+
+    println!("test_ownership() OK!");
+    Ok(())
+}
+
+fn test_xattrs() -> Result<()> {
+    use fs::XattrMode::{Create, Replace, Set};
+    println!("test extended attributes ...");
+
+    let path = "/tmp/xattr.txt";
+    fs::write(path, "labeled")?;
+    assert_eq!(fs::get_xattr(path, "user.label")?, None);
+    assert!(fs::set_xattr(path, "user.label", b"blue", Create)?);
+    assert!(fs::set_xattr(path, "security.hash", b"1234", Set)?);
+    assert_eq!(fs::get_xattr(path, "user.label")?.unwrap(), b"blue");
+    assert_eq!(fs::list_xattrs(path)?, ["security.hash", "user.label"]);
+    assert_err!(
+        fs::set_xattr(path, "user.label", b"red", Create),
+        AlreadyExists
+    );
+    assert!(!fs::set_xattr(path, "user.none", b"red", Replace)?);
+    assert_err!(
+        fs::set_xattr(path, "other.label", b"red", Set),
+        OperationNotSupported
+    );
+    assert_err!(
+        fs::set_xattr(path, "user.big", &[0; 65537], Set),
+        ArgumentListTooLong
+    );
+
+    // only root can see trusted attributes and change security attributes
+    fs::set_xattr(path, "trusted.key", b"secret", Set)?;
+    fs::set_permissions(path, Permissions::from_bits_truncate(0o644))?;
+    fs::set_current_uid(1000);
+    assert_eq!(fs::list_xattrs(path)?, ["security.hash", "user.label"]);
+    assert_err!(fs::get_xattr(path, "trusted.key"), OperationNotPermitted);
+    assert_eq!(fs::get_xattr(path, "security.hash")?.unwrap(), b"1234");
+    assert_err!(
+        fs::set_xattr(path, "user.label", b"red", Set),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::remove_xattr(path, "security.hash"),
+        OperationNotPermitted
+    );
+    fs::set_current_uid(0);
+
+    // symbolic links have no user attributes
+    fs::symlink(path, "/tmp/xattr.lnk")?;
+    assert_eq!(
+        fs::get_xattr("/tmp/xattr.lnk", "user.label")?.unwrap(),
+        b"blue"
+    );
+    assert_eq!(fs::lget_xattr("/tmp/xattr.lnk", "user.label")?, None);
+    assert_err!(
+        fs::lset_xattr("/tmp/xattr.lnk", "user.label", b"red", Set),
+        OperationNotPermitted
+    );
+    fs::remove_file("/tmp/xattr.lnk")?;
+
+    assert!(fs::set_xattr(path, "user.label", b"", Replace)?);
+    assert_eq!(fs::get_xattr(path, "user.label")?.unwrap(), b"");
+    assert!(fs::remove_xattr(path, "user.label")?);
+    assert!(!fs::remove_xattr(path, "user.label")?);
+    fs::remove_file(path)?;
+
+    // the root filesystem does not support them unless it is a ramfs
+    #[cfg(not(feature = "myfs"))]
+    {
+        fs::write("/xattr.txt", "plain")?;
+        assert_err!(
+            fs::get_xattr("/xattr.txt", "user.label"),
+            OperationNotSupported
+        );
+        assert_err!(fs::list_xattrs("/xattr.txt"), OperationNotSupported);
+        assert_err!(
+            fs::set_xattr("/xattr.txt", "user.label", b"blue", Set),
+            OperationNotSupported
+        );
+        fs::remove_file("/xattr.txt")?;
+    }
+
+    println!("test_xattrs() OK!");
+    Ok(())
+}
+
+fn test_mount_flags() -> Result<()> {
+    println!("test mount flags ...");
+
+    fs::mount("none", "/mnt", "ramfs", MountFlags::empty())?;
+    fs::create_dir("/mnt/dir")?;
+    fs::write("/mnt/dir/test.txt", "test")?;
+    let mut file = OpenOptions::new().write(true).open("/mnt/dir/test.txt")?;
+
+    fs::remount("/mnt", MountFlags::RDONLY | MountFlags::NOEXEC)?;
+    assert!(fs::read_to_string("/proc/mounts")?.contains("none /mnt ramfs ro,noexec 0 0"));
+    assert_eq!(fs::read_to_string("/mnt/dir/test.txt")?, "test");
+    assert_err!(file.write(b"test"), ReadOnlyFilesystem);
+    assert_err!(fs::write("/mnt/dir/test.txt", "test"), ReadOnlyFilesystem);
+    assert_err!(File::create("/mnt/new.txt"), ReadOnlyFilesystem);
+    assert_err!(fs::create_dir("/mnt/new"), ReadOnlyFilesystem);
+    assert_err!(fs::create_dir("/mnt/dir"), AlreadyExists);
+    assert_err!(fs::remove_file("/mnt/dir/test.txt"), ReadOnlyFilesystem);
+    assert_err!(fs::remove_dir("/mnt/dir"), ReadOnlyFilesystem);
+    assert_err!(fs::rename("/mnt/dir", "/mnt/dir2"), ReadOnlyFilesystem);
+    let mut opts = OpenOptions::new();
+    opts.read(true).execute(true);
+    assert_err!(opts.open("/mnt/dir/test.txt"), PermissionDenied);
+    assert!(opts.open("/tmp").is_ok()); // other filesystems are not affected
+
+    fs::remount("/mnt", MountFlags::SYNC)?;
+    assert_eq!(file.write(b"TEST"), Ok(4));
+    assert_eq!(fs::read_to_string("/mnt/dir/test.txt")?, "TEST");
+    assert!(opts.open("/mnt/dir/test.txt").is_ok());
+    drop(file);
+
+    fs::remove_file("/mnt/dir/test.txt")?;
+    fs::remove_dir("/mnt/dir")?;
+    fs::umount("/mnt")?;
+    fs::remove_dir("/mnt")?;
+
+    println!("test_mount_flags() OK!");
+    Ok(())
+}
+
+fn test_sync() -> Result<()> {
+    // larger than the block cache, so blocks are evicted while writing
+    let fname = "/sync-test.bin";
+    println!("test sync {:?}:", fname);
+    let data = (0..1_200_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let mut file = File::create(fname)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    drop(file);
+    assert_eq!(fs::read(fname)?, data);
+
+    // nothing is written back for files in memory or devices, but it succeeds
+    let mut file = File::create("/tmp/sync.txt")?;
+    file.write_all(b"sync")?;
+    file.sync_all()?;
+    file.sync_data()?;
+    drop(file);
+    fs::remove_file("/tmp/sync.txt")?;
+    File::options().write(true).open("/dev/null")?.sync_all()?;
+
+    fs::remove_file(fname)?;
+    fs::sync()?;
+
+    println!("test_sync() OK!");
+    Ok(())
+}
+
+fn test_file_id() -> Result<()> {
+    use axfs::fops;
+    println!("test file identity ...");
+
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    for dir in ["/", "/tmp/"] {
+        let (a, b) = (format!("{}id-a.txt", dir), format!("{}id-b.txt", dir));
+        fs::write(&a, "a")?;
+        fs::write(&b, "b")?;
+        let file = fops::File::open(&a, &opts)?;
+        // the same file opened by another path
+        let same = fops::File::open(&format!("{}./id-a.txt", dir), &opts)?;
+        let other = fops::File::open(&b, &opts)?;
+        assert_eq!(file.file_id(), same.file_id());
+        assert_ne!(file.file_id(), other.file_id());
+        drop((file, same, other));
+        fs::remove_file(&a)?;
+        fs::remove_file(&b)?;
+    }
+
+    println!("test_file_id() OK!");
+    Ok(())
+}
+
+fn test_sparse() -> Result<()> {
+    use axfs::fops::{self, AllocMode};
+    println!("test sparse files ...");
+
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    opts.create(true);
+    let mut file = fops::File::open("/tmp/sparse.bin", &opts)?;
+    file.write_at(1 << 32, b"end")?;
+    assert_eq!(file.get_attr()?.size(), (1 << 32) + 3);
+    assert!(file.get_attr()?.blocks() <= 8);
+    assert_eq!(file.seek_hole(0)?, Some(0));
+    assert_eq!(file.seek_data(0)?, Some(1 << 32));
+    assert_eq!(file.seek(io::SeekFrom::Current(0))?, 1 << 32);
+    assert_eq!(file.seek_data((1 << 32) + 3)?, None);
+
+    file.allocate(0, 4096, AllocMode::KeepSize)?;
+    assert_eq!(file.seek_data(0)?, Some(0));
+    file.allocate(1 << 32, 1, AllocMode::PunchHole)?;
+    let mut buf = [1; 3];
+    file.read_at(1 << 32, &mut buf)?;
+    assert_eq!(&buf, b"\0nd");
+    assert_eq!(file.get_attr()?.size(), (1 << 32) + 3);
+    drop(file);
+    fs::remove_file("/tmp/sparse.bin")?;
+
+    // the root filesystem has no holes unless it is a ramfs
+    let mut file = fops::File::open("/sparse.bin", &opts)?;
+    file.write_at(0, b"data")?;
+    assert_eq!(file.seek_data(1)?, Some(1));
+    assert_eq!(file.seek_hole(1)?, Some(4));
+    #[cfg(not(feature = "myfs"))]
+    assert_err!(file.allocate(0, 1, AllocMode::PunchHole), Unsupported);
+    drop(file);
+    fs::remove_file("/sparse.bin")?;
+
+    println!("test_sparse() OK!");
+    Ok(())
+}
+
+fn test_notify() -> Result<()> {
+    use axfs::notify::{WatchMask, Watcher};
+    println!("test notify ...");
+
+    fs::create_dir("/tmp/notify")?;
+    let watcher = Watcher::new();
+    let dir_wd = watcher.add_watch("/tmp/notify/", WatchMask::ALL_EVENTS)?;
+    assert_eq!(
+        watcher.add_watch("/tmp/notify", WatchMask::ALL_EVENTS)?,
+        dir_wd
+    );
+    assert_err!(
+        watcher.add_watch("/tmp/notify/none", WatchMask::ALL_EVENTS),
+        NotFound
+    );
+
+    fs::create_dir("/tmp/notify/sub")?;
+    fs::write("/tmp/notify/a.conf", "x=1")?;
+    fs::set_permissions("/tmp/notify/a.conf", Permissions::from_bits_truncate(0o600))?;
+    fs::remove_file("/tmp/notify/a.conf")?;
+    fs::remove_dir("/tmp/notify/sub")?;
+    let events = watcher.take_events(|_| true);
+    assert!(events.iter().all(|e| e.wd == dir_wd));
+    let events = events
+        .iter()
+        .map(|e| (e.mask, e.name.as_deref().unwrap_or_default()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            (WatchMask::CREATE | WatchMask::ISDIR, "sub"),
+            (WatchMask::CREATE, "a.conf"),
+            (WatchMask::MODIFY, "a.conf"),
+            (WatchMask::ATTRIB, "a.conf"),
+            (WatchMask::DELETE, "a.conf"),
+            (WatchMask::DELETE | WatchMask::ISDIR, "sub"),
+        ]
+    );
+    assert!(!watcher.has_events());
+
+    // watch a file, whose watch is removed with it
+    fs::write("/tmp/notify/c.conf", "x=1")?;
+    watcher.remove_watch(dir_wd)?;
+    let wd = watcher.add_watch("/tmp/notify/c.conf", WatchMask::MODIFY)?;
+    File::options()
+        .append(true)
+        .open("/tmp/notify/c.conf")?
+        .write_all(b"y=2")?;
+    fs::remove_file("/tmp/notify/c.conf")?;
+    let events = watcher.take_events(|_| true);
+    let masks = events.iter().map(|e| (e.wd, e.mask)).collect::<Vec<_>>();
+    assert_eq!(
+        masks,
+        [
+            (dir_wd, WatchMask::IGNORED),
+            (wd, WatchMask::MODIFY),
+            (wd, WatchMask::IGNORED),
+        ]
+    );
+    assert_err!(watcher.remove_watch(wd), InvalidInput);
+    fs::remove_dir("/tmp/notify")?;
+
+    #[cfg(not(feature = "myfs"))] // ramfs does not support renaming
+    {
+        fs::write("/notify-a.txt", "a")?;
+        let wd = watcher.add_watch("/notify-a.txt", WatchMask::MOVE_SELF)?;
+        let root_wd = watcher.add_watch("/", WatchMask::MOVED_FROM | WatchMask::MOVED_TO)?;
+        fs::rename("/notify-a.txt", "/notify-b.txt")?;
+        let events = watcher.take_events(|_| true);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].wd, root_wd);
+        assert_eq!(events[0].mask, WatchMask::MOVED_FROM);
+        assert_eq!(events[0].name.as_deref(), Some("notify-a.txt"));
+        assert_eq!(events[1].mask, WatchMask::MOVED_TO);
+        assert_eq!(events[1].name.as_deref(), Some("notify-b.txt"));
+        assert!(events[0].cookie != 0 && events[0].cookie == events[1].cookie);
+        assert_eq!((events[2].wd, events[2].mask), (wd, WatchMask::MOVE_SELF));
+        // the watch follows the file
+        assert_eq!(
+            watcher.add_watch("/notify-b.txt", WatchMask::MOVE_SELF)?,
+            wd
+        );
+        fs::remove_file("/notify-b.txt")?;
+    }
+
+    println!("test_notify() OK!");
+    Ok(())
+}
+
+fn test_mount_umount() -> Result<()> {
+    println!("test mount and umount ...");
+
+    let mounts = fs::mounts();
+    assert_eq!(mounts[0].target, "/");
+    assert!(mounts
+        .iter()
+        .any(|m| m.target == "/tmp" && m.fstype == "ramfs"));
+
+    let flags = MountFlags::empty();
+    assert_eq!(fs::mount("none", "/mnt", "ramfs", flags), Ok(()));
+    assert_err!(fs::mount("none", "/mnt/", "ramfs", flags), InvalidInput);
+    assert_err!(fs::mount("none", "/mnt2", "nosuchfs", flags), Unsupported);
+    assert!(fs::mounts().iter().any(|m| m.target == "/mnt"));
+    let proc_mounts = fs::read_to_string("/proc/mounts")?;
+    println!("/proc/mounts:\n{}", proc_mounts);
+    assert!(proc_mounts.contains("none /mnt ramfs rw 0 0"));
+
+    fs::write("/mnt/test.txt", "test")?;
+    assert_eq!(fs::read_to_string("/mnt/test.txt")?, "test");
+
+    // busy while a file is opened or it is the current directory
+    let file = File::open("/mnt/test.txt")?;
+    assert_err!(fs::umount("/mnt"), ResourceBusy);
+    drop(file);
+    fs::set_current_dir("/mnt")?;
+    assert_err!(fs::umount("/mnt"), ResourceBusy);
+    fs::set_current_dir("/")?;
+    // also when opened relative to a directory outside of it
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.read(true);
+    let root = axfs::fops::Directory::open_dir("/", &opts)?;
+    let file = root.open_file_at("mnt/test.txt", &opts)?;
+    drop(root);
+    assert_err!(fs::umount("/mnt"), ResourceBusy);
+    drop(file);
+
+    assert_err!(fs::umount("/"), ResourceBusy);
+    assert_err!(fs::umount("/very"), InvalidInput);
+    assert_eq!(fs::umount("/mnt"), Ok(()));
+    assert!(!fs::mounts().iter().any(|m| m.target == "/mnt"));
+    assert!(!fs::read_to_string("/proc/mounts")?.contains("/mnt"));
+    assert_err!(fs::metadata("/mnt/test.txt"), NotFound);
+    assert_eq!(fs::remove_dir("/mnt"), Ok(()));
+
+    println!("test_mount_umount() OK!");
+    Ok(())
+}
+
+fn test_ramfs_limits() -> Result<()> {
+    println!("test limits of ramfs ...");
+
+    let flags = MountFlags::empty();
+    let opts = "size=16k,nr_inodes=4";
+    assert_err!(
+        fs::mount_with_options("none", "/small", "ramfs", flags, "size=1x"),
+        InvalidInput
+    );
+    assert_err!(
+        fs::mount_with_options("none", "/small", "ramfs", flags, "foo=1"),
+        InvalidInput
+    );
+    fs::mount_with_options("none", "/small", "tmpfs", flags, opts)?;
+    let stat = fs::statfs("/small")?;
+    assert_eq!((stat.fstype, stat.block_size), ("ramfs", 4096));
+    assert_eq!((stat.blocks, stat.blocks_free), (4, 4));
+    assert_eq!((stat.files, stat.files_free), (4, 3));
+
+    fs::write("/small/a", [1; 10000])?;
+    let mut file = File::create("/small/b")?;
+    assert_err!(file.write_all(&[1; 8192]), StorageFull);
+    file.write_all(&[1; 4096])?;
+    drop(file);
+    fs::create_dir("/small/dir")?;
+    assert_err!(fs::write("/small/dir/c", "c"), StorageFull);
+    let stat = fs::statfs("/small/dir")?;
+    assert_eq!((stat.blocks_free, stat.files_free), (0, 0));
+
+    fs::remove_file("/small/a")?;
+    assert_eq!(fs::statfs("/small")?.blocks_free, 3);
+    fs::write("/small/dir/c", "c")?;
+    for path in ["/small/dir/c", "/small/b"] {
+        fs::remove_file(path)?;
+    }
+    fs::remove_dir("/small/dir")?;
+    fs::umount("/small")?;
+    fs::remove_dir("/small")?;
+
+    // not limited if the memory size is unknown
+    let stat = fs::statfs("/tmp")?;
+    assert_eq!((stat.fstype, stat.blocks, stat.files), ("ramfs", 0, 0));
+
+    println!("test_ramfs_limits() OK!");
+    Ok(())
+}
+
+fn test_overlayfs() -> Result<()> {
+    println!("test overlayfs ...");
+
+    fs::create_dir_all("/ovl-lower/dir")?;
+    fs::write("/ovl-lower/file.txt", "lower")?;
+    fs::write("/ovl-lower/dir/a.txt", "lower a")?;
+    fs::create_dir_all("/ovl-lower/sub")?;
+    fs::write("/ovl-lower/sub/d.txt", "lower d")?;
+    fs::mount("/ovl-lower", "/ovl", "overlay", MountFlags::empty())?;
+    assert!(fs::read_to_string("/proc/mounts")?.contains("/ovl-lower /ovl overlay rw 0 0"));
+    assert_err!(
+        fs::mount(
+            "/ovl-lower/file.txt",
+            "/ovl2",
+            "overlay",
+            MountFlags::empty()
+        ),
+        NotADirectory
+    );
+
+    // writes copy up, which opened files also see
+    let mut file = File::open("/ovl/file.txt")?;
+    fs::write("/ovl/file.txt", "upper")?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    assert_eq!(content, "upper");
+    drop(file);
+    assert_eq!(fs::read_to_string("/ovl-lower/file.txt")?, "lower");
+    fs::write("/ovl/dir/b.txt", "upper b")?;
+    assert_err!(fs::metadata("/ovl-lower/dir/b.txt"), NotFound);
+    let names = |path| {
+        let mut names = fs::read_dir(path)?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort();
+        Ok::<_, Error>(names)
+    };
+    assert_eq!(names("/ovl/dir")?, ["a.txt", "b.txt"]);
+    fs::chown("/ovl/dir/a.txt", Some(1000), None)?;
+    assert_eq!(fs::metadata("/ovl/dir/a.txt")?.uid(), 1000);
+    assert_eq!(fs::metadata("/ovl-lower/dir/a.txt")?.uid(), 0);
+
+    // removes hide the lower files
+    fs::remove_file("/ovl/dir/a.txt")?;
+    assert_err!(fs::metadata("/ovl/dir/a.txt"), NotFound);
+    assert_eq!(names("/ovl/dir")?, ["b.txt"]);
+    assert_err!(fs::remove_dir("/ovl/dir"), DirectoryNotEmpty);
+    fs::remove_file("/ovl/dir/b.txt")?;
+    fs::remove_dir("/ovl/dir")?;
+    assert_err!(fs::metadata("/ovl/dir"), NotFound);
+    fs::create_dir("/ovl/dir")?;
+    assert!(names("/ovl/dir")?.is_empty());
+    assert_eq!(fs::read_to_string("/ovl-lower/dir/a.txt")?, "lower a");
+
+    // renames copy up, and hide the old names
+    let mut file = File::open("/ovl/file.txt")?;
+    fs::rename("/ovl/file.txt", "/ovl/renamed.txt")?;
+    assert_err!(fs::metadata("/ovl/file.txt"), NotFound);
+    assert_eq!(fs::read_to_string("/ovl/renamed.txt")?, "upper");
+    fs::write("/ovl/renamed.txt", "renamed")?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    assert_eq!(content, "renamed");
+    drop(file);
+    fs::rename("/ovl/sub", "/ovl/dir/sub2")?;
+    assert_err!(fs::metadata("/ovl/sub"), NotFound);
+    assert_eq!(names("/ovl")?, ["dir", "renamed.txt"]);
+    assert_eq!(names("/ovl/dir/sub2")?, ["d.txt"]);
+    assert_eq!(fs::read_to_string("/ovl/dir/sub2/d.txt")?, "lower d");
+    assert_err!(fs::rename("/ovl/dir", "/ovl/dir/sub2/dir"), InvalidInput);
+
+    // renames replace a file, or an empty directory with a directory
+    fs::write("/ovl/new.txt", "new")?;
+    fs::rename("/ovl/new.txt", "/ovl/renamed.txt")?;
+    assert_eq!(fs::read_to_string("/ovl/renamed.txt")?, "new");
+    assert_err!(fs::rename("/ovl/dir", "/ovl/renamed.txt"), NotADirectory);
+    assert_err!(fs::rename("/ovl/renamed.txt", "/ovl/dir"), IsADirectory);
+    fs::create_dir("/ovl/empty")?;
+    assert_err!(fs::rename("/ovl/empty", "/ovl/dir"), DirectoryNotEmpty);
+    fs::rename("/ovl/dir/sub2", "/ovl/empty")?;
+    assert_eq!(names("/ovl/empty")?, ["d.txt"]);
+    fs::rename("/ovl/empty", "/ovl/dir/sub2")?;
+    assert_eq!(names("/ovl")?, ["dir", "renamed.txt"]);
+    assert_err!(
+        fs::rename("/ovl/renamed.txt", "/tmp/renamed.txt"),
+        CrossesDevices
+    );
+    assert_eq!(fs::read_to_string("/ovl-lower/file.txt")?, "lower");
+    assert_eq!(fs::read_to_string("/ovl-lower/sub/d.txt")?, "lower d");
+
+    // the lower directory is busy until the overlay is unmounted
+    fs::mount("none", "/ovl-lower/mnt", "ramfs", MountFlags::empty())?;
+    fs::mount("/ovl-lower/mnt", "/ovl-mnt", "overlay", MountFlags::empty())?;
+    assert_err!(fs::umount("/ovl-lower/mnt"), ResourceBusy);
+    fs::umount("/ovl-mnt")?;
+    fs::umount("/ovl-lower/mnt")?;
+
+    fs::umount("/ovl")?;
+    assert_eq!(fs::read_to_string("/ovl-lower/file.txt")?, "lower");
+    for dir in ["/ovl", "/ovl-mnt", "/ovl-lower/mnt"] {
+        fs::remove_dir(dir)?;
+    }
+    fs::remove_file("/ovl-lower/dir/a.txt")?;
+    fs::remove_dir("/ovl-lower/dir")?;
+    fs::remove_file("/ovl-lower/sub/d.txt")?;
+    fs::remove_dir("/ovl-lower/sub")?;
+    fs::remove_file("/ovl-lower/file.txt")?;
+    fs::remove_dir("/ovl-lower")?;
+
+    println!("test_overlayfs() OK!");
+    Ok(())
+}
+
+fn test_procfs() -> Result<()> {
+    println!("test procfs ...");
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static VALUE: AtomicUsize = AtomicUsize::new(1);
+
+    let root = axfs::procfs_root().unwrap();
+    root.add_file("test", || "generated\n".into());
+    root.dir("sys/test").add_writable_file(
+        "value",
+        || format!("{}\n", VALUE.load(Ordering::Relaxed)),
+        |value| {
+            let value = value.parse().map_err(|_| Error::InvalidInput)?;
+            VALUE.store(value, Ordering::Relaxed);
+            Ok(())
+        },
+    );
+
+    assert_eq!(fs::read_to_string("/proc/test")?, "generated\n");
+    assert_eq!(fs::read_to_string("/proc/sys/vm/overcommit_memory")?, "0\n");
+    let names = fs::read_dir("/proc")?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    assert!(names.contains(&"mounts".into()) && names.contains(&"test".into()));
+
+    assert_eq!(fs::read_to_string("/proc/sys/test/value")?, "1\n");
+    fs::write("/proc/sys/test/value", "42\n")?;
+    assert_eq!(fs::read_to_string("/proc/sys/test/value")?, "42\n");
+    assert_err!(fs::write("/proc/sys/test/value", "x"), InvalidInput);
+    assert_err!(fs::write("/proc/test", "x"), PermissionDenied);
+    assert_err!(fs::remove_file("/proc/test"));
+    assert_eq!(fs::read_to_string("/proc/sys/test/../test/value")?, "42\n");
+
+    println!("test_procfs() OK!");
+    Ok(())
+}
+
+fn test_sysfs() -> Result<()> {
+    println!("test sysfs ...");
+
+    let dev = axfs::pseudofs::PseudoDir::new();
+    dev.add_file("name", || "test-dev\n".into());
+    let root = axfs::sysfs_root().unwrap();
+    root.add_dir("class", dev);
+    root.dir("class").add_file("extra", || "extra\n".into());
+
+    let hugepage = "/sys/kernel/mm/transparent_hugepage/enabled";
+    assert_eq!(fs::read_to_string(hugepage)?, "always [madvise] never\n");
+    assert_eq!(fs::read_to_string("/sys/class/name")?, "test-dev\n");
+    assert_eq!(fs::read_to_string("/sys/class/extra")?, "extra\n");
+    assert!(fs::metadata("/sys/class/../kernel")?.is_dir());
+    assert_err!(fs::write("/sys/class/name", "x"), PermissionDenied);
+
+    println!("test_sysfs() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +978,17 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_links().expect("test_links() failed");
+    test_ownership().expect("test_ownership() failed");
+    test_xattrs().expect("test_xattrs() failed");
+    test_sync().expect("test_sync() failed");
+    test_file_id().expect("test_file_id() failed");
+    test_sparse().expect("test_sparse() failed");
+    test_notify().expect("test_notify() failed");
+    test_mount_umount().expect("test_mount_umount() failed");
+    test_ramfs_limits().expect("test_ramfs_limits() failed");
+    test_mount_flags().expect("test_mount_flags() failed");
+    test_overlayfs().expect("test_overlayfs() failed");
+    test_procfs().expect("test_procfs() failed");
+    test_sysfs().expect("test_sysfs() failed");
 }
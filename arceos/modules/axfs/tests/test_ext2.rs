@@ -0,0 +1,117 @@
+#![cfg(all(feature = "ext2", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, Permissions};
+use axio::{Error, Write};
+use core::time::Duration;
+
+const IMG_PATH: &str = "resources/ext2.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_ext2_attrs() -> axio::Result<()> {
+    let mounts = fs::mounts();
+    assert_eq!(mounts[0].fstype, "ext2");
+
+    // permissions are stored on disk
+    let meta = fs::metadata("/read-only.txt")?;
+    assert!(meta.permissions().owner_readable());
+    assert!(!meta.permissions().owner_writable());
+    assert_eq!(fs::read_to_string("/read-only.txt")?, "Read only!\n");
+    let err = fs::OpenOptions::new()
+        .write(true)
+        .open("/read-only.txt")
+        .err();
+    assert_eq!(err, Some(Error::PermissionDenied));
+
+    // ownership and timestamps are stored on disk
+    fs::write("/owned.txt", "mine")?;
+    let meta = fs::metadata("/owned.txt")?;
+    assert_eq!((meta.uid(), meta.gid()), (0, 0));
+    assert_eq!(meta.permissions().mode(), 0o666);
+    fs::chown("/owned.txt", Some(70000), Some(100))?;
+    fs::set_current_uid(70000);
+    fs::set_permissions("/owned.txt", Permissions::from_bits_truncate(0o600))?;
+    let (atime, mtime) = (Duration::from_secs(1), Duration::from_secs(2));
+    fs::set_times("/owned.txt", Some(atime), Some(mtime))?;
+    fs::set_current_uid(1000);
+    assert_eq!(fs::read("/owned.txt").err(), Some(Error::PermissionDenied));
+    fs::set_current_uid(0);
+    let meta = fs::metadata("/owned.txt")?;
+    assert_eq!((meta.uid(), meta.gid()), (70000, 100));
+    assert_eq!(meta.permissions().mode(), 0o600);
+    assert_eq!((meta.accessed(), meta.modified()), (atime, mtime));
+    assert!(meta.is_file());
+    fs::remove_file("/owned.txt")?;
+
+    // symbolic links on the disk are followed
+    let meta = fs::symlink_metadata("/short-link")?;
+    assert!(meta.is_symlink());
+    assert_eq!(meta.len(), "short.txt".len() as u64);
+    assert_eq!(fs::read_link("/short-link")?, "short.txt");
+    assert_eq!(fs::read_to_string("/short-link")?, "Rust is cool!\n");
+
+    // link counts are stored on the disk
+    fs::hard_link("/short.txt", "/very/short-hard.txt")?;
+    assert_eq!(fs::metadata("/short.txt")?.nlink(), 2);
+    assert_eq!(
+        fs::read_to_string("/very/short-hard.txt")?,
+        "Rust is cool!\n"
+    );
+    fs::remove_file("/very/short-hard.txt")?;
+    assert_eq!(fs::metadata("/short.txt")?.nlink(), 1);
+    fs::symlink("../long.txt", "/very/long-link")?;
+    assert_eq!(fs::read("/very/long-link")?, fs::read("/long.txt")?);
+    fs::remove_file("/very/long-link")?;
+
+    // a file spanning indirect blocks
+    let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    fs::File::create("/big.bin")?.write_all(&data)?;
+    assert_eq!(fs::read("/big.bin")?, data);
+    fs::File::options()
+        .write(true)
+        .open("/big.bin")?
+        .set_len(10)?;
+    assert_eq!(fs::read("/big.bin")?, &data[..10]);
+    fs::remove_file("/big.bin")?;
+
+    // a file removed while opened is freed when it is closed
+    let stat = fs::statfs("/")?;
+    fs::File::create("/orphan.bin")?.write_all(&data)?;
+    let mut file = fs::File::options()
+        .read(true)
+        .write(true)
+        .open("/orphan.bin")?;
+    fs::remove_file("/orphan.bin")?;
+    assert_eq!(fs::metadata("/orphan.bin").err(), Some(Error::NotFound));
+    file.write_all(b"still here")?;
+    assert!(fs::statfs("/")?.blocks_free < stat.blocks_free);
+    drop(file);
+    let new_stat = fs::statfs("/")?;
+    assert_eq!(new_stat.blocks_free, stat.blocks_free);
+    assert_eq!(new_stat.files_free, stat.files_free);
+
+    println!("test_ext2_attrs() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext2() {
+    println!("Testing ext2 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_ext2_attrs().expect("test_ext2_attrs() failed");
+}
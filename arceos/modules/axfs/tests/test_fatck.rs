@@ -0,0 +1,140 @@
+#![cfg(all(feature = "fatfs", not(feature = "myfs")))]
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, MountFlags};
+use axio::Error;
+
+const IMG_PATH: &str = "resources/gpt.img";
+/// The first sector of the `boot` partition, a copy of `fat16.img`.
+const BOOT_START: usize = 2048 * 512;
+
+fn get_u16(buf: &[u8], off: usize) -> usize {
+    u16::from_le_bytes([buf[off], buf[off + 1]]) as usize
+}
+
+/// Loads the disk image, with a lost cluster in the FAT of the `boot`
+/// partition, as if the system crashed while writing a file.
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let mut data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+
+    let bs = &data[BOOT_START..BOOT_START + 512];
+    let (reserved, num_fats, fat_sectors) = (get_u16(bs, 14), bs[16] as usize, get_u16(bs, 22));
+    let root_sectors = get_u16(bs, 17) * 32 / 512;
+    let data_start = reserved + num_fats * fat_sectors + root_sectors;
+    let clusters = (get_u16(bs, 19) - data_start) / bs[13] as usize;
+    for i in 0..num_fats {
+        // mark the last cluster as the end of a chain
+        let entry = BOOT_START + (reserved + i * fat_sectors) * 512 + (clusters + 1) * 2;
+        data[entry..entry + 2].copy_from_slice(&0xffffu16.to_le_bytes());
+    }
+    data[BOOT_START + 0x25] |= 1; // the dirty flag
+    Ok(RamDisk::from(&data))
+}
+
+fn test_fsck() -> axio::Result<()> {
+    let flags = MountFlags::empty();
+    fs::mount("PARTLABEL=boot", "/boot", "vfat", flags)?;
+    assert_eq!(
+        fs::fsck_vfat("blk0p1", false).err(),
+        Some(Error::ResourceBusy)
+    );
+    fs::umount("/boot")?;
+
+    let report = fs::fsck_vfat("PARTLABEL=boot", false)?;
+    assert_eq!(report.fat_bits, 16);
+    assert_eq!((report.files, report.dirs), (4, 4));
+    assert_eq!((report.lost_clusters, report.lost_chains), (1, 1));
+    assert_eq!(report.cross_links, 0);
+    assert!(!report.repaired);
+
+    let report = fs::fsck_vfat("PARTLABEL=boot", true)?;
+    assert!(!report.is_clean());
+    assert!(report.repaired);
+    let report = fs::fsck_vfat("PARTLABEL=boot", false)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(report.lost_clusters, 0);
+
+    fs::mount("PARTLABEL=boot", "/boot", "vfat", flags)?;
+    assert_eq!(fs::read_to_string("/boot/short.txt")?, "Rust is cool!\n");
+    fs::umount("/boot")?;
+
+    println!("test_fsck() OK!");
+    Ok(())
+}
+
+fn test_mkfs() -> axio::Result<()> {
+    let flags = MountFlags::empty();
+    fs::mount("PARTLABEL=boot", "/boot", "vfat", flags)?;
+    assert_eq!(
+        fs::mkfs_vfat("PARTLABEL=boot", None, false),
+        Err(Error::ResourceBusy)
+    );
+    fs::umount("/boot")?;
+    assert_eq!(
+        fs::mkfs_vfat("PARTLABEL=swap", Some("a label too long"), false),
+        Err(Error::InvalidInput)
+    );
+
+    // the swap partition has 1024 sectors, too small for FAT32
+    fs::mkfs_vfat("PARTLABEL=swap", Some("scratch"), false)?;
+    let report = fs::fsck_vfat("PARTLABEL=swap", false)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(report.fat_bits, 12);
+    assert_eq!((report.files, report.dirs, report.used_clusters), (0, 0, 0));
+
+    fs::mount("PARTLABEL=swap", "/scratch", "vfat", flags)?;
+    fs::create_dir("/scratch/dir")?;
+    fs::write("/scratch/dir/file.txt", "Rust is cool!\n".repeat(100))?;
+    fs::umount("/scratch")?;
+
+    let report = fs::fsck_vfat("PARTLABEL=swap", false)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!((report.files, report.dirs), (1, 1));
+    fs::mount("PARTLABEL=swap", "/scratch", "vfat", flags)?;
+    assert_eq!(fs::read("/scratch/dir/file.txt")?.len(), 1400);
+    fs::umount("/scratch")?;
+
+    // FAT32 needs at least 32 MiB, the scratch partition has 40 MiB
+    assert!(fs::mkfs_vfat("PARTLABEL=swap", None, true).is_err());
+    fs::mkfs_vfat("PARTLABEL=scratch", Some("FAT32"), true)?;
+    let report = fs::fsck_vfat("PARTLABEL=scratch", false)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(report.fat_bits, 32);
+    assert_eq!((report.files, report.dirs), (0, 0));
+
+    fs::mount("PARTLABEL=scratch", "/scratch", "vfat", flags)?;
+    fs::create_dir_all("/scratch/very/long/path")?;
+    fs::write("/scratch/very/long/path/test.txt", "Rust is cool!\n")?;
+    fs::write("/scratch/long.txt", "Rust is cool!\n".repeat(1000))?;
+    fs::umount("/scratch")?;
+
+    let report = fs::fsck_vfat("PARTLABEL=scratch", false)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!((report.files, report.dirs), (2, 3));
+    fs::mount("PARTLABEL=scratch", "/scratch", "vfat", flags)?;
+    assert_eq!(fs::read("/scratch/long.txt")?.len(), 14000);
+    assert_eq!(
+        fs::read_to_string("/scratch/very/long/path/test.txt")?,
+        "Rust is cool!\n"
+    );
+    fs::umount("/scratch")?;
+
+    println!("test_mkfs() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_fatck() {
+    println!("Testing mkfs and fsck of FAT with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_fsck().expect("test_fsck() failed");
+    test_mkfs().expect("test_mkfs() failed");
+}
@@ -0,0 +1,191 @@
+#![cfg(feature = "initramfs")]
+
+use std::time::Duration;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Error;
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFCHR: u32 = 0o020000;
+
+/// An entry of a cpio archive in the `newc` format.
+struct Entry<'a> {
+    magic: &'a str,
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    name: &'a str,
+    data: &'a [u8],
+}
+
+impl Default for Entry<'_> {
+    fn default() -> Self {
+        Self {
+            magic: "070701",
+            ino: 0,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            nlink: 1,
+            mtime: 0,
+            name: "",
+            data: b"",
+        }
+    }
+}
+
+fn pad4(archive: &mut Vec<u8>) {
+    archive.resize((archive.len() + 3) & !3, 0);
+}
+
+/// Appends the entry to the archive, with `file_size` in the header, which may
+/// be larger than the data to make a truncated archive.
+fn push_entry(archive: &mut Vec<u8>, entry: &Entry, file_size: usize) {
+    archive.extend_from_slice(entry.magic.as_bytes());
+    let fields = [
+        entry.ino,
+        entry.mode,
+        entry.uid,
+        entry.gid,
+        entry.nlink,
+        entry.mtime,
+        file_size as u32,
+        0, // devmajor
+        0, // devminor
+        0, // rdevmajor
+        0, // rdevminor
+        entry.name.len() as u32 + 1,
+        0, // check
+    ];
+    for field in fields {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(entry.name.as_bytes());
+    archive.push(0);
+    pad4(archive);
+    archive.extend_from_slice(entry.data);
+    pad4(archive);
+}
+
+/// Builds an archive whose last entry is cut off, without the trailer.
+fn make_archive() -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut push = |entry: Entry| push_entry(&mut archive, &entry, entry.data.len());
+    push(Entry {
+        mode: S_IFDIR | 0o750,
+        uid: 1,
+        gid: 2,
+        name: "etc",
+        ..Default::default()
+    });
+    push(Entry {
+        magic: "070702",
+        mode: S_IFREG | 0o640,
+        uid: 1000,
+        gid: 100,
+        mtime: 1_700_000_000,
+        name: "./etc/motd",
+        data: b"Hello, initramfs!\n",
+        ..Default::default()
+    });
+    // the data of a file with hard links is stored with the last link
+    push(Entry {
+        ino: 42,
+        mode: S_IFREG | 0o600,
+        nlink: 2,
+        name: "etc/first",
+        ..Default::default()
+    });
+    push(Entry {
+        ino: 42,
+        mode: S_IFREG | 0o600,
+        nlink: 2,
+        mtime: 1_600_000_000,
+        name: "etc/second",
+        data: b"linked\n",
+        ..Default::default()
+    });
+    push(Entry {
+        mode: S_IFLNK | 0o777,
+        uid: 1000,
+        gid: 100,
+        name: "/motd",
+        data: b"etc/motd",
+        ..Default::default()
+    });
+    push(Entry {
+        mode: S_IFCHR | 0o666,
+        name: "etc/null",
+        ..Default::default()
+    });
+    let cut = Entry {
+        mode: S_IFREG | 0o644,
+        name: "etc/cut",
+        data: b"0123456789",
+        ..Default::default()
+    };
+    push_entry(&mut archive, &cut, 100);
+    archive
+}
+
+fn test_unpacked() -> axio::Result<()> {
+    let meta = fs::metadata("/etc")?;
+    assert!(meta.is_dir());
+    assert_eq!(meta.permissions().mode(), 0o750);
+    assert_eq!((meta.uid(), meta.gid()), (1, 2));
+
+    // both header formats, with the mode, owner and mtime
+    let meta = fs::metadata("/etc/motd")?;
+    assert!(meta.is_file());
+    assert_eq!(meta.permissions().mode(), 0o640);
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+    assert_eq!(meta.modified(), Duration::from_secs(1_700_000_000));
+    assert_eq!(fs::read_to_string("/etc/motd")?, "Hello, initramfs!\n");
+
+    // hard links share the data of the last link
+    assert_eq!(fs::read_to_string("/etc/first")?, "linked\n");
+    assert_eq!(fs::read_to_string("/etc/second")?, "linked\n");
+    assert_eq!(fs::metadata("/etc/first")?.nlink(), 2);
+    assert_eq!(
+        fs::metadata("/etc/first")?.modified(),
+        Duration::from_secs(1_600_000_000)
+    );
+    fs::write("/etc/first", "changed\n")?;
+    assert_eq!(fs::read_to_string("/etc/second")?, "changed\n");
+
+    // symbolic links
+    let meta = fs::symlink_metadata("/motd")?;
+    assert!(meta.is_symlink());
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+    assert_eq!(fs::read_link("/motd")?, "etc/motd");
+    assert_eq!(fs::read_to_string("/motd")?, "Hello, initramfs!\n");
+
+    // device files are skipped, and the truncated entry is not created
+    assert_eq!(fs::metadata("/etc/null").err(), Some(Error::NotFound));
+    assert_eq!(fs::metadata("/etc/cut").err(), Some(Error::NotFound));
+
+    // the pseudo filesystems are mounted as usual
+    assert_eq!(fs::mounts()[0].fstype, "ramfs");
+    assert!(fs::metadata("/dev/null").is_ok());
+
+    println!("test_unpacked() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs() {
+    println!("Testing initramfs ...");
+
+    let archive = make_archive();
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems_initramfs(AxDeviceContainer::from_one(RamDisk::default()), &archive);
+
+    test_unpacked().expect("test_unpacked() failed");
+}
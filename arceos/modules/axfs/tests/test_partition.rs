@@ -0,0 +1,57 @@
+#![cfg(all(feature = "fatfs", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, MountFlags};
+use axio::Error;
+
+const IMG_PATH: &str = "resources/gpt.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_partitions() -> axio::Result<()> {
+    // the Linux filesystem partition is the root
+    let mounts = fs::mounts();
+    assert_eq!(mounts[0].source, "blk0p2");
+
+    let flags = MountFlags::empty();
+    fs::mount("PARTLABEL=boot", "/boot", "vfat", flags)?;
+    assert_eq!(fs::read_to_string("/boot/short.txt")?, "Rust is cool!\n");
+    assert_eq!(
+        fs::mount("blk0", "/disk", "vfat", flags),
+        Err(Error::ResourceBusy)
+    );
+    assert_eq!(
+        fs::mount("/dev/blk0p1", "/disk", "vfat", flags),
+        Err(Error::ResourceBusy)
+    );
+    assert_eq!(
+        fs::mount("PARTLABEL=none", "/disk", "vfat", flags),
+        Err(Error::NotFound)
+    );
+    assert!(fs::mount("PARTLABEL=swap", "/disk", "vfat", flags).is_err());
+    fs::umount("/boot")?;
+
+    println!("test_partitions() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_partition() {
+    println!("Testing partitions with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_partitions().expect("test_partitions() failed");
+}
@@ -8,12 +8,21 @@
 //!
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
+//! - [`RawSocket`]: A raw IP socket that provides POSIX-like APIs.
+//! - [`PacketSocket`]: A socket that sends and receives Ethernet frames
+//!   directly, like `AF_PACKET` sockets of Linux.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`routes`], [`add_route`], [`add_ip_addr`]: Functions to inspect and
+//!   change the routing table and the addresses of the interfaces. Each NIC
+//!   is an interface named `eth0`, `eth1`, etc., followed by the loopback
+//!   interface `lo`.
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `dhcp`: Obtain the IPv4 address, gateway and DNS servers with DHCP at
+//!   boot, and fall back to the static configuration if there is no lease.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -33,16 +42,19 @@ cfg_if::cfg_if! {
 
 pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+#[cfg(feature = "dhcp")]
+pub use self::net_impl::DHCP_POLL_INTERVAL;
+pub use self::net_impl::{add_ip_addr, add_route, del_ip_addr, del_route, routes, RouteInfo};
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{interfaces, tcp_sockets, udp_sockets, InterfaceInfo, SocketInfo};
+pub use self::net_impl::{listen_backlog, set_listen_backlog};
+pub use self::net_impl::{LinkAddr, PacketSocket, PacketType, RawSocket, ETH_P_ALL};
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes the network subsystem by NIC devices.
-pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>) {
+pub fn init_network(net_devs: AxDeviceContainer<AxNetDevice>) {
     info!("Initialize network subsystem...");
-
-    let dev = net_devs.take_one().expect("No NIC device found!");
-    info!("  use NIC 0: {:?}", dev.device_name());
-    net_impl::init(dev);
+    net_impl::init(net_devs);
 }
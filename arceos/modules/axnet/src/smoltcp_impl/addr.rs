@@ -1,10 +1,14 @@
-use core::net::{IpAddr, Ipv4Addr, SocketAddr};
-use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 pub const fn from_core_ipaddr(ip: IpAddr) -> IpAddress {
     match ip {
         IpAddr::V4(ipv4) => IpAddress::Ipv4(Ipv4Address(ipv4.octets())),
-        _ => panic!("IPv6 not supported"),
+        IpAddr::V6(ipv6) => match ipv6.to_ipv4_mapped() {
+            // IPv4-mapped addresses of dual-stack sockets, e.g. `::ffff:10.0.2.2`
+            Some(ipv4) => IpAddress::Ipv4(Ipv4Address(ipv4.octets())),
+            None => IpAddress::Ipv6(Ipv6Address(ipv6.octets())),
+        },
     }
 }
 
@@ -12,7 +16,8 @@ pub const fn into_core_ipaddr(ip: IpAddress) -> IpAddr {
     match ip {
         IpAddress::Ipv4(ipv4) => {
             IpAddr::V4(unsafe { core::mem::transmute::<[u8; 4], Ipv4Addr>(ipv4.0) })
-        } // _ => panic!("IPv6 not supported"),
+        }
+        IpAddress::Ipv6(ipv6) => IpAddr::V6(Ipv6Addr::from_bits(u128::from_be_bytes(ipv6.0))),
     }
 }
 
@@ -28,7 +33,30 @@ pub const fn into_core_sockaddr(addr: IpEndpoint) -> SocketAddr {
 }
 
 pub fn is_unspecified(ip: IpAddress) -> bool {
-    ip.as_bytes() == [0, 0, 0, 0]
+    ip.is_unspecified()
+}
+
+/// Returns the link-local address of an interface with the MAC address `mac`,
+/// whose interface identifier is the modified EUI-64 of the MAC address.
+pub const fn link_local_ipv6(mac: [u8; 6]) -> Ipv6Address {
+    Ipv6Address([
+        0xfe,
+        0x80,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ])
 }
 
 pub const UNSPECIFIED_IP: IpAddress = IpAddress::v4(0, 0, 0, 0);
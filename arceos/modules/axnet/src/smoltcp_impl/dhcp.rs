@@ -0,0 +1,132 @@
+//! DHCPv4 client that configures the IPv4 address, gateway and DNS servers of
+//! `eth0`.
+//!
+//! The lease is renewed by smoltcp's DHCP socket whenever the interface is
+//! polled, so someone must poll it periodically, see [`DHCP_POLL_INTERVAL`].
+//! `eth0` is polled first, so the DHCP packets are always sent through it.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axsync::Mutex;
+use lazyinit::LazyInit;
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::dhcpv4::{self, Config, Event};
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+
+use super::{route, IFACES, SOCKET_SET};
+
+/// How long to wait for a lease at boot before using the static
+/// configuration.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The interval at which the interfaces should be polled to renew the lease.
+pub const DHCP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The static IPv4 address and gateway used when there is no lease.
+type Fallback = Option<(Ipv4Cidr, Option<Ipv4Address>)>;
+
+struct Dhcp {
+    handle: SocketHandle,
+    fallback: Fallback,
+}
+
+static DHCP: LazyInit<Dhcp> = LazyInit::new();
+/// The DNS servers of the current lease.
+static DNS_SERVERS: Mutex<Vec<IpAddress>> = Mutex::new(Vec::new());
+
+/// Returns the DNS servers of the current lease, empty if there is no lease.
+pub(super) fn dns_servers() -> Vec<IpAddress> {
+    DNS_SERVERS.lock().clone()
+}
+
+/// Starts the DHCP client, and waits for a lease until [`DHCP_TIMEOUT`].
+///
+/// The `fallback` address and gateway are configured if no lease is obtained
+/// in time, and replaced once a lease is obtained later.
+pub(super) fn init(fallback: Fallback) {
+    let handle = SOCKET_SET.add(dhcpv4::Socket::new());
+    DHCP.init_once(Dhcp { handle, fallback });
+
+    let deadline = axhal::time::monotonic_time() + DHCP_TIMEOUT;
+    while axhal::time::monotonic_time() < deadline {
+        SOCKET_SET.poll_interfaces();
+        if has_ipv4_addr() {
+            return;
+        }
+        axtask::yield_now();
+    }
+
+    warn!(
+        "DHCP: no lease in {:?}, use the static configuration",
+        DHCP_TIMEOUT
+    );
+    configure(fallback, &[]);
+}
+
+/// Handles the events of the DHCP socket after the interfaces are polled.
+pub(super) fn poll(sockets: &Mutex<SocketSet>) {
+    let Some(dhcp) = DHCP.get() else {
+        return;
+    };
+    // take the lease out, as configuring `eth0` needs other locks
+    let event = match sockets.lock().get_mut::<dhcpv4::Socket>(dhcp.handle).poll() {
+        None => return,
+        Some(Event::Configured(Config {
+            address,
+            router,
+            dns_servers,
+            ..
+        })) => Some((address, router, dns_servers.to_vec())),
+        Some(Event::Deconfigured) => None,
+    };
+    match event {
+        Some((address, router, dns_servers)) => {
+            info!("DHCP: leased {}", address);
+            if let Some(router) = router {
+                info!("  gateway:  {}", router);
+            }
+            for server in &dns_servers {
+                info!("  dns:      {}", server);
+            }
+            configure(Some((address, router)), &dns_servers);
+        }
+        None => {
+            warn!("DHCP: lease lost");
+            configure(dhcp.fallback, &[]);
+        }
+    }
+}
+
+fn has_ipv4_addr() -> bool {
+    IFACES[0]
+        .iface
+        .lock()
+        .ip_addrs()
+        .iter()
+        .any(|cidr| matches!(cidr, IpCidr::Ipv4(_)))
+}
+
+/// Replaces the IPv4 address, default IPv4 route and DNS servers of `eth0`.
+fn configure(config: Fallback, dns_servers: &[Ipv4Address]) {
+    let eth0 = &IFACES[0];
+    eth0.remove_ip_addrs(|cidr| matches!(cidr, IpCidr::Ipv4(_)));
+    if let Some((address, _)) = config {
+        match route::check_addr(0, IpCidr::Ipv4(address)) {
+            Ok(()) => eth0.setup_ip_addr(IpAddress::Ipv4(address.address()), address.prefix_len()),
+            Err(e) => warn!("DHCP: failed to add the address {}: {:?}", address, e),
+        }
+    }
+    let default = route::default_cidr(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED));
+    route::remove(default);
+    match config {
+        Some((_, Some(router))) => {
+            if let Err(e) = route::insert(default, IpAddress::Ipv4(router), 0, true) {
+                warn!("DHCP: failed to add the default route: {:?}", e);
+            }
+        }
+        // the addresses are changed anyway
+        _ => route::sync_routes(),
+    }
+    *DNS_SERVERS.lock() = dns_servers.iter().map(|&s| IpAddress::Ipv4(s)).collect();
+}
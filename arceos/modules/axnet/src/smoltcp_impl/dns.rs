@@ -7,7 +7,7 @@ use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
 use smoltcp::wire::DnsQueryType;
 
 use super::addr::into_core_ipaddr;
-use super::{SocketSetWrapper, ETH0, SOCKET_SET};
+use super::{SocketSetWrapper, IFACES, SOCKET_SET};
 
 /// A DNS socket.
 struct DnsSocket {
@@ -35,7 +35,7 @@ impl DnsSocket {
     pub fn query(&self, name: &str, query_type: DnsQueryType) -> AxResult<Vec<IpAddr>> {
         // let local_addr = self.local_addr.unwrap_or_else(f);
         let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
-        let iface = &ETH0.iface;
+        let iface = &IFACES[0].iface;
         let query_handle = SOCKET_SET
             .with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
                 socket.start_query(iface.lock().context(), name, query_type)
@@ -84,7 +84,19 @@ impl Drop for DnsSocket {
 }
 
 /// Public function for DNS query.
+///
+/// Both IPv4 (`A`) and IPv6 (`AAAA`) addresses are queried, it only fails if
+/// neither query succeeds.
 pub fn dns_query(name: &str) -> AxResult<alloc::vec::Vec<IpAddr>> {
     let socket = DnsSocket::new();
-    socket.query(name, DnsQueryType::A)
+    let v4 = socket.query(name, DnsQueryType::A);
+    let v6 = socket.query(name, DnsQueryType::Aaaa);
+    match (v4, v6) {
+        (Ok(mut v4), Ok(v6)) => {
+            v4.extend(v6);
+            Ok(v4)
+        }
+        (Ok(res), Err(_)) | (Err(_), Ok(res)) => Ok(res),
+        (Err(e), Err(_)) => Err(e),
+    }
 }
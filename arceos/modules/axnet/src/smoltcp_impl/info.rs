@@ -0,0 +1,152 @@
+use alloc::{string::String, vec::Vec};
+use core::net::{IpAddr, SocketAddr};
+use core::sync::atomic::Ordering;
+
+use smoltcp::socket::{tcp, Socket};
+use smoltcp::wire::IpEndpoint;
+
+use super::addr::{into_core_ipaddr, into_core_sockaddr, UNSPECIFIED_ENDPOINT, UNSPECIFIED_IP};
+use super::{IFACES, LISTEN_TABLE, SOCKET_SET};
+
+/// Addresses and statistics of a network interface.
+#[derive(Debug, Clone)]
+pub struct InterfaceInfo {
+    /// The name of the interface, e.g. `eth0`.
+    pub name: String,
+    /// The MAC address.
+    pub mac: [u8; 6],
+    /// The IP addresses with their prefix lengths.
+    pub addrs: Vec<(IpAddr, u8)>,
+    /// The number of received packets.
+    pub rx_packets: u64,
+    /// The number of received bytes.
+    pub rx_bytes: u64,
+    /// The number of transmitted packets.
+    pub tx_packets: u64,
+    /// The number of transmitted bytes.
+    pub tx_bytes: u64,
+}
+
+/// The addresses and state of a socket.
+#[derive(Debug, Clone)]
+pub struct SocketInfo {
+    /// The local address, unspecified if the socket is not bound.
+    pub local_addr: SocketAddr,
+    /// The remote address, unspecified if the socket is not connected.
+    pub peer_addr: SocketAddr,
+    /// The state with the same value as Linux `TCP_*` states, e.g. `1` for
+    /// `TCP_ESTABLISHED` and `10` for `TCP_LISTEN`. Always `7` (`TCP_CLOSE`)
+    /// for UDP sockets.
+    pub state: u8,
+    /// The number of bytes waiting to be transmitted.
+    pub tx_queue: usize,
+    /// The number of bytes waiting to be read.
+    pub rx_queue: usize,
+}
+
+/// Returns the information of all network interfaces.
+pub fn interfaces() -> Vec<InterfaceInfo> {
+    let Some(ifaces) = IFACES.get() else {
+        return Vec::new();
+    };
+    ifaces
+        .iter()
+        .map(|iface| {
+            let addrs = iface
+                .iface
+                .lock()
+                .ip_addrs()
+                .iter()
+                .map(|cidr| (into_core_ipaddr(cidr.address()), cidr.prefix_len()))
+                .collect();
+            let dev = iface.dev.lock();
+            let stats = dev.stats();
+            InterfaceInfo {
+                name: iface.name().into(),
+                mac: iface.ethernet_address().0,
+                addrs,
+                rx_packets: stats.rx_packets.load(Ordering::Relaxed),
+                rx_bytes: stats.rx_bytes.load(Ordering::Relaxed),
+                tx_packets: stats.tx_packets.load(Ordering::Relaxed),
+                tx_bytes: stats.tx_bytes.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+/// Returns the information of all TCP sockets, including listening ones.
+pub fn tcp_sockets() -> Vec<SocketInfo> {
+    if !SOCKET_SET.is_inited() {
+        return Vec::new();
+    }
+    let mut infos: Vec<_> = LISTEN_TABLE
+        .listening_endpoints()
+        .into_iter()
+        .map(|endpoint| SocketInfo {
+            local_addr: into_core_sockaddr(IpEndpoint::new(
+                endpoint.addr.unwrap_or(UNSPECIFIED_IP),
+                endpoint.port,
+            )),
+            peer_addr: into_core_sockaddr(UNSPECIFIED_ENDPOINT),
+            state: 10,
+            tx_queue: 0,
+            rx_queue: 0,
+        })
+        .collect();
+    let set = SOCKET_SET.0.lock();
+    infos.extend(set.iter().filter_map(|(_, socket)| match socket {
+        // pending connections of listening sockets are listed above
+        Socket::Tcp(socket) if socket.state() != tcp::State::Listen => Some(SocketInfo {
+            local_addr: into_core_sockaddr(socket.local_endpoint().unwrap_or(UNSPECIFIED_ENDPOINT)),
+            peer_addr: into_core_sockaddr(socket.remote_endpoint().unwrap_or(UNSPECIFIED_ENDPOINT)),
+            state: linux_tcp_state(socket.state()),
+            tx_queue: socket.send_queue(),
+            rx_queue: socket.recv_queue(),
+        }),
+        _ => None,
+    }));
+    infos
+}
+
+/// Returns the information of all bound UDP sockets.
+pub fn udp_sockets() -> Vec<SocketInfo> {
+    if !SOCKET_SET.is_inited() {
+        return Vec::new();
+    }
+    let set = SOCKET_SET.0.lock();
+    set.iter()
+        .filter_map(|(_, socket)| match socket {
+            Socket::Udp(socket) if socket.is_open() => {
+                let endpoint = socket.endpoint();
+                Some(SocketInfo {
+                    local_addr: into_core_sockaddr(IpEndpoint::new(
+                        endpoint.addr.unwrap_or(UNSPECIFIED_IP),
+                        endpoint.port,
+                    )),
+                    peer_addr: into_core_sockaddr(UNSPECIFIED_ENDPOINT),
+                    state: 7,
+                    tx_queue: 0,
+                    rx_queue: 0,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Converts a smoltcp TCP state to the value of the Linux `TCP_*` state.
+const fn linux_tcp_state(state: tcp::State) -> u8 {
+    match state {
+        tcp::State::Established => 1,
+        tcp::State::SynSent => 2,
+        tcp::State::SynReceived => 3,
+        tcp::State::FinWait1 => 4,
+        tcp::State::FinWait2 => 5,
+        tcp::State::TimeWait => 6,
+        tcp::State::Closed => 7,
+        tcp::State::CloseWait => 8,
+        tcp::State::LastAck => 9,
+        tcp::State::Listen => 10,
+        tcp::State::Closing => 11,
+    }
+}
@@ -1,4 +1,4 @@
-use alloc::{boxed::Box, collections::VecDeque};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use core::ops::{Deref, DerefMut};
 
 use axerrno::{ax_err, AxError, AxResult};
@@ -7,7 +7,7 @@ use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::{listen_backlog, SocketSetWrapper, SOCKET_SET};
 
 const PORT_NUM: usize = 65536;
 
@@ -20,7 +20,7 @@ impl ListenTableEntry {
     pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            syn_queue: VecDeque::new(),
         }
     }
 
@@ -110,6 +110,14 @@ impl ListenTable {
         }
     }
 
+    /// Returns the endpoints of all listening TCP sockets.
+    pub fn listening_endpoints(&self) -> Vec<IpListenEndpoint> {
+        self.tcp
+            .iter()
+            .filter_map(|entry| entry.lock().as_ref().map(|e| e.listen_endpoint))
+            .collect()
+    }
+
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
@@ -121,7 +129,7 @@ impl ListenTable {
                 // not listening on this address
                 return;
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
+            if entry.syn_queue.len() >= listen_backlog() {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
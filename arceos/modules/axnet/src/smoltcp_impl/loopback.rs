@@ -0,0 +1,91 @@
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use smoltcp::iface::SocketSet;
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::time::Instant;
+
+use super::{packet, snoop_tcp_packet, DeviceStats};
+
+/// The MTU of the loopback device, as large as Linux's.
+const LOOPBACK_MTU: usize = 65535;
+
+/// A software device of the `lo` interface, which receives the frames it
+/// transmits.
+///
+/// It uses the Ethernet medium like NICs, so the same sockets can be polled
+/// through it. Its neighbors are resolved by ARP and NDP to itself.
+pub(super) struct LoopbackDevice {
+    queue: VecDeque<Vec<u8>>,
+    pub(super) stats: DeviceStats,
+    /// The index of the interface in `IFACES`.
+    iface: usize,
+}
+
+impl LoopbackDevice {
+    pub fn new(iface: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            stats: DeviceStats::default(),
+            iface,
+        }
+    }
+}
+
+impl Device for LoopbackDevice {
+    type RxToken<'a> = LoopbackRxToken<'a>;
+    type TxToken<'a> = LoopbackTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buf = self.queue.pop_front()?;
+        Some((
+            LoopbackRxToken(self.iface, &self.stats, buf),
+            LoopbackTxToken(self.iface, &mut self.queue, &self.stats),
+        ))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken(self.iface, &mut self.queue, &self.stats))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = LOOPBACK_MTU;
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+pub(super) struct LoopbackRxToken<'a>(usize, &'a DeviceStats, Vec<u8>);
+pub(super) struct LoopbackTxToken<'a>(usize, &'a mut VecDeque<Vec<u8>>, &'a DeviceStats);
+
+impl RxToken for LoopbackRxToken<'_> {
+    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
+        snoop_tcp_packet(&self.2, sockets).ok();
+    }
+
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        trace!("RECV {} bytes on lo: {:02X?}", self.2.len(), self.2);
+        self.1.count_rx(self.2.len());
+        packet::deliver(self.0, &self.2, false);
+        f(&mut self.2)
+    }
+}
+
+impl TxToken for LoopbackTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0; len];
+        let ret = f(&mut buf);
+        trace!("SEND {} bytes on lo: {:02X?}", len, buf);
+        self.2.count_tx(len);
+        packet::deliver(self.0, &buf, true);
+        self.1.push_back(buf);
+        ret
+    }
+}
@@ -1,16 +1,25 @@
 mod addr;
 mod bench;
+#[cfg(feature = "dhcp")]
+mod dhcp;
 mod dns;
+mod info;
 mod listen_table;
+mod loopback;
+mod packet;
+mod raw;
+mod route;
 mod tcp;
 mod udp;
 
-use alloc::vec;
+use alloc::{format, string::String, vec, vec::Vec};
 use core::cell::RefCell;
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-use axdriver::prelude::*;
+use axdriver::{prelude::*, AxDeviceContainer};
 use axdriver_net::{DevError, NetBufPtr};
+use axerrno::{ax_err, AxError, AxResult};
 use axhal::time::{wall_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
 use lazyinit::LazyInit;
@@ -18,11 +27,18 @@ use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
 use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpProtocol, IpVersion};
 
 use self::listen_table::ListenTable;
+use self::loopback::LoopbackDevice;
 
+#[cfg(feature = "dhcp")]
+pub use self::dhcp::DHCP_POLL_INTERVAL;
 pub use self::dns::dns_query;
+pub use self::info::{interfaces, tcp_sockets, udp_sockets, InterfaceInfo, SocketInfo};
+pub use self::packet::{LinkAddr, PacketSocket, PacketType, ETH_P_ALL};
+pub use self::raw::RawSocket;
+pub use self::route::{add_ip_addr, add_route, del_ip_addr, del_route, routes, RouteInfo};
 pub use self::tcp::TcpSocket;
 pub use self::udp::UdpSocket;
 
@@ -37,8 +53,12 @@ macro_rules! env_or_default {
 
 const IP: &str = env_or_default!("AX_IP");
 const GATEWAY: &str = env_or_default!("AX_GW");
+const IP6: &str = env_or_default!("AX_IP6");
+const GATEWAY6: &str = env_or_default!("AX_GW6");
 const DNS_SEVER: &str = "8.8.8.8";
+const DNS_SEVER6: &str = "2001:4860:4860::8888";
 const IP_PREFIX: u8 = 24;
+const IP6_PREFIX: u8 = 64;
 
 const STANDARD_MTU: usize = 1500;
 
@@ -48,22 +68,66 @@ const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
-const LISTEN_QUEUE_SIZE: usize = 512;
+const RAW_RX_BUF_LEN: usize = 64 * 1024;
+const RAW_TX_BUF_LEN: usize = 64 * 1024;
+
+/// The maximum number of pending connections of a listening TCP socket.
+static LISTEN_BACKLOG: AtomicUsize = AtomicUsize::new(512);
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
+/// All interfaces, `eth0` first and `lo` last.
+static IFACES: LazyInit<Vec<InterfaceWrapper>> = LazyInit::new();
 
 struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
 
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
+    stats: DeviceStats,
+    /// The index of the interface in `IFACES`.
+    iface: usize,
+}
+
+/// Numbers of packets and bytes received and transmitted by a device.
+#[derive(Default)]
+struct DeviceStats {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+}
+
+impl DeviceStats {
+    fn count_rx(&self, len: usize) {
+        self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    fn count_tx(&self, len: usize) {
+        self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+}
+
+/// The device of an interface.
+enum IfaceDevice {
+    Nic(DeviceWrapper),
+    Loopback(LoopbackDevice),
+}
+
+impl IfaceDevice {
+    fn stats(&self) -> &DeviceStats {
+        match self {
+            Self::Nic(dev) => &dev.stats,
+            Self::Loopback(dev) => &dev.stats,
+        }
+    }
 }
 
 struct InterfaceWrapper {
-    name: &'static str,
+    name: String,
     ether_addr: EthernetAddress,
-    dev: Mutex<DeviceWrapper>,
+    dev: Mutex<IfaceDevice>,
     iface: Mutex<Interface>,
 }
 
@@ -90,9 +154,44 @@ impl<'a> SocketSetWrapper<'a> {
         socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
     }
 
+    pub fn new_raw_socket(version: IpVersion, protocol: IpProtocol) -> socket::raw::Socket<'a> {
+        let raw_rx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 16],
+            vec![0; RAW_RX_BUF_LEN],
+        );
+        let raw_tx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 16],
+            vec![0; RAW_TX_BUF_LEN],
+        );
+        socket::raw::Socket::new(version, protocol, raw_rx_buffer, raw_tx_buffer)
+    }
+
+    /// Creates a DNS socket that queries the IPv4 server if an interface has
+    /// an IPv4 address, and the IPv6 server if one has a global IPv6 address.
+    /// The servers of the DHCP lease replace the default IPv4 server.
     pub fn new_dns_socket() -> socket::dns::Socket<'a> {
-        let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
-        socket::dns::Socket::new(&[server_addr], vec![])
+        let (mut has_v4, mut has_v6) = (false, false);
+        for iface in IFACES.iter() {
+            for cidr in iface.iface.lock().ip_addrs() {
+                match cidr.address() {
+                    IpAddress::Ipv4(_) => has_v4 = true,
+                    IpAddress::Ipv6(addr) => has_v6 |= !addr.is_link_local(),
+                }
+            }
+        }
+        #[cfg(feature = "dhcp")]
+        let mut servers = dhcp::dns_servers();
+        #[cfg(not(feature = "dhcp"))]
+        let mut servers = vec![];
+        if servers.is_empty() && (has_v4 || !has_v6) {
+            servers.push(DNS_SEVER.parse().expect("invalid DNS server address"));
+        }
+        if has_v6 {
+            servers.truncate(smoltcp::config::DNS_MAX_SERVER_COUNT - 1);
+            servers.push(DNS_SEVER6.parse().expect("invalid DNS server address"));
+        }
+        servers.truncate(smoltcp::config::DNS_MAX_SERVER_COUNT);
+        socket::dns::Socket::new(&servers, vec![])
     }
 
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
@@ -120,7 +219,11 @@ impl<'a> SocketSetWrapper<'a> {
     }
 
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        for iface in IFACES.iter() {
+            iface.poll(&self.0);
+        }
+        #[cfg(feature = "dhcp")]
+        dhcp::poll(&self.0);
     }
 
     pub fn remove(&self, handle: SocketHandle) {
@@ -130,12 +233,15 @@ impl<'a> SocketSetWrapper<'a> {
 }
 
 impl InterfaceWrapper {
-    fn new(name: &'static str, dev: AxNetDevice, ether_addr: EthernetAddress) -> Self {
+    fn new(name: String, mut dev: IfaceDevice, ether_addr: EthernetAddress) -> Self {
         let mut config = Config::new(HardwareAddress::Ethernet(ether_addr));
         config.random_seed = RANDOM_SEED;
 
-        let mut dev = DeviceWrapper::new(dev);
-        let iface = Mutex::new(Interface::new(config, &mut dev, Self::current_time()));
+        let now = Self::current_time();
+        let iface = Mutex::new(match &mut dev {
+            IfaceDevice::Nic(dev) => Interface::new(config, dev, now),
+            IfaceDevice::Loopback(dev) => Interface::new(config, dev, now),
+        });
         Self {
             name,
             ether_addr,
@@ -149,7 +255,7 @@ impl InterfaceWrapper {
     }
 
     pub fn name(&self) -> &str {
-        self.name
+        &self.name
     }
 
     pub fn ethernet_address(&self) -> EthernetAddress {
@@ -163,11 +269,20 @@ impl InterfaceWrapper {
         });
     }
 
-    pub fn setup_gateway(&self, gateway: IpAddress) {
-        let mut iface = self.iface.lock();
-        match gateway {
-            IpAddress::Ipv4(v4) => iface.routes_mut().add_default_ipv4_route(v4).unwrap(),
-        };
+    /// Removes the addresses that `f` returns `true` for, and returns whether
+    /// any is removed. The order of the others is kept, as the first address
+    /// of a family is the default source address.
+    pub fn remove_ip_addrs(&self, f: impl Fn(&IpCidr) -> bool) -> bool {
+        let mut removed = false;
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            let kept: Vec<_> = ip_addrs.iter().filter(|cidr| !f(cidr)).copied().collect();
+            removed = kept.len() != ip_addrs.len();
+            ip_addrs.clear();
+            for cidr in kept {
+                ip_addrs.push(cidr).unwrap();
+            }
+        });
+        removed
     }
 
     pub fn poll(&self, sockets: &Mutex<SocketSet>) {
@@ -175,14 +290,37 @@ impl InterfaceWrapper {
         let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
         let timestamp = Self::current_time();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        match dev.deref_mut() {
+            IfaceDevice::Nic(dev) => iface.poll(timestamp, dev, &mut sockets),
+            IfaceDevice::Loopback(dev) => iface.poll(timestamp, dev, &mut sockets),
+        };
+    }
+
+    /// Sends an Ethernet frame through the device directly.
+    pub fn send_frame(&self, frame: &[u8]) -> AxResult {
+        fn send<D: Device>(dev: &mut D, frame: &[u8]) -> AxResult {
+            if frame.len() > dev.capabilities().max_transmission_unit {
+                return ax_err!(InvalidInput, "frame too long");
+            }
+            let token = dev
+                .transmit(InterfaceWrapper::current_time())
+                .ok_or(AxError::WouldBlock)?;
+            token.consume(frame.len(), |buf| buf.copy_from_slice(frame));
+            Ok(())
+        }
+        match self.dev.lock().deref_mut() {
+            IfaceDevice::Nic(dev) => send(dev, frame),
+            IfaceDevice::Loopback(dev) => send(dev, frame),
+        }
     }
 }
 
 impl DeviceWrapper {
-    fn new(inner: AxNetDevice) -> Self {
+    fn new(inner: AxNetDevice, iface: usize) -> Self {
         Self {
             inner: RefCell::new(inner),
+            stats: DeviceStats::default(),
+            iface,
         }
     }
 }
@@ -210,7 +348,7 @@ impl Device for DeviceWrapper {
                 return None;
             }
         };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+        Some((AxNetRxToken(self, rx_buf), AxNetTxToken(self)))
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
@@ -220,7 +358,7 @@ impl Device for DeviceWrapper {
             return None;
         }
         if dev.can_transmit() {
-            Some(AxNetTxToken(&self.inner))
+            Some(AxNetTxToken(self))
         } else {
             None
         }
@@ -235,8 +373,8 @@ impl Device for DeviceWrapper {
     }
 }
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
-struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
+struct AxNetRxToken<'a>(&'a DeviceWrapper, NetBufPtr);
+struct AxNetTxToken<'a>(&'a DeviceWrapper);
 
 impl<'a> RxToken for AxNetRxToken<'a> {
     fn preprocess(&self, sockets: &mut SocketSet<'_>) {
@@ -253,8 +391,10 @@ impl<'a> RxToken for AxNetRxToken<'a> {
             rx_buf.packet_len(),
             rx_buf.packet()
         );
+        self.0.stats.count_rx(rx_buf.packet_len());
+        packet::deliver(self.0.iface, rx_buf.packet(), false);
         let result = f(rx_buf.packet_mut());
-        self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
+        self.0.inner.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
         result
     }
 }
@@ -264,30 +404,57 @@ impl<'a> TxToken for AxNetTxToken<'a> {
     where
         F: FnOnce(&mut [u8]) -> R,
     {
-        let mut dev = self.0.borrow_mut();
+        let mut dev = self.0.inner.borrow_mut();
         let mut tx_buf = dev.alloc_tx_buffer(len).unwrap();
         let ret = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
+        packet::deliver(self.0.iface, tx_buf.packet(), true);
         dev.transmit(tx_buf).unwrap();
+        self.0.stats.count_tx(len);
         ret
     }
 }
 
 fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smoltcp::wire::Error> {
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
+    use smoltcp::wire::{
+        EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket,
+    };
 
     let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
-
-    if ipv4_packet.next_header() == IpProtocol::Tcp {
-        let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
-        let src_addr = (ipv4_packet.src_addr(), tcp_packet.src_port()).into();
-        let dst_addr = (ipv4_packet.dst_addr(), tcp_packet.dst_port()).into();
-        let is_first = tcp_packet.syn() && !tcp_packet.ack();
-        if is_first {
-            // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
+    let (src_ip, dst_ip, payload): (IpAddress, IpAddress, _) = match ether_frame.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let packet = Ipv4Packet::new_checked(ether_frame.payload())?;
+            if packet.next_header() != IpProtocol::Tcp {
+                return Ok(());
+            }
+            (
+                packet.src_addr().into(),
+                packet.dst_addr().into(),
+                packet.payload(),
+            )
+        }
+        EthernetProtocol::Ipv6 => {
+            // extension headers are not expected before TCP
+            let packet = Ipv6Packet::new_checked(ether_frame.payload())?;
+            if packet.next_header() != IpProtocol::Tcp {
+                return Ok(());
+            }
+            (
+                packet.src_addr().into(),
+                packet.dst_addr().into(),
+                packet.payload(),
+            )
         }
+        _ => return Ok(()),
+    };
+
+    let tcp_packet = TcpPacket::new_checked(payload)?;
+    let src_addr = (src_ip, tcp_packet.src_port()).into();
+    let dst_addr = (dst_ip, tcp_packet.dst_port()).into();
+    let is_first = tcp_packet.syn() && !tcp_packet.ack();
+    if is_first {
+        // create a socket for the first incoming TCP packet, as the later accept() returns.
+        LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
     }
     Ok(())
 }
@@ -300,31 +467,116 @@ pub fn poll_interfaces() {
     SOCKET_SET.poll_interfaces();
 }
 
+/// Returns the maximum number of pending connections of a listening TCP
+/// socket, like `net.core.somaxconn` of Linux.
+pub fn listen_backlog() -> usize {
+    LISTEN_BACKLOG.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum number of pending connections of a listening TCP socket.
+///
+/// It takes effect on the next incoming connection.
+pub fn set_listen_backlog(backlog: usize) {
+    LISTEN_BACKLOG.store(backlog, Ordering::Relaxed);
+}
+
 /// Benchmark raw socket transmit bandwidth.
 pub fn bench_transmit() {
-    ETH0.dev.lock().bench_transmit_bandwidth();
+    match IFACES[0].dev.lock().deref_mut() {
+        IfaceDevice::Nic(dev) => dev.bench_transmit_bandwidth(),
+        IfaceDevice::Loopback(_) => warn!("no NIC to benchmark"),
+    }
 }
 
 /// Benchmark raw socket receive bandwidth.
 pub fn bench_receive() {
-    ETH0.dev.lock().bench_receive_bandwidth();
+    match IFACES[0].dev.lock().deref_mut() {
+        IfaceDevice::Nic(dev) => dev.bench_receive_bandwidth(),
+        IfaceDevice::Loopback(_) => warn!("no NIC to benchmark"),
+    }
 }
 
-pub(crate) fn init(net_dev: AxNetDevice) {
-    let ether_addr = EthernetAddress(net_dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
+pub(crate) fn init(mut net_devs: AxDeviceContainer<AxNetDevice>) {
+    #[cfg(feature = "dhcp")]
+    use smoltcp::wire::Ipv4Cidr;
+
+    let mut ifaces = Vec::new();
+    while let Some(dev) = net_devs.take_one() {
+        info!("  use NIC {}: {:?}", ifaces.len(), dev.device_name());
+        let ether_addr = EthernetAddress(dev.mac_address().0);
+        let dev = IfaceDevice::Nic(DeviceWrapper::new(dev, ifaces.len()));
+        let iface = InterfaceWrapper::new(format!("eth{}", ifaces.len()), dev, ether_addr);
+        // the link-local address is always configured and resolved with NDP
+        let link_local = IpAddress::Ipv6(addr::link_local_ipv6(ether_addr.0));
+        iface.setup_ip_addr(link_local, IP6_PREFIX);
+        ifaces.push(iface);
+    }
+    let has_nic = !ifaces.is_empty();
+    if !has_nic {
+        warn!("No NIC device found, only the loopback interface is available");
+    }
+
+    // the static addresses are of `eth0`, and optional
+    let parse = |addr: &str, what: &str| {
+        (has_nic && !addr.is_empty()).then(|| {
+            addr.parse::<IpAddress>()
+                .unwrap_or_else(|_| panic!("invalid {what}: {addr:?}"))
+        })
+    };
+    let ip = parse(IP, "IP address");
+    let gateway = parse(GATEWAY, "gateway IP address");
+    let ip6 = parse(IP6, "IPv6 address");
+    let gateway6 = parse(GATEWAY6, "gateway IPv6 address");
+    // with DHCP, the static IPv4 address and gateway are only the fallback
+    #[cfg(feature = "dhcp")]
+    let (fallback, ip, gateway) = match (ip, gateway) {
+        (Some(IpAddress::Ipv4(ip)), Some(IpAddress::Ipv4(gateway))) => (
+            Some((Ipv4Cidr::new(ip, IP_PREFIX), Some(gateway))),
+            None,
+            None,
+        ),
+        (Some(IpAddress::Ipv4(ip)), _) => (Some((Ipv4Cidr::new(ip, IP_PREFIX), None)), None, None),
+        _ => (None, None, None),
+    };
+    if let Some(ip) = ip {
+        ifaces[0].setup_ip_addr(ip, IP_PREFIX);
+    }
+    if let Some(ip6) = ip6 {
+        ifaces[0].setup_ip_addr(ip6, IP6_PREFIX);
+    }
 
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
-    eth0.setup_gateway(gateway);
+    // `lo` is polled last, so the broadcasts are sent through `eth0`
+    let dev = IfaceDevice::Loopback(LoopbackDevice::new(ifaces.len()));
+    let lo = InterfaceWrapper::new("lo".into(), dev, EthernetAddress([0; 6]));
+    lo.setup_ip_addr(IpAddress::v4(127, 0, 0, 1), 8);
+    lo.setup_ip_addr(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 128);
+    ifaces.push(lo);
 
-    ETH0.init_once(eth0);
+    IFACES.init_once(ifaces);
     SOCKET_SET.init_once(SocketSetWrapper::new());
     LISTEN_TABLE.init_once(ListenTable::new());
 
-    info!("created net interface {:?}:", ETH0.name());
-    info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
-    info!("  gateway:  {}", gateway);
+    for gateway in gateway.into_iter().chain(gateway6) {
+        route::insert(route::default_cidr(gateway), gateway, 0, true)
+            .expect("failed to add the default route");
+    }
+
+    for iface in IFACES.iter() {
+        info!("created net interface {:?}:", iface.name());
+        info!("  ether:    {}", iface.ethernet_address());
+        for cidr in iface.iface.lock().ip_addrs() {
+            match cidr.address() {
+                IpAddress::Ipv4(_) => info!("  ip:       {}", cidr),
+                IpAddress::Ipv6(_) => info!("  ip6:      {}", cidr),
+            }
+        }
+    }
+    for gateway in gateway.iter().chain(&gateway6) {
+        info!("  gateway:  {}", gateway);
+    }
+
+    #[cfg(feature = "dhcp")]
+    if has_nic {
+        dhcp::init(fallback);
+    }
 }
@@ -0,0 +1,254 @@
+//! Packet sockets that send and receive the Ethernet frames of interfaces
+//! directly, like `AF_PACKET` sockets of Linux.
+//!
+//! Every frame sent or received by a device is copied to the packet sockets
+//! that want it, while it is still processed by smoltcp as usual.
+
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering};
+
+use axerrno::{ax_err, AxError, AxResult};
+use axio::PollState;
+use axsync::Mutex;
+use smoltcp::wire::{EthernetFrame, ETHERNET_HEADER_LEN};
+
+use super::{IFACES, SOCKET_SET};
+
+/// The protocol that matches the frames of all protocols, `ETH_P_ALL` of
+/// Linux.
+pub const ETH_P_ALL: u16 = 0x0003;
+
+/// How many received frames a packet socket queues at most. More frames are
+/// dropped.
+const PACKET_QUEUE_LEN: usize = 64;
+
+/// The type of a frame received by a packet socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+    /// To the interface.
+    Host,
+    /// To the broadcast address.
+    Broadcast,
+    /// To a multicast address.
+    Multicast,
+    /// To another host, received in promiscuous mode.
+    OtherHost,
+    /// Sent by the interface.
+    Outgoing,
+}
+
+/// The link-layer address of a frame, like `sockaddr_ll` of Linux.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkAddr {
+    /// The index of the interface in [`interfaces`](crate::interfaces),
+    /// starting from 1. 0 stands for all interfaces.
+    pub ifindex: usize,
+    /// The Ethernet protocol of the frame, in host byte order.
+    pub protocol: u16,
+    /// The type of the frame.
+    pub pkttype: PacketType,
+    /// The source MAC address of the frame, or the MAC address of the
+    /// interface for a local address.
+    pub hwaddr: [u8; 6],
+}
+
+struct PacketInner {
+    protocol: AtomicU16,
+    ifindex: AtomicUsize,
+    queue: Mutex<VecDeque<(Vec<u8>, LinkAddr)>>,
+}
+
+static PACKET_SOCKETS: Mutex<Vec<Weak<PacketInner>>> = Mutex::new(Vec::new());
+
+/// A packet socket that provides POSIX-like APIs.
+///
+/// The frames sent and received include the Ethernet header, like a
+/// `SOCK_RAW` packet socket of Linux.
+pub struct PacketSocket {
+    inner: Arc<PacketInner>,
+    nonblock: AtomicBool,
+}
+
+impl PacketSocket {
+    /// Creates a new packet socket that receives the frames of the Ethernet
+    /// protocol `protocol` on all interfaces. No frame is received if it is 0,
+    /// and all frames are received if it is [`ETH_P_ALL`].
+    pub fn new(protocol: u16) -> Self {
+        let inner = Arc::new(PacketInner {
+            protocol: AtomicU16::new(protocol),
+            ifindex: AtomicUsize::new(0),
+            queue: Mutex::new(VecDeque::new()),
+        });
+        PACKET_SOCKETS.lock().push(Arc::downgrade(&inner));
+        Self {
+            inner,
+            nonblock: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the interface and protocol that the socket is bound to.
+    pub fn local_addr(&self) -> LinkAddr {
+        let ifindex = self.inner.ifindex.load(Ordering::Acquire);
+        LinkAddr {
+            ifindex,
+            protocol: self.inner.protocol.load(Ordering::Acquire),
+            pkttype: PacketType::Host,
+            hwaddr: match ifindex {
+                0 => [0; 6],
+                i => IFACES[i - 1].ethernet_address().0,
+            },
+        }
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this packet socket into or out of nonblocking mode.
+    ///
+    /// This will result in `recv_from`, `send`, and `send_to` operations
+    /// becoming nonblocking, i.e., immediately returning
+    /// [`Err(WouldBlock)`](AxError::WouldBlock) if they could not be completed.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds the socket to the interface `ifindex`, or all interfaces if it is
+    /// 0. The protocol is changed to `protocol` unless it is 0.
+    pub fn bind(&self, ifindex: usize, protocol: u16) -> AxResult {
+        if ifindex > IFACES.len() {
+            return ax_err!(NoSuchDevice, "socket bind() failed: no such interface");
+        }
+        if protocol != 0 {
+            self.inner.protocol.store(protocol, Ordering::Release);
+        }
+        self.inner.ifindex.store(ifindex, Ordering::Release);
+        debug!("packet socket: bound on interface {}", ifindex);
+        Ok(())
+    }
+
+    /// Sends a frame through the interface `ifindex`. On success, returns the
+    /// number of bytes written.
+    pub fn send_to(&self, buf: &[u8], ifindex: usize) -> AxResult<usize> {
+        if ifindex == 0 || ifindex > IFACES.len() {
+            return ax_err!(NoSuchDevice, "socket send_to() failed: no such interface");
+        }
+        if buf.len() < ETHERNET_HEADER_LEN {
+            return ax_err!(InvalidInput, "socket send_to() failed: frame too short");
+        }
+        let iface = &IFACES[ifindex - 1];
+        self.block_on(|| iface.send_frame(buf))?;
+        Ok(buf.len())
+    }
+
+    /// Sends a frame through the interface to which it is bound.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        self.send_to(buf, self.inner.ifindex.load(Ordering::Acquire))
+    }
+
+    /// Receives a single frame on the socket. On success, returns the number
+    /// of bytes read and the address of the frame.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, LinkAddr)> {
+        self.block_on(|| {
+            let (frame, addr) = self
+                .inner
+                .queue
+                .lock()
+                .pop_front()
+                .ok_or(AxError::WouldBlock)?;
+            let len = frame.len().min(buf.len());
+            buf[..len].copy_from_slice(&frame[..len]);
+            Ok((len, addr))
+        })
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        Ok(PollState {
+            readable: !self.inner.queue.lock().is_empty(),
+            writable: true,
+        })
+    }
+}
+
+/// Private methods
+impl PacketSocket {
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PacketSocket {
+    fn drop(&mut self) {
+        let inner = Arc::as_ptr(&self.inner);
+        PACKET_SOCKETS.lock().retain(|s| s.as_ptr() != inner);
+    }
+}
+
+impl PacketInner {
+    fn wants(&self, ifindex: usize, protocol: u16) -> bool {
+        let (bound_ifindex, bound_protocol) = (
+            self.ifindex.load(Ordering::Acquire),
+            self.protocol.load(Ordering::Acquire),
+        );
+        (bound_ifindex == 0 || bound_ifindex == ifindex)
+            && (bound_protocol == ETH_P_ALL || (bound_protocol != 0 && bound_protocol == protocol))
+    }
+}
+
+/// Copies a frame sent or received by the interface `iface` to the packet
+/// sockets that want it.
+pub(super) fn deliver(iface: usize, frame: &[u8], outgoing: bool) {
+    let sockets = PACKET_SOCKETS.lock();
+    if sockets.is_empty() {
+        return;
+    }
+    let Ok(ether_frame) = EthernetFrame::new_checked(frame) else {
+        return;
+    };
+    let dst_addr = ether_frame.dst_addr();
+    let pkttype = if outgoing {
+        PacketType::Outgoing
+    } else if dst_addr.is_broadcast() {
+        PacketType::Broadcast
+    } else if dst_addr.is_multicast() {
+        PacketType::Multicast
+    } else if dst_addr == IFACES[iface].ethernet_address() {
+        PacketType::Host
+    } else {
+        PacketType::OtherHost
+    };
+    let addr = LinkAddr {
+        ifindex: iface + 1,
+        protocol: ether_frame.ethertype().into(),
+        pkttype,
+        hwaddr: ether_frame.src_addr().0,
+    };
+    for socket in sockets.iter().filter_map(Weak::upgrade) {
+        if socket.wants(addr.ifindex, addr.protocol) {
+            let mut queue = socket.queue.lock();
+            if queue.len() < PACKET_QUEUE_LEN {
+                queue.push_back((frame.to_vec(), addr));
+            }
+        }
+    }
+}
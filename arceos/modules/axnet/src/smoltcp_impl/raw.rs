@@ -0,0 +1,292 @@
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use spin::RwLock;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::raw::{self, SendError};
+use smoltcp::wire::{
+    Icmpv6Packet, IpAddress, IpProtocol, IpVersion, Ipv4Packet, Ipv4Repr, Ipv6Packet, Ipv6Repr,
+    IPV4_HEADER_LEN, IPV6_HEADER_LEN,
+};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr, is_unspecified};
+use super::{route, SocketSetWrapper, IFACES, SOCKET_SET};
+
+/// The hop limit of the packets sent by raw sockets.
+const RAW_HOP_LIMIT: u8 = 64;
+
+/// A raw IP socket that provides POSIX-like APIs.
+///
+/// Like Linux, the IP header is built for the data to send, and the data
+/// received includes the IP header for IPv4 but not for IPv6. The checksum of
+/// ICMPv6 messages is filled in when sending.
+pub struct RawSocket {
+    handle: SocketHandle,
+    version: IpVersion,
+    protocol: IpProtocol,
+    local_addr: RwLock<Option<IpAddress>>,
+    peer_addr: RwLock<Option<IpAddress>>,
+    nonblock: AtomicBool,
+}
+
+impl RawSocket {
+    /// Creates a new raw socket for the packets of the IP protocol number
+    /// `protocol`, of IPv6 if `ipv6` is `true` or IPv4 otherwise.
+    pub fn new(ipv6: bool, protocol: u8) -> Self {
+        let version = if ipv6 {
+            IpVersion::Ipv6
+        } else {
+            IpVersion::Ipv4
+        };
+        let protocol = IpProtocol::from(protocol);
+        let socket = SocketSetWrapper::new_raw_socket(version, protocol);
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            version,
+            protocol,
+            local_addr: RwLock::new(None),
+            peer_addr: RwLock::new(None),
+            nonblock: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the local address, or the unspecified address if not bound.
+    pub fn local_addr(&self) -> AxResult<IpAddr> {
+        let addr = self.local_addr.read().unwrap_or(match self.version {
+            IpVersion::Ipv4 => IpAddress::v4(0, 0, 0, 0),
+            IpVersion::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0),
+        });
+        Ok(into_core_ipaddr(addr))
+    }
+
+    /// Returns the remote address, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn peer_addr(&self) -> AxResult<IpAddr> {
+        match *self.peer_addr.read() {
+            Some(addr) => Ok(into_core_ipaddr(addr)),
+            None => Err(AxError::NotConnected),
+        }
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this raw socket into or out of nonblocking mode.
+    ///
+    /// This will result in `recv_from`, `send`, and `send_to` operations
+    /// becoming nonblocking, i.e., immediately returning
+    /// [`Err(WouldBlock)`](AxError::WouldBlock) if they could not be completed.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds the socket to a local address, which becomes the source address
+    /// of the packets sent, and the only destination of the packets received.
+    pub fn bind(&self, local_addr: IpAddr) -> AxResult {
+        let addr = self.checked_addr(local_addr)?;
+        let mut self_local_addr = self.local_addr.write();
+        if self_local_addr.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+        if !is_unspecified(addr) && !IFACES.iter().any(|i| i.iface.lock().has_ip_addr(addr)) {
+            return ax_err!(InvalidInput, "socket bind() failed: not a local address");
+        }
+        *self_local_addr = Some(addr);
+        debug!("raw socket {}: bound on {}", self.handle, addr);
+        Ok(())
+    }
+
+    /// Connects the socket to a remote address, so that [`send`](Self::send)
+    /// can be used, and only the packets from the address are received.
+    pub fn connect(&self, addr: IpAddr) -> AxResult {
+        let addr = self.checked_addr(addr)?;
+        *self.peer_addr.write() = Some(addr);
+        debug!("raw socket {}: connected to {}", self.handle, addr);
+        Ok(())
+    }
+
+    /// Sends a packet with the data as payload to the given address. On
+    /// success, returns the number of bytes written.
+    pub fn send_to(&self, buf: &[u8], remote_addr: IpAddr) -> AxResult<usize> {
+        let addr = self.checked_addr(remote_addr)?;
+        if is_unspecified(addr) {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        self.send_impl(buf, addr)
+    }
+
+    /// Sends a packet with the data as payload to the remote address to which
+    /// it is connected.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        match *self.peer_addr.read() {
+            Some(addr) => self.send_impl(buf, addr),
+            None => ax_err!(NotConnected, "socket send() failed"),
+        }
+    }
+
+    /// Receives a single packet on the socket. On success, returns the number
+    /// of bytes read and the source address.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        let local_addr = *self.local_addr.read();
+        let peer_addr = *self.peer_addr.read();
+        let unmatched = |filter: Option<IpAddress>, addr: IpAddress| {
+            filter.is_some_and(|filter| !is_unspecified(filter) && filter != addr)
+        };
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+                while socket.can_recv() {
+                    let packet = socket
+                        .recv()
+                        .map_err(|_| ax_err_type!(BadState, "socket recv_from() failed"))?;
+                    let Some((src_addr, dst_addr, payload)) = parse_packet(packet) else {
+                        continue;
+                    };
+                    if unmatched(local_addr, dst_addr) || unmatched(peer_addr, src_addr) {
+                        continue;
+                    }
+                    let data = match self.version {
+                        IpVersion::Ipv4 => packet,
+                        IpVersion::Ipv6 => payload,
+                    };
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+                    return Ok((len, into_core_ipaddr(src_addr)));
+                }
+                Err(AxError::WouldBlock)
+            })
+        })
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+            Ok(PollState {
+                readable: socket.can_recv(),
+                writable: socket.can_send(),
+            })
+        })
+    }
+}
+
+/// Private methods
+impl RawSocket {
+    /// Converts `addr`, and checks that it is of the IP version of the socket.
+    fn checked_addr(&self, addr: IpAddr) -> AxResult<IpAddress> {
+        let addr = from_core_ipaddr(addr);
+        if addr.version() != self.version {
+            return ax_err!(InvalidInput, "address of another address family");
+        }
+        Ok(addr)
+    }
+
+    fn send_impl(&self, buf: &[u8], dst_addr: IpAddress) -> AxResult<usize> {
+        // find the source address before locking the sockets, as the
+        // interfaces are locked before the sockets when polling
+        let src_addr = match *self.local_addr.read() {
+            Some(addr) if !is_unspecified(addr) => addr,
+            _ => route::source_addr(dst_addr)
+                .ok_or_else(|| ax_err_type!(ConnectionRefused, "socket send() failed"))?,
+        };
+        let header_len = match self.version {
+            IpVersion::Ipv4 => IPV4_HEADER_LEN,
+            IpVersion::Ipv6 => IPV6_HEADER_LEN,
+        };
+        let len = header_len + buf.len();
+
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<raw::Socket, _, _>(self.handle, |socket| {
+                if len > socket.payload_send_capacity() || len > u16::MAX as usize {
+                    return ax_err!(InvalidInput, "socket send() failed: message too long");
+                }
+                let packet = socket
+                    .send(len)
+                    .map_err(|SendError::BufferFull| AxError::WouldBlock)?;
+                self.emit_packet(packet, src_addr, dst_addr, buf);
+                Ok(buf.len())
+            })
+        })
+    }
+
+    fn emit_packet(&self, packet: &mut [u8], src: IpAddress, dst: IpAddress, payload: &[u8]) {
+        match (src, dst) {
+            (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+                let repr = Ipv4Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: payload.len(),
+                    hop_limit: RAW_HOP_LIMIT,
+                };
+                let mut packet = Ipv4Packet::new_unchecked(packet);
+                repr.emit(&mut packet, &ChecksumCapabilities::default());
+                packet.payload_mut().copy_from_slice(payload);
+            }
+            (IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) => {
+                let repr = Ipv6Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: payload.len(),
+                    hop_limit: RAW_HOP_LIMIT,
+                };
+                let mut packet = Ipv6Packet::new_unchecked(packet);
+                repr.emit(&mut packet);
+                packet.payload_mut().copy_from_slice(payload);
+                if self.protocol == IpProtocol::Icmpv6 && payload.len() >= 4 {
+                    Icmpv6Packet::new_unchecked(packet.payload_mut()).fill_checksum(&src, &dst);
+                }
+            }
+            _ => unreachable!("source address of another address family"),
+        }
+    }
+
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RawSocket {
+    fn drop(&mut self) {
+        SOCKET_SET.remove(self.handle);
+    }
+}
+
+/// Returns the source address, destination address and payload of an IP
+/// packet.
+fn parse_packet(packet: &[u8]) -> Option<(IpAddress, IpAddress, &[u8])> {
+    match IpVersion::of_packet(packet).ok()? {
+        IpVersion::Ipv4 => {
+            let ipv4 = Ipv4Packet::new_checked(packet).ok()?;
+            let payload = &packet[ipv4.header_len() as usize..ipv4.total_len() as usize];
+            Some((ipv4.src_addr().into(), ipv4.dst_addr().into(), payload))
+        }
+        IpVersion::Ipv6 => {
+            let ipv6 = Ipv6Packet::new_checked(packet).ok()?;
+            let payload = &packet[IPV6_HEADER_LEN..ipv6.total_len()];
+            Some((ipv6.src_addr().into(), ipv6.dst_addr().into(), payload))
+        }
+    }
+}
@@ -0,0 +1,526 @@
+//! The routing table shared by all interfaces, and the addresses of each
+//! interface.
+//!
+//! All sockets are in one socket set polled by every interface, and smoltcp
+//! sends a packet through the first interface that can route it. So the
+//! routes of each smoltcp interface are generated from the table, leaving out
+//! the destinations that a longer prefix routes through another interface.
+//! smoltcp also sends the packets to the networks of the addresses of an
+//! interface directly, so these networks may neither overlap the networks of
+//! another interface nor contain its routes. Then only the interface chosen
+//! by the longest-prefix match can route a packet.
+//!
+//! Packets are not forwarded between interfaces.
+
+use alloc::{string::String, vec::Vec};
+use core::net::IpAddr;
+
+use axerrno::{ax_err, AxResult};
+use axsync::Mutex;
+use smoltcp::iface::Route as IfaceRoute;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr};
+use super::IFACES;
+
+/// A route of the routing table.
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    /// The destination network.
+    pub dest: IpAddr,
+    /// The prefix length of the destination network.
+    pub prefix_len: u8,
+    /// The next hop, `None` for the network an interface is directly
+    /// connected to.
+    pub gateway: Option<IpAddr>,
+    /// The name of the interface to send the packets through.
+    pub iface: String,
+}
+
+/// A route added with a gateway.
+struct Route {
+    cidr: IpCidr,
+    gateway: IpAddress,
+    iface: usize,
+}
+
+static ROUTES: Mutex<Vec<Route>> = Mutex::new(Vec::new());
+
+fn to_bits(addr: IpAddress) -> (u128, u8) {
+    match addr {
+        IpAddress::Ipv4(addr) => (u32::from_be_bytes(addr.0) as u128, 32),
+        IpAddress::Ipv6(addr) => (u128::from_be_bytes(addr.0), 128),
+    }
+}
+
+fn from_bits(bits: u128, width: u8) -> IpAddress {
+    if width == 32 {
+        IpAddress::Ipv4(Ipv4Address((bits as u32).to_be_bytes()))
+    } else {
+        IpAddress::Ipv6(Ipv6Address(bits.to_be_bytes()))
+    }
+}
+
+/// Returns the mask of the network bits of a prefix.
+fn prefix_mask(prefix_len: u8, width: u8) -> u128 {
+    let all = u128::MAX >> (128 - width as u32);
+    all & !(all.checked_shr(prefix_len as u32).unwrap_or(0))
+}
+
+/// Returns the default route of the address family of `addr`.
+pub(super) fn default_cidr(addr: IpAddress) -> IpCidr {
+    match addr {
+        IpAddress::Ipv4(_) => IpCidr::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 0),
+        IpAddress::Ipv6(_) => IpCidr::new(IpAddress::Ipv6(Ipv6Address::UNSPECIFIED), 0),
+    }
+}
+
+/// Clears the host bits of `cidr`.
+fn network(cidr: IpCidr) -> IpCidr {
+    let (bits, width) = to_bits(cidr.address());
+    let mask = prefix_mask(cidr.prefix_len(), width);
+    IpCidr::new(from_bits(bits & mask, width), cidr.prefix_len())
+}
+
+/// Checks the prefix length of `addr`, and returns the network.
+fn checked_cidr(addr: IpAddress, prefix_len: u8, host_bits: bool) -> AxResult<IpCidr> {
+    let (bits, width) = to_bits(addr);
+    if prefix_len > width {
+        return ax_err!(InvalidInput, "invalid prefix length");
+    }
+    if !host_bits && bits & !prefix_mask(prefix_len, width) != 0 {
+        return ax_err!(InvalidInput, "invalid prefix for the prefix length");
+    }
+    Ok(IpCidr::new(addr, prefix_len))
+}
+
+/// Returns whether `a` and `b` have an address in common.
+fn overlaps(a: &IpCidr, b: &IpCidr) -> bool {
+    a.contains_subnet(b) || b.contains_subnet(a)
+}
+
+fn is_link_local(cidr: &IpCidr) -> bool {
+    matches!(cidr.address(), IpAddress::Ipv6(addr) if addr.is_link_local())
+}
+
+/// Pushes the parts of `cidr` not covered by any of `holes` to `out`, as few
+/// prefixes as possible.
+fn subtract(cidr: IpCidr, holes: &[IpCidr], out: &mut Vec<IpCidr>) {
+    if holes.iter().any(|hole| hole.contains_subnet(&cidr)) {
+        return;
+    }
+    if !holes.iter().any(|hole| cidr.contains_subnet(hole)) {
+        out.push(cidr);
+        return;
+    }
+    // prefixes are either nested or disjoint, so split it into halves
+    let (bits, width) = to_bits(cidr.address());
+    let len = cidr.prefix_len() + 1;
+    let high = bits | (1 << (width - len));
+    subtract(IpCidr::new(from_bits(bits, width), len), holes, out);
+    subtract(IpCidr::new(from_bits(high, width), len), holes, out);
+}
+
+/// Returns the networks that interfaces are directly connected to, except
+/// the link-local ones that every interface has.
+fn connected() -> Vec<(usize, IpCidr)> {
+    let mut nets = Vec::new();
+    for (i, iface) in IFACES.iter().enumerate() {
+        for cidr in iface.iface.lock().ip_addrs() {
+            if !is_link_local(cidr) {
+                nets.push((i, network(*cidr)));
+            }
+        }
+    }
+    nets
+}
+
+/// Regenerates the routes of the smoltcp interfaces from `routes`.
+///
+/// The connected networks are routed by smoltcp without holes, so
+/// [`check_network`] keeps them apart from those of other interfaces.
+fn sync(routes: &[Route]) -> AxResult {
+    let nets = connected();
+    let all = || {
+        nets.iter()
+            .copied()
+            .chain(routes.iter().map(|r| (r.iface, r.cidr)))
+    };
+    for (i, iface) in IFACES.iter().enumerate() {
+        let mut iface_routes = Vec::new();
+        for route in routes.iter().filter(|r| r.iface == i) {
+            let holes: Vec<_> = all()
+                .filter(|&(j, cidr)| j != i && cidr.prefix_len() > route.cidr.prefix_len())
+                .map(|(_, cidr)| cidr)
+                .collect();
+            let mut parts = Vec::new();
+            subtract(route.cidr, &holes, &mut parts);
+            iface_routes.extend(parts.into_iter().map(|cidr| IfaceRoute {
+                cidr,
+                via_router: route.gateway,
+                preferred_until: None,
+                expires_at: None,
+            }));
+        }
+        let mut full = false;
+        iface.iface.lock().routes_mut().update(|storage| {
+            storage.clear();
+            for route in iface_routes {
+                if storage.push(route).is_err() {
+                    full = true;
+                    break;
+                }
+            }
+        });
+        if full {
+            return ax_err!(StorageFull, "too many routes on an interface");
+        }
+    }
+    Ok(())
+}
+
+/// Regenerates the routes of the smoltcp interfaces after the addresses of
+/// an interface are changed.
+pub(super) fn sync_routes() {
+    if let Err(e) = sync(&ROUTES.lock()) {
+        warn!("failed to update routes: {:?}", e);
+    }
+}
+
+fn iface_index(name: &str) -> AxResult<usize> {
+    match IFACES.iter().position(|iface| iface.name() == name) {
+        Some(i) => Ok(i),
+        None => ax_err!(NotFound, "no such interface"),
+    }
+}
+
+/// Checks that the network `cidr` of an address of the interface `iface`
+/// neither overlaps the networks in `nets` of other interfaces nor contains
+/// the `routes` through them.
+fn check_network(
+    iface: usize,
+    cidr: IpCidr,
+    nets: &[(usize, IpCidr)],
+    routes: &[Route],
+) -> AxResult {
+    if is_link_local(&cidr) {
+        return Ok(());
+    }
+    if nets
+        .iter()
+        .any(|(i, net)| *i != iface && overlaps(net, &cidr))
+    {
+        return ax_err!(InvalidInput, "network of another interface");
+    }
+    if routes
+        .iter()
+        .any(|r| r.iface != iface && cidr.contains_subnet(&r.cidr))
+    {
+        return ax_err!(
+            InvalidInput,
+            "network with routes through another interface"
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the network of the address `cidr` can be added to the
+/// interface `iface`, see [`check_network`].
+pub(super) fn check_addr(iface: usize, cidr: IpCidr) -> AxResult {
+    check_network(iface, network(cidr), &connected(), &ROUTES.lock())
+}
+
+/// Returns the interface of the longest prefix in `nets` that contains
+/// `addr`, the first one of the same length.
+fn longest_match(
+    addr: IpAddress,
+    nets: impl IntoIterator<Item = (usize, IpCidr)>,
+) -> Option<usize> {
+    let mut best: Option<(usize, u8)> = None;
+    for (i, cidr) in nets {
+        if cidr.contains_addr(&addr) && best.map_or(true, |(_, len)| cidr.prefix_len() > len) {
+            best = Some((i, cidr.prefix_len()));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// Returns the index of the interface to send the packets to `addr` through,
+/// by the longest-prefix match.
+pub(super) fn lookup(addr: IpAddress) -> Option<usize> {
+    let routes = ROUTES.lock();
+    // connected networks come first and take precedence over routes of the
+    // same prefix length
+    let nets = connected()
+        .into_iter()
+        .chain(routes.iter().map(|r| (r.iface, r.cidr)));
+    longest_match(addr, nets)
+}
+
+/// Returns the source address of the packets to `addr`, an address of the
+/// interface chosen by [`lookup`]. Link-local addresses are only used for
+/// link-local destinations unless there is no other address.
+pub(super) fn source_addr(addr: IpAddress) -> Option<IpAddress> {
+    let link_local = |addr: &IpAddress| matches!(addr, IpAddress::Ipv6(a) if a.is_link_local());
+    let iface = &IFACES[lookup(addr).unwrap_or(0)];
+    let addrs: Vec<_> = iface
+        .iface
+        .lock()
+        .ip_addrs()
+        .iter()
+        .map(|cidr| cidr.address())
+        .filter(|src| src.version() == addr.version())
+        .collect();
+    addrs
+        .iter()
+        .find(|src| link_local(src) == link_local(&addr))
+        .or(addrs.first())
+        .copied()
+}
+
+/// Adds a route to the table, replacing the one to the same destination if
+/// `replace` is `true`.
+pub(super) fn insert(cidr: IpCidr, gateway: IpAddress, iface: usize, replace: bool) -> AxResult {
+    let mut routes = ROUTES.lock();
+    let old = match routes.iter().position(|r| r.cidr == cidr) {
+        Some(_) if !replace => return ax_err!(AlreadyExists, "route exists"),
+        Some(i) => Some(routes.remove(i)),
+        None => None,
+    };
+    routes.push(Route {
+        cidr,
+        gateway,
+        iface,
+    });
+    if let Err(e) = sync(&routes) {
+        routes.pop();
+        routes.extend(old);
+        sync(&routes)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Removes the route to `cidr` from the table. Returns `false` if there is no
+/// such route.
+pub(super) fn remove(cidr: IpCidr) -> bool {
+    let mut routes = ROUTES.lock();
+    let Some(i) = routes.iter().position(|r| r.cidr == cidr) else {
+        return false;
+    };
+    routes.remove(i);
+    // removing a route never needs more space
+    sync(&routes).ok();
+    true
+}
+
+/// Returns all routes, with those of the directly connected networks first.
+pub fn routes() -> Vec<RouteInfo> {
+    if !IFACES.is_inited() {
+        return Vec::new();
+    }
+    let info = |cidr: IpCidr, gateway: Option<IpAddress>, iface: usize| RouteInfo {
+        dest: into_core_ipaddr(cidr.address()),
+        prefix_len: cidr.prefix_len(),
+        gateway: gateway.map(into_core_ipaddr),
+        iface: IFACES[iface].name().into(),
+    };
+    let mut infos: Vec<_> = connected()
+        .into_iter()
+        .map(|(i, cidr)| info(cidr, None, i))
+        .collect();
+    infos.extend(
+        ROUTES
+            .lock()
+            .iter()
+            .map(|r| info(r.cidr, Some(r.gateway), r.iface)),
+    );
+    infos
+}
+
+/// Adds a route to the network `dest`/`prefix_len` via `gateway`.
+///
+/// The packets are sent through the interface named `iface`, or the one
+/// directly connected to the gateway if it is `None`. The network must not be
+/// inside a network that another interface is directly connected to.
+pub fn add_route(dest: IpAddr, prefix_len: u8, gateway: IpAddr, iface: Option<&str>) -> AxResult {
+    let cidr = checked_cidr(from_core_ipaddr(dest), prefix_len, false)?;
+    let gateway = from_core_ipaddr(gateway);
+    if to_bits(gateway).1 != to_bits(cidr.address()).1 {
+        return ax_err!(InvalidInput, "gateway of another address family");
+    }
+    let iface = match iface {
+        Some(name) => iface_index(name)?,
+        None => match connected()
+            .into_iter()
+            .find(|(_, net)| net.contains_addr(&gateway))
+        {
+            Some((i, _)) => i,
+            None => return ax_err!(InvalidInput, "gateway unreachable"),
+        },
+    };
+    if connected()
+        .iter()
+        .any(|(i, net)| *i != iface && net.contains_subnet(&cidr))
+    {
+        return ax_err!(InvalidInput, "inside a network of another interface");
+    }
+    insert(cidr, gateway, iface, false)
+}
+
+/// Removes the route to the network `dest`/`prefix_len`.
+///
+/// The routes of the directly connected networks are removed with the
+/// addresses, see [`del_ip_addr`].
+pub fn del_route(dest: IpAddr, prefix_len: u8) -> AxResult {
+    let cidr = checked_cidr(from_core_ipaddr(dest), prefix_len, false)?;
+    if remove(cidr) {
+        Ok(())
+    } else {
+        ax_err!(NotFound, "no such route")
+    }
+}
+
+/// Adds the address `addr`/`prefix_len` to the interface named `iface`.
+///
+/// The network of the address must neither overlap a network that another
+/// interface is directly connected to, nor contain a route through another
+/// interface.
+pub fn add_ip_addr(iface: &str, addr: IpAddr, prefix_len: u8) -> AxResult {
+    let cidr = checked_cidr(from_core_ipaddr(addr), prefix_len, true)?;
+    let index = iface_index(iface)?;
+    check_addr(index, cidr)?;
+    let iface = &IFACES[index];
+    let mut res = Ok(());
+    iface.iface.lock().update_ip_addrs(|addrs| {
+        res = if addrs.iter().any(|c| c.address() == cidr.address()) {
+            ax_err!(AlreadyExists, "address exists")
+        } else if addrs.push(cidr).is_err() {
+            ax_err!(StorageFull, "too many addresses on the interface")
+        } else {
+            Ok(())
+        };
+    });
+    res?;
+    sync_routes();
+    Ok(())
+}
+
+/// Removes the address `addr` from the interface named `iface`.
+pub fn del_ip_addr(iface: &str, addr: IpAddr) -> AxResult {
+    let addr = from_core_ipaddr(addr);
+    let iface = &IFACES[iface_index(iface)?];
+    if !iface.remove_ip_addrs(|cidr| cidr.address() == addr) {
+        return ax_err!(NotFound, "no such address");
+    }
+    sync_routes();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn addr(s: &str) -> IpAddress {
+        s.parse().unwrap()
+    }
+
+    fn route(dest: &str, iface: usize) -> Route {
+        let dest = cidr(dest);
+        Route {
+            cidr: dest,
+            gateway: dest.address(),
+            iface,
+        }
+    }
+
+    #[test]
+    fn test_prefix_mask() {
+        assert_eq!(prefix_mask(0, 32), 0);
+        assert_eq!(prefix_mask(8, 32), 0xff00_0000);
+        assert_eq!(prefix_mask(32, 32), 0xffff_ffff);
+        assert_eq!(prefix_mask(0, 128), 0);
+        assert_eq!(prefix_mask(64, 128), u128::MAX << 64);
+        assert_eq!(prefix_mask(128, 128), u128::MAX);
+        assert_eq!(network(cidr("10.1.2.3/16")), cidr("10.1.0.0/16"));
+    }
+
+    #[test]
+    fn test_subtract() {
+        let mut out = Vec::new();
+        subtract(cidr("10.0.0.0/8"), &[cidr("192.168.0.0/16")], &mut out);
+        assert_eq!(out, [cidr("10.0.0.0/8")]);
+
+        out.clear();
+        subtract(cidr("10.1.0.0/16"), &[cidr("10.0.0.0/8")], &mut out);
+        assert!(out.is_empty());
+
+        out.clear();
+        subtract(cidr("0.0.0.0/0"), &[cidr("10.0.0.0/8")], &mut out);
+        let expected = [
+            "0.0.0.0/5",
+            "8.0.0.0/7",
+            "11.0.0.0/8",
+            "12.0.0.0/6",
+            "16.0.0.0/4",
+            "32.0.0.0/3",
+            "64.0.0.0/2",
+            "128.0.0.0/1",
+        ];
+        assert_eq!(out, expected.map(cidr));
+
+        // nested holes and holes of another family
+        out.clear();
+        let holes = [cidr("10.0.0.0/9"), cidr("10.0.0.0/10"), cidr("::/0")];
+        subtract(cidr("10.0.0.0/8"), &holes, &mut out);
+        assert_eq!(out, [cidr("10.128.0.0/9")]);
+
+        out.clear();
+        subtract(
+            cidr("2001:db8::/32"),
+            &[cidr("2001:db8:8000::/33")],
+            &mut out,
+        );
+        assert_eq!(out, [cidr("2001:db8::/33")]);
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let nets = [
+            (0, cidr("10.0.0.0/16")),
+            (1, cidr("10.0.2.0/24")),
+            (1, cidr("0.0.0.0/0")),
+            (0, cidr("2001:db8::/64")),
+            (2, cidr("10.0.2.0/24")),
+        ];
+        assert_eq!(longest_match(addr("10.0.1.5"), nets), Some(0));
+        assert_eq!(longest_match(addr("10.0.2.5"), nets), Some(1));
+        assert_eq!(longest_match(addr("8.8.8.8"), nets), Some(1));
+        assert_eq!(longest_match(addr("2001:db8::1"), nets), Some(0));
+        assert_eq!(longest_match(addr("2001:db9::1"), nets), None);
+    }
+
+    #[test]
+    fn test_check_network() {
+        let nets = [(0, cidr("10.0.0.0/16")), (1, cidr("192.168.1.0/24"))];
+        let routes = [route("172.16.0.0/12", 1)];
+        let check = |iface, net| check_network(iface, cidr(net), &nets, &routes);
+        assert!(check(1, "10.1.0.0/16").is_ok());
+        assert!(check(0, "10.0.2.0/24").is_ok());
+        // overlapping networks of another interface
+        assert!(check(1, "10.0.2.0/24").is_err());
+        assert!(check(1, "10.0.0.0/8").is_err());
+        assert!(check(2, "192.168.0.0/16").is_err());
+        // routes through another interface
+        assert!(check(0, "172.16.0.0/16").is_ok());
+        assert!(check(0, "172.0.0.0/8").is_err());
+        assert!(check(1, "172.0.0.0/8").is_ok());
+        // every interface has a link-local address
+        let nets = [(0, cidr("fe80::/64"))];
+        assert!(check_network(1, cidr("fe80::/64"), &nets, &[]).is_ok());
+    }
+}
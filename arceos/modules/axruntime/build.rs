@@ -0,0 +1,15 @@
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    // the initramfs embedded in the kernel image, empty if not given
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("initramfs.cpio");
+    match env::var("AX_INITRAMFS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::copy(&path, &out)
+                .unwrap_or_else(|e| panic!("failed to read initramfs {:?}: {}", path, e));
+        }
+        _ => fs::write(&out, []).unwrap(),
+    }
+    println!("cargo:rerun-if-env-changed=AX_INITRAMFS");
+}
@@ -0,0 +1,143 @@
+//! The initramfs, a cpio archive unpacked into the root RAM filesystem.
+//!
+//! It is either embedded in the kernel image from the file at `AX_INITRAMFS`
+//! at build time, or loaded into memory by the loader (e.g. QEMU `-initrd`),
+//! which passes its range in the `linux,initrd-start` and `linux,initrd-end`
+//! properties of `/chosen` in the device tree. The embedded one is preferred.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axhal::mem::{phys_to_virt, PAGE_SIZE_4K};
+
+static EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+/// The physical range of the loaded initramfs, page aligned, or empty.
+static LOADED_START: AtomicUsize = AtomicUsize::new(0);
+static LOADED_END: AtomicUsize = AtomicUsize::new(0);
+/// The size of the loaded initramfs in the range above.
+static LOADED_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// Finds the initramfs passed by the loader in the device tree at `dtb`.
+///
+/// It must be called before the allocator is initialized, which does not
+/// use the memory of the initramfs until [`release`].
+pub(crate) fn init(dtb: usize) {
+    if !EMBEDDED.is_empty() || dtb == 0 {
+        return;
+    }
+    if let Some((start, end)) = find_initrd(dtb) {
+        info!("Found initramfs at [PA:{:#x}, PA:{:#x})", start, end);
+        LOADED_START.store(start & !(PAGE_SIZE_4K - 1), Ordering::Relaxed);
+        LOADED_END.store(
+            (end + PAGE_SIZE_4K - 1) & !(PAGE_SIZE_4K - 1),
+            Ordering::Relaxed,
+        );
+        LOADED_SIZE.store(end - start, Ordering::Relaxed);
+    }
+}
+
+/// Returns the physical range of memory used by the loaded initramfs.
+pub(crate) fn reserved() -> Option<(usize, usize)> {
+    let start = LOADED_START.load(Ordering::Relaxed);
+    let end = LOADED_END.load(Ordering::Relaxed);
+    (start < end).then_some((start, end))
+}
+
+/// Returns the content of the initramfs, if any.
+pub(crate) fn archive() -> Option<&'static [u8]> {
+    if !EMBEDDED.is_empty() {
+        return Some(EMBEDDED);
+    }
+    let (start, _) = reserved()?;
+    let size = LOADED_SIZE.load(Ordering::Relaxed);
+    // SAFETY: the memory is mapped and not used by the allocator until released.
+    let start = phys_to_virt(start.into()).as_ptr();
+    Some(unsafe { core::slice::from_raw_parts(start, size) })
+}
+
+/// Gives the memory of the loaded initramfs to the allocator after it is
+/// unpacked.
+pub(crate) fn release() {
+    let Some((start, end)) = reserved() else {
+        return;
+    };
+    LOADED_END.store(start, Ordering::Relaxed);
+    let vaddr = phys_to_virt(start.into()).as_usize();
+    #[cfg(feature = "alloc")]
+    let res = axalloc::global_add_memory(vaddr, end - start);
+    #[cfg(feature = "alt_alloc")]
+    let res = alt_axalloc::global_add_memory(vaddr, end - start);
+    #[cfg(not(any(feature = "alloc", feature = "alt_alloc")))]
+    let res: Result<(), ()> = Ok(());
+    if let Err(e) = res {
+        warn!("failed to release the initramfs memory: {:?}", e);
+    }
+}
+
+/// Returns the range of the initrd in the properties of `/chosen`.
+fn find_initrd(dtb: usize) -> Option<(usize, usize)> {
+    let base = phys_to_virt(dtb.into()).as_ptr();
+    // SAFETY: the header of a device tree is 40 bytes.
+    let header = unsafe { core::slice::from_raw_parts(base, 40) };
+    let be32 = |data: &[u8], off: usize| {
+        let bytes = data.get(off..off + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    if be32(header, 0)? != FDT_MAGIC {
+        return None;
+    }
+    let total_size = be32(header, 4)? as usize;
+    // SAFETY: the whole device tree is `total_size` bytes.
+    let fdt = unsafe { core::slice::from_raw_parts(base, total_size) };
+    let mut off = be32(fdt, 8)? as usize;
+    let strings = be32(fdt, 12)? as usize;
+
+    let (mut depth, mut in_chosen) = (0, false);
+    let (mut start, mut end) = (None, None);
+    loop {
+        let token = be32(fdt, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let len = fdt.get(off..)?.iter().position(|&b| b == 0)?;
+                depth += 1;
+                if depth == 2 {
+                    in_chosen = &fdt[off..off + len] == b"chosen";
+                }
+                off = (off + len + 4) & !3;
+            }
+            FDT_END_NODE if in_chosen && depth == 2 => break,
+            FDT_END_NODE => depth -= 1,
+            FDT_PROP => {
+                let len = be32(fdt, off)? as usize;
+                let name_off = strings + be32(fdt, off + 4)? as usize;
+                let value = fdt.get(off + 8..off + 8 + len)?;
+                off = (off + 8 + len + 3) & !3;
+                if !in_chosen || depth != 2 {
+                    continue;
+                }
+                let name_len = fdt.get(name_off..)?.iter().position(|&b| b == 0)?;
+                let value = match len {
+                    4 => be32(value, 0)? as usize,
+                    8 => u64::from_be_bytes(value.try_into().unwrap()) as usize,
+                    _ => continue,
+                };
+                match &fdt[name_off..name_off + name_len] {
+                    b"linux,initrd-start" => start = Some(value),
+                    b"linux,initrd-end" => end = Some(value),
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            _ => break,
+        }
+    }
+    let (start, end) = (start?, end?);
+    (start < end).then_some((start, end))
+}
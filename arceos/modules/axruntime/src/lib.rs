@@ -10,9 +10,17 @@
 //! - `irq`: Enable interrupt handling support.
 //! - `multitask`: Enable multi-threading support.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
-//! - `fs`: Enable filesystem support.
+//! - `fs`: Enable filesystem support, with the kernel state in `/proc` and
+//!   the devices in `/sys`.
 //! - `net`: Enable networking support.
+//! - `dhcp`: Configure the network with DHCP, and renew the lease in a
+//!   background task if `multitask` and `irq` are enabled.
 //! - `display`: Enable graphics support.
+//! - `initramfs`: Use a RAM filesystem unpacked from a cpio archive as the
+//!   root, either embedded from the file at `AX_INITRAMFS` at build time, or
+//!   loaded by the loader as the initrd in the device tree.
+//! - `9p`: Register the virtio-9p devices, whose shared host directories can
+//!   be mounted as type `9p`.
 //!
 //! All the features are optional and disabled by default.
 
@@ -28,6 +36,13 @@ mod lang_items;
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "initramfs")]
+mod initramfs;
+#[cfg(feature = "fs")]
+mod procfs;
+#[cfg(feature = "fs")]
+mod sysfs;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -141,6 +156,9 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         );
     }
 
+    #[cfg(feature = "initramfs")]
+    self::initramfs::init(dtb);
+
     #[cfg(any(feature = "alloc", feature = "alt_alloc"))]
     init_allocator();
 
@@ -159,15 +177,39 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         let all_devices = axdriver::init_drivers();
 
         #[cfg(feature = "fs")]
-        axfs::init_filesystems(all_devices.block);
+        {
+            let device_classes = self::sysfs::device_classes(&all_devices);
+            #[cfg(feature = "initramfs")]
+            {
+                match self::initramfs::archive() {
+                    Some(archive) => axfs::init_filesystems_initramfs(all_devices.block, archive),
+                    None => axfs::init_filesystems(all_devices.block),
+                }
+                self::initramfs::release();
+            }
+            #[cfg(not(feature = "initramfs"))]
+            axfs::init_filesystems(all_devices.block);
+            #[cfg(feature = "9p")]
+            axfs::register_9p_devices(all_devices.p9);
+            self::sysfs::init_sysfs(device_classes);
+        }
+
+        #[cfg(all(feature = "fs", feature = "multitask", feature = "irq"))]
+        init_writeback();
 
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
 
+        #[cfg(all(feature = "dhcp", feature = "multitask", feature = "irq"))]
+        init_dhcp_renewal();
+
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
     }
 
+    #[cfg(feature = "fs")]
+    self::procfs::init_procfs();
+
     #[cfg(feature = "smp")]
     self::mp::start_secondary_cpus(cpu_id);
 
@@ -192,6 +234,11 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
     unsafe { main() };
 
+    #[cfg(feature = "fs")]
+    if let Err(e) = axfs::api::sync() {
+        warn!("failed to sync filesystems: {:?}", e);
+    }
+
     #[cfg(feature = "multitask")]
     axtask::exit(0);
     #[cfg(not(feature = "multitask"))]
@@ -201,30 +248,53 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     }
 }
 
+/// Spawns a task to write back cached filesystem data periodically.
+#[cfg(all(feature = "fs", feature = "multitask", feature = "irq"))]
+fn init_writeback() {
+    let writeback = || loop {
+        axtask::sleep(axfs::WRITEBACK_INTERVAL);
+        if let Err(e) = axfs::api::sync() {
+            warn!("failed to write back filesystems: {:?}", e);
+        }
+    };
+    axtask::spawn_raw(writeback, "writeback".into(), axconfig::TASK_STACK_SIZE);
+}
+
+/// Spawns a task to poll the network periodically, so that the DHCP lease is
+/// renewed even if no application is using the network.
+#[cfg(all(feature = "dhcp", feature = "multitask", feature = "irq"))]
+fn init_dhcp_renewal() {
+    let renewal = || loop {
+        axtask::sleep(axnet::DHCP_POLL_INTERVAL);
+        axnet::poll_interfaces();
+    };
+    axtask::spawn_raw(renewal, "dhcp".into(), axconfig::TASK_STACK_SIZE);
+}
+
 #[cfg(feature = "alloc")]
 fn init_allocator() {
-    use axhal::mem::{memory_regions, phys_to_virt, MemRegionFlags};
+    use axhal::mem::phys_to_virt;
 
     info!("Initialize global memory allocator...");
     info!("  use {} allocator.", axalloc::global_allocator().name());
 
     let mut max_region_size = 0;
     let mut max_region_paddr = 0.into();
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.size > max_region_size {
-            max_region_size = r.size;
-            max_region_paddr = r.paddr;
+    for (paddr, size) in free_regions() {
+        if size > max_region_size {
+            max_region_size = size;
+            max_region_paddr = paddr;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr == max_region_paddr {
-            axalloc::global_init(phys_to_virt(r.paddr).as_usize(), r.size);
+    for (paddr, size) in free_regions() {
+        if paddr == max_region_paddr {
+            axalloc::global_init(phys_to_virt(paddr).as_usize(), size);
             break;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr != max_region_paddr {
-            axalloc::global_add_memory(phys_to_virt(r.paddr).as_usize(), r.size)
+    for (paddr, size) in free_regions() {
+        if paddr != max_region_paddr {
+            axalloc::global_add_memory(phys_to_virt(paddr).as_usize(), size)
                 .expect("add heap memory region failed");
         }
     }
@@ -232,33 +302,59 @@ fn init_allocator() {
 
 #[cfg(feature = "alt_alloc")]
 fn init_allocator() {
-    use axhal::mem::{memory_regions, phys_to_virt, MemRegionFlags};
+    use axhal::mem::phys_to_virt;
 
     info!("Initialize global memory allocator...");
     info!("  use {} allocator.", alt_axalloc::global_allocator().name());
 
     let mut max_region_size = 0;
     let mut max_region_paddr = 0.into();
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.size > max_region_size {
-            max_region_size = r.size;
-            max_region_paddr = r.paddr;
+    for (paddr, size) in free_regions() {
+        if size > max_region_size {
+            max_region_size = size;
+            max_region_paddr = paddr;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr == max_region_paddr {
-            alt_axalloc::global_init(phys_to_virt(r.paddr).as_usize(), r.size);
+    for (paddr, size) in free_regions() {
+        if paddr == max_region_paddr {
+            alt_axalloc::global_init(phys_to_virt(paddr).as_usize(), size);
             break;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr != max_region_paddr {
-            alt_axalloc::global_add_memory(phys_to_virt(r.paddr).as_usize(), r.size)
+    for (paddr, size) in free_regions() {
+        if paddr != max_region_paddr {
+            alt_axalloc::global_add_memory(phys_to_virt(paddr).as_usize(), size)
                 .expect("add heap memory region failed");
         }
     }
 }
 
+/// Returns the physical address and size of the free memory regions, except
+/// the memory of the initramfs loaded by the loader.
+#[cfg(any(feature = "alloc", feature = "alt_alloc"))]
+fn free_regions() -> impl Iterator<Item = (axhal::mem::PhysAddr, usize)> {
+    use axhal::mem::{memory_regions, MemRegionFlags};
+
+    #[cfg(feature = "initramfs")]
+    let reserved = self::initramfs::reserved();
+    #[cfg(not(feature = "initramfs"))]
+    let reserved: Option<(usize, usize)> = None;
+
+    memory_regions()
+        .filter(|r| r.flags.contains(MemRegionFlags::FREE))
+        .flat_map(move |r| {
+            let (start, end) = (r.paddr.as_usize(), r.paddr.as_usize() + r.size);
+            let parts = match reserved {
+                Some((rs, re)) if rs < end && re > start => [(start, rs), (re, end)],
+                _ => [(start, end), (end, end)],
+            };
+            parts
+                .into_iter()
+                .filter(|(s, e)| s < e)
+                .map(|(s, e)| (s.into(), e - s))
+        })
+}
+
 #[cfg(feature = "irq")]
 fn init_interrupt() {
     use axhal::time::TIMER_IRQ_NUM;
@@ -0,0 +1,280 @@
+//! Files of `/proc` rendered from the state of the other modules.
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// Adds the files about the enabled modules to `/proc`.
+pub(crate) fn init_procfs() {
+    let Some(root) = axfs::procfs_root() else {
+        return;
+    };
+    root.add_file("uptime", uptime);
+    root.add_file("cpuinfo", cpuinfo);
+    #[cfg(feature = "alloc")]
+    root.add_file("meminfo", meminfo);
+    #[cfg(feature = "irq")]
+    root.add_file("interrupts", interrupts);
+    #[cfg(feature = "multitask")]
+    init_tasks(&root);
+    #[cfg(feature = "net")]
+    init_net(&root);
+}
+
+fn uptime() -> String {
+    let now = axhal::time::monotonic_time();
+    // the idle time is not tracked
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+fn cpuinfo() -> String {
+    let mut s = String::new();
+    for cpu in 0..axconfig::SMP {
+        writeln!(s, "processor\t: {}", cpu).ok();
+        writeln!(s, "arch\t\t: {}", option_env!("AX_ARCH").unwrap_or("")).ok();
+        writeln!(
+            s,
+            "platform\t: {}\n",
+            option_env!("AX_PLATFORM").unwrap_or("")
+        )
+        .ok();
+    }
+    s
+}
+
+#[cfg(feature = "alloc")]
+fn meminfo() -> String {
+    use axhal::mem::PAGE_SIZE_4K;
+
+    let allocator = axalloc::global_allocator();
+    let total = (allocator.used_pages() + allocator.available_pages()) * PAGE_SIZE_4K;
+    // free bytes of the byte allocator can be used without allocating pages
+    let free = allocator.available_pages() * PAGE_SIZE_4K + allocator.available_bytes();
+    format!(
+        "MemTotal:{:>16} kB\nMemFree:{:>17} kB\nMemAvailable:{:>12} kB\n",
+        total / 1024,
+        free / 1024,
+        free / 1024,
+    )
+}
+
+#[cfg(feature = "irq")]
+fn interrupts() -> String {
+    let mut s = format!("{:6}{:>10}\n", "", "total");
+    for (irq, count) in axhal::irq::irq_counts() {
+        writeln!(s, "{:>4}: {:>10}", irq, count).ok();
+    }
+    s
+}
+
+/// Generates a directory for each task, and `self` for the current one.
+#[cfg(feature = "multitask")]
+fn init_tasks(root: &axfs::pseudofs::PseudoDir) {
+    use alloc::{string::ToString, sync::Arc, vec::Vec};
+    use axfs::pseudofs::PseudoDir;
+    use axtask::AxTaskRef;
+
+    fn task_dir(task: &AxTaskRef) -> Arc<PseudoDir> {
+        let dir = PseudoDir::new();
+        let weak = Arc::downgrade(task);
+        dir.add_file("stat", move || {
+            weak.upgrade().map_or_else(String::new, task_stat)
+        });
+        let weak = Arc::downgrade(task);
+        dir.add_file("status", move || {
+            weak.upgrade().map_or_else(String::new, task_status)
+        });
+        dir
+    }
+
+    root.set_entries(|| {
+        let mut entries: Vec<_> = axtask::all_tasks()
+            .iter()
+            .map(|task| (task.id().as_u64().to_string(), task_dir(task)))
+            .collect();
+        entries.push(("self".into(), task_dir(axtask::current().as_task_ref())));
+        entries
+    });
+}
+
+#[cfg(feature = "multitask")]
+fn task_stat(task: axtask::AxTaskRef) -> String {
+    let id = task.id().as_u64();
+    // pid (comm) state ppid pgrp session tty_nr tpgid flags, then the fault
+    // and time counters, priority nice num_threads itrealvalue starttime
+    format!(
+        "{id} ({}) {} 0 {id} {id} 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 0\n",
+        task.name(),
+        task.state_char(),
+    )
+}
+
+#[cfg(feature = "multitask")]
+fn task_status(task: axtask::AxTaskRef) -> String {
+    let id = task.id().as_u64();
+    let state = match task.state_char() {
+        'R' => "R (running)",
+        'S' => "S (sleeping)",
+        _ => "Z (zombie)",
+    };
+    format!(
+        "Name:\t{}\nState:\t{}\nTgid:\t{id}\nPid:\t{id}\nPPid:\t0\nThreads:\t1\n",
+        task.name(),
+        state,
+    )
+}
+
+/// Adds the network statistics and the tunables of `axnet`.
+#[cfg(feature = "net")]
+fn init_net(root: &axfs::pseudofs::PseudoDir) {
+    let net = root.dir("net");
+    net.add_file("dev", net_dev);
+    net.add_file("route", net_route);
+    net.add_file("ipv6_route", net_ipv6_route);
+    net.add_file("tcp", || net_sockets(axnet::tcp_sockets()));
+    net.add_file("udp", || net_sockets(axnet::udp_sockets()));
+    root.dir("sys/net/core").add_writable_file(
+        "somaxconn",
+        || format!("{}\n", axnet::listen_backlog()),
+        |value| {
+            let backlog = value.parse().map_err(|_| axerrno::AxError::InvalidInput)?;
+            axnet::set_listen_backlog(backlog);
+            Ok(())
+        },
+    );
+}
+
+#[cfg(feature = "net")]
+fn net_dev() -> String {
+    let mut s = String::from(
+        "Inter-|   Receive                                                |  Transmit\n \
+         face |bytes    packets errs drop fifo frame compressed multicast|\
+         bytes    packets errs drop fifo colls carrier compressed\n",
+    );
+    for iface in axnet::interfaces() {
+        writeln!(
+            s,
+            "{:>6}: {:>7} {:>7}    0    0    0     0          0         0 \
+             {:>8} {:>7}    0    0    0     0       0          0",
+            iface.name, iface.rx_bytes, iface.rx_packets, iface.tx_bytes, iface.tx_packets,
+        )
+        .ok();
+    }
+    s
+}
+
+/// Formats the IPv4 routes like Linux, with the addresses in hex of the
+/// network byte order read as a native (little-endian) word.
+#[cfg(feature = "net")]
+fn net_route() -> String {
+    use core::net::{IpAddr, Ipv4Addr};
+
+    const RTF_UP: u16 = 0x1;
+    const RTF_GATEWAY: u16 = 0x2;
+    let hex = |ip: Ipv4Addr| u32::from_le_bytes(ip.octets());
+
+    let mut s = format!(
+        "{:<127}\n",
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT"
+    );
+    for route in axnet::routes() {
+        let IpAddr::V4(dest) = route.dest else {
+            continue;
+        };
+        let (gateway, flags) = match route.gateway {
+            Some(IpAddr::V4(gateway)) => (gateway, RTF_UP | RTF_GATEWAY),
+            _ => (Ipv4Addr::UNSPECIFIED, RTF_UP),
+        };
+        let mask = Ipv4Addr::from_bits(
+            u32::MAX
+                .checked_shl(32 - route.prefix_len as u32)
+                .unwrap_or(0),
+        );
+        let line = format!(
+            "{}\t{:08X}\t{:08X}\t{:04X}\t0\t0\t0\t{:08X}\t0\t0\t0",
+            route.iface,
+            hex(dest),
+            hex(gateway),
+            flags,
+            hex(mask),
+        );
+        writeln!(s, "{:<127}", line).ok();
+    }
+    s
+}
+
+/// Formats the IPv6 routes like Linux: destination, prefix length, source,
+/// source prefix length, next hop, metric, reference count, use count, flags
+/// and interface.
+#[cfg(feature = "net")]
+fn net_ipv6_route() -> String {
+    use core::net::{IpAddr, Ipv6Addr};
+
+    const RTF_UP: u32 = 0x1;
+    const RTF_GATEWAY: u32 = 0x2;
+
+    let mut s = String::new();
+    for route in axnet::routes() {
+        let IpAddr::V6(dest) = route.dest else {
+            continue;
+        };
+        let (gateway, flags) = match route.gateway {
+            Some(IpAddr::V6(gateway)) => (gateway, RTF_UP | RTF_GATEWAY),
+            _ => (Ipv6Addr::UNSPECIFIED, RTF_UP),
+        };
+        writeln!(
+            s,
+            "{:032x} {:02x} {:032x} 00 {:032x} 00000000 00000000 00000000 {:08x} {:>8}",
+            dest.to_bits(),
+            route.prefix_len,
+            0,
+            gateway.to_bits(),
+            flags,
+            route.iface,
+        )
+        .ok();
+    }
+    s
+}
+
+#[cfg(feature = "net")]
+fn net_sockets(sockets: alloc::vec::Vec<axnet::SocketInfo>) -> String {
+    use core::net::{IpAddr, SocketAddr};
+
+    /// Formats the address like Linux, in hex of the network byte order read
+    /// as native (little-endian) words.
+    fn hex_addr(addr: SocketAddr) -> String {
+        let mut s = String::new();
+        match addr.ip() {
+            IpAddr::V4(ip) => write!(s, "{:08X}", u32::from_le_bytes(ip.octets())).ok(),
+            IpAddr::V6(ip) => ip
+                .octets()
+                .chunks(4)
+                .try_for_each(|w| write!(s, "{:08X}", u32::from_le_bytes(w.try_into().unwrap())))
+                .ok(),
+        };
+        write!(s, ":{:04X}", addr.port()).ok();
+        s
+    }
+
+    let mut s = String::from(
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   \
+         uid  timeout inode\n",
+    );
+    for (i, socket) in sockets.into_iter().enumerate() {
+        writeln!(
+            s,
+            "{:>4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 00000000     0        0 0",
+            i,
+            hex_addr(socket.local_addr),
+            hex_addr(socket.peer_addr),
+            socket.state,
+            socket.tx_queue,
+            socket.rx_queue,
+        )
+        .ok();
+    }
+    s
+}
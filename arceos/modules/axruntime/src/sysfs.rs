@@ -0,0 +1,149 @@
+//! Files of `/sys` about the discovered devices and the system.
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::{format, string::String};
+
+use axdriver::{AllDevices, DeviceBus};
+use axfs::pseudofs::PseudoDir;
+
+/// The name of the clocksource used by `axhal::time`, as Linux calls it.
+const CLOCKSOURCE: &str = if cfg!(target_arch = "x86_64") {
+    "tsc"
+} else if cfg!(target_arch = "riscv64") {
+    "riscv_clocksource"
+} else if cfg!(target_arch = "aarch64") {
+    "arch_sys_counter"
+} else if cfg!(target_arch = "loongarch64") {
+    "Constant"
+} else {
+    "jiffies"
+};
+
+/// Builds the directories of all devices, grouped by their class, e.g.
+/// `net/eth0`.
+///
+/// It must be called before the devices are passed to other modules, while
+/// the returned directory is added to `/sys` by [`init_sysfs`] later.
+pub(crate) fn device_classes(all_devices: &AllDevices) -> Arc<PseudoDir> {
+    #[allow(unused_imports)]
+    use axdriver::prelude::*;
+
+    let classes = PseudoDir::new();
+    #[cfg(feature = "net")]
+    for (i, (dev, bus)) in all_devices
+        .net
+        .iter()
+        .zip(all_devices.net.buses())
+        .enumerate()
+    {
+        let dir = device_dir(
+            &classes,
+            &format!("net/eth{}", i),
+            dev.device_name(),
+            "net",
+            bus,
+        );
+        let mac = dev.mac_address().0;
+        let mac = format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}\n",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        );
+        dir.add_file("address", move || mac.clone());
+    }
+    for (i, (dev, bus)) in all_devices
+        .block
+        .iter()
+        .zip(all_devices.block.buses())
+        .enumerate()
+    {
+        let dir = device_dir(
+            &classes,
+            &format!("block/blk{}", i),
+            dev.device_name(),
+            "block",
+            bus,
+        );
+        // in 512-byte sectors like Linux
+        let size = format!("{}\n", dev.num_blocks() * dev.block_size() as u64 / 512);
+        let block_size = format!("{}\n", dev.block_size());
+        dir.add_file("size", move || size.clone());
+        dir.add_file("block_size", move || block_size.clone());
+    }
+    #[cfg(feature = "display")]
+    for (i, (dev, bus)) in all_devices
+        .display
+        .iter()
+        .zip(all_devices.display.buses())
+        .enumerate()
+    {
+        let dir = device_dir(
+            &classes,
+            &format!("graphics/fb{}", i),
+            dev.device_name(),
+            "display",
+            bus,
+        );
+        let info = dev.info();
+        let virtual_size = format!("{},{}\n", info.width, info.height);
+        dir.add_file("virtual_size", move || virtual_size.clone());
+    }
+    classes
+}
+
+/// Creates the directory at `path` with the files common to all devices.
+fn device_dir(
+    classes: &PseudoDir,
+    path: &str,
+    name: &str,
+    ty: &'static str,
+    bus: &DeviceBus,
+) -> Arc<PseudoDir> {
+    let dir = classes.dir(path);
+    let name = format!("{}\n", name);
+    let bus = match *bus {
+        DeviceBus::Pci {
+            bus,
+            device,
+            function,
+        } => format!("pci 0000:{:02x}:{:02x}.{}\n", bus, device, function),
+        DeviceBus::Mmio { base, size } => format!("mmio {:#x}-{:#x}\n", base, base + size),
+        DeviceBus::Platform => String::from("platform\n"),
+    };
+    dir.add_file("name", move || name.clone());
+    dir.add_file("type", move || format!("{}\n", ty));
+    dir.add_file("bus", move || bus.clone());
+    dir
+}
+
+/// Adds the device directories built by [`device_classes`], the clocksource
+/// and the CPU topology to `/sys`.
+pub(crate) fn init_sysfs(device_classes: Arc<PseudoDir>) {
+    let Some(root) = axfs::sysfs_root() else {
+        return;
+    };
+    root.add_dir("class", device_classes);
+
+    let clocksource = root.dir("devices/system/clocksource/clocksource0");
+    clocksource.add_file("current_clocksource", || format!("{}\n", CLOCKSOURCE));
+    clocksource.add_file("available_clocksource", || format!("{}\n", CLOCKSOURCE));
+
+    let cpus = root.dir("devices/system/cpu");
+    let range = || match axconfig::SMP {
+        1 => String::from("0\n"),
+        n => format!("0-{}\n", n - 1),
+    };
+    cpus.add_file("possible", range);
+    cpus.add_file("present", range);
+    cpus.add_file("online", range);
+    for cpu in 0..axconfig::SMP {
+        let dir = cpus.dir(&format!("cpu{}", cpu));
+        dir.add_file("online", || "1\n".into());
+        // every CPU is a core of the same package without SMT
+        let topology = dir.dir("topology");
+        topology.add_file("core_id", move || format!("{}\n", cpu));
+        topology.add_file("physical_package_id", || "0\n".into());
+        topology.add_file("thread_siblings_list", move || format!("{}\n", cpu));
+    }
+}
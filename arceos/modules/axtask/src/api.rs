@@ -89,6 +89,14 @@ pub fn on_timer_tick() {
     RUN_QUEUE.lock().scheduler_timer_tick();
 }
 
+/// Returns all tasks, ordered by their IDs.
+///
+/// Exited tasks are included until they are dropped, e.g. while someone is
+/// still holding a reference to join them.
+pub fn all_tasks() -> alloc::vec::Vec<AxTaskRef> {
+    crate::task::all_tasks()
+}
+
 /// Adds the given task to the run queue, returns the task reference.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
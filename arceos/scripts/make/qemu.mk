@@ -33,6 +33,12 @@ qemu_args-$(BLK) += \
   -device virtio-blk-$(vdev-suffix),drive=disk0 \
   -drive id=disk0,if=none,format=raw,file=$(DISK_IMG)
 
+ifneq ($(SHARE),)
+  qemu_args-y += \
+    -device virtio-9p-$(vdev-suffix),fsdev=share0,mount_tag=host \
+    -fsdev local,id=share0,path=$(SHARE),security_model=none
+endif
+
 qemu_args-$(NET) += \
   -device virtio-net-$(vdev-suffix),netdev=net0
 
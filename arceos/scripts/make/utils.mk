@@ -22,6 +22,11 @@ define make_disk_image
   $(if $(filter $(1),fat32), $(call make_disk_image_fat32,$(2)))
 endef
 
+define make_disk_image_manifest
+  @printf "    $(GREEN_C)Creating$(END_C) disk image \"$(2)\" from \"$(1)\" ...\n"
+  @RUSTFLAGS="" cargo run --manifest-path tools/mkimg/Cargo.toml --release -q -- $(1) $(2)
+endef
+
 define mk_pflash
   @if [ "$(ARCH)" = "x86_64" ]; then \
     RUSTFLAGS="" cargo build -p origin --target x86_64-unknown-none --release; \
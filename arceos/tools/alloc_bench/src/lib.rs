@@ -0,0 +1,61 @@
+//! Host-side workload replay and benchmark harness for the byte allocators
+//! used by ArceOS (`tlsf`, `slab`, `buddy` and [`EarlyAllocator`]).
+//!
+//! Any [`allocator::ByteAllocator`] can be driven by a recorded or synthetic
+//! [`Trace`], including the workload of the lab1 challenge, without booting a
+//! kernel. See [`replay`] and [`Report`].
+//!
+//! [`EarlyAllocator`]: bump_allocator::EarlyAllocator
+
+mod replay;
+mod trace;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::replay::{replay, Harness, Report};
+pub use self::trace::{Op, Trace};
+
+/// The byte allocators the harness knows how to construct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocatorKind {
+    Tlsf,
+    Slab,
+    Buddy,
+    Early,
+}
+
+impl AllocatorKind {
+    /// All known allocators.
+    pub const ALL: [AllocatorKind; 4] = [Self::Tlsf, Self::Slab, Self::Buddy, Self::Early];
+
+    /// Returns the allocator selected by `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Returns the name of the allocator.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Tlsf => "tlsf",
+            Self::Slab => "slab",
+            Self::Buddy => "buddy",
+            Self::Early => "early",
+        }
+    }
+
+    /// Replays `trace` against a fresh allocator of this kind with
+    /// `arena_size` bytes of memory.
+    pub fn replay(self, trace: &Trace, arena_size: usize) -> Report {
+        match self {
+            Self::Tlsf => replay(allocator::TlsfByteAllocator::new(), trace, arena_size),
+            Self::Slab => replay(allocator::SlabByteAllocator::new(), trace, arena_size),
+            Self::Buddy => replay(allocator::BuddyByteAllocator::new(), trace, arena_size),
+            Self::Early => replay(
+                bump_allocator::EarlyAllocator::<0x1000>::new(),
+                trace,
+                arena_size,
+            ),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::{env, fs, process};
+
+use alloc_bench::{AllocatorKind, Trace};
+
+const DEFAULT_ARENA_SIZE: usize = 128 * 1024 * 1024;
+const LAB1_MAX_ROUNDS: usize = 100_000;
+
+fn usage() -> ! {
+    eprintln!("Usage: alloc_bench <tlsf|slab|buddy|early|all> <lab1|random|TRACE_FILE> [ARENA_MB]");
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        usage();
+    }
+
+    let kinds = if args[1] == "all" {
+        AllocatorKind::ALL.to_vec()
+    } else {
+        vec![AllocatorKind::from_name(&args[1]).unwrap_or_else(|| usage())]
+    };
+    let arena_size = match args.get(3) {
+        Some(mb) => mb.parse::<usize>().unwrap_or_else(|_| usage()) * 1024 * 1024,
+        None => DEFAULT_ARENA_SIZE,
+    };
+    let trace = match args[2].as_str() {
+        "lab1" => Trace::lab1(LAB1_MAX_ROUNDS),
+        "random" => Trace::random(0x2024, 1_000_000, 64 * 1024, 4096),
+        path => {
+            let text = fs::read_to_string(path).unwrap_or_else(|err| {
+                eprintln!("cannot read {}: {}", path, err);
+                process::exit(1);
+            });
+            Trace::parse(&text).unwrap_or_else(|err| {
+                eprintln!("invalid trace {}: {}", path, err);
+                process::exit(1);
+            })
+        }
+    };
+
+    for kind in kinds {
+        println!("=== {} ({} ops) ===", kind.name(), trace.ops().len());
+        println!("{}", kind.replay(&trace, arena_size));
+    }
+}
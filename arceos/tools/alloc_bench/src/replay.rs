@@ -0,0 +1,270 @@
+//! Replays a [`Trace`] against a [`ByteAllocator`] and collects statistics.
+
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use allocator::{AllocError, ByteAllocator};
+
+use crate::trace::{Op, Trace};
+
+const PAGE_SIZE: usize = 0x1000;
+const MIN_HEAP_SIZE: usize = 0x8000; // 32 K, the same as axalloc
+
+/// A page-aligned region of host memory that plays the role of physical
+/// memory. Heap regions are carved from it from bottom to top and are never
+/// given back, like the pages `axalloc` hands to its byte allocator.
+struct Arena {
+    base: NonNull<u8>,
+    layout: Layout,
+    pos: usize,
+}
+
+impl Arena {
+    fn new(size: usize) -> Self {
+        let size = size.max(MIN_HEAP_SIZE).next_multiple_of(PAGE_SIZE);
+        let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+        let base = NonNull::new(unsafe { alloc_zeroed(layout) }).expect("arena allocation failed");
+        Self {
+            base,
+            layout,
+            pos: 0,
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.base.as_ptr() as usize
+    }
+
+    fn end(&self) -> usize {
+        self.start() + self.layout.size()
+    }
+
+    fn take(&mut self, size: usize) -> Option<usize> {
+        if self.pos + size > self.layout.size() {
+            return None;
+        }
+        let start = self.start() + self.pos;
+        self.pos += size;
+        Some(start)
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.base.as_ptr(), self.layout) }
+    }
+}
+
+struct Block {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    tag: u8,
+}
+
+/// Statistics of one replay.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of allocations performed.
+    pub allocs: usize,
+    /// Number of frees performed.
+    pub frees: usize,
+    /// Number of [`Op::Round`] markers reached. For the lab1 workload this is
+    /// the "Indicator" count.
+    pub rounds: usize,
+    /// Index of the operation that failed, if the replay stopped early.
+    pub failed_at: Option<usize>,
+    /// The error of the failed operation.
+    pub error: Option<AllocError>,
+    /// Peak of the sum of requested sizes of live blocks.
+    pub peak_live_bytes: usize,
+    /// Peak of [`ByteAllocator::used_bytes`].
+    pub peak_used_bytes: usize,
+    /// Total memory handed to the allocator (initial heap plus expansions).
+    pub heap_bytes: usize,
+    /// Time spent inside `alloc` and `dealloc` only.
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Allocator operations per second, excluding harness overhead.
+    pub fn ops_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            (self.allocs + self.frees) as f64 / secs
+        }
+    }
+
+    /// The fraction of the heap that could not be used for live data at the
+    /// peak: `1 - peak_live_bytes / heap_bytes`.
+    ///
+    /// It counts both internal fragmentation (rounding and headers) and
+    /// external fragmentation (free space the allocator could not use, which
+    /// forced the heap to grow).
+    pub fn fragmentation(&self) -> f64 {
+        if self.heap_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.peak_live_bytes as f64 / self.heap_bytes as f64
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "allocs:          {}", self.allocs)?;
+        writeln!(f, "frees:           {}", self.frees)?;
+        writeln!(f, "rounds:          {}", self.rounds)?;
+        match (self.failed_at, &self.error) {
+            (Some(idx), Some(err)) => writeln!(f, "stopped:         op #{} ({:?})", idx, err)?,
+            _ => writeln!(f, "stopped:         completed")?,
+        }
+        writeln!(f, "peak live:       {} bytes", self.peak_live_bytes)?;
+        writeln!(f, "peak used:       {} bytes", self.peak_used_bytes)?;
+        writeln!(f, "heap size:       {} bytes", self.heap_bytes)?;
+        writeln!(f, "fragmentation:   {:.2}%", self.fragmentation() * 100.0)?;
+        write!(f, "throughput:      {:.0} ops/sec", self.ops_per_sec())
+    }
+}
+
+/// Drives a byte allocator with a trace inside an arena of a fixed size.
+///
+/// The heap starts at 32 KB and is expanded on demand from the arena with the
+/// same policy as `axalloc::GlobalAllocator`, so running out of the arena
+/// corresponds to the kernel running out of memory.
+pub struct Harness<A: ByteAllocator> {
+    alloc: A,
+    arena: Arena,
+    live: HashMap<u32, Block>,
+    live_bytes: usize,
+    report: Report,
+}
+
+impl<A: ByteAllocator> Harness<A> {
+    /// Creates a harness with `arena_size` bytes of memory and initializes
+    /// `alloc` with the first 32 KB of it.
+    pub fn new(mut alloc: A, arena_size: usize) -> Self {
+        let mut arena = Arena::new(arena_size);
+        let heap = arena.take(MIN_HEAP_SIZE).unwrap();
+        alloc.init(heap, MIN_HEAP_SIZE);
+        Self {
+            alloc,
+            arena,
+            live: HashMap::new(),
+            live_bytes: 0,
+            report: Report {
+                heap_bytes: MIN_HEAP_SIZE,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Replays `trace` until it ends or an allocation fails, and returns the
+    /// statistics.
+    ///
+    /// Every block is filled with a tag when allocated and checked when freed,
+    /// so overlapping blocks are detected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocator returns a block that is misaligned, outside the
+    /// arena, or was corrupted while alive, or if the trace is malformed.
+    pub fn run(mut self, trace: &Trace) -> Report {
+        for (idx, op) in trace.ops().iter().enumerate() {
+            let res = match *op {
+                Op::Alloc { id, size, align } => self.alloc(id, size, align),
+                Op::Free { id } => {
+                    self.free(id);
+                    Ok(())
+                }
+                Op::Round => {
+                    self.report.rounds += 1;
+                    Ok(())
+                }
+            };
+            if let Err(e) = res {
+                self.report.failed_at = Some(idx);
+                self.report.error = Some(e);
+                break;
+            }
+        }
+        self.report
+    }
+
+    fn alloc(&mut self, id: u32, size: usize, align: usize) -> Result<(), AllocError> {
+        let layout = Layout::from_size_align(size, align).map_err(|_| AllocError::InvalidParam)?;
+        let ptr = loop {
+            let start = Instant::now();
+            let res = self.alloc.alloc(layout);
+            self.report.elapsed += start.elapsed();
+            match res {
+                Ok(ptr) => break ptr,
+                Err(AllocError::NoMemory) => self.expand(layout)?,
+                Err(e) => return Err(e),
+            }
+        };
+
+        let addr = ptr.as_ptr() as usize;
+        assert_eq!(addr % align, 0, "block {} is misaligned", id);
+        assert!(
+            addr >= self.arena.start() && addr + size <= self.arena.end(),
+            "block {} is outside the arena",
+            id
+        );
+        let tag = (id % 251) as u8 + 1;
+        unsafe { ptr.as_ptr().write_bytes(tag, size) };
+        if self.live.insert(id, Block { ptr, layout, tag }).is_some() {
+            panic!("block {} allocated twice", id);
+        }
+
+        self.report.allocs += 1;
+        self.live_bytes += size;
+        self.report.peak_live_bytes = self.report.peak_live_bytes.max(self.live_bytes);
+        self.report.peak_used_bytes = self.report.peak_used_bytes.max(self.alloc.used_bytes());
+        Ok(())
+    }
+
+    fn free(&mut self, id: u32) {
+        let block = self
+            .live
+            .remove(&id)
+            .unwrap_or_else(|| panic!("block {} freed but not allocated", id));
+        let size = block.layout.size();
+        if size > 0 {
+            let bytes = unsafe { std::slice::from_raw_parts(block.ptr.as_ptr(), size) };
+            assert!(
+                bytes[0] == block.tag && bytes[size - 1] == block.tag,
+                "block {} was corrupted",
+                id
+            );
+        }
+
+        let start = Instant::now();
+        self.alloc.dealloc(block.ptr, block.layout);
+        self.report.elapsed += start.elapsed();
+        self.report.frees += 1;
+        self.live_bytes -= size;
+    }
+
+    /// Grows the heap the way `axalloc::GlobalAllocator::alloc` does.
+    fn expand(&mut self, layout: Layout) -> Result<(), AllocError> {
+        let old_size = self.alloc.total_bytes();
+        let expand_size = old_size
+            .max(layout.size())
+            .next_power_of_two()
+            .max(PAGE_SIZE);
+        let heap = self.arena.take(expand_size).ok_or(AllocError::NoMemory)?;
+        self.alloc.add_memory(heap, expand_size)?;
+        self.report.heap_bytes += expand_size;
+        Ok(())
+    }
+}
+
+/// Replays `trace` against `alloc` with `arena_size` bytes of memory.
+pub fn replay<A: ByteAllocator>(alloc: A, trace: &Trace, arena_size: usize) -> Report {
+    Harness::new(alloc, arena_size).run(trace)
+}
@@ -0,0 +1,101 @@
+use crate::*;
+
+const ARENA_SIZE: usize = 16 * 1024 * 1024;
+
+#[test]
+fn test_trace_text_roundtrip() {
+    let trace = Trace::random(42, 1000, 4096, 64);
+    assert_eq!(Trace::parse(&trace.to_string()).unwrap(), trace);
+
+    let trace = Trace::parse("# comment\na 1 16\na 2 100 64 # tail\nr\n\nf 1\n").unwrap();
+    assert_eq!(
+        trace.ops(),
+        &[
+            Op::Alloc {
+                id: 1,
+                size: 16,
+                align: 8
+            },
+            Op::Alloc {
+                id: 2,
+                size: 100,
+                align: 64
+            },
+            Op::Round,
+            Op::Free { id: 1 },
+        ]
+    );
+
+    assert!(Trace::parse("x 1").is_err());
+    assert!(Trace::parse("a 1").is_err());
+    assert!(Trace::parse("a 1 16 3").is_err());
+    assert!(Trace::parse("f 1 2").is_err());
+}
+
+#[test]
+fn test_random_trace_is_balanced() {
+    let trace = Trace::random(7, 5000, 1 << 16, 128);
+    let allocs = trace
+        .ops()
+        .iter()
+        .filter(|op| matches!(op, Op::Alloc { .. }))
+        .count();
+    let frees = trace
+        .ops()
+        .iter()
+        .filter(|op| matches!(op, Op::Free { .. }))
+        .count();
+    assert_eq!(allocs, frees);
+}
+
+fn check_random(kind: AllocatorKind) {
+    let trace = Trace::random(1, 20000, 8192, 256);
+    let report = kind.replay(&trace, ARENA_SIZE);
+    assert_eq!(
+        report.failed_at,
+        None,
+        "{}: {:?}",
+        kind.name(),
+        report.error
+    );
+    assert_eq!(report.allocs, report.frees);
+    assert!(report.peak_live_bytes > 0);
+    assert!(report.peak_live_bytes <= report.heap_bytes);
+    assert!((0.0..1.0).contains(&report.fragmentation()));
+}
+
+fn check_lab1(kind: AllocatorKind) {
+    let report = kind.replay(&Trace::lab1(10_000), ARENA_SIZE);
+    assert!(
+        report.failed_at.is_some(),
+        "{}: never ran out of memory",
+        kind.name()
+    );
+    assert!(report.rounds > 0, "{}: no round completed", kind.name());
+    println!("{}: lab1 indicator {}", kind.name(), report.rounds);
+}
+
+#[test]
+fn test_tlsf() {
+    check_random(AllocatorKind::Tlsf);
+    check_lab1(AllocatorKind::Tlsf);
+}
+
+#[test]
+fn test_slab() {
+    check_random(AllocatorKind::Slab);
+    check_lab1(AllocatorKind::Slab);
+}
+
+#[test]
+fn test_buddy() {
+    check_random(AllocatorKind::Buddy);
+    check_lab1(AllocatorKind::Buddy);
+}
+
+#[test]
+#[ignore = "`EarlyAllocator` is left unimplemented for the alt_alloc exercise"]
+fn test_early() {
+    check_random(AllocatorKind::Early);
+    check_lab1(AllocatorKind::Early);
+}
@@ -0,0 +1,196 @@
+//! Allocation traces: recorded workloads and synthetic generators.
+//!
+//! A trace is a flat list of [`Op`]s. Blocks are named by a numeric id chosen
+//! by the trace, so the same trace can be replayed against any allocator.
+//!
+//! The text form has one operation per line:
+//!
+//! ```text
+//! # comment
+//! a <id> <size> [align]    allocate block <id>
+//! f <id>                   free block <id>
+//! r                        end of a round (one "Indicator" step in lab1)
+//! ```
+
+use std::fmt;
+
+/// A single operation in a trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Allocate `size` bytes aligned to `align` and name the block `id`.
+    Alloc { id: u32, size: usize, align: usize },
+    /// Free the block previously allocated as `id`.
+    Free { id: u32 },
+    /// Marks the end of a round of the workload.
+    Round,
+}
+
+/// An ordered list of allocator operations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    ops: Vec<Op>,
+}
+
+impl Trace {
+    /// Creates an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the operations of the trace.
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Appends an operation.
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    /// Parses a trace from its text form.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut trace = Self::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}: {:?}", lineno + 1, msg, line);
+            let num = |s: Option<&str>| -> Result<usize, String> {
+                s.ok_or_else(|| err("missing field"))?
+                    .parse()
+                    .map_err(|_| err("invalid number"))
+            };
+            let mut fields = line.split_whitespace();
+            let op = match fields.next() {
+                Some("a") => {
+                    let id = num(fields.next())? as u32;
+                    let size = num(fields.next())?;
+                    let align = match fields.next() {
+                        Some(s) => num(Some(s))?,
+                        None => 8,
+                    };
+                    if !align.is_power_of_two() {
+                        return Err(err("alignment is not a power of two"));
+                    }
+                    Op::Alloc { id, size, align }
+                }
+                Some("f") => Op::Free {
+                    id: num(fields.next())? as u32,
+                },
+                Some("r") => Op::Round,
+                _ => return Err(err("unknown operation")),
+            };
+            if fields.next().is_some() {
+                return Err(err("trailing fields"));
+            }
+            trace.push(op);
+        }
+        Ok(trace)
+    }
+
+    /// The workload of the lab1 challenge application.
+    ///
+    /// Each round allocates a block of every power-of-two size from 32 bytes
+    /// to 512 KB (each enlarged by the round number), then frees every other
+    /// block, keeping the rest alive forever. The number of rounds completed
+    /// before running out of memory is the "Indicator" count.
+    pub fn lab1(rounds: usize) -> Self {
+        let mut trace = Self::new();
+        let mut next_id = 0;
+        for delta in 0..rounds {
+            let mut ids = Vec::new();
+            let mut base = 32;
+            loop {
+                trace.push(Op::Alloc {
+                    id: next_id,
+                    size: base + delta,
+                    align: 1,
+                });
+                ids.push(next_id);
+                next_id += 1;
+                if base >= 512 * 1024 {
+                    break;
+                }
+                base *= 2;
+            }
+            for (i, &id) in ids.iter().enumerate().rev() {
+                if i % 2 == 0 {
+                    trace.push(Op::Free { id });
+                }
+            }
+            trace.push(Op::Round);
+        }
+        trace
+    }
+
+    /// A random mix of allocations and frees.
+    ///
+    /// Sizes are spread log-uniformly over `[1, max_size]` and alignments
+    /// over `[1, 64]`. About two thirds of the operations are allocations
+    /// while fewer than `max_live` blocks are alive. All blocks still alive
+    /// at the end are freed, so the trace is balanced.
+    pub fn random(seed: u64, ops: usize, max_size: usize, max_live: usize) -> Self {
+        let mut rng = XorShift::new(seed);
+        let mut trace = Self::new();
+        let mut live: Vec<u32> = Vec::new();
+        let mut next_id = 0;
+        let max_bits = usize::BITS - max_size.max(1).leading_zeros();
+        for _ in 0..ops {
+            let alloc = live.is_empty() || (live.len() < max_live && rng.next() % 3 != 0);
+            if alloc {
+                let bits = 1 + rng.next() as u32 % max_bits;
+                let span = 1usize << (bits - 1);
+                let size = (span + rng.next() as usize % span).min(max_size.max(1));
+                let align = 1 << (rng.next() % 7);
+                trace.push(Op::Alloc {
+                    id: next_id,
+                    size,
+                    align,
+                });
+                live.push(next_id);
+                next_id += 1;
+            } else {
+                let idx = rng.next() as usize % live.len();
+                trace.push(Op::Free {
+                    id: live.swap_remove(idx),
+                });
+            }
+        }
+        for id in live {
+            trace.push(Op::Free { id });
+        }
+        trace
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in &self.ops {
+            match op {
+                Op::Alloc { id, size, align } => writeln!(f, "a {} {} {}", id, size, align)?,
+                Op::Free { id } => writeln!(f, "f {}", id)?,
+                Op::Round => writeln!(f, "r")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tiny deterministic PRNG, so traces are reproducible from a seed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
@@ -0,0 +1,72 @@
+//! The `newc` cpio format, which the initramfs loader of `axfs` unpacks.
+
+use std::io::{self, Write};
+
+use crate::image::{Image, NodeKind};
+
+const MAGIC: &[u8] = b"070701";
+const TRAILER: &str = "TRAILER!!!";
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+fn write_padding<W: Write>(out: &mut W, len: usize) -> io::Result<()> {
+    let padding = len.next_multiple_of(4) - len;
+    out.write_all(&[0; 3][..padding])
+}
+
+/// Writes an entry of the archive, whose header and name are padded to 4
+/// bytes, and so is the data.
+fn write_entry<W: Write>(
+    out: &mut W,
+    ino: u32,
+    name: &str,
+    mode: u32,
+    data: &[u8],
+) -> io::Result<()> {
+    if data.len() > u32::MAX as usize {
+        let msg = format!("{} is too large for cpio", name);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+    let nlink = if mode & S_IFDIR != 0 { 2 } else { 1 };
+    // ino, mode, uid, gid, nlink, mtime, filesize, devmajor, devminor,
+    // rdevmajor, rdevminor, namesize, check
+    let fields = [
+        ino,
+        mode,
+        0,
+        0,
+        nlink,
+        0,
+        data.len() as u32,
+        0,
+        0,
+        0,
+        0,
+        name.len() as u32 + 1,
+        0,
+    ];
+    out.write_all(MAGIC)?;
+    for field in fields {
+        write!(out, "{:08x}", field)?;
+    }
+    out.write_all(name.as_bytes())?;
+    out.write_all(&[0])?;
+    write_padding(out, MAGIC.len() + fields.len() * 8 + name.len() + 1)?;
+    out.write_all(data)?;
+    write_padding(out, data.len())
+}
+
+/// Writes `image` to `out` as a cpio archive.
+///
+/// The owners and timestamps are all zero, so the archive is the same each
+/// time for the same files.
+pub fn write_cpio<W: Write>(image: &Image, out: &mut W) -> io::Result<()> {
+    for (ino, (path, node)) in (1..).zip(image.iter()) {
+        let name = path.trim_start_matches('/');
+        match &node.kind {
+            NodeKind::Dir => write_entry(out, ino, name, S_IFDIR | node.mode, &[])?,
+            NodeKind::File(data) => write_entry(out, ino, name, S_IFREG | node.mode, data)?,
+        }
+    }
+    write_entry(out, 0, TRAILER, 0, &[])
+}
@@ -0,0 +1,50 @@
+//! FAT images, written with the same `fatfs` crate that `axfs` reads them
+//! with.
+//!
+//! FAT has no permission bits, so the modes of the files are dropped.
+
+use std::fs::File;
+
+use fatfs::{FatType, FileSystem, FormatVolumeOptions, FsOptions, StdIoWrapper, Write};
+
+use crate::image::{Image, NodeKind};
+
+/// The volume ID, which is usually random, but fixed for reproducible images.
+const VOLUME_ID: u32 = 0x4152_4345;
+const VOLUME_LABEL: [u8; 11] = *b"ARCEOS     ";
+
+/// Formats `file` as a FAT filesystem of `size` bytes, and writes `image` to
+/// it.
+///
+/// The filesystem is FAT32 if `fat32` is set, otherwise its type is chosen by
+/// its size. The timestamps are all zero.
+pub fn write_fat(image: &Image, file: File, size: u64, fat32: bool) -> Result<(), String> {
+    let err = |e| format!("{:?}", e);
+    file.set_len(size).map_err(|e| e.to_string())?;
+    let mut storage = StdIoWrapper::from(file);
+
+    let mut opts = FormatVolumeOptions::new()
+        .volume_id(VOLUME_ID)
+        .volume_label(VOLUME_LABEL);
+    if fat32 {
+        opts = opts.fat_type(FatType::Fat32);
+    }
+    fatfs::format_volume(&mut storage, opts).map_err(err)?;
+
+    let fs = FileSystem::new(storage, FsOptions::new()).map_err(err)?;
+    let root = fs.root_dir();
+    for (path, node) in image.iter() {
+        let path = path.trim_start_matches('/');
+        match &node.kind {
+            NodeKind::Dir => {
+                root.create_dir(path).map_err(err)?;
+            }
+            NodeKind::File(data) => {
+                let mut file = root.create_file(path).map_err(err)?;
+                file.write_all(data).map_err(err)?;
+            }
+        }
+    }
+    drop(root);
+    fs.unmount().map_err(err)
+}
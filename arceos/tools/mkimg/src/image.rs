@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::manifest::{Entry, Manifest, Source};
+
+const DEFAULT_DIR_MODE: u32 = 0o755;
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// The kind and content of a [`Node`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir,
+    File(Vec<u8>),
+}
+
+/// A file or directory in an [`Image`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// The permission bits.
+    pub mode: u32,
+}
+
+/// The files and directories of an image, by their absolute paths.
+///
+/// They are sorted by path, so a directory always comes before the entries in
+/// it, and the image is the same for the same files whatever order they are
+/// found on the host.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+    nodes: BTreeMap<String, Node>,
+}
+
+/// The permission bits of a file on the host.
+#[cfg(unix)]
+fn host_mode(meta: &fs::Metadata, _default: u32) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn host_mode(_meta: &fs::Metadata, default: u32) -> u32 {
+    default
+}
+
+fn read_host(path: &Path) -> Result<(Vec<u8>, fs::Metadata), String> {
+    let err = |e: std::io::Error| format!("cannot read {}: {}", path.display(), e);
+    let meta = fs::metadata(path).map_err(err)?;
+    Ok((fs::read(path).map_err(err)?, meta))
+}
+
+impl Image {
+    /// Creates an empty image.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the image of `manifest`, reading the files and running the
+    /// builds on the host.
+    ///
+    /// Each directory is built once, before its first output is read.
+    pub fn build(manifest: &Manifest) -> Result<Self, String> {
+        let mut image = Self::new();
+        let mut built = BTreeSet::new();
+        for entry in &manifest.entries {
+            if let Source::Build { dir, .. } = &entry.source {
+                if built.insert(dir.clone()) {
+                    run_make(dir)?;
+                }
+            }
+            image.add_entry(entry)?;
+        }
+        Ok(image)
+    }
+
+    /// Adds the files of `entry`, which must already be built.
+    pub fn add_entry(&mut self, entry: &Entry) -> Result<(), String> {
+        let path = &entry.path;
+        match &entry.source {
+            Source::Dir => self.add_dir(path, entry.mode.unwrap_or(DEFAULT_DIR_MODE)),
+            Source::Tree(dir) => {
+                self.add_tree(path, dir)?;
+                if let Some(mode) = entry.mode {
+                    self.add_dir(path, mode)?;
+                }
+                Ok(())
+            }
+            Source::File(file) => self.add_host_file(path, file, entry),
+            Source::Build { dir, output } => self.add_host_file(path, &dir.join(output), entry),
+            Source::Zero => {
+                let mode = entry.mode.unwrap_or(DEFAULT_FILE_MODE);
+                let size = entry.size.unwrap_or_default();
+                self.add_file(path, vec![0; size as usize], mode)
+            }
+        }
+    }
+
+    fn add_host_file(&mut self, path: &str, file: &Path, entry: &Entry) -> Result<(), String> {
+        let (mut data, meta) = read_host(file)?;
+        if let Some(size) = entry.size {
+            if data.len() as u64 > size {
+                return Err(format!(
+                    "{} is larger than {} bytes: {}",
+                    file.display(),
+                    size,
+                    data.len()
+                ));
+            }
+            data.resize(size as usize, 0);
+        }
+        let mode = entry
+            .mode
+            .unwrap_or_else(|| host_mode(&meta, DEFAULT_FILE_MODE));
+        self.add_file(path, data, mode)
+    }
+
+    fn add_tree(&mut self, path: &str, dir: &Path) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("cannot read {}: {}", dir.display(), e);
+        let meta = fs::metadata(dir).map_err(err)?;
+        if !meta.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        self.add_dir(path, host_mode(&meta, DEFAULT_DIR_MODE))?;
+        for child in fs::read_dir(dir).map_err(err)? {
+            let child = child.map_err(err)?;
+            let name = child.file_name();
+            let name = name
+                .to_str()
+                .ok_or_else(|| format!("invalid file name in {}: {:?}", dir.display(), name))?;
+            let child_path = format!("{}/{}", path, name);
+            // follow symlinks, neither FAT nor the initramfs loader has them
+            if child.path().is_dir() {
+                self.add_tree(&child_path, &child.path())?;
+            } else {
+                let (data, meta) = read_host(&child.path())?;
+                self.add_file(&child_path, data, host_mode(&meta, DEFAULT_FILE_MODE))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the parent directories of `path` that do not exist yet.
+    fn add_parents(&mut self, path: &str) -> Result<(), String> {
+        let mut end = 0;
+        while let Some(pos) = path[end + 1..].find('/') {
+            end += 1 + pos;
+            let parent = &path[..end];
+            match self.nodes.get(parent) {
+                Some(Node {
+                    kind: NodeKind::File(_),
+                    ..
+                }) => return Err(format!("{} is not a directory", parent)),
+                Some(_) => {}
+                None => {
+                    let node = Node {
+                        kind: NodeKind::Dir,
+                        mode: DEFAULT_DIR_MODE,
+                    };
+                    self.nodes.insert(parent.into(), node);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the directory at the absolute path `path`, or changes the mode of
+    /// it if it exists.
+    pub fn add_dir(&mut self, path: &str, mode: u32) -> Result<(), String> {
+        self.add_parents(path)?;
+        match self.nodes.get_mut(path) {
+            Some(Node {
+                kind: NodeKind::File(_),
+                ..
+            }) => Err(format!("{} is a file", path)),
+            Some(node) => {
+                node.mode = mode;
+                Ok(())
+            }
+            None => {
+                let kind = NodeKind::Dir;
+                self.nodes.insert(path.into(), Node { kind, mode });
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds the file at the absolute path `path`, which replaces the file
+    /// there if any.
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>, mode: u32) -> Result<(), String> {
+        self.add_parents(path)?;
+        if let Some(Node {
+            kind: NodeKind::Dir,
+            ..
+        }) = self.nodes.get(path)
+        {
+            return Err(format!("{} is a directory", path));
+        }
+        let kind = NodeKind::File(data);
+        self.nodes.insert(path.into(), Node { kind, mode });
+        Ok(())
+    }
+
+    /// Returns the node at the absolute path `path`.
+    pub fn get(&self, path: &str) -> Option<&Node> {
+        self.nodes.get(path)
+    }
+
+    /// Iterates over the files and directories, each directory before the
+    /// entries in it.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Node)> {
+        self.nodes.iter().map(|(path, node)| (path.as_str(), node))
+    }
+}
+
+fn run_make(dir: &Path) -> Result<(), String> {
+    let status = Command::new("make")
+        .arg("-C")
+        .arg(dir)
+        .status()
+        .map_err(|e| format!("cannot run make: {}", e))?;
+    if !status.success() {
+        return Err(format!("make -C {} failed: {}", dir.display(), status));
+    }
+    Ok(())
+}
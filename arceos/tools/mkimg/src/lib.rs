@@ -0,0 +1,57 @@
+//! Host tool to build ArceOS disk images from a [`Manifest`] of the files to
+//! put in them, instead of mounting the image with `sudo` and copying them.
+//!
+//! An image is a FAT filesystem for the `fs` feature, or a cpio archive for
+//! the initramfs. The same manifest and files always give the same image, so
+//! images can be rebuilt and compared in CI. See [`Manifest::parse`] for the
+//! format of the manifest.
+
+mod cpio;
+#[cfg(feature = "fat")]
+mod fat;
+mod image;
+mod manifest;
+
+#[cfg(test)]
+mod tests;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub use self::cpio::write_cpio;
+pub use self::image::{Image, Node, NodeKind};
+pub use self::manifest::{normalize, parse_size, Entry, Format, Manifest, Source, DEFAULT_SIZE};
+
+/// Writes `image` to the file at `output` in `format`, as an image of `size`
+/// bytes for FAT.
+pub fn write_image(image: &Image, format: Format, size: u64, output: &Path) -> Result<(), String> {
+    let file =
+        File::create(output).map_err(|e| format!("cannot create {}: {}", output.display(), e))?;
+    match format {
+        Format::Cpio => {
+            let mut out = BufWriter::new(file);
+            write_cpio(image, &mut out)
+                .and_then(|_| out.flush())
+                .map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "fat")]
+        Format::Fat | Format::Fat32 => fat::write_fat(image, file, size, format == Format::Fat32),
+        #[cfg(not(feature = "fat"))]
+        Format::Fat | Format::Fat32 => {
+            let _ = size;
+            Err("FAT images need the `fat` feature".into())
+        }
+    }
+}
+
+/// Builds the image of the manifest at `manifest` and writes it to `output`.
+pub fn build_image(manifest: &Path, output: &Path) -> Result<(), String> {
+    let text = std::fs::read_to_string(manifest)
+        .map_err(|e| format!("cannot read {}: {}", manifest.display(), e))?;
+    let base = manifest.parent().unwrap_or(Path::new("."));
+    let manifest = Manifest::parse(&text, base)
+        .map_err(|e| format!("invalid manifest {}: {}", manifest.display(), e))?;
+    let image = Image::build(&manifest)?;
+    write_image(&image, manifest.format, manifest.size, output)
+}
@@ -0,0 +1,17 @@
+use std::{env, path::Path, process};
+
+fn usage() -> ! {
+    eprintln!("Usage: mkimg <MANIFEST> <OUTPUT>");
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        usage();
+    }
+    if let Err(err) = mkimg::build_image(Path::new(&args[1]), Path::new(&args[2])) {
+        eprintln!("mkimg: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+/// The format of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A FAT filesystem, FAT12, FAT16 or FAT32 by the size of the image.
+    Fat,
+    /// A FAT32 filesystem, like `mkfs.fat -F 32`.
+    Fat32,
+    /// A cpio archive in the `newc` format, e.g. for an initramfs.
+    Cpio,
+}
+
+impl Format {
+    /// Returns the format selected by `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fat" => Some(Self::Fat),
+            "fat32" => Some(Self::Fat32),
+            "cpio" => Some(Self::Cpio),
+            _ => None,
+        }
+    }
+}
+
+/// Where the content of an [`Entry`] comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// An empty directory.
+    Dir,
+    /// A file on the host.
+    File(PathBuf),
+    /// A directory on the host, copied with everything in it.
+    Tree(PathBuf),
+    /// The file `output` in the directory `dir` on the host, after running
+    /// `make -C dir`, e.g. a payload.
+    Build { dir: PathBuf, output: PathBuf },
+    /// A file filled with zeros.
+    Zero,
+}
+
+/// A line of the manifest that puts something at `path` in the image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The absolute path in the image.
+    pub path: String,
+    pub source: Source,
+    /// The permission bits, which default to those on the host, or `0755`
+    /// for directories not on the host and `0644` for files.
+    pub mode: Option<u32>,
+    /// The size of the file, which is padded with zeros to it.
+    pub size: Option<u64>,
+}
+
+/// What to put in an image, parsed from its text form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub format: Format,
+    /// The size of the image in bytes, unused for cpio archives.
+    pub size: u64,
+    /// The entries in order, a later one replaces the files of an earlier
+    /// one at the same paths.
+    pub entries: Vec<Entry>,
+}
+
+/// The size of an image if not given, the same as `make disk_img`.
+pub const DEFAULT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Parses a size in bytes, with an optional `K`, `M` or `G` suffix.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let (num, shift) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 20),
+        b'G' | b'g' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    num.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+impl Manifest {
+    /// Parses the text form of a manifest, one setting or entry per line:
+    ///
+    /// ```text
+    /// format <fat|fat32|cpio>              # the image format (fat32 by default)
+    /// size <bytes>                         # the image size (64M by default)
+    /// dir <path> [mode=<octal>]
+    /// file <path> <host file> [mode=<octal>] [size=<bytes>]
+    /// tree <path> <host dir> [mode=<octal>]
+    /// build <path> <host dir> <output> [mode=<octal>] [size=<bytes>]
+    /// zero <path> size=<bytes> [mode=<octal>]
+    /// ```
+    ///
+    /// `#` starts a comment. Host paths are relative to `base`. The mode of a
+    /// `tree` applies to its top directory only.
+    pub fn parse(text: &str, base: &std::path::Path) -> Result<Self, String> {
+        let mut manifest = Self {
+            format: Format::Fat32,
+            size: DEFAULT_SIZE,
+            entries: Vec::new(),
+        };
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}: {:?}", lineno + 1, msg, line);
+            let mut fields = Vec::new();
+            let (mut mode, mut size) = (None, None);
+            for field in line.split_whitespace() {
+                if let Some(val) = field.strip_prefix("mode=") {
+                    let val = u32::from_str_radix(val, 8).map_err(|_| err("invalid mode"))?;
+                    if val > 0o7777 {
+                        return Err(err("invalid mode"));
+                    }
+                    mode = Some(val);
+                } else if let Some(val) = field.strip_prefix("size=") {
+                    size = Some(parse_size(val).ok_or_else(|| err("invalid size"))?);
+                } else {
+                    fields.push(field);
+                }
+            }
+            let host = |s: &str| base.join(s);
+
+            let (path, source) = match fields[..] {
+                ["format", name] => {
+                    manifest.format =
+                        Format::from_name(name).ok_or_else(|| err("unknown format"))?;
+                    continue;
+                }
+                ["size", val] => {
+                    manifest.size = parse_size(val).ok_or_else(|| err("invalid size"))?;
+                    continue;
+                }
+                ["dir", path] => (path, Source::Dir),
+                ["file", path, file] => (path, Source::File(host(file))),
+                ["tree", path, dir] => (path, Source::Tree(host(dir))),
+                ["build", path, dir, output] => (
+                    path,
+                    Source::Build {
+                        dir: host(dir),
+                        output: output.into(),
+                    },
+                ),
+                ["zero", path] if size.is_some() => (path, Source::Zero),
+                ["zero", _] => return Err(err("missing size")),
+                _ => return Err(err("invalid entry")),
+            };
+            if size.is_some() && matches!(source, Source::Dir | Source::Tree(_)) {
+                return Err(err("size of a directory"));
+            }
+            manifest.entries.push(Entry {
+                path: normalize(path).ok_or_else(|| err("invalid path"))?,
+                source,
+                mode,
+                size,
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+/// Normalizes the absolute path `path` in the image, which must not be the
+/// root or contain `.` or `..`.
+pub fn normalize(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    let mut normalized = String::new();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        if name == "." || name == ".." {
+            return None;
+        }
+        normalized.push('/');
+        normalized.push_str(name);
+    }
+    (!normalized.is_empty()).then_some(normalized)
+}
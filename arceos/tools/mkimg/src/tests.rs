@@ -0,0 +1,309 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+/// A directory on the host for the files of a test, removed after it.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("mkimg-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn file(data: &[u8]) -> NodeKind {
+    NodeKind::File(data.to_vec())
+}
+
+#[test]
+fn test_parse_manifest() {
+    let base = Path::new("/host");
+    let text = "\
+        # an initramfs\n\
+        format cpio\n\
+        size 1M  # unused\n\
+        \n\
+        dir /etc mode=0700\n\
+        file /etc/hosts hosts\n\
+        file //sbin/init ../init mode=755 size=4K\n\
+        tree /data data\n\
+        build /bin/app payload/app app\n\
+        zero /swap size=2k\n";
+    let manifest = Manifest::parse(text, base).unwrap();
+    assert_eq!(manifest.format, Format::Cpio);
+    assert_eq!(manifest.size, 1024 * 1024);
+    let entry = |path: &str, source, mode, size| Entry {
+        path: path.into(),
+        source,
+        mode,
+        size,
+    };
+    assert_eq!(
+        manifest.entries,
+        [
+            entry("/etc", Source::Dir, Some(0o700), None),
+            entry("/etc/hosts", Source::File("/host/hosts".into()), None, None),
+            entry(
+                "/sbin/init",
+                Source::File("/host/../init".into()),
+                Some(0o755),
+                Some(4096)
+            ),
+            entry("/data", Source::Tree("/host/data".into()), None, None),
+            entry(
+                "/bin/app",
+                Source::Build {
+                    dir: "/host/payload/app".into(),
+                    output: "app".into()
+                },
+                None,
+                None
+            ),
+            entry("/swap", Source::Zero, None, Some(2048)),
+        ]
+    );
+
+    let manifest = Manifest::parse("", base).unwrap();
+    assert_eq!(manifest.format, Format::Fat32);
+    assert_eq!(manifest.size, DEFAULT_SIZE);
+    assert!(manifest.entries.is_empty());
+}
+
+#[test]
+fn test_parse_errors() {
+    let err = |text| Manifest::parse(text, Path::new(".")).unwrap_err();
+    assert_eq!(err("\n\nfoo /a"), "line 3: invalid entry: \"foo /a\"");
+    assert_eq!(
+        err("format ext4"),
+        "line 1: unknown format: \"format ext4\""
+    );
+    assert_eq!(err("size 12X"), "line 1: invalid size: \"size 12X\"");
+    assert_eq!(
+        err("dir /a mode=0999"),
+        "line 1: invalid mode: \"dir /a mode=0999\""
+    );
+    assert_eq!(
+        err("dir /a mode=17777"),
+        "line 1: invalid mode: \"dir /a mode=17777\""
+    );
+    assert_eq!(
+        err("dir /a size=1"),
+        "line 1: size of a directory: \"dir /a size=1\""
+    );
+    assert_eq!(err("zero /a"), "line 1: missing size: \"zero /a\"");
+    assert_eq!(err("file a b"), "line 1: invalid path: \"file a b\"");
+    assert_eq!(
+        err("file /a/../b b"),
+        "line 1: invalid path: \"file /a/../b b\""
+    );
+    assert_eq!(err("dir /"), "line 1: invalid path: \"dir /\"");
+    assert_eq!(err("file /a"), "line 1: invalid entry: \"file /a\"");
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("0"), Some(0));
+    assert_eq!(parse_size("512"), Some(512));
+    assert_eq!(parse_size("4K"), Some(4096));
+    assert_eq!(parse_size("64M"), Some(64 << 20));
+    assert_eq!(parse_size("2g"), Some(2 << 30));
+    assert_eq!(parse_size(""), None);
+    assert_eq!(parse_size("K"), None);
+    assert_eq!(parse_size("-1"), None);
+    assert_eq!(parse_size("99999999999G"), None);
+}
+
+#[test]
+fn test_image() {
+    let mut image = Image::new();
+    image.add_file("/a/b/c", b"c".to_vec(), 0o600).unwrap();
+    image.add_dir("/a", 0o700).unwrap();
+    image.add_file("/a-b", b"ab".to_vec(), 0o644).unwrap();
+    image.add_file("/a/b/c", b"new".to_vec(), 0o644).unwrap();
+
+    let paths: Vec<_> = image.iter().map(|(path, _)| path).collect();
+    assert_eq!(paths, ["/a", "/a-b", "/a/b", "/a/b/c"]);
+    assert_eq!(image.get("/a").unwrap().mode, 0o700);
+    assert_eq!(image.get("/a/b").unwrap().mode, 0o755);
+    assert_eq!(image.get("/a/b/c").unwrap().kind, file(b"new"));
+
+    assert!(image.add_dir("/a/b/c", 0o755).is_err());
+    assert!(image.add_file("/a/b/c/d", vec![], 0o644).is_err());
+    assert!(image.add_file("/a/b", vec![], 0o644).is_err());
+}
+
+#[test]
+fn test_build_image() {
+    let dir = TempDir::new("build");
+    let host = &dir.0;
+    fs::create_dir_all(host.join("tree/sub")).unwrap();
+    fs::write(host.join("tree/x"), "x").unwrap();
+    fs::write(host.join("tree/sub/y"), "y").unwrap();
+    fs::write(host.join("hello"), "hello").unwrap();
+    let text = "\
+        tree /data tree mode=0700\n\
+        file /etc/hello hello size=8\n\
+        file /data/x hello\n\
+        zero /zero size=3 mode=0600\n";
+    let manifest = Manifest::parse(text, host).unwrap();
+    let image = Image::build(&manifest).unwrap();
+
+    let paths: Vec<_> = image.iter().map(|(path, _)| path).collect();
+    assert_eq!(
+        paths,
+        [
+            "/data",
+            "/data/sub",
+            "/data/sub/y",
+            "/data/x",
+            "/etc",
+            "/etc/hello",
+            "/zero"
+        ]
+    );
+    assert_eq!(image.get("/data").unwrap().mode, 0o700);
+    assert_eq!(image.get("/data/sub/y").unwrap().kind, file(b"y"));
+    assert_eq!(image.get("/data/x").unwrap().kind, file(b"hello"));
+    assert_eq!(image.get("/etc/hello").unwrap().kind, file(b"hello\0\0\0"));
+    let zero = image.get("/zero").unwrap();
+    assert_eq!((&zero.kind, zero.mode), (&file(&[0; 3]), 0o600));
+
+    let manifest = Manifest::parse("file /a hello size=4", host).unwrap();
+    let err = Image::build(&manifest).unwrap_err();
+    assert!(err.contains("is larger than 4 bytes"), "{}", err);
+    let manifest = Manifest::parse("file /a missing", host).unwrap();
+    assert!(Image::build(&manifest)
+        .unwrap_err()
+        .starts_with("cannot read"));
+    let manifest = Manifest::parse("tree /a hello", host).unwrap();
+    assert!(Image::build(&manifest)
+        .unwrap_err()
+        .ends_with("is not a directory"));
+}
+
+/// Parses the entries of a `newc` cpio archive, as `(name, mode, data)`.
+fn parse_cpio(mut archive: &[u8]) -> Vec<(String, u32, Vec<u8>)> {
+    let mut entries = Vec::new();
+    let pad = |len: usize| len.next_multiple_of(4);
+    loop {
+        assert_eq!(&archive[..6], b"070701");
+        let field = |i: usize| {
+            let hex = std::str::from_utf8(&archive[6 + i * 8..14 + i * 8]).unwrap();
+            u32::from_str_radix(hex, 16).unwrap() as usize
+        };
+        let (mode, size, namesize) = (field(1), field(6), field(11));
+        assert_eq!((field(2), field(3), field(5)), (0, 0, 0)); // uid, gid, mtime
+        let name_end = 110 + namesize;
+        let name = std::str::from_utf8(&archive[110..name_end - 1]).unwrap();
+        assert_eq!(archive[name_end - 1], 0);
+        if name == "TRAILER!!!" {
+            assert_eq!(archive.len(), pad(name_end));
+            return entries;
+        }
+        let data = &archive[pad(name_end)..pad(name_end) + size];
+        entries.push((name.into(), mode as u32, data.to_vec()));
+        archive = &archive[pad(pad(name_end) + size)..];
+    }
+}
+
+#[test]
+fn test_write_cpio() {
+    let mut image = Image::new();
+    image.add_file("/bin/sh", b"#!".to_vec(), 0o755).unwrap();
+    image
+        .add_file("/etc/motd", b"hello".to_vec(), 0o644)
+        .unwrap();
+
+    let mut archive = Vec::new();
+    write_cpio(&image, &mut archive).unwrap();
+    assert_eq!(archive.len() % 4, 0);
+    assert_eq!(
+        parse_cpio(&archive),
+        [
+            ("bin".into(), 0o040755, vec![]),
+            ("bin/sh".into(), 0o100755, b"#!".to_vec()),
+            ("etc".into(), 0o040755, vec![]),
+            ("etc/motd".into(), 0o100644, b"hello".to_vec()),
+        ]
+    );
+
+    // reproducible
+    let mut again = Vec::new();
+    write_cpio(&image.clone(), &mut again).unwrap();
+    assert_eq!(archive, again);
+
+    let mut empty = Vec::new();
+    write_cpio(&Image::new(), &mut empty).unwrap();
+    assert!(parse_cpio(&empty).is_empty());
+}
+
+#[test]
+fn test_build_cpio_image() {
+    let dir = TempDir::new("cpio");
+    let host = &dir.0;
+    fs::write(host.join("a"), "abc").unwrap();
+    fs::write(
+        host.join("img.manifest"),
+        "format cpio\nfile /x/a a mode=0600\n",
+    )
+    .unwrap();
+
+    let output = host.join("out.cpio");
+    build_image(&host.join("img.manifest"), &output).unwrap();
+    let entries = parse_cpio(&fs::read(&output).unwrap());
+    assert_eq!(
+        entries,
+        [
+            ("x".into(), 0o040755, vec![]),
+            ("x/a".into(), 0o100600, b"abc".to_vec()),
+        ]
+    );
+
+    let err = build_image(&host.join("missing"), &output).unwrap_err();
+    assert!(err.starts_with("cannot read"), "{}", err);
+}
+
+#[cfg(feature = "fat")]
+#[test]
+fn test_write_fat() {
+    use fatfs::{FatType, FileSystem, FsOptions, Read, StdIoWrapper};
+
+    let dir = TempDir::new("fat");
+    let mut image = Image::new();
+    image
+        .add_file("/sbin/init", b"init".to_vec(), 0o755)
+        .unwrap();
+    image
+        .add_file("/long file name.txt", vec![7; 5000], 0o644)
+        .unwrap();
+
+    let output = dir.0.join("disk.img");
+    write_image(&image, Format::Fat32, 64 << 20, &output).unwrap();
+    assert_eq!(fs::metadata(&output).unwrap().len(), 64 << 20);
+    let first = fs::read(&output).unwrap();
+    write_image(&image, Format::Fat32, 64 << 20, &output).unwrap();
+    assert!(fs::read(&output).unwrap() == first, "not reproducible");
+
+    let storage = StdIoWrapper::from(fs::File::open(&output).unwrap());
+    let fs = FileSystem::new(storage, FsOptions::new()).unwrap();
+    assert_eq!(fs.fat_type(), FatType::Fat32);
+    let root = fs.root_dir();
+    for (path, expected) in [
+        ("sbin/init", b"init".to_vec()),
+        ("long file name.txt", vec![7; 5000]),
+    ] {
+        let mut buf = vec![0; expected.len()];
+        root.open_file(path).unwrap().read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+}
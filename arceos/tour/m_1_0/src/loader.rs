@@ -1,5 +1,5 @@
 use std::io::{self, Read};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use axhal::paging::MappingFlags;
 use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
 use axmm::AddrSpace;
@@ -31,7 +31,12 @@ pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<()> {
 
 fn load_file(fname: &str, buf: &mut [u8]) -> io::Result<usize> {
     ax_println!("app: {}", fname);
-    let mut file = File::open(fname)?;
+    let mut file = open_executable(fname)?;
     let n = file.read(buf)?;
     Ok(n)
 }
+
+/// Opens an executable, refusing ones on a filesystem mounted `noexec`.
+fn open_executable(fname: &str) -> io::Result<File> {
+    OpenOptions::new().read(true).execute(true).open(fname)
+}
@@ -1,7 +1,7 @@
 use std::io::{self, Read};
 use std::io::SeekFrom;
 use std::io::Seek;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use alloc::vec::Vec;
 use alloc::vec;
 use axhal::paging::MappingFlags;
@@ -18,7 +18,7 @@ use elf::ElfBytes;
 const ELF_HEAD_BUF_SIZE: usize = 256;
 
 pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<usize> {
-    let mut file = File::open(fname)?;
+    let mut file = open_executable(fname)?;
     let (phdrs, entry, _, _) = load_elf_phdrs(&mut file)?;
 
     for phdr in &phdrs {
@@ -74,3 +74,8 @@ fn load_elf_phdrs(file: &mut File) -> io::Result<(Vec<ProgramHeader>, usize, usi
         .collect();
     Ok((phdrs, ehdr.e_entry as usize, ehdr.e_phoff as usize, ehdr.e_phnum as usize))
 }
+
+/// Opens an executable, refusing ones on a filesystem mounted `noexec`.
+fn open_executable(fname: &str) -> io::Result<File> {
+    OpenOptions::new().read(true).execute(true).open(fname)
+}
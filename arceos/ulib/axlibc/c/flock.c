@@ -1,9 +1,11 @@
 #include <stdio.h>
 #include <sys/file.h>
 
+#ifndef AX_CONFIG_FS
 // TODO
 int flock(int __fd, int __operation)
 {
     unimplemented();
     return 0;
 }
+#endif
@@ -2,12 +2,14 @@
 #include <sys/stat.h>
 #include <sys/types.h>
 
+#ifndef AX_CONFIG_FS
 // TODO:
 int fchmod(int fd, mode_t mode)
 {
     unimplemented();
     return 0;
 }
+#endif
 
 // TODO:
 int mkdir(const char *path, mode_t mode)
@@ -16,12 +18,14 @@ int mkdir(const char *path, mode_t mode)
     return 0;
 }
 
+#ifndef AX_CONFIG_FS
 // TODO
 int chmod(const char *path, mode_t mode)
 {
     unimplemented();
     return 0;
 }
+#endif
 
 // TODO
 mode_t umask(mode_t mask)
@@ -6,6 +6,7 @@
 #include <time.h>
 #include <unistd.h>
 
+#ifndef AX_CONFIG_FS
 // TODO:
 uid_t geteuid(void)
 {
@@ -19,6 +20,7 @@ uid_t getuid(void)
     unimplemented();
     return 0;
 }
+#endif
 
 // TODO
 pid_t setsid(void)
@@ -61,13 +63,6 @@ int access(const char *pathname, int mode)
     return 0;
 }
 
-// TODO:
-ssize_t readlink(const char *path, char *buf, size_t bufsiz)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO:
 int unlink(const char *pathname)
 {
@@ -82,27 +77,6 @@ int rmdir(const char *pathname)
     return 0;
 }
 
-// TODO:
-int fsync(int fd)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int fdatasync(int __fildes)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fchown(int fd, uid_t owner, gid_t group)
-{
-    unimplemented("owner: %x group: %x", owner, group);
-    return 0;
-}
-
 // TODO:
 int ftruncate(int fd, off_t length)
 {
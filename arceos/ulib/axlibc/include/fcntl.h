@@ -100,10 +100,15 @@
 #define AT_FDCWD      (-100)
 #define AT_EMPTY_PATH 0x1000
 
+#define AT_SYMLINK_NOFOLLOW 0x100
+
 #define SYNC_FILE_RANGE_WAIT_BEFORE 1
 #define SYNC_FILE_RANGE_WRITE       2
 #define SYNC_FILE_RANGE_WAIT_AFTER  4
 
+#define FALLOC_FL_KEEP_SIZE  1
+#define FALLOC_FL_PUNCH_HOLE 2
+
 #define loff_t off_t
 
 struct flock {
@@ -117,6 +122,8 @@ struct flock {
 int fcntl(int fd, int cmd, ... /* arg */);
 int posix_fadvise(int __fd, unsigned long __offset, unsigned long __len, int __advise);
 int sync_file_range(int, off_t, off_t, unsigned);
+int fallocate(int, int, off_t, off_t);
+int posix_fallocate(int, off_t, off_t);
 
 int open(const char *filename, int flags, ...);
 
@@ -0,0 +1,14 @@
+#ifndef _NETINET_IF_ETHER_H
+#define _NETINET_IF_ETHER_H
+
+#define ETH_ALEN      6
+#define ETH_HLEN      14
+#define ETH_DATA_LEN  1500
+#define ETH_FRAME_LEN 1514
+
+#define ETH_P_ALL  0x0003
+#define ETH_P_IP   0x0800
+#define ETH_P_ARP  0x0806
+#define ETH_P_IPV6 0x86DD
+
+#endif // _NETINET_IF_ETHER_H
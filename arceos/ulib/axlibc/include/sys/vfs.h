@@ -0,0 +1 @@
+#include <sys/statfs.h>
@@ -1,7 +1,13 @@
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_ulong, c_void};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_chmod, sys_chown, sys_fallocate, sys_fchmod, sys_fchown, sys_fdatasync, sys_fgetxattr,
+    sys_flistxattr, sys_flock, sys_fremovexattr, sys_fsetxattr, sys_fstat, sys_fstatfs, sys_fsync,
+    sys_getcwd, sys_getegid, sys_geteuid, sys_getgid, sys_getuid, sys_getxattr, sys_lchown,
+    sys_lgetxattr, sys_link, sys_listxattr, sys_llistxattr, sys_lremovexattr, sys_lseek,
+    sys_lsetxattr, sys_lstat, sys_mount, sys_open, sys_readlink, sys_removexattr, sys_rename,
+    sys_setgid, sys_setuid, sys_setxattr, sys_stat, sys_statfs, sys_symlink, sys_sync, sys_umount2,
+    sys_utimensat,
 };
 
 use crate::{ctypes, utils::e};
@@ -51,6 +57,52 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Get the usage of the filesystem that `path` lives in and write it into
+/// `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_statfs(path, buf))
+}
+
+/// Get the usage of the filesystem that the file `fd` lives in and write it
+/// into `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_fstatfs(fd, buf))
+}
+
+/// Create a symbolic link `linkpath` that points to `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    e(sys_symlink(target, linkpath))
+}
+
+/// Create a hard link `new` to the file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn link(old: *const c_char, new: *const c_char) -> c_int {
+    e(sys_link(old, new))
+}
+
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlink(path, buf, bufsiz) as _) as _
+}
+
 /// Get the path of the current directory.
 #[no_mangle]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -65,3 +117,321 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Synchronize the data and metadata of the file `fd` to the device.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Synchronize the data of the file `fd` to the device.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fdatasync(fd: c_int) -> c_int {
+    e(sys_fdatasync(fd))
+}
+
+/// Write all cached filesystem data back to the devices.
+#[no_mangle]
+pub unsafe extern "C" fn sync() {
+    sys_sync()
+}
+
+/// Apply or remove an advisory lock on the file `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    e(sys_flock(fd, operation))
+}
+
+/// Allocate or deallocate the space of a range of the file `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fallocate(
+    fd: c_int,
+    mode: c_int,
+    offset: ctypes::off_t,
+    len: ctypes::off_t,
+) -> c_int {
+    e(sys_fallocate(fd, mode, offset, len))
+}
+
+/// Allocate the space of a range of the file `fd`, extending the file to its
+/// end.
+///
+/// Return 0 if the operation succeeds, otherwise return the error number.
+#[no_mangle]
+pub unsafe extern "C" fn posix_fallocate(
+    fd: c_int,
+    offset: ctypes::off_t,
+    len: ctypes::off_t,
+) -> c_int {
+    sys_fallocate(fd, 0, offset, len).min(0).abs()
+}
+
+/// Get the value of the extended attribute `name` of the file `path` into
+/// `value`, or only its size if `size` is 0.
+///
+/// Return the size of the value, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn getxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    e(sys_getxattr(path, name, value, size) as _) as _
+}
+
+/// Same as [`getxattr`], but a symbolic link itself is accessed.
+#[no_mangle]
+pub unsafe extern "C" fn lgetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    e(sys_lgetxattr(path, name, value, size) as _) as _
+}
+
+/// Same as [`getxattr`], but for the file `fd`.
+#[no_mangle]
+pub unsafe extern "C" fn fgetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut c_void,
+    size: usize,
+) -> ctypes::ssize_t {
+    e(sys_fgetxattr(fd, name, value, size) as _) as _
+}
+
+/// Get the null-terminated names of the extended attributes of the file
+/// `path` into `list`, or only their total size if `size` is 0.
+///
+/// Return the size of the list, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn listxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: usize,
+) -> ctypes::ssize_t {
+    e(sys_listxattr(path, list, size) as _) as _
+}
+
+/// Same as [`listxattr`], but a symbolic link itself is accessed.
+#[no_mangle]
+pub unsafe extern "C" fn llistxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: usize,
+) -> ctypes::ssize_t {
+    e(sys_llistxattr(path, list, size) as _) as _
+}
+
+/// Same as [`listxattr`], but for the file `fd`.
+#[no_mangle]
+pub unsafe extern "C" fn flistxattr(fd: c_int, list: *mut c_char, size: usize) -> ctypes::ssize_t {
+    e(sys_flistxattr(fd, list, size) as _) as _
+}
+
+/// Set the extended attribute `name` of the file `path` to `value`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    e(sys_setxattr(path, name, value, size, flags))
+}
+
+/// Same as [`setxattr`], but a symbolic link itself is changed.
+#[no_mangle]
+pub unsafe extern "C" fn lsetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    e(sys_lsetxattr(path, name, value, size, flags))
+}
+
+/// Same as [`setxattr`], but for the file `fd`.
+#[no_mangle]
+pub unsafe extern "C" fn fsetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+    flags: c_int,
+) -> c_int {
+    e(sys_fsetxattr(fd, name, value, size, flags))
+}
+
+/// Remove the extended attribute `name` of the file `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn removexattr(path: *const c_char, name: *const c_char) -> c_int {
+    e(sys_removexattr(path, name))
+}
+
+/// Same as [`removexattr`], but a symbolic link itself is changed.
+#[no_mangle]
+pub unsafe extern "C" fn lremovexattr(path: *const c_char, name: *const c_char) -> c_int {
+    e(sys_lremovexattr(path, name))
+}
+
+/// Same as [`removexattr`], but for the file `fd`.
+#[no_mangle]
+pub unsafe extern "C" fn fremovexattr(fd: c_int, name: *const c_char) -> c_int {
+    e(sys_fremovexattr(fd, name))
+}
+
+/// Mount the filesystem of type `fstype` on `source` at `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    e(sys_mount(source, target, fstype, flags, data))
+}
+
+/// Unmount the filesystem mounted at `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn umount(target: *const c_char) -> c_int {
+    e(sys_umount2(target, 0))
+}
+
+/// Unmount the filesystem mounted at `target` with `flags`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn umount2(target: *const c_char, flags: c_int) -> c_int {
+    e(sys_umount2(target, flags))
+}
+
+/// Change the permissions of the file `path` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_chmod(path, mode))
+}
+
+/// Change the permissions of the file `fd` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmod(fd, mode))
+}
+
+/// Change the owner and the group of the file `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn chown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_chown(path, owner, group))
+}
+
+/// Change the owner and the group of the file `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    e(sys_fchown(fd, owner, group))
+}
+
+/// Change the owner and the group of the symbolic link `path` itself.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn lchown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_lchown(path, owner, group))
+}
+
+/// Change the access and modification times of the file `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    e(sys_utimensat(dirfd, path, times, flags))
+}
+
+/// Change the access and modification times of the file `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    e(sys_utimensat(fd, core::ptr::null(), times, 0))
+}
+
+/// Get the real user ID of the calling task.
+#[no_mangle]
+pub unsafe extern "C" fn getuid() -> ctypes::uid_t {
+    sys_getuid()
+}
+
+/// Get the effective user ID of the calling task.
+#[no_mangle]
+pub unsafe extern "C" fn geteuid() -> ctypes::uid_t {
+    sys_geteuid()
+}
+
+/// Get the real group ID of the calling task.
+#[no_mangle]
+pub unsafe extern "C" fn getgid() -> ctypes::gid_t {
+    sys_getgid()
+}
+
+/// Get the effective group ID of the calling task.
+#[no_mangle]
+pub unsafe extern "C" fn getegid() -> ctypes::gid_t {
+    sys_getegid()
+}
+
+/// Set the user ID of the calling task.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setuid(uid: ctypes::uid_t) -> c_int {
+    e(sys_setuid(uid))
+}
+
+/// Set the group ID of the calling task.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setgid(gid: ctypes::gid_t) -> c_int {
+    e(sys_setgid(gid))
+}
@@ -0,0 +1,38 @@
+use core::ffi::{c_char, c_int};
+
+use arceos_posix_api::{sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch};
+
+use crate::utils::e;
+
+/// Create an inotify instance.
+///
+/// Return its file descriptor if succeed.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_init() -> c_int {
+    e(sys_inotify_init1(0))
+}
+
+/// Create an inotify instance with `flags`.
+///
+/// Return its file descriptor if succeed.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_init1(flags: c_int) -> c_int {
+    e(sys_inotify_init1(flags))
+}
+
+/// Watch the file at `path` for the events in `mask` with the inotify
+/// instance `fd`.
+///
+/// Return the watch descriptor if succeed.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    e(sys_inotify_add_watch(fd, path, mask))
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+///
+/// Return 0 if succeed.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    e(sys_inotify_rm_watch(fd, wd))
+}
@@ -53,6 +53,8 @@ mod utils;
 mod fd_ops;
 #[cfg(feature = "fs")]
 mod fs;
+#[cfg(feature = "fs")]
+mod inotify;
 #[cfg(any(feature = "select", feature = "epoll"))]
 mod io_mpx;
 #[cfg(feature = "alloc")]
@@ -100,7 +102,19 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{ax_open, fstat, getcwd, link, lseek, lstat, readlink, rename, stat, symlink};
+#[cfg(feature = "fs")]
+pub use self::fs::{chmod, chown, fchmod, fchown, flock, futimens, lchown, utimensat};
+#[cfg(feature = "fs")]
+pub use self::fs::{fallocate, fstatfs, posix_fallocate, statfs};
+#[cfg(feature = "fs")]
+pub use self::fs::{fgetxattr, flistxattr, fremovexattr, fsetxattr, getxattr, lgetxattr};
+#[cfg(feature = "fs")]
+pub use self::fs::{listxattr, llistxattr, lremovexattr, lsetxattr, removexattr, setxattr};
+#[cfg(feature = "fs")]
+pub use self::fs::{getegid, geteuid, getgid, getuid, setgid, setuid};
+#[cfg(feature = "fs")]
+pub use self::inotify::{inotify_add_watch, inotify_init, inotify_init1, inotify_rm_watch};
 
 #[cfg(feature = "net")]
 pub use self::net::{
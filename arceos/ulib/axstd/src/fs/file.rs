@@ -15,8 +15,9 @@ pub struct File {
     inner: api::AxFileHandle,
 }
 
-/// Metadata information about a file.
-pub struct Metadata(api::AxFileAttr);
+/// Metadata information about a file, with its ownership, timestamps and
+/// number of hard links.
+pub struct Metadata(pub(super) api::AxFileAttr, pub(super) api::AxFileMeta);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -64,6 +65,13 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the option to open the file to be executed, which fails if its
+    /// filesystem is mounted `noexec`.
+    pub fn execute(&mut self, execute: bool) -> &mut Self {
+        self.0.execute(execute);
+        self
+    }
+
     /// Opens a file at `path` with the options specified by `self`.
     pub fn open(&self, path: &str) -> Result<File> {
         api::ax_open_file(path, &self.0).map(|inner| File { inner })
@@ -90,6 +98,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible for [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +124,39 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.1.nlink
+    }
+
+    /// Returns the user ID of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.1.uid
+    }
+
+    /// Returns the group ID of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.1.gid
+    }
+
+    /// Returns the time of the last access to the file, in seconds since the
+    /// epoch.
+    pub const fn atime(&self) -> i64 {
+        self.1.atime.as_secs() as i64
+    }
+
+    /// Returns the time of the last modification of the file, in seconds since
+    /// the epoch.
+    pub const fn mtime(&self) -> i64 {
+        self.1.mtime.as_secs() as i64
+    }
+
+    /// Returns the time of the last status change of the file, in seconds
+    /// since the epoch.
+    pub const fn ctime(&self) -> i64 {
+        self.1.ctime.as_secs() as i64
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +166,8 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("uid", &self.uid())
+            .field("gid", &self.gid())
             .finish_non_exhaustive()
     }
 }
@@ -160,7 +209,18 @@ impl File {
 
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
-        api::ax_file_attr(&self.inner).map(Metadata)
+        let attr = api::ax_file_attr(&self.inner)?;
+        Ok(Metadata(attr, api::ax_file_meta(&self.inner)?))
+    }
+
+    /// Attempts to sync all data and metadata of the file to the device.
+    pub fn sync_all(&self) -> Result<()> {
+        api::ax_flush_file(&self.inner)
+    }
+
+    /// Same as [`sync_all`](Self::sync_all), metadata is always synced.
+    pub fn sync_data(&self) -> Result<()> {
+        api::ax_flush_file(&self.inner)
     }
 }
 
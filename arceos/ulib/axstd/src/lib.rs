@@ -30,12 +30,20 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `fs-ro`: Mount the main filesystem read-only.
+//!     - `fs-overlay`: Keep changes to the main filesystem in memory with an overlay.
+//!     - `fs-ext2`: Support ext2/ext3/ext4 filesystems.
+//!     - `fs-initramfs`: Use a RAM filesystem unpacked from a cpio archive as the root.
+//!     - `fs-9p`: Support mounting host directories shared by virtio-9p.
 //!     - `net`: Enable networking support.
+//!     - `net-loopback`: Enable networking support with the loopback interface only, no NIC driver.
+//!     - `net-dhcp`: Configure the network with DHCP, falling back to the static config.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
 //!     - `bus-pci`: Use PCI bus to probe all PCI devices.
+//!     - `driver-dyn`: Use all the enabled drivers of a class at once, e.g. both virtio-net and ixgbe.
 //!     - `driver-ramdisk`: Use the RAM disk to emulate the block device.
 //!     - `driver-ixgbe`: Enable the Intel 82599 10Gbit NIC driver.
 //!     - `driver-bcm2835-sdhci`: Enable the BCM2835 SDHCI driver (Raspberry Pi SD card).
@@ -73,5 +81,5 @@ pub mod time;
 
 #[cfg(feature = "fs")]
 pub mod fs;
-#[cfg(feature = "net")]
+#[cfg(feature = "net-loopback")]
 pub mod net;
@@ -84,6 +84,7 @@ run_test "tour/m_3_1" "y" "y" "payload/fileops_c/fileops" "" "FileOps ok!" "mono
 run_test "tour/h_1_0" "y" "y" "payload/skernel/skernel" "" "Shutdown vm normally!" "Hypervisor ok!"
 run_test "tour/h_2_0" "y" "y" "tour/u_3_0/u_3_0_riscv64-qemu-virt.bin" "make A=tour/u_3_0/" "Got pflash magic: pfld"
 run_test "tour/h_3_0" "y" "y" "tour/u_6_0/u_6_0_riscv64-qemu-virt.bin" "make A=tour/u_6_0/" "Multi-task(Preemptible) ok!"
+run_test "examples/loopback" "n" "" "" "" "TCP over 127.0.0.1 OK!" "UDP over ::1 OK!" "Loopback tests run OK!"
 run_test "tour/h_4_0" "y" "y" "tour/m_1_1/m_1_1_riscv64-qemu-virt.bin" "make A=tour/m_1_1" "monolithic kernel exit [Some(0)] normally!" "handle_syscall ..."
 
 if [[ -s Error.log ]]; then